@@ -0,0 +1,155 @@
+//! gRPC health reporting. The status of each service is set by a single
+//! task, which reports the service as serving only while all of the
+//! service's health checks pass.
+
+use bip300301_enforcer_lib::{
+    cli::{WalletConfig, WalletSyncSource},
+    validator::Validator,
+    wallet::Wallet,
+};
+use tokio::sync::watch;
+use tonic_health::{ServingStatus, server::HealthReporter};
+
+/// A condition that a service's health depends on
+pub struct HealthCheck {
+    /// Name of the condition, for logs
+    name: &'static str,
+    rx: watch::Receiver<bool>,
+    /// Value of the channel while the check passes
+    healthy: bool,
+}
+
+impl HealthCheck {
+    pub fn new(name: &'static str, rx: watch::Receiver<bool>, healthy: bool) -> Self {
+        Self { name, rx, healthy }
+    }
+
+    /// Block processing has not halted
+    pub fn not_halted(validator: &Validator) -> Self {
+        Self::new(
+            "block processing halted",
+            validator.subscribe_halted(),
+            false,
+        )
+    }
+}
+
+/// Names of the checks that are failing, marking the current values as seen
+fn failing_checks(checks: &mut [HealthCheck]) -> Vec<&'static str> {
+    checks
+        .iter_mut()
+        .filter(|check| *check.rx.borrow_and_update() != check.healthy)
+        .map(|check| check.name)
+        .collect()
+}
+
+/// Health checks for the wallet gRPC service, as enabled by the wallet
+/// config
+pub fn wallet_health_checks(wallet: &Wallet, wallet_opts: &WalletConfig) -> Vec<HealthCheck> {
+    let mut checks = vec![HealthCheck::not_halted(wallet.validator())];
+    if wallet_opts.require_unlock {
+        checks.push(HealthCheck::new(
+            "wallet locked",
+            wallet.subscribe_unlocked(),
+            true,
+        ));
+    }
+    if wallet_opts.initial_sync_timeout_secs.is_some() {
+        checks.push(HealthCheck::new(
+            "initial wallet sync incomplete",
+            wallet.subscribe_synced(),
+            true,
+        ));
+    }
+    if wallet_opts.chain_source_probe_interval_secs.is_some()
+        && wallet_opts.sync_source != WalletSyncSource::Disabled
+    {
+        checks.push(HealthCheck::new(
+            "no chain source reachable",
+            wallet.subscribe_chain_source_reachable(),
+            true,
+        ));
+    }
+    if wallet_opts.sync_stale_threshold_secs.is_some() {
+        checks.push(HealthCheck::new(
+            "wallet sync stale",
+            wallet.subscribe_sync_stale(),
+            false,
+        ));
+    }
+    if wallet_opts.low_balance_degrade_health {
+        checks.push(HealthCheck::new(
+            "wallet balance low",
+            wallet.subscribe_low_balance(),
+            false,
+        ));
+    }
+    checks
+}
+
+/// Set the health status of `service` whenever the result of its checks
+/// changes. Runs until a check's channel is closed.
+pub async fn report_service_health(
+    health_reporter: HealthReporter,
+    service: &'static str,
+    mut checks: Vec<HealthCheck>,
+) {
+    let mut current_status = None;
+    loop {
+        let failing = failing_checks(&mut checks);
+        let status = if failing.is_empty() {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        if current_status != Some(status) {
+            if failing.is_empty() {
+                tracing::info!(%service, "setting health status to serving");
+            } else {
+                tracing::warn!(
+                    %service,
+                    failing_checks = ?failing,
+                    "setting health status to not serving"
+                );
+            }
+            health_reporter.set_service_status(service, status).await;
+            current_status = Some(status);
+        }
+        if checks.is_empty() {
+            return;
+        }
+        let changes = checks.iter_mut().map(|check| Box::pin(check.rx.changed()));
+        let (res, _, _) = futures::future::select_all(changes).await;
+        if res.is_err() {
+            tracing::warn!(
+                %service,
+                "health check channel closed, no longer updating health status"
+            );
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::watch;
+
+    use super::{HealthCheck, failing_checks};
+
+    #[test]
+    fn test_failing_checks() {
+        let (halted_tx, halted_rx) = watch::channel(false);
+        let (synced_tx, synced_rx) = watch::channel(false);
+        let mut checks = vec![
+            HealthCheck::new("halted", halted_rx, false),
+            HealthCheck::new("not synced", synced_rx, true),
+        ];
+        assert_eq!(failing_checks(&mut checks), vec!["not synced"]);
+        synced_tx.send_replace(true);
+        assert!(failing_checks(&mut checks).is_empty());
+        // Every failing check is reported, not only the most recent change
+        halted_tx.send_replace(true);
+        synced_tx.send_replace(false);
+        assert_eq!(failing_checks(&mut checks), vec!["halted", "not synced"]);
+    }
+}
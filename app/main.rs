@@ -1,7 +1,8 @@
-use std::{future::Future, net::SocketAddr, time::Duration};
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
 
 use bdk_wallet::bip39::{Language, Mnemonic};
 use bip300301_enforcer_lib::{
+    backoff,
     cli::{self, LogFormatter, WalletSyncSource},
     errors::ErrorChain,
     p2p::compute_signet_magic,
@@ -39,10 +40,23 @@ use tower_http::{
     trace::{DefaultOnFailure, DefaultOnResponse, TraceLayer},
 };
 use tracing::Instrument;
-use tracing_subscriber::{filter as tracing_filter, layer::SubscriberExt};
+use tracing_subscriber::{
+    Layer as _, filter as tracing_filter, layer::SubscriberExt, registry::LookupSpan,
+};
 use wallet::Wallet;
 
+use crate::{
+    health::HealthCheck,
+    rate_limit::{
+        MethodClass, RateLimitLayer, RateLimited, RateLimiter, RpcRateLimitLayer, WithRemoteAddr,
+    },
+    rpc_logger::RedactingRpcLoggerLayer,
+};
+
 mod file_descriptors;
+mod health;
+mod rate_limit;
+mod rpc_logger;
 
 /// Saturating predecessor of a log level
 fn saturating_pred_level(log_level: tracing::Level) -> tracing::Level {
@@ -75,67 +89,109 @@ where
 }
 
 // Configure logger. The returned guard should be dropped when the program
-// exits.
+// exits. The returned admin server can be used to reload the log filter at
+// runtime.
 fn set_tracing_subscriber(
     log_formatter: LogFormatter,
-    log_level: tracing::Level,
-    rolling_log_appender: tracing_appender::rolling::RollingFileAppender,
-) -> miette::Result<tracing_appender::non_blocking::WorkerGuard> {
-    let targets_filter = {
-        let default_directives_str = targets_directive_str([
-            ("", saturating_pred_level(log_level)),
-            ("bip300301", log_level),
-            ("cusf_enforcer_mempool", log_level),
-            ("jsonrpsee_core::tracing", log_level),
-            ("bip300301_enforcer", log_level),
-        ]);
-        let directives_str = match std::env::var(tracing_filter::EnvFilter::DEFAULT_ENV) {
-            Ok(env_directives) => format!("{default_directives_str},{env_directives}"),
-            Err(std::env::VarError::NotPresent) => default_directives_str,
-            Err(err) => return Err(err).into_diagnostic(),
-        };
-        tracing_filter::EnvFilter::builder()
-            .parse(directives_str)
-            .into_diagnostic()?
+    logger_opts: &cli::LoggerConfig,
+    rolling_log_appender: Option<tracing_appender::rolling::RollingFileAppender>,
+) -> miette::Result<(
+    Option<tracing_appender::non_blocking::WorkerGuard>,
+    server::admin::Server,
+)> {
+    let log_level = logger_opts.level;
+    let default_directives_str = targets_directive_str([
+        ("", saturating_pred_level(log_level)),
+        ("bip300301", log_level),
+        ("cusf_enforcer_mempool", log_level),
+        ("jsonrpsee_core::tracing", log_level),
+        ("bip300301_enforcer", log_level),
+    ]);
+    // Base directives for the log filter, and for filters set via
+    // `admin.set_log_filter`
+    let directives_str = match std::env::var(tracing_filter::EnvFilter::DEFAULT_ENV) {
+        Ok(env_directives) => format!("{default_directives_str},{env_directives}"),
+        Err(std::env::VarError::NotPresent) => default_directives_str,
+        Err(err) => return Err(err).into_diagnostic(),
     };
+    let targets_filter = tracing_filter::EnvFilter::builder()
+        .parse(&directives_str)
+        .into_diagnostic()?;
+    let (targets_filter, log_filter_handle) =
+        tracing_subscriber::reload::Layer::new(targets_filter);
     // If no writer is provided (as here!), logs end up at stdout.
-    let mut stdout_layer = tracing_subscriber::fmt::layer()
-        .event_format(log_formatter.with_file(true).with_line_number(true))
-        .fmt_fields(log_formatter);
-    let is_terminal = std::io::IsTerminal::is_terminal(&stdout_layer.writer()());
-    stdout_layer.set_ansi(is_terminal);
-
-    // Ensure the appender is non-blocking!
-    let (file_appender, guard) = tracing_appender::non_blocking(rolling_log_appender);
-
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_writer(file_appender)
-        .event_format(log_formatter.with_file(true).with_line_number(true))
-        .fmt_fields(log_formatter)
-        .with_ansi(false);
+    let stdout_layer = (!logger_opts.disable_stdout).then(|| {
+        let mut stdout_layer = tracing_subscriber::fmt::layer()
+            .event_format(
+                log_formatter
+                    .with_file(true)
+                    .with_line_number(true)
+                    .with_timestamp(logger_opts.timestamp),
+            )
+            .fmt_fields(log_formatter);
+        let ansi = logger_opts
+            .ansi
+            .unwrap_or_else(|| std::io::IsTerminal::is_terminal(&stdout_layer.writer()()));
+        stdout_layer.set_ansi(ansi);
+        stdout_layer
+    });
+
+    let mut log_file = None;
+    let file_layer = rolling_log_appender.map(|rolling_log_appender| {
+        // Ensure the appender is non-blocking! If backpressure is set to
+        // block, logging only blocks once the buffer is full.
+        let (file_appender, guard) = tracing_appender::non_blocking::NonBlockingBuilder::default()
+            .lossy(match logger_opts.file_backpressure {
+                cli::LogFileBackpressure::Drop => true,
+                cli::LogFileBackpressure::Block => false,
+            })
+            .buffered_lines_limit(logger_opts.file_buffered_lines)
+            .finish(rolling_log_appender);
+        log_file = Some((file_appender.error_counter(), guard));
+        tracing_subscriber::fmt::layer()
+            .with_writer(file_appender)
+            .event_format(log_formatter.with_file(true).with_line_number(true))
+            .fmt_fields(log_formatter)
+            .with_ansi(false)
+    });
+    // The log filter only applies to the output layers, so that request IDs
+    // are stored for all spans, including spans that are not logged
+    let span_request_id_layer =
+        SpanRequestIdLayer.with_filter(tracing_filter::filter_fn(|metadata| metadata.is_span()));
+    let output_layer = stdout_layer
+        .and_then(file_layer)
+        .with_filter(targets_filter);
     let tracing_subscriber = tracing_subscriber::registry()
-        .with(targets_filter)
-        .with(stdout_layer)
-        .with(file_layer);
+        .with(span_request_id_layer)
+        .with(output_layer);
 
     tracing::subscriber::set_global_default(tracing_subscriber)
         .into_diagnostic()
         .map_err(|err| miette::miette!("setting default subscriber failed: {err:#}"))?;
 
-    Ok(guard)
+    let admin_server = server::admin::Server::new(directives_str, log_filter_handle);
+    let Some((log_file_error_counter, guard)) = log_file else {
+        return Ok((None, admin_server));
+    };
+    let admin_server =
+        admin_server.with_log_file(logger_opts.file_backpressure, log_file_error_counter);
+    Ok((Some(guard), admin_server))
 }
 
+/// Rules requested are from [`rpc_client::block_template_rules`]
 async fn get_block_template<RpcClient>(
     rpc_client: &RpcClient,
     network: bitcoin::Network,
+    extra_rules: &[String],
 ) -> Result<bitcoin_jsonrpsee::client::BlockTemplate, wallet::error::BitcoinCoreRPC>
 where
     RpcClient: MainClient + Sync,
 {
-    let mut request = bitcoin_jsonrpsee::client::BlockTemplateRequest::default();
-    if network == bitcoin::Network::Signet {
-        request.rules.push("signet".to_owned())
-    }
+    let request = bitcoin_jsonrpsee::client::BlockTemplateRequest {
+        rules: rpc_client::block_template_rules(rpc_client, network, extra_rules).await,
+        ..Default::default()
+    };
+    tracing::trace!(rules = ?request.rules, "requesting block template");
     rpc_client
         .get_block_template(request)
         .await
@@ -175,6 +231,92 @@ fn propagate_request_id_layer() -> PropagateRequestIdLayer {
     PropagateRequestIdLayer::new(HeaderName::from_static(REQUEST_ID_HEADER))
 }
 
+/// Name of the span field that request IDs are recorded in.
+const REQUEST_ID_SPAN_FIELD: &str = "request_id";
+
+/// Request ID recorded on a span, stored in the span's extensions.
+#[derive(Clone, Debug)]
+struct SpanRequestId(String);
+
+#[derive(Default)]
+struct SpanRequestIdVisitor(Option<String>);
+
+impl tracing::field::Visit for SpanRequestIdVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == REQUEST_ID_SPAN_FIELD {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == REQUEST_ID_SPAN_FIELD {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// Tracing layer that stores the request ID field of each span in the span's
+/// extensions, so that it can be read back later (eg. from the panic hook).
+struct SpanRequestIdLayer;
+
+impl SpanRequestIdLayer {
+    fn store<S>(
+        id: &tracing::span::Id,
+        visitor: SpanRequestIdVisitor,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    {
+        if let Some(request_id) = visitor.0
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().replace(SpanRequestId(request_id));
+        }
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for SpanRequestIdLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = SpanRequestIdVisitor::default();
+        attrs.record(&mut visitor);
+        Self::store(id, visitor, ctx)
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = SpanRequestIdVisitor::default();
+        values.record(&mut visitor);
+        Self::store(id, visitor, ctx)
+    }
+}
+
+/// Request ID of the current span, or of the closest ancestor span that has
+/// one.
+fn current_span_request_id() -> Option<String> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+            registry.span(id)?.scope().find_map(|span| {
+                span.extensions()
+                    .get::<SpanRequestId>()
+                    .map(|SpanRequestId(request_id)| request_id.clone())
+            })
+        })
+        .flatten()
+}
+
 #[derive(Debug, Clone)]
 struct FailureHandler;
 use tower_http::classify::GrpcFailureClass;
@@ -196,15 +338,41 @@ impl tower_http::trace::OnFailure<GrpcFailureClass> for FailureHandler {
     }
 }
 
+/// JSON-RPC server config. jsonrpsee does not support HTTP/2 keepalive, so
+/// WebSocket pings are used instead.
+fn json_rpc_server_config(
+    connection_opts: &cli::ServerConnectionConfig,
+) -> jsonrpsee::server::ServerConfig {
+    let mut config = jsonrpsee::server::ServerConfig::builder();
+    if let Some(keepalive_interval) = connection_opts.keepalive_interval() {
+        let mut ping_config =
+            jsonrpsee::server::PingConfig::new().ping_interval(keepalive_interval);
+        if let Some(keepalive_timeout) = connection_opts.keepalive_timeout() {
+            ping_config = ping_config.inactive_limit(keepalive_timeout);
+        }
+        config = config.enable_ws_ping(ping_config);
+    }
+    config.build()
+}
+
 async fn spawn_json_rpc_server(
     validator: Either<Validator, Wallet>,
+    mut admin_server: server::admin::Server,
+    chain_info: server::chain::ChainInfo,
     serve_addr: SocketAddr,
+    rate_limiter: Arc<RateLimiter>,
+    connection_opts: &cli::ServerConnectionConfig,
+    rpc_log_buffer_size: u32,
 ) -> miette::Result<jsonrpsee::server::ServerHandle> {
-    let methods = match validator {
+    let mut methods: jsonrpsee::server::Methods = match validator {
         Either::Left(validator) => {
+            admin_server = admin_server.with_validator(validator.clone());
             server::validator::json_rpc::RpcServer::into_rpc(validator).into()
         }
         Either::Right(wallet) => {
+            admin_server = admin_server
+                .with_validator(wallet.validator().clone())
+                .with_wallet(wallet.clone());
             let mut methods: jsonrpsee::server::Methods =
                 server::validator::json_rpc::RpcServer::into_rpc(wallet.validator().clone()).into();
             methods
@@ -213,6 +381,12 @@ async fn spawn_json_rpc_server(
             methods
         }
     };
+    methods
+        .merge(server::admin::RpcServer::into_rpc(admin_server))
+        .into_diagnostic()?;
+    methods
+        .merge(server::chain::RpcServer::into_rpc(chain_info))
+        .into_diagnostic()?;
 
     tracing::info!("Listening for JSON-RPC on {}", serve_addr);
 
@@ -244,36 +418,225 @@ async fn spawn_json_rpc_server(
         .into_inner();
 
     let http_middleware = tower::ServiceBuilder::new().layer(tracer);
-    let rpc_middleware = RpcServiceBuilder::new().rpc_logger(1024);
+    let rpc_middleware = RpcServiceBuilder::new()
+        .layer(RedactingRpcLoggerLayer::new(rpc_log_buffer_size))
+        .layer(RpcRateLimitLayer::new(
+            rate_limiter,
+            classify_json_rpc_method,
+        ));
 
-    let handle = jsonrpsee::server::Server::builder()
+    let service_builder = jsonrpsee::server::Server::builder()
+        .set_config(json_rpc_server_config(connection_opts))
         .set_http_middleware(http_middleware)
         .set_rpc_middleware(rpc_middleware)
-        .build(serve_addr)
-        .await
-        .map_err(|err| miette!("initialize JSON-RPC server at `{serve_addr}`: {err:#}"))?
-        .start(methods);
+        .to_service_builder();
+    let listener = bind_json_rpc_listener(serve_addr).await?;
+    let (stop_handle, handle) = jsonrpsee::server::stop_channel();
+    spawn_json_rpc_accept_loop(listener, stop_handle.clone(), move |stream, remote_addr| {
+        let service = WithRemoteAddr::new(
+            service_builder
+                .clone()
+                .build(methods.clone(), stop_handle.clone()),
+            remote_addr,
+        );
+        let stopped = stop_handle.clone().shutdown();
+        tokio::spawn(async move {
+            if let Err(err) =
+                jsonrpsee::server::serve_with_graceful_shutdown(stream, service, stopped).await
+            {
+                tracing::debug!(%remote_addr, "JSON-RPC connection error: {err:#}");
+            }
+        });
+    });
     Ok(handle)
 }
 
+async fn bind_json_rpc_listener(serve_addr: SocketAddr) -> miette::Result<tokio::net::TcpListener> {
+    tokio::net::TcpListener::bind(serve_addr)
+        .await
+        .map_err(|err| miette!("initialize JSON-RPC server at `{serve_addr}`: {err:#}"))
+}
+
+/// Accept connections until the server is stopped, passing each connection
+/// and the client's address to `serve_connection`.
+/// The JSON-RPC servers accept connections themselves, rather than letting
+/// jsonrpsee do it, so that the rate limiter can key requests by client
+/// address.
+fn spawn_json_rpc_accept_loop<F>(
+    listener: tokio::net::TcpListener,
+    stop_handle: jsonrpsee::server::StopHandle,
+    serve_connection: F,
+) where
+    F: Fn(tokio::net::TcpStream, SocketAddr) + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            let (stream, remote_addr) = tokio::select! {
+                res = listener.accept() => match res {
+                    Ok(conn) => conn,
+                    Err(err) => {
+                        tracing::warn!("failed to accept JSON-RPC connection: {err:#}");
+                        continue;
+                    }
+                },
+                () = stop_handle.clone().shutdown() => break,
+            };
+            if let Err(err) = stream.set_nodelay(true) {
+                tracing::debug!(%remote_addr, "failed to set TCP_NODELAY: {err:#}");
+            }
+            serve_connection(stream, remote_addr);
+        }
+    });
+}
+
+/// Rate limit class for a gRPC method, from the request path
+/// (`/{service}/{method}`)
+fn classify_grpc_method(uri: &http::Uri) -> MethodClass {
+    let Some((service, method)) = uri.path().trim_start_matches('/').split_once('/') else {
+        return MethodClass::Default;
+    };
+    let expensive = if service == WalletServiceServer::<Wallet>::NAME {
+        matches!(
+            method,
+            "ListSidechainDepositTransactions" | "ListTransactions" | "ListUnspentOutputs"
+        )
+    } else if service == ValidatorServiceServer::<Validator>::NAME {
+        matches!(method, "GetBlockInfo" | "GetTwoWayPegData")
+    } else {
+        false
+    };
+    if expensive {
+        MethodClass::Expensive
+    } else {
+        MethodClass::Default
+    }
+}
+
+/// Rate limit class for a JSON-RPC method on the main JSON-RPC server
+fn classify_json_rpc_method(method: &str) -> MethodClass {
+    if matches!(
+        method,
+        "wallet.list_sidechain_deposit_transactions"
+            | "wallet.list_wallet_transactions"
+            | "wallet.list_unspent"
+            | "wallet.list_withdrawal_bundle_events"
+            | "wallet.get_enforcer_block_template"
+            | "wallet.get_candidate_block_hex"
+            | "validator.get_block_info"
+    ) {
+        MethodClass::Expensive
+    } else {
+        MethodClass::Default
+    }
+}
+
+/// Rate limit class for a JSON-RPC method on the GBT server
+fn classify_gbt_method(method: &str) -> MethodClass {
+    if method == "getblocktemplate" {
+        MethodClass::Expensive
+    } else {
+        MethodClass::Default
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 enum GrpcServerError {
+    #[error("unable to bind gRPC Unix socket at `{}`", path.display())]
+    #[diagnostic(code(grpc_server::bind_unix_socket))]
+    BindUnixSocket {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
     #[error("unable to serve gRPC at `{addr}`")]
     #[diagnostic(code(grpc_server::serve))]
     Serve {
-        addr: SocketAddr,
+        addr: cli::ServeAddr,
         source: tonic::transport::Error,
     },
-    #[error("unable to build reflection service")]
-    #[diagnostic(code(grpc_server::reflection))]
-    Reflection(#[from] tonic_reflection::server::Error),
+    #[error("unable to set permissions of gRPC Unix socket at `{}`", path.display())]
+    #[diagnostic(code(grpc_server::set_unix_socket_permissions))]
+    SetUnixSocketPermissions {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[error("serving gRPC on a Unix socket is not supported on this platform")]
+    #[diagnostic(code(grpc_server::unix_socket_unsupported))]
+    UnixSocketUnsupported,
+}
+
+/// Bind a Unix socket listener, removing a stale socket file at the same
+/// path if one exists. A socket file is only considered stale if
+/// connecting to it is refused.
+/// If `mode` is set, the socket is bound in a private directory next to
+/// `path`, and linked into place once its permissions are set, so that the
+/// socket is never reachable with the permissions from the process umask.
+#[cfg(unix)]
+async fn bind_grpc_unix_socket(
+    path: &std::path::Path,
+    mode: Option<u32>,
+) -> Result<tokio::net::UnixListener, GrpcServerError> {
+    use std::os::unix::fs::{DirBuilderExt as _, FileTypeExt as _, PermissionsExt as _};
+
+    let bind_err = |err: std::io::Error| GrpcServerError::BindUnixSocket {
+        path: path.to_owned(),
+        source: err,
+    };
+    if let Ok(metadata) = std::fs::symlink_metadata(path)
+        && metadata.file_type().is_socket()
+    {
+        // If the socket is in use, binding fails
+        if let Err(err) = tokio::net::UnixStream::connect(path).await
+            && err.kind() == std::io::ErrorKind::ConnectionRefused
+        {
+            tracing::debug!("removing stale gRPC Unix socket at `{}`", path.display());
+            std::fs::remove_file(path).map_err(bind_err)?;
+        }
+    }
+    let Some(mode) = mode else {
+        return tokio::net::UnixListener::bind(path).map_err(bind_err);
+    };
+    let private_dir = path.with_file_name(format!(".grpc-{}.tmp", std::process::id()));
+    if let Err(err) = std::fs::remove_dir_all(&private_dir)
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        return Err(bind_err(err));
+    }
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(bind_err)?;
+    let private_path = private_dir.join("sock");
+    let res = (|| -> Result<_, GrpcServerError> {
+        let listener = tokio::net::UnixListener::bind(&private_path).map_err(bind_err)?;
+        std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(mode)).map_err(
+            |err| GrpcServerError::SetUnixSocketPermissions {
+                path: path.to_owned(),
+                source: err,
+            },
+        )?;
+        // Unlike renaming, linking fails if `path` already exists
+        std::fs::hard_link(&private_path, path).map_err(bind_err)?;
+        Ok(listener)
+    })();
+    if let Err(err) = std::fs::remove_dir_all(&private_dir) {
+        tracing::warn!(
+            "unable to remove temporary directory `{}`: {:#}",
+            private_dir.display(),
+            ErrorChain::new(&err)
+        );
+    }
+    res
 }
 
 async fn run_grpc_server<F: Future<Output = ()>>(
     validator: Either<Validator, Wallet>,
     shutdown_tx: futures::channel::mpsc::Sender<()>,
     shutdown_signal: F,
-    addr: SocketAddr,
+    addr: cli::ServeAddr,
+    unix_socket_mode: Option<u32>,
+    wallet_health_checks: Vec<HealthCheck>,
+    rate_limiter: Arc<RateLimiter>,
+    connection_opts: cli::ServerConnectionConfig,
 ) -> Result<(), GrpcServerError> {
     // Ordering here matters! Order here is from official docs on request IDs tracings
     // https://docs.rs/tower-http/latest/tower_http/request_id/index.html#using-trace
@@ -304,10 +667,19 @@ async fn run_grpc_server<F: Future<Output = ()>>(
                 .on_failure(FailureHandler),
         )
         .layer(propagate_request_id_layer())
+        .layer(RateLimitLayer::new(
+            rate_limiter,
+            classify_grpc_method,
+            |err: &RateLimited| tonic::Status::resource_exhausted(err.to_string()).into_http(),
+        ))
         .into_inner();
 
     let crypto_service = CryptoServiceServer::new(server::crypto::CryptoServiceServer);
     let mut builder = Server::builder()
+        .tcp_keepalive(connection_opts.tcp_keepalive())
+        .http2_keepalive_interval(connection_opts.keepalive_interval())
+        .http2_keepalive_timeout(connection_opts.keepalive_timeout())
+        .max_concurrent_streams(connection_opts.max_concurrent_streams)
         .layer(tracer)
         .add_service(crypto_service)
         .add_service(ValidatorServiceServer::new({
@@ -315,7 +687,13 @@ async fn run_grpc_server<F: Future<Output = ()>>(
                 Either::Left(ref validator) => validator,
                 Either::Right(ref wallet) => wallet.validator(),
             };
-            server::validator::Server::new(validator.clone(), shutdown_tx.clone())
+            server::validator::Server::new(
+                validator.clone(),
+                shutdown_tx.clone(),
+                Arc::new(server::subscription_limit::SubscriptionLimiter::new(
+                    &connection_opts,
+                )),
+            )
         }));
 
     let mut reflection_service_builder = tonic_reflection::server::Builder::configure()
@@ -333,40 +711,97 @@ async fn run_grpc_server<F: Future<Output = ()>>(
 
     let (health_reporter, health_service) = tonic_health::server::health_reporter();
 
-    // Set all services to have the "serving" status.
-    // TODO: somehow expose the health reporter to the running services, and
-    // dynamically update if we're running into issues.
-    for service in [
-        ValidatorServiceServer::<Validator>::NAME,
-        WalletServiceServer::<Wallet>::NAME,
-        CryptoServiceServer::<server::crypto::CryptoServiceServer>::NAME,
-    ] {
-        tracing::debug!("Setting health status for service: {service}");
-        health_reporter
-            .set_service_status(service, tonic_health::ServingStatus::Serving)
-            .await;
+    // The crypto service is always serving. The validator and wallet
+    // services are serving while all of their health checks pass.
+    health_reporter
+        .set_service_status(
+            CryptoServiceServer::<server::crypto::CryptoServiceServer>::NAME,
+            tonic_health::ServingStatus::Serving,
+        )
+        .await;
+    {
+        // If the wallet is disabled, the wallet service only depends on
+        // block processing
+        let (validator, wallet_health_checks) = match validator {
+            Either::Left(ref validator) => (validator, vec![HealthCheck::not_halted(validator)]),
+            Either::Right(ref wallet) => (wallet.validator(), wallet_health_checks),
+        };
+        tokio::spawn(health::report_service_health(
+            health_reporter.clone(),
+            ValidatorServiceServer::<Validator>::NAME,
+            vec![HealthCheck::not_halted(validator)],
+        ));
+        tokio::spawn(health::report_service_health(
+            health_reporter.clone(),
+            WalletServiceServer::<Wallet>::NAME,
+            wallet_health_checks,
+        ));
     }
 
-    tracing::info!("Listening for gRPC on {addr} with reflection");
+    // Reflection is nice to have, but not essential. Serve without it rather
+    // than failing to start.
+    let reflection_service = match reflection_service_builder.build_v1() {
+        Ok(reflection_service) => {
+            tracing::info!("Listening for gRPC on {addr} with reflection");
+            Some(reflection_service)
+        }
+        Err(err) => {
+            tracing::warn!(
+                "unable to build reflection service, serving gRPC without reflection: {:#}",
+                ErrorChain::new(&err)
+            );
+            tracing::info!("Listening for gRPC on {addr}");
+            None
+        }
+    };
 
     let server = builder
-        .add_service(
-            reflection_service_builder
-                .build_v1()
-                .map_err(GrpcServerError::Reflection)?,
-        )
+        .add_optional_service(reflection_service)
         .add_service(health_service);
 
-    server
-        .serve_with_shutdown(addr, shutdown_signal)
-        .await
-        .map_err(|err| GrpcServerError::Serve { addr, source: err })
+    let res = match &addr {
+        cli::ServeAddr::Tcp(socket_addr) => {
+            server
+                .serve_with_shutdown(*socket_addr, shutdown_signal)
+                .await
+        }
+        #[cfg(unix)]
+        cli::ServeAddr::Unix(path) => {
+            let listener = bind_grpc_unix_socket(path, unix_socket_mode).await?;
+            let incoming = futures::stream::unfold(listener, |listener| async move {
+                let conn = listener.accept().await.map(|(stream, _)| stream);
+                Some((conn, listener))
+            });
+            let res = server
+                .serve_with_incoming_shutdown(incoming, shutdown_signal)
+                .await;
+            tracing::debug!("removing gRPC Unix socket at `{}`", path.display());
+            if let Err(err) = std::fs::remove_file(path) {
+                tracing::warn!(
+                    "unable to remove gRPC Unix socket at `{}`: {:#}",
+                    path.display(),
+                    ErrorChain::new(&err)
+                );
+            }
+            res
+        }
+        #[cfg(not(unix))]
+        cli::ServeAddr::Unix(_) => {
+            let _ = unix_socket_mode;
+            return Err(GrpcServerError::UnixSocketUnsupported);
+        }
+    };
+    res.map_err(|err| GrpcServerError::Serve { addr, source: err })
 }
 
 async fn spawn_gbt_server(
     server: cusf_enforcer_mempool::server::Server<Wallet>,
     serve_addr: SocketAddr,
+    rate_limiter: Arc<RateLimiter>,
+    connection_opts: &cli::ServerConnectionConfig,
+    rpc_log_buffer_size: u32,
 ) -> miette::Result<jsonrpsee::server::ServerHandle> {
+    use cusf_enforcer_mempool::server::RpcServer;
     let rpc_server = server.into_rpc();
 
     tracing::info!(
@@ -407,16 +842,34 @@ async fn spawn_gbt_server(
         .into_inner();
 
     let http_middleware = tower::ServiceBuilder::new().layer(tracer);
-    let rpc_middleware = RpcServiceBuilder::new().rpc_logger(1024);
+    let rpc_middleware = RpcServiceBuilder::new()
+        .layer(RedactingRpcLoggerLayer::new(rpc_log_buffer_size))
+        .layer(RpcRateLimitLayer::new(rate_limiter, classify_gbt_method));
 
-    use cusf_enforcer_mempool::server::RpcServer;
-    let handle = jsonrpsee::server::Server::builder()
+    let methods: jsonrpsee::server::Methods = rpc_server.into();
+    let service_builder = jsonrpsee::server::Server::builder()
+        .set_config(json_rpc_server_config(connection_opts))
         .set_http_middleware(http_middleware)
         .set_rpc_middleware(rpc_middleware)
-        .build(serve_addr)
-        .await
-        .map_err(|err| miette!("initialize JSON-RPC server at `{serve_addr}`: {err:#}"))?
-        .start(rpc_server);
+        .to_service_builder();
+    let listener = bind_json_rpc_listener(serve_addr).await?;
+    let (stop_handle, handle) = jsonrpsee::server::stop_channel();
+    spawn_json_rpc_accept_loop(listener, stop_handle.clone(), move |stream, remote_addr| {
+        let service = WithRemoteAddr::new(
+            service_builder
+                .clone()
+                .build(methods.clone(), stop_handle.clone()),
+            remote_addr,
+        );
+        let stopped = stop_handle.clone().shutdown();
+        tokio::spawn(async move {
+            if let Err(err) =
+                jsonrpsee::server::serve_with_graceful_shutdown(stream, service, stopped).await
+            {
+                tracing::debug!(%remote_addr, "GBT connection error: {err:#}");
+            }
+        });
+    });
     Ok(handle)
 }
 
@@ -427,6 +880,9 @@ async fn start_gbt_server(
     sample_block_template: bitcoin_jsonrpsee::client::BlockTemplate,
     mempool: cusf_enforcer_mempool::mempool::MempoolSync<Wallet>,
     serve_addr: SocketAddr,
+    rate_limiter: Arc<RateLimiter>,
+    connection_opts: &cli::ServerConnectionConfig,
+    rpc_log_buffer_size: u32,
 ) -> miette::Result<jsonrpsee::server::ServerHandle> {
     let gbt_server = cusf_enforcer_mempool::server::Server::new(
         mining_reward_address.script_pubkey(),
@@ -436,7 +892,14 @@ async fn start_gbt_server(
         sample_block_template,
     )
     .into_diagnostic()?;
-    let gbt_server_handle = spawn_gbt_server(gbt_server, serve_addr).await?;
+    let gbt_server_handle = spawn_gbt_server(
+        gbt_server,
+        serve_addr,
+        rate_limiter,
+        connection_opts,
+        rpc_log_buffer_size,
+    )
+    .await?;
     Ok(gbt_server_handle)
 }
 
@@ -545,6 +1008,102 @@ where
     Ok(mempool)
 }
 
+/// Maximum backoff between mempool sync retries
+const MEMPOOL_SYNC_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Run mempool sync until the shutdown signal is received.
+/// If the mempool sync task fails, eg. due to a ZMQ disconnect, the ZMQ
+/// subscription is re-established and the mempool is resynced, with
+/// exponential backoff. The error is only sent via `err_tx` once
+/// `max_retries` consecutive attempts have failed.
+/// `on_synced` is called with each synced mempool, and may return a server
+/// handle that is stopped if the mempool sync fails.
+async fn mempool_sync_task<Enforcer, RpcClient, Signal, OnSynced, OnSyncedFut>(
+    enforcer: Enforcer,
+    rpc_client: RpcClient,
+    zmq_addr_sequence: &str,
+    cli: &cli::Config,
+    err_tx: oneshot::Sender<MempoolTaskError<Enforcer>>,
+    shutdown_signal: Signal,
+    mut on_synced: OnSynced,
+) -> Result<()>
+where
+    Enforcer: cusf_enforcer_mempool::cusf_enforcer::CusfEnforcer + Clone + Send + Sync + 'static,
+    RpcClient: bitcoin_jsonrpsee::client::MainClient + Clone + Send + Sync + 'static,
+    Signal: Future<Output = ()> + Clone + Send,
+    OnSynced: FnMut(cusf_enforcer_mempool::mempool::MempoolSync<Enforcer>) -> OnSyncedFut,
+    OnSyncedFut: Future<Output = Result<Option<jsonrpsee::server::ServerHandle>>>,
+{
+    let initial_backoff = Duration::from_secs(cli.mempool_sync_retry_backoff_secs);
+    let mut synced_once = false;
+    let mut failed_attempts: u32 = 0;
+    loop {
+        let (sync_err_tx, sync_err_rx) = oneshot::channel();
+        let err = match sync_mempool(
+            enforcer.clone(),
+            rpc_client.clone(),
+            zmq_addr_sequence,
+            sync_err_tx,
+            shutdown_signal.clone(),
+        )
+        .await
+        {
+            Ok(mempool) => {
+                synced_once = true;
+                failed_attempts = 0;
+                let server_handle = on_synced(mempool).await?;
+                let err = tokio::select! {
+                    () = shutdown_signal.clone() => None,
+                    Ok(err) = sync_err_rx => Some(err),
+                };
+                if let Some(server_handle) = server_handle {
+                    // This should never fail. The only failure mode is the
+                    // server already being stopped, and we have full control
+                    // over that.
+                    if let Err(err) = server_handle.stop() {
+                        tracing::error!("error stopping JSON-RPC server: {err:#}");
+                    }
+                    server_handle.stopped().await;
+                }
+                match err {
+                    Some(err) => err,
+                    None => return Ok(()),
+                }
+            }
+            // Fail fast if the mempool has never synced, eg. due to
+            // misconfiguration
+            Err(err) if !synced_once => return Err(err.into()),
+            Err(err) => err,
+        };
+        if failed_attempts >= cli.mempool_sync_max_retries {
+            tracing::error!(
+                failed_attempts,
+                "mempool sync failed, no retries remaining: {:#}",
+                ErrorChain::new(&err)
+            );
+            let _send_err: Result<(), _> = err_tx.send(err);
+            return Ok(());
+        }
+        let backoff = backoff::exponential_backoff(
+            initial_backoff,
+            failed_attempts,
+            MEMPOOL_SYNC_MAX_BACKOFF,
+        );
+        failed_attempts += 1;
+        tracing::warn!(
+            attempt = failed_attempts,
+            max_retries = cli.mempool_sync_max_retries,
+            ?backoff,
+            "mempool sync failed, resyncing mempool after backoff: {:#}",
+            ErrorChain::new(&err)
+        );
+        tokio::select! {
+            () = shutdown_signal.clone() => return Ok(()),
+            () = tokio::time::sleep(backoff) => (),
+        }
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 enum EnforcerTaskErr {
     #[error(transparent)]
@@ -579,9 +1138,7 @@ struct ErrRxs {
     shutdown_tx: futures::channel::mpsc::Sender<()>,
 }
 
-async fn get_zmq_addr_sequence(
-    mainchain_client: bitcoin_jsonrpsee::jsonrpsee::http_client::HttpClient,
-) -> Result<String> {
+async fn get_zmq_addr_sequence(mainchain_client: rpc_client::MainchainClient) -> Result<String> {
     let notifications = mainchain_client
         .get_zmq_notifications()
         .await
@@ -615,12 +1172,148 @@ async fn get_zmq_addr_sequence(
     Ok(address)
 }
 
+/// Check that Bitcoin Core publishes `hashblock` notifications, and that
+/// the configured ZMQ address for them is reachable
+async fn check_zmq_addr_hashblock(
+    mainchain_client: &rpc_client::MainchainClient,
+    zmq_addr_hashblock: &str,
+) -> Result<()> {
+    let notifications = mainchain_client
+        .get_zmq_notifications()
+        .await
+        .map_err(|err| BitcoinCoreRPC {
+            method: "getzmqnotifications".to_string(),
+            error: err,
+        })?;
+    let hashblock_addrs: Vec<_> = notifications
+        .iter()
+        .filter(|n| n.notification_type == "pubhashblock")
+        .map(|n| n.address.as_str())
+        .collect();
+    if hashblock_addrs.is_empty() {
+        #[derive(Debug, Diagnostic, Error)]
+        #[error(
+            "unable to find ZMQ notification for `pubhashblock` in `getzmqnotifications` response"
+        )]
+        #[diagnostic(
+            help(
+                "Your Bitcoin Core instance is not configured to send ZMQ notifications for the `pubhashblock` notification type. Set `-zmqpubhashblock`, or unset `--node-zmq-addr-hashblock`"
+            ),
+            code(bip300301_enforcer::zmq_pubhashblock_notification_missing)
+        )]
+        struct ZmqNotificationMissing;
+
+        return Err(ZmqNotificationMissing.into());
+    }
+    if !hashblock_addrs.contains(&zmq_addr_hashblock) {
+        tracing::warn!(
+            %zmq_addr_hashblock,
+            "`--node-zmq-addr-hashblock` does not match any `pubhashblock` address in `getzmqnotifications` response: {}",
+            hashblock_addrs.join(", ")
+        );
+    }
+    tracing::debug!(%zmq_addr_hashblock, "Ensuring ZMQ address for `hashblock` is reachable");
+    match is_address_port_open(zmq_addr_hashblock).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(miette!(
+            "ZMQ address for `hashblock` notifications is not reachable: {zmq_addr_hashblock}"
+        )),
+        Err(err) => Err(miette!(
+            "failed to check if ZMQ address is reachable: failed to connect to {zmq_addr_hashblock}: {err:#}"
+        )),
+    }
+}
+
+/// Check that the wallet is initialized, as required for wallet-based
+/// mempool sync, following the configured policy if it is not.
+/// Returns `false` if the shutdown signal is received while waiting.
+async fn wallet_initialized_or_wait<Signal>(
+    wallet: &Wallet,
+    policy: cli::UninitializedWalletPolicy,
+    shutdown_signal: Signal,
+) -> Result<bool>
+where
+    Signal: Future<Output = ()>,
+{
+    const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+    let mut shutdown_signal = std::pin::pin!(shutdown_signal);
+    while !wallet.is_initialized().await {
+        match policy {
+            cli::UninitializedWalletPolicy::Fail => {
+                return Err(miette!(
+                    "Wallet-based mempool sync requires an initialized wallet! Create one with the CreateWallet RPC method, or set `--wallet-uninitialized-policy wait`."
+                ));
+            }
+            cli::UninitializedWalletPolicy::Wait => {
+                tracing::warn!(
+                    "mempool sync task w/wallet: wallet is not initialized, mining is paused until the wallet is created or unlocked. Checking again in {RETRY_INTERVAL:?}"
+                );
+                tokio::select! {
+                    () = tokio::time::sleep(RETRY_INTERVAL) => (),
+                    () = &mut shutdown_signal => return Ok(false),
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Get the address for block reward payment, following the configured
+/// policy if the wallet is unable to provide one.
+/// Returns `None` if the shutdown signal is received while retrying.
+async fn get_mining_reward_address<Signal>(
+    wallet: &Wallet,
+    mining_opts: &cli::MiningConfig,
+    shutdown_signal: Signal,
+) -> Result<Option<bitcoin::Address>>
+where
+    Signal: Future<Output = ()>,
+{
+    const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+    if let Some(coinbase_recipient) = &mining_opts.coinbase_recipient {
+        return Ok(Some(coinbase_recipient.clone()));
+    }
+    let mut shutdown_signal = std::pin::pin!(shutdown_signal);
+    loop {
+        let err = match wallet.get_new_address().await {
+            Ok(address) => return Ok(Some(address)),
+            Err(err) => miette::Report::from_err(err),
+        };
+        match mining_opts.coinbase_recipient_policy {
+            cli::CoinbaseRecipientPolicy::Fail => {
+                return Err(err.wrap_err("failed to get mining reward address"));
+            }
+            cli::CoinbaseRecipientPolicy::Retry => {
+                tracing::warn!(
+                    "failed to get mining reward address, retrying in {RETRY_INTERVAL:?}: {err:#}"
+                );
+                tokio::select! {
+                    () = tokio::time::sleep(RETRY_INTERVAL) => (),
+                    () = &mut shutdown_signal => return Ok(None),
+                }
+            }
+            cli::CoinbaseRecipientPolicy::Fallback => {
+                let Some(fallback) = &mining_opts.coinbase_recipient_fallback else {
+                    return Err(
+                        err.wrap_err("failed to get mining reward address, and no fallback is set")
+                    );
+                };
+                tracing::warn!(
+                    %fallback,
+                    "failed to get mining reward address, using fallback: {err:#}"
+                );
+                return Ok(Some(fallback.clone()));
+            }
+        }
+    }
+}
+
 /// Returns a join handle for the main task, a shared future for the shutdown signal,
 /// and error receivers for the main task sub components
 async fn spawn_task(
     enforcer: Either<Validator, Wallet>,
     cli: cli::Config,
-    mainchain_client: bitcoin_jsonrpsee::jsonrpsee::http_client::HttpClient,
+    mainchain_client: rpc_client::MainchainClient,
     network: bitcoin::Network,
 ) -> Result<(
     JoinHandle<Result<()>>,
@@ -685,15 +1378,16 @@ async fn spawn_task(
             let shutdown_signal = shutdown_signal.clone();
             let task_handle = tokio::task::spawn(async move {
                 tracing::info!("mempool sync task w/validator: starting");
-                let _mempool = sync_mempool(
+                mempool_sync_task(
                     validator,
                     mainchain_client,
                     &node_zmq_addr_sequence,
+                    &cli,
                     enforcer_task_err_tx,
                     shutdown_signal,
+                    |_mempool| async { Ok(None) },
                 )
-                .await?;
-                Ok(())
+                .await
             });
             (
                 task_handle,
@@ -708,68 +1402,80 @@ async fn spawn_task(
                 // A pre-requisite for the mempool sync task is that the wallet is
                 // initialized and unlocked. Give a nice error message if this is not
                 // the case!
-                if !wallet.is_initialized().await {
-                    return Err(miette!(
-                        "Wallet-based mempool sync requires an initialized wallet! Create one with the CreateWallet RPC method."
-                    ));
+                if cli.wallet_opts.require_unlock {
+                    tracing::info!("mempool sync task w/wallet: waiting for wallet unlock");
+                    tokio::select! {
+                        () = wallet.wait_until_unlocked() => (),
+                        () = shutdown_signal.clone() => return Ok(()),
+                    }
+                } else if !wallet_initialized_or_wait(
+                    &wallet,
+                    cli.wallet_opts.uninitialized_policy,
+                    shutdown_signal.clone(),
+                )
+                .await?
+                {
+                    return Ok(());
+                }
+                if cli.wallet_opts.initial_sync_timeout_secs.is_some() {
+                    tracing::info!("mempool sync task w/wallet: waiting for initial wallet sync");
+                    tokio::select! {
+                        () = wallet.wait_until_synced() => (),
+                        () = shutdown_signal.clone() => return Ok(()),
+                    }
                 }
 
-                let mining_reward_address = match cli.mining_opts.coinbase_recipient {
-                    Some(mining_reward_address) => Ok(mining_reward_address),
-                    None => wallet.get_new_address().await,
+                let Some(mining_reward_address) =
+                    get_mining_reward_address(&wallet, &cli.mining_opts, shutdown_signal.clone())
+                        .await?
+                else {
+                    return Ok(());
                 };
-
-                let mining_reward_address = match mining_reward_address {
-                    Ok(mining_reward_address) => mining_reward_address,
+                tracing::info!(%mining_reward_address, "using mining reward address");
+                let network_info = match mainchain_client.get_network_info().await {
+                    Ok(network_info) => network_info,
                     Err(err) => {
                         let err = miette::Report::from_err(err);
-                        return Err(err.wrap_err("failed to get mining reward address"));
+                        return Err(err.wrap_err("failed to get network info"));
                     }
                 };
-                let network_info = match mainchain_client.get_network_info().await {
-                    Ok(network_info) => network_info,
+                let sample_block_template = match get_block_template(
+                    &mainchain_client,
+                    network,
+                    &cli.mining_opts.block_template_rules,
+                )
+                .await
+                {
+                    Ok(block_template) => block_template,
                     Err(err) => {
                         let err = miette::Report::from_err(err);
-                        return Err(err.wrap_err("failed to get network info"));
+                        return Err(err.wrap_err("failed to get sample block template"));
                     }
                 };
-                let sample_block_template =
-                    match get_block_template(&mainchain_client, network).await {
-                        Ok(block_template) => block_template,
-                        Err(err) => {
-                            let err = miette::Report::from_err(err);
-                            return Err(err.wrap_err("failed to get sample block template"));
-                        }
-                    };
-                let mempool = sync_mempool(
+                let gbt_rate_limiter = Arc::new(RateLimiter::new(&cli.rate_limit_opts));
+                mempool_sync_task(
                     wallet,
                     mainchain_client,
                     &node_zmq_addr_sequence,
+                    &cli,
                     enforcer_task_err_tx,
-                    shutdown_signal.clone(),
-                )
-                .await?;
-
-                let server_handle = start_gbt_server(
-                    mining_reward_address,
-                    network,
-                    network_info,
-                    sample_block_template,
-                    mempool,
-                    cli.serve_rpc_addr,
+                    shutdown_signal,
+                    |mempool| {
+                        start_gbt_server(
+                            mining_reward_address.clone(),
+                            network,
+                            network_info.clone(),
+                            sample_block_template.clone(),
+                            mempool,
+                            cli.serve_rpc_addr,
+                            gbt_rate_limiter.clone(),
+                            &cli.server_connection_opts,
+                            cli.logger_opts.rpc_log_buffer_size,
+                        )
+                        .map_ok(Some)
+                    },
                 )
-                .await?;
-
-                shutdown_signal.clone().await;
-
-                tracing::debug!("stopping `getblocktemplate` JSON-RPC server");
-
-                // This should never fail. The only failure mode is the server
-                // already being stopped, and we have full control over that.
-                if let Err(err) = server_handle.stop() {
-                    tracing::error!("error stopping `getblocktemplate` JSON-RPC server: {err:#}");
-                }
-                Ok(())
+                .await
             });
             (
                 task_handle,
@@ -778,16 +1484,29 @@ async fn spawn_task(
         }
     };
 
+    let wallet_health_checks = match &enforcer {
+        Either::Left(_) => Vec::new(),
+        Either::Right(wallet) => health::wallet_health_checks(wallet, &cli.wallet_opts),
+    };
     let (grpc_server_err_tx, grpc_server_err_rx) = oneshot::channel();
     let _grpc_server_task: JoinHandle<()> = {
         let shutdown_signal = shutdown_signal.clone();
         let shutdown_tx = shutdown_tx.clone();
         tokio::task::spawn(
-            run_grpc_server(enforcer, shutdown_tx, shutdown_signal, cli.serve_grpc_addr)
-                .inspect(|_| tracing::info!("gRPC server finished"))
-                .unwrap_or_else(|err| {
-                    let _send_err = grpc_server_err_tx.send(err);
-                }),
+            run_grpc_server(
+                enforcer,
+                shutdown_tx,
+                shutdown_signal,
+                cli.serve_grpc_addr.clone(),
+                cli.serve_grpc_unix_socket_mode,
+                wallet_health_checks,
+                Arc::new(RateLimiter::new(&cli.rate_limit_opts)),
+                cli.server_connection_opts.clone(),
+            )
+            .inspect(|_| tracing::info!("gRPC server finished"))
+            .unwrap_or_else(|err| {
+                let _send_err = grpc_server_err_tx.send(err);
+            }),
         )
     };
 
@@ -800,6 +1519,164 @@ async fn spawn_task(
     Ok((task_handle, shutdown_signal, err_rxs))
 }
 
+/// Run the initial wallet sync, failing if it does not complete within
+/// `timeout`
+async fn initial_wallet_sync(wallet: &Wallet, full_scan: bool, timeout: Duration) -> Result<()> {
+    tracing::info!("waiting up to {timeout:?} for initial wallet sync");
+    match tokio::time::timeout(timeout, wallet.initial_sync(full_scan)).await {
+        Ok(res) => {
+            res.map_err(|err| miette::Report::from_err(err).wrap_err("initial wallet sync failed"))
+        }
+        Err(_elapsed) => Err(
+            miette::Report::from_err(wallet::error::NotSynced).wrap_err(format!(
+                "initial wallet sync did not complete within {timeout:?}"
+            )),
+        ),
+    }
+}
+
+/// Run a wallet full scan. Cancelling the full scan (via
+/// `wallet.cancel_full_scan`) is not an error.
+async fn full_scan_unless_cancelled(wallet: &Wallet) -> Result<()> {
+    match wallet.full_scan().await {
+        Ok(_) | Err(wallet::error::FullScan::Cancelled) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns `true` if binding both addresses would fail, ie. if they have the
+/// same port, and the same IP or either IP is a wildcard address
+fn bind_addrs_conflict(lhs: &SocketAddr, rhs: &SocketAddr) -> bool {
+    lhs.port() == rhs.port()
+        && (lhs.ip() == rhs.ip() || lhs.ip().is_unspecified() || rhs.ip().is_unspecified())
+}
+
+/// Check that servers that will be started are configured to bind different
+/// addresses, that do not conflict with the Bitcoin Core RPC or ZMQ
+/// addresses if Bitcoin Core is on the same host. The `getblocktemplate`
+/// server on `--serve-rpc-addr` is only started if both the wallet and
+/// mempool are enabled.
+/// Addresses with port 0 are assigned a free port when bound, and are
+/// ignored.
+fn check_serve_addrs(cli: &cli::Config) -> Result<()> {
+    let mut serve_addrs = Vec::new();
+    // Unix socket paths cannot conflict with TCP addresses
+    if let cli::ServeAddr::Tcp(serve_grpc_addr) = cli.serve_grpc_addr {
+        serve_addrs.push(("--serve-grpc-addr", serve_grpc_addr));
+    }
+    serve_addrs.push(("--serve-json-rpc-addr", cli.serve_json_rpc_addr));
+    if cli.enable_wallet && cli.enable_mempool {
+        serve_addrs.push(("--serve-rpc-addr", cli.serve_rpc_addr));
+    }
+    // Addresses bound by Bitcoin Core, if it is on the same host. ZMQ
+    // endpoints that are not `tcp://IP:PORT` are ignored.
+    let node_zmq_addrs = [
+        ("--node-zmq-addr-sequence", &cli.node_zmq_addr_sequence),
+        ("--node-zmq-addr-hashblock", &cli.node_zmq_addr_hashblock),
+    ];
+    let node_addrs = std::iter::once(("--node-rpc-addr", Some(cli.node_rpc_opts.addr)))
+        .chain(node_zmq_addrs.into_iter().filter_map(|(arg, zmq_addr)| {
+            let zmq_addr = zmq_addr
+                .as_ref()?
+                .strip_prefix("tcp://")
+                .and_then(|addr| addr.parse::<SocketAddr>().ok());
+            Some((arg, zmq_addr))
+        }))
+        .filter_map(|(arg, addr)| Some((arg, addr?)))
+        .filter(|(_, addr)| addr.ip().is_loopback());
+    for (idx, (arg, addr)) in serve_addrs.iter().enumerate() {
+        if addr.port() == 0 {
+            continue;
+        }
+        if let Some((other_arg, other_addr)) = serve_addrs[..idx]
+            .iter()
+            .find(|(_, other_addr)| bind_addrs_conflict(other_addr, addr))
+        {
+            return Err(miette!(
+                "`{other_arg}` (`{other_addr}`) and `{arg}` (`{addr}`) conflict, servers must be served on different addresses"
+            ));
+        }
+        if let Some((node_arg, node_addr)) = node_addrs
+            .clone()
+            .find(|(_, node_addr)| bind_addrs_conflict(node_addr, addr))
+        {
+            return Err(miette!(
+                "`{arg}` (`{addr}`) conflicts with Bitcoin Core's address, `{node_arg}` (`{node_addr}`)"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that deposits considered final cannot be reorged away, ie. that
+/// the deposit finality depth is greater than the maximum reorg depth
+fn check_deposit_finality_depth(cli: &cli::Config) -> Result<()> {
+    if let Some(deposit_finality_depth) = cli.deposit_finality_depth
+        && let Some(max_reorg_depth) = cli.max_reorg_depth
+        && deposit_finality_depth <= max_reorg_depth
+    {
+        return Err(miette!(
+            "`--deposit-finality-depth` ({deposit_finality_depth}) must be greater than `--max-reorg-depth` ({max_reorg_depth})"
+        ));
+    }
+    Ok(())
+}
+
+/// The GBT server builds the coinbase reward output itself, from a single
+/// script, so the coinbase reward split does not apply to it. Reject the
+/// split if the GBT server is enabled, rather than ignoring it.
+fn check_coinbase_reward_split(cli: &cli::Config) -> Result<()> {
+    if cli.enable_wallet && cli.enable_mempool && !cli.mining_opts.coinbase_reward_split.is_empty()
+    {
+        return Err(miette!(
+            "`--coinbase-reward-split` is not supported with `--enable-mempool`, as the GBT server pays the block reward to a single address. Use `--signet-miner-coinbase-recipient` instead."
+        ));
+    }
+    Ok(())
+}
+
+/// Check that configured coinbase recipient addresses are valid for the
+/// network that the mainchain node is on. Addresses are only checked
+/// syntactically when parsed, since the network is not known until then.
+fn check_coinbase_recipients(
+    mining_opts: &cli::MiningConfig,
+    network: bitcoin::Network,
+) -> Result<()> {
+    let addresses = [
+        (
+            "--signet-miner-coinbase-recipient",
+            mining_opts
+                .coinbase_recipient
+                .as_ref()
+                .map(|addr| addr.as_unchecked()),
+        ),
+        (
+            "--signet-miner-coinbase-recipient-fallback",
+            mining_opts
+                .coinbase_recipient_fallback
+                .as_ref()
+                .map(|addr| addr.as_unchecked()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(arg, addr)| Some((arg, addr?)))
+    .chain(
+        mining_opts
+            .coinbase_reward_split
+            .iter()
+            .map(|recipient| ("--coinbase-reward-split", &recipient.address)),
+    );
+    for (arg, addr) in addresses {
+        if !addr.is_valid_for_network(network) {
+            return Err(miette!(
+                "`{arg}` address `{}` is not valid for the mainchain network ({network})",
+                addr.clone().assume_checked()
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let (mut self_interrupt_tx, mut self_interrupt_rx) = futures::channel::mpsc::unbounded();
@@ -821,17 +1698,29 @@ async fn main() -> Result<()> {
                 None => format!("{:#?}", info.payload()).to_string(),
             },
         };
-        tracing::error!(location, "Panicked during execution: `{payload}`");
+        // Only present if the panic happened within a request span
+        let request_id = current_span_request_id();
+        tracing::error!(
+            location,
+            request_id = request_id.as_deref(),
+            "Panicked during execution: `{payload}`"
+        );
         default_hook(info); // Panics are bad. re-throw!
     }));
 
     let cli = cli::Config::parse();
+    let () = check_serve_addrs(&cli)?;
+    let () = check_deposit_finality_depth(&cli)?;
+    let () = check_coinbase_reward_split(&cli)?;
     // Assign the tracing guard to a variable so that it is dropped when the end of main is reached.
-    let _tracing_guard = set_tracing_subscriber(
-        cli.log_formatter(),
-        cli.logger_opts.level,
-        cli.rolling_log_appender()?,
-    )?;
+    let rolling_log_appender = if cli.logger_opts.disable_file {
+        None
+    } else {
+        Some(cli.rolling_log_appender()?)
+    };
+    let (_tracing_guard, admin_server) =
+        set_tracing_subscriber(cli.log_formatter(), &cli.logger_opts, rolling_log_appender)?;
+    let admin_server = admin_server.with_config(cli.clone());
     tracing::info!(
         data_dir = %cli.data_dir.display(),
         log_dir = %cli.log_dir().display(),
@@ -870,44 +1759,70 @@ async fn main() -> Result<()> {
         cli.node_rpc_opts.user.as_deref().unwrap_or("cookie"),
         cli.node_rpc_opts.addr,
     );
+    // Bitcoin Core creates a new cookie each time it starts, so the client's
+    // credentials must be kept up to date. The watcher is spawned before the
+    // shutdown signal exists.
+    let (cookie_watcher_shutdown_tx, cookie_watcher_shutdown_rx) = oneshot::channel::<()>();
+    if cli.node_rpc_opts.cookie_path.is_some() {
+        tokio::spawn(rpc_client::watch_cookie_file(
+            mainchain_client.clone(),
+            cli.node_rpc_opts.clone(),
+            cli.enable_wallet && cli.enable_mempool,
+            cookie_watcher_shutdown_rx.map(|_| ()),
+        ));
+    }
 
-    let mut info = None;
-    while info.is_none() {
-        // From Bitcoin Core src/rpc/protocol.h
-        const RPC_IN_WARMUP: i32 = -28;
-
+    // Poll interval while Bitcoin Core is warming up, doubled after each
+    // attempt up to the maximum
+    const WARMUP_POLL_INITIAL_DELAY: Duration = Duration::from_millis(250);
+    const WARMUP_POLL_MAX_DELAY: Duration = Duration::from_secs(10);
+    let warmup_timeout = Duration::from_secs(cli.node_rpc_opts.warmup_timeout_secs);
+    let warmup_start = tokio::time::Instant::now();
+    let mut delay = WARMUP_POLL_INITIAL_DELAY;
+    let info = loop {
         // If Bitcoin Core is booting up, we don't want to fail hard.
         // Check for errors that should go away after a little while,
         // and tolerate those.
         match mainchain_client.get_blockchain_info().await {
-            Ok(inner_info) => {
-                info = Some(inner_info);
-                Ok(())
+            Ok(info) => break info,
+            Err(Error::Call(err)) if err.code() == rpc_client::RPC_IN_WARMUP => {
+                if warmup_start.elapsed() >= warmup_timeout {
+                    return Err(miette!(
+                        "Bitcoin Core at `{}` is still warming up after {:?} ({}). Set `--node-rpc-warmup-timeout-secs` to wait longer",
+                        cli.node_rpc_opts.addr,
+                        warmup_timeout,
+                        err.message(),
+                    ));
+                }
+                if delay == WARMUP_POLL_INITIAL_DELAY {
+                    tracing::info!(
+                        status = err.message(),
+                        "Waiting for Bitcoin Core to finish warming up",
+                    );
+                } else {
+                    tracing::debug!(
+                        status = err.message(),
+                        retry_in = ?delay,
+                        "Bitcoin Core is warming up, retrying...",
+                    );
+                }
             }
-
-            Err(Error::Call(err)) if err.code() == RPC_IN_WARMUP => {
-                tracing::debug!(
-                    err = format!("{}: {}", err.code(), err.message()),
-                    "Transient Bitcoin Core error, retrying...",
-                );
-                Ok(())
+            Err(err @ (Error::Transport(_) | Error::RequestTimeout)) => {
+                return Err(miette!(
+                    "Bitcoin Core JSON-RPC server at `{}` is unreachable, check `--node-rpc-addr` and credentials: {err:#}",
+                    cli.node_rpc_opts.addr,
+                ));
+            }
+            Err(err) => {
+                let err = wallet::error::BitcoinCoreRPC {
+                    method: "getblockchaininfo".to_string(),
+                    error: err,
+                };
+                return Err(miette!("failed to get blockchain info: {err:#}"));
             }
-
-            Err(err) => Err(wallet::error::BitcoinCoreRPC {
-                method: "getblockchaininfo".to_string(),
-                error: err,
-            }),
         }
-        .map_err(|err| miette!("failed to get blockchain info: {err:#}"))?;
-
-        let delay = tokio::time::Duration::from_millis(250);
         tokio::time::sleep(delay).await;
-    }
-
-    let Some(info) = info else {
-        return Err(miette!(
-            "was never able to query bitcoin core blockchain info"
-        ));
+        delay = (delay * 2).min(WARMUP_POLL_MAX_DELAY);
     };
 
     tracing::info!(
@@ -915,6 +1830,15 @@ async fn main() -> Result<()> {
         blocks = %info.blocks,
         "Connected to mainchain client",
     );
+    let () = check_coinbase_recipients(&cli.mining_opts, info.chain)?;
+    if let Some(address) = &cli.wallet_opts.coinbase_consolidation_address
+        && !address.as_unchecked().is_valid_for_network(info.chain)
+    {
+        return Err(miette!(
+            "`--wallet-coinbase-consolidation-address` address `{address}` is not valid for the mainchain network ({})",
+            info.chain
+        ));
+    }
 
     // Both wallet data and validator data are stored under the same root
     // directory. Add a subdirectories to clearly indicate which
@@ -927,16 +1851,40 @@ async fn main() -> Result<()> {
         std::fs::create_dir_all(data_dir).into_diagnostic()?;
     }
 
+    if let Some(snapshot_path) = &cli.import_validator_snapshot {
+        let _header = Validator::import_snapshot(snapshot_path, &validator_data_dir, info.chain)?;
+    }
+
     let validator = Validator::new(
         mainchain_client.clone(),
         mainchain_rest_client,
         &validator_data_dir,
         info.chain,
+        cli.max_reorg_depth,
+        cli.deposit_finality_depth(),
     )
     .into_diagnostic()?;
 
+    // The webhook task subscribes to events before the validator starts, so
+    // it is spawned before the shutdown signal exists
+    let (webhook_shutdown_tx, webhook_shutdown_rx) = oneshot::channel::<()>();
+    if let Some(webhook_url) = &cli.webhook_url {
+        tracing::info!("delivering deposit and withdrawal bundle webhooks");
+        let _webhook_task = validator.spawn_webhook_task(
+            webhook_url.clone(),
+            cli.webhook_max_retries,
+            Duration::from_millis(cli.webhook_retry_backoff_ms),
+            webhook_shutdown_rx.map(|_| ()),
+        );
+    }
+
     let signet_challenge = if info.chain == bitcoin::Network::Signet {
-        let block_template = get_block_template(&mainchain_client, info.chain).await?;
+        let block_template = get_block_template(
+            &mainchain_client,
+            info.chain,
+            &cli.mining_opts.block_template_rules,
+        )
+        .await?;
         let Some(signet_challenge) = block_template.signet_challenge else {
             return Err(miette!("signet challenge not found in block template"));
         };
@@ -972,6 +1920,8 @@ async fn main() -> Result<()> {
         None
     };
 
+    let chain_info = server::chain::ChainInfo::new(info.chain, signet_challenge.as_deref());
+
     let enforcer: Either<Validator, Wallet> = if cli.enable_wallet {
         // The wallet needs the txindex in order to operate. Will lead to obscure errors later
         // if we fail RPC requests due to the index not being there.
@@ -991,15 +1941,6 @@ async fn main() -> Result<()> {
         let magic = signet_challenge
             .map(|signet_challenge| compute_signet_magic(&signet_challenge))
             .unwrap_or_else(|| info.chain.magic());
-        let wallet = Wallet::new(
-            &wallet_data_dir,
-            &cli,
-            mainchain_client.clone(),
-            validator,
-            magic,
-        )
-        .await?;
-
         let (mnemonic, auto_create) = match (
             cli.wallet_opts.mnemonic_path.clone(),
             cli.wallet_opts.auto_create,
@@ -1025,6 +1966,16 @@ async fn main() -> Result<()> {
             _ => (None, false),
         };
 
+        let wallet = Wallet::new(
+            &wallet_data_dir,
+            &cli,
+            mainchain_client.clone(),
+            validator,
+            magic,
+            mnemonic.as_ref(),
+        )
+        .await?;
+
         if !wallet.is_initialized().await && auto_create {
             tracing::info!("auto-creating new wallet");
             wallet.create_wallet(mnemonic, None).await?;
@@ -1034,14 +1985,39 @@ async fn main() -> Result<()> {
     } else {
         Either::Left(validator)
     };
+    // `hashblock` notifications are only used to trigger periodic wallet syncs
+    if enforcer.is_right()
+        && !cli.wallet_opts.skip_periodic_sync
+        && cli.wallet_opts.sync_source != WalletSyncSource::Disabled
+        && let Some(zmq_addr_hashblock) = &cli.node_zmq_addr_hashblock
+    {
+        let () = check_zmq_addr_hashblock(&mainchain_client, zmq_addr_hashblock).await?;
+    }
     // Start JSON-RPC server
-    let json_rpc_server_handle = spawn_json_rpc_server(enforcer.clone(), cli.serve_json_rpc_addr)
-        .await
-        .map_err(|err| miette!("Failed to spawn JSON-RPC server: {err:#}"))?;
+    let json_rpc_server_handle = spawn_json_rpc_server(
+        enforcer.clone(),
+        admin_server,
+        chain_info,
+        cli.serve_json_rpc_addr,
+        Arc::new(RateLimiter::new(&cli.rate_limit_opts)),
+        &cli.server_connection_opts,
+        cli.logger_opts.rpc_log_buffer_size,
+    )
+    .await
+    .map_err(|err| miette!("Failed to spawn JSON-RPC server: {err:#}"))?;
 
     let (main_task_handle, shutdown_signal, mut err_rxs) =
         spawn_task(enforcer.clone(), cli.clone(), mainchain_client, info.chain).await?;
 
+    tokio::spawn({
+        let shutdown_signal = shutdown_signal.clone();
+        async move {
+            shutdown_signal.await;
+            let _send_err: Result<(), _> = webhook_shutdown_tx.send(());
+            let _send_err: Result<(), _> = cookie_watcher_shutdown_tx.send(());
+        }
+    });
+
     let json_rpc_handle: JoinHandle<Result<(), miette::Report>> = {
         let shutdown_signal = shutdown_signal.clone();
         tokio::spawn(async move {
@@ -1069,15 +2045,90 @@ async fn main() -> Result<()> {
         // periodic sync. Therefore we expose a knob to disable it.
         let sync_source_disabled = cli.wallet_opts.sync_source == WalletSyncSource::Disabled;
 
-        if cli.wallet_opts.full_scan {
-            wallet.full_scan().await?;
+        let full_scan = cli.wallet_opts.full_scan;
+        let periodic_sync = !cli.wallet_opts.skip_periodic_sync && !sync_source_disabled;
+        let initial_sync_timeout = cli
+            .wallet_opts
+            .initial_sync_timeout_secs
+            .map(Duration::from_secs);
+
+        if cli.wallet_opts.require_unlock {
+            // Neither scanning nor syncing is possible until the wallet is
+            // unlocked, so defer both to a task that waits for the unlock.
+            if full_scan || periodic_sync || initial_sync_timeout.is_some() {
+                let wallet = wallet.clone();
+                let shutdown_signal = shutdown_signal.clone();
+                let handle = tokio::spawn(async move {
+                    tracing::info!("wallet sync task: waiting for wallet unlock");
+                    tokio::select! {
+                        () = wallet.wait_until_unlocked() => (),
+                        () = shutdown_signal.clone() => return Ok(()),
+                    }
+                    if let Some(timeout) = initial_sync_timeout {
+                        initial_wallet_sync(&wallet, full_scan, timeout).await?;
+                    } else if full_scan {
+                        let () = full_scan_unless_cancelled(&wallet).await?;
+                    }
+                    if periodic_sync {
+                        wallet.sync_task(shutdown_signal).await?;
+                    }
+                    Ok(())
+                });
+                wallet_sync_task_handle = Some(handle);
+            }
+        } else {
+            // If the wallet has not been created yet, the first periodic sync
+            // after it is created counts as the initial sync.
+            if let Some(timeout) = initial_sync_timeout
+                && wallet.is_initialized().await
+            {
+                initial_wallet_sync(&wallet, full_scan, timeout).await?;
+            } else if full_scan {
+                let () = full_scan_unless_cancelled(&wallet).await?;
+            }
+
+            if periodic_sync {
+                let wallet = wallet.clone();
+                let shutdown_signal = shutdown_signal.clone();
+                let handle = tokio::spawn(async move { wallet.sync_task(shutdown_signal).await });
+                wallet_sync_task_handle = Some(handle);
+            }
         }
 
-        if !cli.wallet_opts.skip_periodic_sync && !sync_source_disabled {
+        if let Some(zmq_addr) = cli.node_zmq_addr_hashblock.clone()
+            && periodic_sync
+        {
             let wallet = wallet.clone();
             let shutdown_signal = shutdown_signal.clone();
-            let handle = tokio::spawn(async move { wallet.sync_task(shutdown_signal).await });
-            wallet_sync_task_handle = Some(handle);
+            tokio::spawn(async move {
+                wallet
+                    .hashblock_sync_trigger_task(zmq_addr, shutdown_signal)
+                    .await
+            });
+        }
+
+        if let Some(probe_interval_secs) = cli.wallet_opts.chain_source_probe_interval_secs
+            && !sync_source_disabled
+        {
+            let wallet = wallet.clone();
+            let shutdown_signal = shutdown_signal.clone();
+            let probe_interval = Duration::from_secs(probe_interval_secs);
+            tokio::spawn(async move {
+                wallet
+                    .chain_source_monitor_task(probe_interval, shutdown_signal)
+                    .await
+            });
+        }
+
+        if let Some(stale_threshold_secs) = cli.wallet_opts.sync_stale_threshold_secs {
+            let wallet = wallet.clone();
+            let shutdown_signal = shutdown_signal.clone();
+            let stale_threshold = Duration::from_secs(stale_threshold_secs.get());
+            tokio::spawn(async move {
+                wallet
+                    .sync_staleness_monitor_task(stale_threshold, shutdown_signal)
+                    .await
+            });
         }
     }
 
@@ -1308,3 +2359,242 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bip300301_enforcer_lib::rpc_client::mock::{self, MockMainClient};
+
+    use bip300301_enforcer_lib::cli;
+    use clap::Parser as _;
+
+    use super::{
+        check_coinbase_recipients, check_coinbase_reward_split, check_deposit_finality_depth,
+        check_serve_addrs, get_block_template,
+    };
+
+    fn parse_cli(args: &[&str]) -> cli::Config {
+        cli::Config::try_parse_from(
+            std::iter::once("bip300301_enforcer").chain(args.iter().copied()),
+        )
+        .unwrap()
+    }
+
+    /// From Bitcoin Core src/rpc/protocol.h
+    const RPC_CLIENT_IN_INITIAL_DOWNLOAD: i32 = -10;
+
+    fn mock_client() -> MockMainClient {
+        MockMainClient::new().with_error(
+            "getblocktemplate",
+            RPC_CLIENT_IN_INITIAL_DOWNLOAD,
+            "Bitcoin Core is in initial sync and waiting for blocks...",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_block_template_error() {
+        let client = mock_client();
+        let err = get_block_template(&client, bitcoin::Network::Regtest, &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err.method, "getblocktemplate");
+        assert!(matches!(
+            err.error,
+            jsonrpsee::core::client::Error::Call(err) if err.code() == RPC_CLIENT_IN_INITIAL_DOWNLOAD
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_block_template_signet_rule() {
+        for (network, expect_signet_rule) in [
+            (bitcoin::Network::Regtest, false),
+            (bitcoin::Network::Signet, true),
+        ] {
+            let client = mock_client();
+            let _err = get_block_template(&client, network, &[]).await.unwrap_err();
+            let params = block_template_request_params(&client);
+            assert_eq!(params.contains("\"signet\""), expect_signet_rule);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_block_template() {
+        let client =
+            MockMainClient::new().with_response("getblocktemplate", mock::regtest_block_template());
+        let template = get_block_template(&client, bitcoin::Network::Regtest, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            template.prev_blockhash,
+            bitcoin::constants::genesis_block(bitcoin::Network::Regtest).block_hash()
+        );
+        assert_eq!(
+            template.compact_target,
+            bitcoin::CompactTarget::from_consensus(0x207f_ffff)
+        );
+        assert!(template.transactions.is_empty());
+        assert_eq!(template.signet_challenge, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_template_signet_challenge() {
+        let signet_challenge = bitcoin::ScriptBuf::from_hex("51").unwrap();
+        let mut response = mock::regtest_block_template();
+        response["signet_challenge"] = signet_challenge.to_hex_string().into();
+        let client = MockMainClient::new().with_response("getblocktemplate", response);
+        let template = get_block_template(&client, bitcoin::Network::Signet, &[])
+            .await
+            .unwrap();
+        assert_eq!(template.signet_challenge, Some(signet_challenge));
+    }
+
+    /// Params of the only `getblocktemplate` request
+    fn block_template_request_params(client: &MockMainClient) -> String {
+        let requests: Vec<_> = client
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "getblocktemplate")
+            .collect();
+        assert_eq!(requests.len(), 1);
+        requests[0].params.as_ref().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_check_serve_addrs() {
+        // Default addresses do not conflict
+        assert!(check_serve_addrs(&parse_cli(&["--enable-wallet", "--enable-mempool"])).is_ok());
+        // The GBT server is only started with both the wallet and mempool
+        // enabled
+        let same_rpc_addrs = ["--serve-rpc-addr", "127.0.0.1:8123"];
+        assert!(check_serve_addrs(&parse_cli(&same_rpc_addrs)).is_ok());
+        assert!(
+            check_serve_addrs(&parse_cli(
+                &[
+                    same_rpc_addrs.as_slice(),
+                    &["--enable-wallet", "--enable-mempool"]
+                ]
+                .concat()
+            ))
+            .is_err()
+        );
+        assert!(check_serve_addrs(&parse_cli(&["--serve-grpc-addr", "127.0.0.1:8123"])).is_err());
+        // Port 0 binds a free port
+        assert!(
+            check_serve_addrs(&parse_cli(&[
+                "--serve-grpc-addr",
+                "127.0.0.1:0",
+                "--serve-json-rpc-addr",
+                "127.0.0.1:0",
+            ]))
+            .is_ok()
+        );
+        // Wildcard addresses conflict with any address on the same port
+        assert!(check_serve_addrs(&parse_cli(&["--serve-grpc-addr", "0.0.0.0:8123"])).is_err());
+        assert!(check_serve_addrs(&parse_cli(&["--serve-grpc-addr", "[::]:8123"])).is_err());
+        // Addresses must not conflict with a local Bitcoin Core
+        assert!(check_serve_addrs(&parse_cli(&["--serve-grpc-addr", "127.0.0.1:18443"])).is_err());
+        assert!(
+            check_serve_addrs(&parse_cli(&[
+                "--node-zmq-addr-sequence",
+                "tcp://127.0.0.1:29000",
+                "--serve-json-rpc-addr",
+                "0.0.0.0:29000",
+            ]))
+            .is_err()
+        );
+        assert!(
+            check_serve_addrs(&parse_cli(&[
+                "--node-rpc-addr",
+                "192.0.2.1:18443",
+                "--serve-grpc-addr",
+                "127.0.0.1:18443",
+            ]))
+            .is_ok()
+        );
+        // Unix sockets do not conflict with TCP addresses
+        let cli = parse_cli(&["--serve-grpc-addr", "unix:/tmp/enforcer.sock"]);
+        assert_eq!(
+            cli.serve_grpc_addr,
+            cli::ServeAddr::Unix("/tmp/enforcer.sock".into())
+        );
+        assert!(check_serve_addrs(&cli).is_ok());
+        assert!(
+            cli::Config::try_parse_from(["bip300301_enforcer", "--serve-grpc-addr", "unix:"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_deposit_finality_depth() {
+        let cli = parse_cli(&[]);
+        assert!(check_deposit_finality_depth(&cli).is_ok());
+        assert_eq!(cli.deposit_finality_depth(), None);
+        // Defaults to one more than the max reorg depth
+        let cli = parse_cli(&["--max-reorg-depth", "6"]);
+        assert!(check_deposit_finality_depth(&cli).is_ok());
+        assert_eq!(
+            cli.deposit_finality_depth().map(|depth| depth.get()),
+            Some(7)
+        );
+        let cli = parse_cli(&["--max-reorg-depth", "6", "--deposit-finality-depth", "10"]);
+        assert!(check_deposit_finality_depth(&cli).is_ok());
+        assert_eq!(
+            cli.deposit_finality_depth().map(|depth| depth.get()),
+            Some(10)
+        );
+        let cli = parse_cli(&["--max-reorg-depth", "6", "--deposit-finality-depth", "6"]);
+        assert!(check_deposit_finality_depth(&cli).is_err());
+    }
+
+    #[test]
+    fn test_check_coinbase_recipients() {
+        let signet_addr = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+        let mainnet_addr = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        for (arg, addr, expect_ok) in [
+            ("--signet-miner-coinbase-recipient", signet_addr, true),
+            ("--signet-miner-coinbase-recipient", mainnet_addr, false),
+            (
+                "--signet-miner-coinbase-recipient-fallback",
+                mainnet_addr,
+                false,
+            ),
+        ] {
+            let cli = parse_cli(&[arg, addr]);
+            assert_eq!(
+                check_coinbase_recipients(&cli.mining_opts, bitcoin::Network::Signet).is_ok(),
+                expect_ok,
+                "{arg} {addr}"
+            );
+        }
+        let cli = parse_cli(&["--coinbase-reward-split", &format!("{mainnet_addr}:1")]);
+        assert!(check_coinbase_recipients(&cli.mining_opts, bitcoin::Network::Bitcoin).is_ok());
+        assert!(check_coinbase_recipients(&cli.mining_opts, bitcoin::Network::Signet).is_err());
+    }
+
+    #[test]
+    fn test_check_coinbase_reward_split() {
+        let addr = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        let single = format!("{addr}:1");
+        let split = format!("{addr}:1,{addr}:2");
+        assert!(
+            check_coinbase_reward_split(&parse_cli(&["--coinbase-reward-split", &split])).is_ok()
+        );
+        assert!(
+            check_coinbase_reward_split(&parse_cli(&[
+                "--enable-wallet",
+                "--enable-mempool",
+                "--coinbase-reward-split",
+                &single,
+            ]))
+            .is_err()
+        );
+        assert!(
+            check_coinbase_reward_split(&parse_cli(&[
+                "--enable-wallet",
+                "--enable-mempool",
+                "--coinbase-reward-split",
+                &split,
+            ]))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! Per-client rate limiting for the gRPC and JSON-RPC servers
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bip300301_enforcer_lib::cli::RateLimitConfig;
+use futures::future::Either;
+use jsonrpsee::{
+    MethodResponse,
+    core::middleware::{Batch, BatchEntry, Notification, RpcServiceT},
+    types::{ErrorObject, Id, Request},
+};
+use thiserror::Error;
+use tokio::time::Instant;
+
+/// JSON-RPC error code for rate limited calls
+const RATE_LIMITED_JSON_RPC_CODE: i32 = -32005;
+
+/// Methods are rate limited separately for each class
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MethodClass {
+    Default,
+    /// Methods that are expensive to serve
+    Expensive,
+}
+
+/// Prune expired entries once this many clients are being tracked
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Rate limit for a single method class.
+/// Allows bursts of up to one second's worth of requests.
+#[derive(Clone, Copy, Debug)]
+struct Limit {
+    /// Time between requests, at the sustained rate
+    interval: Duration,
+    /// Maximum time that requests may run ahead of the sustained rate
+    burst: Duration,
+}
+
+impl Limit {
+    fn new(requests_per_second: NonZeroU32) -> Self {
+        let interval = Duration::from_secs(1) / requests_per_second.get();
+        Self {
+            interval,
+            burst: interval * requests_per_second.get(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("rate limit exceeded, retry after {retry_after:?}")]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+/// Token bucket rate limiter, implemented as a generic cell rate algorithm,
+/// with a bucket for each client and method class.
+/// Requests that exceed the limit are rejected rather than delayed.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: HashMap<MethodClass, Limit>,
+    /// Time at which each bucket will be full again, if no more requests
+    /// are made. Clients with an unknown address share a bucket.
+    theoretical_arrivals: Mutex<HashMap<(Option<IpAddr>, MethodClass), Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let mut limits = HashMap::from([(
+            MethodClass::Expensive,
+            Limit::new(config.expensive_requests_per_second),
+        )]);
+        if let Some(requests_per_second) = config.requests_per_second {
+            limits.insert(MethodClass::Default, Limit::new(requests_per_second));
+        }
+        Self {
+            limits,
+            theoretical_arrivals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn try_acquire(&self, client: Option<IpAddr>, class: MethodClass) -> Result<(), RateLimited> {
+        let Some(limit) = self.limits.get(&class) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut theoretical_arrivals = self
+            .theoretical_arrivals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let theoretical_arrival = theoretical_arrivals
+            .get(&(client, class))
+            .map_or(now, |theoretical_arrival| {
+                std::cmp::max(*theoretical_arrival, now)
+            });
+        let new_theoretical_arrival = theoretical_arrival + limit.interval;
+        let ahead = new_theoretical_arrival - now;
+        if ahead > limit.burst {
+            return Err(RateLimited {
+                retry_after: ahead - limit.burst,
+            });
+        }
+        if theoretical_arrivals.len() >= PRUNE_THRESHOLD {
+            theoretical_arrivals.retain(|_, theoretical_arrival| *theoretical_arrival > now);
+        }
+        theoretical_arrivals.insert((client, class), new_theoretical_arrival);
+        Ok(())
+    }
+}
+
+/// Tower layer that rate limits HTTP requests, keyed by the client's IP
+/// address, if known. Rejected requests are answered with the response
+/// returned by `reject`.
+#[derive(Clone)]
+pub struct RateLimitLayer<Classify, Reject> {
+    limiter: Arc<RateLimiter>,
+    classify: Classify,
+    reject: Reject,
+}
+
+impl<Classify, Reject> RateLimitLayer<Classify, Reject> {
+    pub fn new(limiter: Arc<RateLimiter>, classify: Classify, reject: Reject) -> Self {
+        Self {
+            limiter,
+            classify,
+            reject,
+        }
+    }
+}
+
+impl<S, Classify, Reject> tower::Layer<S> for RateLimitLayer<Classify, Reject>
+where
+    Classify: Clone,
+    Reject: Clone,
+{
+    type Service = RateLimit<S, Classify, Reject>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+            classify: self.classify.clone(),
+            reject: self.reject.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S, Classify, Reject> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+    classify: Classify,
+    reject: Reject,
+}
+
+/// Address of the client connection that a request was received on.
+/// Servers that accept connections themselves insert this into the
+/// request extensions, via [`WithRemoteAddr`].
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// Service that inserts the client's [`RemoteAddr`] into the extensions of
+/// each request on a connection
+#[derive(Clone, Debug)]
+pub struct WithRemoteAddr<S> {
+    inner: S,
+    remote_addr: RemoteAddr,
+}
+
+impl<S> WithRemoteAddr<S> {
+    pub fn new(inner: S, remote_addr: SocketAddr) -> Self {
+        Self {
+            inner,
+            remote_addr: RemoteAddr(remote_addr),
+        }
+    }
+}
+
+impl<S, Body> tower::Service<http::Request<Body>> for WithRemoteAddr<S>
+where
+    S: tower::Service<http::Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        request.extensions_mut().insert(self.remote_addr);
+        self.inner.call(request)
+    }
+}
+
+/// Client IP address, if the server provides connection info, either as a
+/// [`RemoteAddr`] or as tonic's `TcpConnectInfo`
+fn client_ip(extensions: &http::Extensions) -> Option<IpAddr> {
+    if let Some(RemoteAddr(remote_addr)) = extensions.get() {
+        return Some(remote_addr.ip());
+    }
+    extensions
+        .get::<tonic::transport::server::TcpConnectInfo>()
+        .and_then(|connect_info| connect_info.remote_addr())
+        .map(|remote_addr| remote_addr.ip())
+}
+
+impl<S, Classify, Reject, ReqBody, ResBody> tower::Service<http::Request<ReqBody>>
+    for RateLimit<S, Classify, Reject>
+where
+    S: tower::Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    Classify: Fn(&http::Uri) -> MethodClass,
+    Reject: Fn(&RateLimited) -> http::Response<ResBody>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        futures::future::Either<S::Future, std::future::Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let class = (self.classify)(request.uri());
+        let client = client_ip(request.extensions());
+        match self.limiter.try_acquire(client, class) {
+            Ok(()) => futures::future::Either::Left(self.inner.call(request)),
+            Err(err) => {
+                tracing::debug!(
+                    ?client,
+                    ?class,
+                    uri = %request.uri(),
+                    "rejecting request: {err}"
+                );
+                futures::future::Either::Right(std::future::ready(Ok((self.reject)(&err))))
+            }
+        }
+    }
+}
+
+fn rate_limited_json_rpc_err(err: &RateLimited) -> ErrorObject<'static> {
+    ErrorObject::owned(RATE_LIMITED_JSON_RPC_CODE, err.to_string(), None::<()>)
+}
+
+/// JSON-RPC middleware that rate limits calls by method class, keyed by
+/// the client's IP address, if known. Each call in a batch counts as a
+/// request, and a batch is rejected if any of its calls would exceed the
+/// limit.
+#[derive(Clone)]
+pub struct RpcRateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+    classify: fn(&str) -> MethodClass,
+}
+
+impl<S> RpcServiceT for RpcRateLimit<S>
+where
+    S: RpcServiceT<
+            MethodResponse = MethodResponse,
+            NotificationResponse = MethodResponse,
+            BatchResponse = MethodResponse,
+        > + Send
+        + Sync,
+{
+    type MethodResponse = S::MethodResponse;
+    type NotificationResponse = S::NotificationResponse;
+    type BatchResponse = S::BatchResponse;
+
+    fn call<'a>(
+        &self,
+        request: Request<'a>,
+    ) -> impl Future<Output = Self::MethodResponse> + Send + 'a {
+        let class = (self.classify)(request.method_name());
+        let client = client_ip(request.extensions());
+        match self.limiter.try_acquire(client, class) {
+            Ok(()) => Either::Left(self.inner.call(request)),
+            Err(err) => {
+                tracing::debug!(
+                    ?client,
+                    ?class,
+                    method = request.method_name(),
+                    "rejecting request: {err}"
+                );
+                let response = MethodResponse::error(request.id, rate_limited_json_rpc_err(&err));
+                Either::Right(std::future::ready(response))
+            }
+        }
+    }
+
+    fn batch<'a>(
+        &self,
+        requests: Batch<'a>,
+    ) -> impl Future<Output = Self::BatchResponse> + Send + 'a {
+        let client = client_ip(requests.extensions());
+        let res = requests.iter().try_for_each(|entry| {
+            let method = match entry {
+                Ok(BatchEntry::Call(request)) => request.method_name(),
+                Ok(BatchEntry::Notification(notification)) => notification.method_name(),
+                Err(_) => return Ok(()),
+            };
+            self.limiter.try_acquire(client, (self.classify)(method))
+        });
+        match res {
+            Ok(()) => Either::Left(self.inner.batch(requests)),
+            Err(err) => {
+                tracing::debug!(?client, "rejecting batch: {err}");
+                let response = MethodResponse::error(Id::Null, rate_limited_json_rpc_err(&err));
+                Either::Right(std::future::ready(response))
+            }
+        }
+    }
+
+    fn notification<'a>(
+        &self,
+        notification: Notification<'a>,
+    ) -> impl Future<Output = Self::NotificationResponse> + Send + 'a {
+        let class = (self.classify)(notification.method_name());
+        let client = client_ip(notification.extensions());
+        match self.limiter.try_acquire(client, class) {
+            Ok(()) => Either::Left(self.inner.notification(notification)),
+            Err(err) => {
+                tracing::debug!(
+                    ?client,
+                    ?class,
+                    method = notification.method_name(),
+                    "rejecting notification: {err}"
+                );
+                Either::Right(std::future::ready(MethodResponse::notification()))
+            }
+        }
+    }
+}
+
+/// Layer for [`RpcRateLimit`]. Calls are classified by method name.
+#[derive(Clone)]
+pub struct RpcRateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    classify: fn(&str) -> MethodClass,
+}
+
+impl RpcRateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>, classify: fn(&str) -> MethodClass) -> Self {
+        Self { limiter, classify }
+    }
+}
+
+impl<S> tower::Layer<S> for RpcRateLimitLayer {
+    type Service = RpcRateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RpcRateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+            classify: self.classify,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, SocketAddr},
+        num::NonZeroU32,
+    };
+
+    use bip300301_enforcer_lib::cli::RateLimitConfig;
+
+    use super::{MethodClass, RateLimiter, RemoteAddr, client_ip};
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_second: NonZeroU32::new(2),
+            expensive_requests_per_second: NonZeroU32::new(1).unwrap(),
+        });
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+        let other_client = Some(IpAddr::from([127, 0, 0, 2]));
+        // Burst of one second's worth of requests
+        assert!(limiter.try_acquire(client, MethodClass::Default).is_ok());
+        assert!(limiter.try_acquire(client, MethodClass::Default).is_ok());
+        assert!(limiter.try_acquire(client, MethodClass::Default).is_err());
+        // Clients and method classes are limited separately
+        assert!(
+            limiter
+                .try_acquire(other_client, MethodClass::Default)
+                .is_ok()
+        );
+        assert!(limiter.try_acquire(client, MethodClass::Expensive).is_ok());
+        assert!(limiter.try_acquire(client, MethodClass::Expensive).is_err());
+    }
+
+    /// Expensive methods are rate limited even if other methods are not
+    #[test]
+    fn test_rate_limiter_expensive_only() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_second: None,
+            expensive_requests_per_second: NonZeroU32::new(1).unwrap(),
+        });
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+        for _ in 0..10 {
+            assert!(limiter.try_acquire(client, MethodClass::Default).is_ok());
+        }
+        assert!(limiter.try_acquire(client, MethodClass::Expensive).is_ok());
+        assert!(limiter.try_acquire(client, MethodClass::Expensive).is_err());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let mut extensions = http::Extensions::new();
+        assert_eq!(client_ip(&extensions), None);
+        let remote_addr = SocketAddr::from(([127, 0, 0, 1], 18443));
+        extensions.insert(RemoteAddr(remote_addr));
+        assert_eq!(client_ip(&extensions), Some(remote_addr.ip()));
+    }
+}
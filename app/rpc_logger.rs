@@ -0,0 +1,233 @@
+//! JSON-RPC request and response logging, with the params and results of
+//! methods that handle secrets redacted
+
+use std::future::Future;
+
+use bip300301_enforcer_lib::server::REDACTED_JSON_RPC_METHODS;
+use futures::future::Either;
+use jsonrpsee::{
+    core::middleware::{
+        Batch, Notification, RpcServiceT,
+        layer::{RpcLogger, RpcLoggerLayer},
+    },
+    types::Request,
+};
+use serde::Serialize;
+
+fn is_redacted_method(method: &str) -> bool {
+    REDACTED_JSON_RPC_METHODS.contains(&method)
+}
+
+/// Returns `true` if a serialized request, notification, or batch calls a
+/// redacted method. If the value cannot be serialized, it is treated as
+/// redacted.
+fn calls_redacted_method<T: Serialize>(value: &T) -> bool {
+    fn any_redacted(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Array(values) => values.iter().any(any_redacted),
+            serde_json::Value::Object(fields) => fields.iter().any(|(key, value)| {
+                (key == "method" && value.as_str().is_some_and(is_redacted_method))
+                    || any_redacted(value)
+            }),
+            _ => false,
+        }
+    }
+    serde_json::to_value(value).map_or(true, |value| any_redacted(&value))
+}
+
+/// Logs requests and responses in the same way as jsonrpsee's
+/// [`RpcLogger`], except for calls to [`REDACTED_JSON_RPC_METHODS`], which
+/// are logged without params or results
+#[derive(Clone, Debug)]
+pub struct RedactingRpcLogger<S> {
+    inner: S,
+    logger: RpcLogger<S>,
+}
+
+impl<S> RpcServiceT for RedactingRpcLogger<S>
+where
+    S: RpcServiceT + Clone + Send + Sync + 'static,
+    RpcLogger<S>: RpcServiceT<
+            MethodResponse = S::MethodResponse,
+            NotificationResponse = S::NotificationResponse,
+            BatchResponse = S::BatchResponse,
+        >,
+{
+    type MethodResponse = S::MethodResponse;
+    type NotificationResponse = S::NotificationResponse;
+    type BatchResponse = S::BatchResponse;
+
+    fn call<'a>(
+        &self,
+        request: Request<'a>,
+    ) -> impl Future<Output = Self::MethodResponse> + Send + 'a {
+        if is_redacted_method(request.method_name()) {
+            tracing::trace!(
+                target: "jsonrpsee",
+                method = request.method_name(),
+                "request params and response redacted"
+            );
+            Either::Left(self.inner.call(request))
+        } else {
+            Either::Right(self.logger.call(request))
+        }
+    }
+
+    fn batch<'a>(
+        &self,
+        requests: Batch<'a>,
+    ) -> impl Future<Output = Self::BatchResponse> + Send + 'a {
+        if calls_redacted_method(&requests) {
+            tracing::trace!(
+                target: "jsonrpsee",
+                "batch calls a redacted method, batch and response redacted"
+            );
+            Either::Left(self.inner.batch(requests))
+        } else {
+            Either::Right(self.logger.batch(requests))
+        }
+    }
+
+    fn notification<'a>(
+        &self,
+        notification: Notification<'a>,
+    ) -> impl Future<Output = Self::NotificationResponse> + Send + 'a {
+        if calls_redacted_method(&notification) {
+            tracing::trace!(target: "jsonrpsee", "notification params redacted");
+            Either::Left(self.inner.notification(notification))
+        } else {
+            Either::Right(self.logger.notification(notification))
+        }
+    }
+}
+
+/// Layer for [`RedactingRpcLogger`]. Logged requests and responses are
+/// truncated to `max` bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct RedactingRpcLoggerLayer {
+    max: u32,
+}
+
+impl RedactingRpcLoggerLayer {
+    pub fn new(max: u32) -> Self {
+        Self { max }
+    }
+}
+
+impl<S> tower::Layer<S> for RedactingRpcLoggerLayer
+where
+    S: Clone,
+{
+    type Service = RedactingRpcLogger<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RedactingRpcLogger {
+            logger: RpcLoggerLayer::new(self.max).layer(inner.clone()),
+            inner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use jsonrpsee::{
+        RpcModule,
+        server::{Server, middleware::rpc::RpcServiceBuilder},
+    };
+    use tracing_subscriber::{filter::Targets, layer::SubscriberExt as _};
+
+    use super::RedactingRpcLoggerLayer;
+
+    const SECRET_PARAM: &str = "secret-param";
+    const SECRET_RESULT: &str = "secret-result";
+    const PUBLIC_PARAM: &str = "public-param";
+
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn call(client: &reqwest::Client, url: &str, method: &str, param: &str) -> String {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": [param],
+        });
+        client
+            .post(url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_redacted_methods_are_not_logged() {
+        let logs = LogBuffer::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(Targets::new().with_target("jsonrpsee", tracing::Level::TRACE))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer({
+                        let logs = logs.clone();
+                        move || logs.clone()
+                    }),
+            );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut module = RpcModule::new(());
+        module
+            .register_method("admin.export_private_descriptors", |_, _, _| {
+                SECRET_RESULT.to_owned()
+            })
+            .unwrap();
+        module
+            .register_method("admin.ping", |_, _, _| "pong".to_owned())
+            .unwrap();
+        let server = Server::builder()
+            .set_rpc_middleware(RpcServiceBuilder::new().layer(RedactingRpcLoggerLayer::new(1024)))
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(module);
+
+        let client = reqwest::Client::new();
+        let response = call(
+            &client,
+            &url,
+            "admin.export_private_descriptors",
+            SECRET_PARAM,
+        )
+        .await;
+        assert!(response.contains(SECRET_RESULT));
+        let _response = call(&client, &url, "admin.ping", PUBLIC_PARAM).await;
+        handle.stop().unwrap();
+        handle.stopped().await;
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        // Requests to other methods are still logged
+        assert!(logs.contains(PUBLIC_PARAM));
+        assert!(!logs.contains(SECRET_PARAM));
+        assert!(!logs.contains(SECRET_RESULT));
+    }
+}
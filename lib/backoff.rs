@@ -0,0 +1,29 @@
+//! Exponential backoff between retries
+
+use std::time::Duration;
+
+/// Delay before a retry, where `retries` is the number of retries that have
+/// already been made. Doubles from `initial` after each retry, up to `max`.
+pub fn exponential_backoff(initial: Duration, retries: u32, max: Duration) -> Duration {
+    initial
+        .saturating_mul(2u32.saturating_pow(retries))
+        .min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::exponential_backoff;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let initial = Duration::from_secs(1);
+        let max = Duration::from_secs(60);
+        assert_eq!(exponential_backoff(initial, 0, max), initial);
+        assert_eq!(exponential_backoff(initial, 3, max), Duration::from_secs(8));
+        assert_eq!(exponential_backoff(initial, 6, max), max);
+        // Does not overflow
+        assert_eq!(exponential_backoff(initial, u32::MAX, max), max);
+    }
+}
@@ -1,8 +1,10 @@
 use std::{
     env,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs},
+    num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize},
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
 use clap::{Args, Parser, ValueEnum};
@@ -10,6 +12,8 @@ use thiserror::Error;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::fmt::format as tracing_format;
 
+use crate::types::SidechainNumber;
+
 const DEFAULT_NODE_RPC_ADDR: SocketAddr =
     SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 18443));
 
@@ -91,15 +95,68 @@ impl LogFormat {
     }
 }
 
+/// Behavior when the log file writer falls behind
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFileBackpressure {
+    /// Drop log lines if the buffer is full
+    #[default]
+    Drop,
+    /// Block logging threads until there is space in the buffer, so that no
+    /// log lines are lost
+    Block,
+}
+
+/// Possible timestamp formats for log output.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum LogTimestamp {
+    /// RFC 3339 timestamps, eg. `2025-01-01T00:00:00.000000Z`
+    #[default]
+    Rfc3339,
+    /// Seconds since the unix epoch, eg. `1735689600.000000`
+    Unix,
+    /// Time elapsed since the logger was initialized
+    Uptime,
+    /// Do not display timestamps
+    None,
+}
+
+/// Formats timestamps as seconds since the unix epoch, with microsecond
+/// precision
+#[derive(Clone, Copy, Debug)]
+struct UnixEpochTime;
+
+impl tracing_subscriber::fmt::time::FormatTime for UnixEpochTime {
+    fn format_time(&self, writer: &mut tracing_format::Writer<'_>) -> std::fmt::Result {
+        let since_epoch = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            writer,
+            "{}.{:06}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_micros()
+        )
+    }
+}
+
 /// Log formatter, equivalent to [`tracing_subscriber::fmt::format::Format`]
 #[derive(Clone, Copy, Debug)]
 pub struct LogFormatter {
     format: LogFormat,
     display_filename: Option<bool>,
     display_line_number: Option<bool>,
+    timestamp: LogTimestamp,
+    /// Used to compute uptime timestamps
+    initialized_at: std::time::Instant,
 }
 
 impl LogFormatter {
+    pub fn with_timestamp(mut self, timestamp: LogTimestamp) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
     pub fn with_file(mut self, display_filename: bool) -> Self {
         self.display_filename = Some(display_filename);
         self
@@ -122,6 +179,37 @@ impl LogFormatter {
         }
         format
     }
+
+    /// Format an event using the configured timestamp format
+    fn format_event_with_timer<C, N, F>(
+        &self,
+        format: tracing_format::Format<F>,
+        ctx: &tracing_subscriber::fmt::FmtContext<'_, C, N>,
+        writer: tracing_format::Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> std::fmt::Result
+    where
+        C: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+        N: for<'a> tracing_subscriber::fmt::FormatFields<'a> + 'static,
+        tracing_format::Format<F>: tracing_subscriber::fmt::FormatEvent<C, N>,
+        tracing_format::Format<F, UnixEpochTime>: tracing_subscriber::fmt::FormatEvent<C, N>,
+        tracing_format::Format<F, tracing_subscriber::fmt::time::Uptime>:
+            tracing_subscriber::fmt::FormatEvent<C, N>,
+        tracing_format::Format<F, ()>: tracing_subscriber::fmt::FormatEvent<C, N>,
+    {
+        use tracing_subscriber::fmt::{FormatEvent as _, time::Uptime};
+        let format = self.set_format_opts(format);
+        match self.timestamp {
+            LogTimestamp::Rfc3339 => format.format_event(ctx, writer, event),
+            LogTimestamp::Unix => format
+                .with_timer(UnixEpochTime)
+                .format_event(ctx, writer, event),
+            LogTimestamp::Uptime => format
+                .with_timer(Uptime::from(self.initialized_at))
+                .format_event(ctx, writer, event),
+            LogTimestamp::None => format.without_time().format_event(ctx, writer, event),
+        }
+    }
 }
 
 impl From<LogFormat> for LogFormatter {
@@ -130,6 +218,8 @@ impl From<LogFormat> for LogFormatter {
             format,
             display_filename: None,
             display_line_number: None,
+            timestamp: LogTimestamp::default(),
+            initialized_at: std::time::Instant::now(),
         }
     }
 }
@@ -148,18 +238,12 @@ where
         use tracing_subscriber::fmt::format::{Format, Full};
         let format: Format<Full> = Format::default();
         match self.format {
-            LogFormat::Compact => self
-                .set_format_opts(format.compact())
-                .format_event(ctx, writer, event),
-            LogFormat::Full => self
-                .set_format_opts(format)
-                .format_event(ctx, writer, event),
-            LogFormat::Json => self
-                .set_format_opts(format.json())
-                .format_event(ctx, writer, event),
-            LogFormat::Pretty => self
-                .set_format_opts(format.pretty())
-                .format_event(ctx, writer, event),
+            LogFormat::Compact => {
+                self.format_event_with_timer(format.compact(), ctx, writer, event)
+            }
+            LogFormat::Full => self.format_event_with_timer(format, ctx, writer, event),
+            LogFormat::Json => self.format_event_with_timer(format.json(), ctx, writer, event),
+            LogFormat::Pretty => self.format_event_with_timer(format.pretty(), ctx, writer, event),
         }
     }
 }
@@ -230,20 +314,139 @@ pub struct LoggerConfig {
     /// Log file directory.
     #[arg(long = "log-directory")]
     directory: Option<PathBuf>,
+    /// Behavior when log lines are produced faster than they can be written
+    /// to the log file.
+    #[arg(default_value_t, long = "log-file-backpressure", value_enum)]
+    pub file_backpressure: LogFileBackpressure,
+    /// Maximum number of log lines buffered before they are written to the
+    /// log file.
+    #[arg(
+        default_value_t = tracing_appender::non_blocking::DEFAULT_BUFFERED_LINES_LIMIT,
+        long = "log-file-buffered-lines"
+    )]
+    pub file_buffered_lines: usize,
     /// Log file rotation frequency.
     /// If set, a new log file will be created at the specified interval.
     #[arg(default_value_t, long = "log-rotation", value_enum)]
     pub rotation: LogRotation,
+    /// Do not write logs to stdout.
+    #[arg(
+        long = "log-disable-stdout",
+        default_value_t = false,
+        conflicts_with = "disable_file"
+    )]
+    pub disable_stdout: bool,
+    /// Do not write logs to a log file.
+    #[arg(long = "log-disable-file", default_value_t = false)]
+    pub disable_file: bool,
+    /// Enable or disable ANSI colors in stdout log output.
+    /// If not set, ANSI colors are enabled if stdout is a terminal.
+    #[arg(long = "log-ansi")]
+    pub ansi: Option<bool>,
+    /// Timestamp format for stdout log output.
+    #[arg(default_value_t, long = "log-timestamp", value_enum)]
+    pub timestamp: LogTimestamp,
+    /// Maximum length of JSON-RPC requests and responses in log output, in
+    /// characters. Longer requests and responses are truncated. Applies to
+    /// all JSON-RPC servers.
+    #[arg(long = "rpc-log-buffer-size", default_value_t = 1024)]
+    pub rpc_log_buffer_size: u32,
 }
 
+/// The network is not known until the enforcer connects to the mainchain
+/// node, so the address is assumed to be valid for it, and must be checked
+/// at startup.
 fn parse_bitcoin_address(s: &str) -> Result<bitcoin::Address, String> {
     let unchecked =
         bitcoin::Address::from_str(s).map_err(|_| "invalid bitcoin address".to_string())?;
+    Ok(unchecked.assume_checked())
+}
+
+/// Recipient of a share of the coinbase reward, in the format
+/// `ADDRESS:WEIGHT`
+#[derive(Clone, Debug)]
+pub struct CoinbaseRewardRecipient {
+    pub address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+    pub weight: NonZeroU32,
+}
+
+impl FromStr for CoinbaseRewardRecipient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, weight) = s
+            .rsplit_once(':')
+            .ok_or_else(|| "expected `ADDRESS:WEIGHT`".to_string())?;
+        let address = bitcoin::Address::from_str(address)
+            .map_err(|_| "invalid bitcoin address".to_string())?;
+        let weight = weight
+            .parse()
+            .map_err(|_| "weight must be a positive integer".to_string())?;
+        Ok(Self { address, weight })
+    }
+}
+
+/// Minimum deposit amount for a sidechain, in the format
+/// `SIDECHAIN_NUMBER:SATS`
+#[derive(Clone, Copy, Debug)]
+pub struct SidechainDepositMinimum {
+    pub sidechain_number: SidechainNumber,
+    pub minimum: bitcoin::Amount,
+}
+
+impl FromStr for SidechainDepositMinimum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sidechain_number, minimum_sats) = s
+            .split_once(':')
+            .ok_or_else(|| "expected `SIDECHAIN_NUMBER:SATS`".to_string())?;
+        let sidechain_number = sidechain_number
+            .parse::<u8>()
+            .map_err(|_| "sidechain number must be an integer from 0 to 255".to_string())?
+            .into();
+        let minimum_sats = minimum_sats
+            .parse()
+            .map_err(|_| "minimum must be a non-negative integer".to_string())?;
+        Ok(Self {
+            sidechain_number,
+            minimum: bitcoin::Amount::from_sat(minimum_sats),
+        })
+    }
+}
+
+/// Minimum deposit amounts, in the format
+/// `SIDECHAIN_NUMBER:SATS[,SIDECHAIN_NUMBER:SATS...]`. Each sidechain may only
+/// be listed once.
+#[derive(Clone, Debug)]
+pub struct SidechainDepositMinimums(pub Vec<SidechainDepositMinimum>);
 
-    let checked_addr = unchecked
-        .require_network(bitcoin::Network::Signet)
-        .map_err(|_| "bitcoin address is not for signet".to_string())?;
-    Ok(checked_addr)
+impl FromStr for SidechainDepositMinimums {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut deposit_minimums = Vec::<SidechainDepositMinimum>::new();
+        for deposit_minimum in s.split(',') {
+            let deposit_minimum: SidechainDepositMinimum = deposit_minimum.parse()?;
+            if deposit_minimums
+                .iter()
+                .any(|other| other.sidechain_number == deposit_minimum.sidechain_number)
+            {
+                return Err(format!(
+                    "duplicate minimum for sidechain {}",
+                    deposit_minimum.sidechain_number
+                ));
+            }
+            deposit_minimums.push(deposit_minimum);
+        }
+        Ok(Self(deposit_minimums))
+    }
+}
+
+fn parse_sidechain_number(s: &str) -> Result<SidechainNumber, String> {
+    s.parse::<u8>()
+        .map(SidechainNumber::from)
+        .map_err(|_| "sidechain number must be an integer from 0 to 255".to_string())
 }
 
 #[derive(Clone, Args)]
@@ -267,6 +470,99 @@ pub struct MiningConfig {
     /// Address for block reward payment
     #[arg(long = "signet-miner-coinbase-recipient", value_parser = parse_bitcoin_address)]
     pub coinbase_recipient: Option<bitcoin::Address>,
+    /// What to do if no coinbase recipient is set, and the wallet is unable
+    /// to provide an address for block reward payment.
+    #[arg(
+        default_value_t,
+        long = "signet-miner-coinbase-recipient-policy",
+        value_enum
+    )]
+    pub coinbase_recipient_policy: CoinbaseRecipientPolicy,
+    /// Address for block reward payment, if the wallet is unable to provide
+    /// one. Required if the coinbase recipient policy is `fallback`.
+    #[arg(
+        long = "signet-miner-coinbase-recipient-fallback",
+        value_parser = parse_bitcoin_address,
+        required_if_eq("coinbase_recipient_policy", "fallback")
+    )]
+    pub coinbase_recipient_fallback: Option<bitcoin::Address>,
+    /// Split the coinbase reward across multiple addresses, as
+    /// comma-separated `ADDRESS:WEIGHT` pairs. Each address receives a share
+    /// of the reward proportional to its weight.
+    /// Only blocks mined by the wallet are split. The GBT server builds the
+    /// reward output itself, paying a single address, so this is rejected
+    /// if `--enable-mempool` is set.
+    #[arg(
+        long = "coinbase-reward-split",
+        value_delimiter = ',',
+        conflicts_with = "coinbase_recipient"
+    )]
+    pub coinbase_reward_split: Vec<CoinbaseRewardRecipient>,
+    /// Additional comma-separated rules to request in `getblocktemplate`.
+    /// Rules for active BIP9 deployments are requested automatically if
+    /// Bitcoin Core supports `getdeploymentinfo`, as is `signet` on signet.
+    #[arg(
+        long = "block-template-rules",
+        value_delimiter = ',',
+        value_parser = parse_block_template_rule
+    )]
+    pub block_template_rules: Vec<String>,
+    /// Maximum number of mempool transactions to include in block templates
+    /// produced by the GBT server. Transactions with the lowest fee rates
+    /// are excluded first. Does not count the coinbase transaction, or
+    /// Drivechain transactions added by the enforcer.
+    #[arg(long = "block-template-max-txs")]
+    pub block_template_max_txs: Option<usize>,
+    /// Maximum total weight of mempool transactions to include in block
+    /// templates produced by the GBT server, in weight units. Transactions
+    /// with the lowest fee rates are excluded first. Does not count the
+    /// coinbase transaction, or Drivechain transactions added by the
+    /// enforcer. Templates are always limited to the consensus block weight
+    /// limit, so this can only be used to lower it.
+    #[arg(long = "block-template-max-weight")]
+    pub block_template_max_weight: Option<u64>,
+}
+
+/// Rule names are deployment names, eg. `segwit` or `taproot`
+fn parse_block_template_rule(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        return Err("block template rule must not be empty".to_owned());
+    }
+    if !s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "invalid block template rule `{s}`: rules may only contain ASCII alphanumeric characters, `_`, and `-`"
+        ));
+    }
+    Ok(s.to_owned())
+}
+
+/// What to do if no coinbase recipient is set, and the wallet is unable to
+/// provide an address for block reward payment
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoinbaseRecipientPolicy {
+    /// Stop the mempool sync task
+    #[default]
+    Fail,
+    /// Retry until the wallet is able to provide an address
+    Retry,
+    /// Use the fallback coinbase recipient
+    Fallback,
+}
+
+/// What the wallet-based mempool sync task does if the wallet is not
+/// initialized at startup
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UninitializedWalletPolicy {
+    /// Exit with an error
+    #[default]
+    Fail,
+    /// Wait until the wallet is created or unlocked, checking periodically
+    Wait,
 }
 
 #[derive(Args, Clone)]
@@ -288,9 +584,15 @@ pub struct NodeRpcConfig {
     /// be set together with cookie path.
     #[arg(long = "node-rpc-pass")]
     pub pass: Option<String>,
+    /// Maximum time to wait for Bitcoin Core to finish warming up at
+    /// startup, in seconds. The enforcer exits if Bitcoin Core is still
+    /// warming up after this time.
+    #[arg(default_value_t = 600, long = "node-rpc-warmup-timeout-secs")]
+    pub warmup_timeout_secs: u64,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum WalletSyncSource {
     /// Communicates over the Electrum protocol.
     Electrum,
@@ -301,6 +603,79 @@ pub enum WalletSyncSource {
     Disabled,
 }
 
+#[derive(Clone, Args)]
+pub struct RateLimitConfig {
+    /// Maximum number of requests per second from each client, to each of
+    /// the gRPC and JSON-RPC servers. Clients that exceed the limit receive
+    /// a `ResourceExhausted` (gRPC) or `429 Too Many Requests` (HTTP) error.
+    /// Bursts of up to one second's worth of requests are allowed.
+    /// If not set, requests are not rate limited.
+    #[arg(long = "rate-limit-requests-per-second")]
+    pub requests_per_second: Option<NonZeroU32>,
+    /// Maximum number of requests per second from each client, for methods
+    /// that are expensive to serve, such as listing deposits or
+    /// transactions, and `getblocktemplate`. Applies instead of
+    /// `--rate-limit-requests-per-second` for these methods.
+    #[arg(
+        long = "rate-limit-expensive-requests-per-second",
+        default_value = "10"
+    )]
+    pub expensive_requests_per_second: NonZeroU32,
+}
+
+/// Connection settings for the gRPC and JSON-RPC servers, for keeping
+/// long-lived streams alive through load balancers and proxies that drop
+/// idle connections
+#[derive(Clone, Args)]
+pub struct ServerConnectionConfig {
+    /// Interval between TCP keepalive probes on server connections, in
+    /// seconds. If not set, TCP keepalive is disabled.
+    #[arg(long = "server-tcp-keepalive-secs")]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Interval between keepalive pings on idle server connections, in
+    /// seconds. HTTP/2 pings are used for gRPC, and WebSocket pings for
+    /// JSON-RPC. If not set, keepalive pings are not sent.
+    #[arg(long = "server-keepalive-interval-secs")]
+    pub keepalive_interval_secs: Option<u64>,
+    /// Close connections that do not respond to a keepalive ping within
+    /// this many seconds. Only applies if
+    /// `--server-keepalive-interval-secs` is set.
+    #[arg(
+        long = "server-keepalive-timeout-secs",
+        requires = "keepalive_interval_secs"
+    )]
+    pub keepalive_timeout_secs: Option<u64>,
+    /// Maximum number of concurrent HTTP/2 streams per gRPC connection.
+    /// If not set, the number of streams is not limited.
+    #[arg(long = "server-max-concurrent-streams")]
+    pub max_concurrent_streams: Option<u32>,
+    /// Maximum number of concurrent gRPC event and sync progress
+    /// subscriptions, across all clients. Further subscriptions are rejected
+    /// until an existing subscription ends.
+    /// If not set, the number of subscriptions is not limited.
+    #[arg(long = "server-max-subscriptions")]
+    pub max_subscriptions: Option<NonZeroUsize>,
+    /// Maximum number of concurrent gRPC event and sync progress
+    /// subscriptions for each client IP address.
+    /// If not set, the number of subscriptions per client is not limited.
+    #[arg(long = "server-max-subscriptions-per-client")]
+    pub max_subscriptions_per_client: Option<NonZeroUsize>,
+}
+
+impl ServerConnectionConfig {
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        self.tcp_keepalive_secs.map(Duration::from_secs)
+    }
+
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval_secs.map(Duration::from_secs)
+    }
+
+    pub fn keepalive_timeout(&self) -> Option<Duration> {
+        self.keepalive_timeout_secs.map(Duration::from_secs)
+    }
+}
+
 #[derive(Clone, Args)]
 pub struct WalletConfig {
     /// If true, the wallet will perform a full scan of the blockchain on startup, before
@@ -321,6 +696,18 @@ pub struct WalletConfig {
     /// Regtest: http://localhost:3003
     #[arg(long = "wallet-esplora-url")]
     pub esplora_url: Option<url::Url>,
+    /// Maximum number of requests per second to make to the Esplora server,
+    /// shared between wallet syncs and other requests.
+    /// If not provided, requests are not rate limited.
+    #[arg(long = "wallet-esplora-requests-per-second")]
+    pub esplora_requests_per_second: Option<NonZeroU32>,
+    /// Number of times to retry an Esplora request that fails with a
+    /// retryable status, such as 429 (Too Many Requests). Retries use
+    /// exponential backoff. If a request is still rejected with 429 after
+    /// these retries, it is retried a few more times, after waiting for the
+    /// server's `Retry-After`.
+    #[arg(long = "wallet-esplora-max-retries", default_value_t = 6)]
+    pub esplora_max_retries: usize,
     /// If no host is provided, a default value is used based on the network
     /// we're on.
     ///
@@ -333,6 +720,126 @@ pub struct WalletConfig {
     /// Signet: 50001, regtest: 60401
     #[arg(long = "wallet-electrum-port")]
     pub electrum_port: Option<u16>,
+    /// Maximum number of concurrent connections to the Electrum server.
+    /// A wallet sync holds one connection, so this should be greater than one
+    /// to serve other requests while syncing.
+    #[arg(long = "wallet-electrum-max-connections", default_value = "4")]
+    pub electrum_max_connections: NonZeroUsize,
+    /// Number of times to retry a failed Electrum request. The connection to
+    /// the Electrum server is re-established before each retry.
+    #[arg(long = "wallet-electrum-retries", default_value_t = 1)]
+    pub electrum_retries: u8,
+    /// Minimum deposit amount, in sats. Deposits below this amount are
+    /// rejected before a transaction is built.
+    /// If not provided, the dust threshold for the deposit output script is
+    /// used.
+    #[arg(long = "wallet-deposit-dust-threshold-sats")]
+    pub deposit_dust_threshold_sats: Option<u64>,
+    /// Minimum deposit amounts for specific sidechains, as comma-separated
+    /// `SIDECHAIN_NUMBER:SATS` pairs. Deposits to a sidechain below its
+    /// minimum are rejected before a transaction is built. Each sidechain may
+    /// only be listed once.
+    #[arg(long = "wallet-sidechain-deposit-minimums")]
+    pub sidechain_deposit_minimums: Option<SidechainDepositMinimums>,
+    /// If set, the wallet only creates deposits and withdrawal bundles for
+    /// these sidechains, as comma-separated sidechain numbers. Other
+    /// sidechains are also excluded from the wallet's coinbase messages.
+    #[arg(
+        long = "wallet-sidechain-allowlist",
+        value_delimiter = ',',
+        value_parser = parse_sidechain_number,
+        conflicts_with = "sidechain_denylist"
+    )]
+    pub sidechain_allowlist: Option<Vec<SidechainNumber>>,
+    /// The wallet does not create deposits or withdrawal bundles for these
+    /// sidechains, as comma-separated sidechain numbers. They are also
+    /// excluded from the wallet's coinbase messages.
+    #[arg(
+        long = "wallet-sidechain-denylist",
+        value_delimiter = ',',
+        value_parser = parse_sidechain_number
+    )]
+    pub sidechain_denylist: Vec<SidechainNumber>,
+    /// How long idempotency keys for broadcast requests are remembered, in
+    /// seconds. A repeated request with the same key within this window
+    /// returns the original result.
+    #[arg(long = "wallet-idempotency-key-ttl-secs", default_value_t = 24 * 60 * 60)]
+    pub idempotency_key_ttl_secs: u64,
+    /// Number of confirmations of the block that finalizes a withdrawal
+    /// bundle (as failed or succeeded), before the bundle proposal is
+    /// deleted from the wallet. The default deletes the proposal as soon as
+    /// the finalizing block is connected.
+    #[arg(long = "wallet-bundle-deletion-confirmations", default_value = "1")]
+    pub bundle_deletion_confirmations: NonZeroU32,
+    /// Number of recent blocks to keep fee rate records for
+    #[arg(long = "wallet-fee-history-window", default_value = "1008")]
+    pub fee_history_window: NonZeroU32,
+    /// Confirmation target in blocks for the `economy` fee tier
+    #[arg(long = "wallet-fee-tier-economy-conf-target", default_value = "144")]
+    pub fee_tier_economy_conf_target: NonZeroU16,
+    /// Confirmation target in blocks for the `normal` fee tier
+    #[arg(long = "wallet-fee-tier-normal-conf-target", default_value = "6")]
+    pub fee_tier_normal_conf_target: NonZeroU16,
+    /// Confirmation target in blocks for the `priority` fee tier
+    #[arg(long = "wallet-fee-tier-priority-conf-target", default_value = "1")]
+    pub fee_tier_priority_conf_target: NonZeroU16,
+    /// If set, the wallet database is compacted (`VACUUM`) at this interval,
+    /// between periodic wallet syncs. Compaction briefly holds an exclusive
+    /// lock on the wallet database.
+    #[arg(long = "wallet-db-compaction-interval-secs")]
+    pub db_compaction_interval_secs: Option<u64>,
+    /// Number of read-only connections to the wallet database, so that
+    /// queries do not queue behind writes (eg. during sync)
+    #[arg(long = "wallet-db-read-connections", default_value = "4")]
+    pub db_read_connections: NonZeroUsize,
+    /// If set, wallet API calls fail with a "wallet busy" error if a wallet
+    /// lock cannot be acquired within this many seconds, eg. while a slow
+    /// sync is in progress. Syncs and block processing always wait for the
+    /// wallet lock.
+    #[arg(long = "wallet-lock-timeout-secs")]
+    pub lock_timeout_secs: Option<u64>,
+    /// If set, a warning is logged when the wallet balance drops below this
+    /// many sats after a sync, with target
+    /// `bip300301_enforcer_lib::wallet::low_balance`.
+    #[arg(long = "wallet-low-balance-threshold-sats")]
+    pub low_balance_threshold_sats: Option<u64>,
+    /// Report the wallet gRPC service as not serving while the wallet
+    /// balance is below the low balance threshold.
+    #[arg(
+        long = "wallet-low-balance-degrade-health",
+        default_value_t = false,
+        requires = "low_balance_threshold_sats"
+    )]
+    pub low_balance_degrade_health: bool,
+
+    /// If set, once the wallet has more than this many mature coinbase
+    /// outputs, they are consolidated into a single output while mempool fee
+    /// rates are low. Checked after each periodic wallet sync.
+    #[arg(long = "wallet-coinbase-consolidation-threshold")]
+    pub coinbase_consolidation_threshold: Option<NonZeroUsize>,
+    /// Address to consolidate coinbase outputs to. If not set, coinbase
+    /// outputs are consolidated to a new wallet address.
+    #[arg(
+        long = "wallet-coinbase-consolidation-address",
+        value_parser = parse_bitcoin_address,
+        requires = "coinbase_consolidation_threshold"
+    )]
+    pub coinbase_consolidation_address: Option<bitcoin::Address>,
+    /// Only consolidate coinbase outputs if the fee rate required for
+    /// inclusion in the next block, estimated from the mempool, is at most
+    /// this many sat/vB.
+    #[arg(
+        long = "wallet-coinbase-consolidation-max-fee-rate",
+        default_value_t = 2
+    )]
+    pub coinbase_consolidation_max_fee_rate: u64,
+
+    /// If set, wait for an initial wallet sync before serving
+    /// `getblocktemplate` and reporting the wallet gRPC service as serving.
+    /// A freshly created wallet is fully scanned. Startup fails if the sync
+    /// does not complete within this many seconds.
+    #[arg(long = "wallet-initial-sync-timeout-secs")]
+    pub initial_sync_timeout_secs: Option<u64>,
 
     /// Skip the periodic wallet sync task. This can be useful if
     /// the wallet is large and periodic syncs are not feasible.
@@ -341,10 +848,99 @@ pub struct WalletConfig {
     /// The source of the wallet sync.
     #[arg(long = "wallet-sync-source", default_value_t = WalletSyncSource::default(), value_enum)]
     pub sync_source: WalletSyncSource,
+    /// Chain sources to fall back to, in priority order, if syncing via the
+    /// primary sync source fails. Ignored if the sync source is disabled.
+    /// Fallback sources that fail to initialize at startup are skipped.
+    #[arg(
+        long = "wallet-sync-fallback-source",
+        value_delimiter = ',',
+        value_enum
+    )]
+    pub sync_fallback_sources: Vec<WalletSyncSource>,
+    /// Before each sync, check that all chain sources agree on the block hash
+    /// at the lowest of their tip heights, to detect a misbehaving server.
+    /// Syncs fail unless at least two chain sources respond and agree.
+    /// Requires at least one fallback source.
+    #[arg(
+        long = "wallet-sync-cross-check-tips",
+        default_value_t = false,
+        requires = "sync_fallback_sources"
+    )]
+    pub sync_cross_check_tips: bool,
+    /// Probe the chain sources at this interval, in seconds, and report the
+    /// wallet gRPC service as not serving while none are reachable.
+    /// If not set, chain sources are not monitored.
+    #[arg(long = "wallet-chain-source-probe-interval-secs")]
+    pub chain_source_probe_interval_secs: Option<u64>,
+    /// Number of consecutive failed probes before the chain sources are
+    /// reported as unreachable
+    #[arg(long = "wallet-chain-source-failure-threshold", default_value = "3")]
+    pub chain_source_failure_threshold: NonZeroU32,
+    /// Report the wallet gRPC service as not serving while the last
+    /// successful wallet sync is older than this many seconds. Until the
+    /// first sync, the age is measured from startup. Should be generous on
+    /// networks with slow or irregular block times.
+    /// If not set, sync staleness is not monitored.
+    #[arg(long = "wallet-sync-stale-threshold-secs")]
+    pub sync_stale_threshold_secs: Option<NonZeroU64>,
 
     /// Path to a file containing exactly 12 space-separated BIP39 mnemonic words.
     #[arg(long = "wallet-seed-file", conflicts_with = "auto_create")]
     pub mnemonic_path: Option<PathBuf>,
+
+    /// Start with the wallet locked, and wait for an explicit unlock via the
+    /// UnlockWallet RPC before syncing the wallet or serving
+    /// `getblocktemplate`. Requires an encrypted wallet.
+    #[arg(
+        long = "wallet-require-unlock",
+        default_value_t = false,
+        conflicts_with_all = ["auto_create", "mnemonic_path"]
+    )]
+    pub require_unlock: bool,
+    /// What to do if the wallet is not initialized when the mempool sync task
+    /// starts, if the mempool is enabled. Not applicable with
+    /// `--wallet-require-unlock`, which always waits for the wallet to be
+    /// unlocked.
+    #[arg(
+        default_value_t,
+        long = "wallet-uninitialized-policy",
+        value_enum,
+        conflicts_with = "require_unlock"
+    )]
+    pub uninitialized_policy: UninitializedWalletPolicy,
+    /// Allow exporting the wallet's descriptors with private keys, via
+    /// `admin.export_private_descriptors`. The JSON-RPC server is not
+    /// authenticated, so this should only be enabled if the server is not
+    /// reachable by untrusted clients.
+    #[arg(
+        long = "wallet-allow-private-descriptor-export",
+        default_value_t = false
+    )]
+    pub allow_private_descriptor_export: bool,
+}
+
+impl WalletConfig {
+    /// Minimum deposit amount for a sidechain, if configured
+    pub fn sidechain_deposit_minimum(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Option<bitcoin::Amount> {
+        self.sidechain_deposit_minimums
+            .as_ref()?
+            .0
+            .iter()
+            .find(|deposit_minimum| deposit_minimum.sidechain_number == sidechain_number)
+            .map(|deposit_minimum| deposit_minimum.minimum)
+    }
+
+    /// Whether the wallet creates deposits, withdrawal bundles, and coinbase
+    /// messages for a sidechain
+    pub fn is_sidechain_allowed(&self, sidechain_number: SidechainNumber) -> bool {
+        self.sidechain_allowlist
+            .as_ref()
+            .is_none_or(|allowlist| allowlist.contains(&sidechain_number))
+            && !self.sidechain_denylist.contains(&sidechain_number)
+    }
 }
 
 #[derive(miette::Diagnostic, Debug, Error)]
@@ -363,8 +959,61 @@ const DEFAULT_SERVE_RPC_ADDR: SocketAddr =
     SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8122));
 const DEFAULT_SERVE_JSON_RPC_ADDR: SocketAddr =
     SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8123));
-const DEFAULT_SERVE_GRPC_ADDR: SocketAddr =
-    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 50_051));
+const DEFAULT_SERVE_GRPC_ADDR: ServeAddr = ServeAddr::Tcp(SocketAddr::V4(SocketAddrV4::new(
+    Ipv4Addr::LOCALHOST,
+    50_051,
+)));
+
+const UNIX_SERVE_ADDR_PREFIX: &str = "unix:";
+
+/// Address to serve on, either `ip:port` or `unix:/path/to/socket`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ServeAddr {
+    Tcp(SocketAddr),
+    /// Unix domain socket path
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for ServeAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "{UNIX_SERVE_ADDR_PREFIX}{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for ServeAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix(UNIX_SERVE_ADDR_PREFIX) {
+            if path.is_empty() {
+                return Err("Unix socket path must not be empty".to_string());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        s.parse()
+            .map(Self::Tcp)
+            .map_err(|_| "expected `IP:PORT` or `unix:PATH`".to_string())
+    }
+}
+
+impl serde::Serialize for ServeAddr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+fn parse_unix_socket_mode(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| "expected octal permissions, eg. `660`".to_string())
+}
 
 fn get_long_version() -> clap::builder::Str {
     format!(
@@ -389,25 +1038,98 @@ pub struct Config {
     /// getblocktemplate.
     #[arg(long, default_value_t = false)]
     pub enable_mempool: bool,
+    /// If mempool sync fails while running (eg. due to a ZMQ disconnect),
+    /// re-establish the ZMQ subscription and resync the mempool up to this
+    /// many times before exiting. Retries use exponential backoff.
+    #[arg(long, default_value_t = 5)]
+    pub mempool_sync_max_retries: u32,
+    /// Initial backoff between mempool sync retries, in seconds
+    #[arg(long, default_value_t = 1)]
+    pub mempool_sync_retry_backoff_secs: u64,
+    /// If fetching a block from Bitcoin Core fails with a transient error
+    /// (eg. a connection error) while the wallet is connecting blocks, retry
+    /// up to this many times. Retries use exponential backoff.
+    /// Errors such as a missing or pruned block are not retried.
+    #[arg(long, default_value_t = 5)]
+    pub block_fetch_max_retries: u32,
+    /// Initial backoff between block fetch retries, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub block_fetch_retry_backoff_ms: u64,
+    /// If a reorg deeper than this many blocks is detected, halt block
+    /// processing instead of reorging. Operator intervention is then
+    /// required. If not set, reorgs of any depth are processed.
+    #[arg(long)]
+    pub max_reorg_depth: Option<NonZeroU32>,
+    /// Number of confirmations after which a deposit is considered final.
+    /// Must be greater than `--max-reorg-depth`, so that final deposits
+    /// cannot be reorged away. Defaults to one more than `--max-reorg-depth`,
+    /// if set.
+    #[arg(long)]
+    pub deposit_finality_depth: Option<NonZeroU32>,
+    /// Restore the validator database from a snapshot file (exported with
+    /// `admin.export_validator_snapshot`) before starting, instead of
+    /// syncing from genesis. Fails if a validator database already exists.
+    #[arg(long)]
+    pub import_validator_snapshot: Option<PathBuf>,
+    /// Directory that `admin.export_validator_snapshot` writes snapshots
+    /// to. Snapshot export is disabled if not set.
+    #[arg(long)]
+    pub validator_snapshot_dir: Option<PathBuf>,
+    /// Enable admin methods that change the state of the running enforcer:
+    /// `admin.set_log_filter`, `admin.export_validator_snapshot`, and
+    /// `admin.reconcile_proposals`. The JSON-RPC server is not
+    /// authenticated, so this should only be enabled if the server is not
+    /// reachable by untrusted clients.
+    #[arg(long, default_value_t = false)]
+    pub enable_admin_mutations: bool,
+    /// If set, POST a JSON payload to this URL for each deposit and
+    /// withdrawal bundle event in connected blocks. Webhooks are delivered
+    /// in the background, and do not delay block processing.
+    #[arg(long)]
+    pub webhook_url: Option<url::Url>,
+    /// Retry failed webhook deliveries up to this many times. Retries use
+    /// exponential backoff.
+    #[arg(long, default_value_t = 5)]
+    pub webhook_max_retries: u32,
+    /// Initial backoff between webhook delivery retries, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub webhook_retry_backoff_ms: u64,
     #[command(flatten)]
     pub logger_opts: LoggerConfig,
     #[command(flatten)]
     pub mining_opts: MiningConfig,
     #[command(flatten)]
     pub node_rpc_opts: NodeRpcConfig,
+    #[command(flatten)]
+    pub rate_limit_opts: RateLimitConfig,
+    #[command(flatten)]
+    pub server_connection_opts: ServerConnectionConfig,
     /// Bitcoin node ZMQ endpoint for `sequence`. If not set, we try to find
     /// it via `bitcoin-cli getzmqnotifications`.
     #[arg(long)]
     pub node_zmq_addr_sequence: Option<String>,
+    /// Bitcoin node ZMQ endpoint for `hashblock`. If set, the wallet syncs
+    /// as soon as a new block is announced, rather than waiting for the next
+    /// periodic sync. Has no effect if the wallet is disabled, or if
+    /// periodic wallet syncs are disabled. Otherwise, startup fails if the
+    /// endpoint is not reachable, or if Bitcoin Core does not publish
+    /// `pubhashblock` notifications.
+    #[arg(long)]
+    pub node_zmq_addr_hashblock: Option<String>,
     /// Serve RPCs such as `getblocktemplate` on this address
     #[arg(default_value_t = DEFAULT_SERVE_RPC_ADDR, long)]
     pub serve_rpc_addr: SocketAddr,
     /// Serve other JSON-RPC methods on this address
     #[arg(default_value_t = DEFAULT_SERVE_JSON_RPC_ADDR, long)]
     pub serve_json_rpc_addr: SocketAddr,
-    /// Serve gRPCs on this address
+    /// Serve gRPCs on this address, either `IP:PORT`, or `unix:PATH` to
+    /// serve on a Unix domain socket
     #[arg(default_value_t = DEFAULT_SERVE_GRPC_ADDR, long)]
-    pub serve_grpc_addr: SocketAddr,
+    pub serve_grpc_addr: ServeAddr,
+    /// File permissions for the gRPC Unix domain socket, in octal, eg. `660`.
+    /// If not set, permissions are determined by the process umask.
+    #[arg(long, value_parser = parse_unix_socket_mode)]
+    pub serve_grpc_unix_socket_mode: Option<u32>,
     #[command(flatten)]
     pub wallet_opts: WalletConfig,
 
@@ -423,6 +1145,15 @@ impl Config {
         env!("GIT_HASH")
     }
 
+    /// Number of confirmations after which a deposit is considered final,
+    /// either as configured or as implied by the maximum reorg depth
+    pub fn deposit_finality_depth(&self) -> Option<NonZeroU32> {
+        self.deposit_finality_depth.or_else(|| {
+            self.max_reorg_depth
+                .and_then(|max_reorg_depth| max_reorg_depth.checked_add(1))
+        })
+    }
+
     pub fn bitcoin_cli(&self, network: bitcoin::Network) -> crate::bins::BitcoinCli {
         crate::bins::BitcoinCli {
             path: self.mining_opts.bitcoin_cli_path.clone(),
@@ -472,3 +1203,46 @@ impl Config {
             .map_err(RollingLoggerError::Init)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser as _;
+
+    use super::{Config, SidechainDepositMinimum};
+    use crate::types::SidechainNumber;
+
+    #[test]
+    fn test_parse_sidechain_deposit_minimum() {
+        let deposit_minimum: SidechainDepositMinimum = "1:2000".parse().unwrap();
+        assert_eq!(deposit_minimum.sidechain_number, SidechainNumber::from(1));
+        assert_eq!(deposit_minimum.minimum.to_sat(), 2000);
+        assert!("1".parse::<SidechainDepositMinimum>().is_err());
+        assert!("256:2000".parse::<SidechainDepositMinimum>().is_err());
+        assert!("1:-1".parse::<SidechainDepositMinimum>().is_err());
+    }
+
+    #[test]
+    fn test_parse_sidechain_deposit_minimums() {
+        let parse = |deposit_minimums: &str| {
+            Config::try_parse_from([
+                "bip300301_enforcer",
+                "--wallet-sidechain-deposit-minimums",
+                deposit_minimums,
+            ])
+        };
+        let cli = parse("0:1000,1:2000").unwrap();
+        assert_eq!(
+            cli.wallet_opts
+                .sidechain_deposit_minimum(SidechainNumber::from(1))
+                .map(|minimum| minimum.to_sat()),
+            Some(2000)
+        );
+        assert_eq!(
+            cli.wallet_opts
+                .sidechain_deposit_minimum(SidechainNumber::from(2)),
+            None
+        );
+        assert!(parse("0:1000,0:2000").is_err());
+        assert!(parse("0:1000,").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+pub mod backoff;
 pub mod bins;
 pub mod cli;
 mod convert;
@@ -755,6 +755,60 @@ pub fn parse_op_drivechain(input: &[u8]) -> IResult<&[u8], SidechainNumber> {
     Ok((input, SidechainNumber::from(sidechain_number)))
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum DecodeOpDrivechainError {
+    #[error("expected `{expected}` at byte {index}, found `{found:#04x}`")]
+    #[diagnostic(code(decode_op_drivechain::unexpected_byte))]
+    UnexpectedByte {
+        index: usize,
+        expected: &'static str,
+        found: u8,
+    },
+    #[error("script ended at byte {index}, expected `{expected}`")]
+    #[diagnostic(code(decode_op_drivechain::unexpected_end))]
+    UnexpectedEnd {
+        index: usize,
+        expected: &'static str,
+    },
+}
+
+/// Decoded OP_DRIVECHAIN script
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+pub struct DecodedOpDrivechain {
+    pub sidechain_number: SidechainNumber,
+    /// Script bytes following `OP_DRIVECHAIN <sidechain number> OP_TRUE`
+    #[serde(with = "hex::serde")]
+    pub remaining: Vec<u8>,
+}
+
+/// Decode an OP_DRIVECHAIN script, explaining why the script is invalid if
+/// it is rejected. Accepts exactly the scripts accepted by
+/// [`parse_op_drivechain`].
+pub fn decode_op_drivechain(script: &[u8]) -> Result<DecodedOpDrivechain, DecodeOpDrivechainError> {
+    const SIDECHAIN_NUMBER: &str = "<sidechain number>";
+    let expect = |index: usize, expected: &'static str, byte: Option<u8>| {
+        let Some(found) = script.get(index).copied() else {
+            return Err(DecodeOpDrivechainError::UnexpectedEnd { index, expected });
+        };
+        match byte {
+            Some(byte) if byte != found => Err(DecodeOpDrivechainError::UnexpectedByte {
+                index,
+                expected,
+                found,
+            }),
+            _ => Ok(found),
+        }
+    };
+    let _ = expect(0, "OP_DRIVECHAIN", Some(OP_DRIVECHAIN.to_u8()))?;
+    let _ = expect(1, "OP_PUSHBYTES_1", Some(OP_PUSHBYTES_1.to_u8()))?;
+    let sidechain_number = expect(2, SIDECHAIN_NUMBER, None)?;
+    let _ = expect(3, "OP_TRUE", Some(OP_TRUE.to_u8()))?;
+    Ok(DecodedOpDrivechain {
+        sidechain_number: SidechainNumber::from(sidechain_number),
+        remaining: script[4..].to_vec(),
+    })
+}
+
 pub fn try_parse_op_return_address(script: &Script) -> Option<Vec<u8>> {
     let mut instructions = script.instructions();
     let Some(Ok(Instruction::Op(OP_RETURN))) = instructions.next() else {
@@ -928,6 +982,41 @@ mod tests {
     use super::*;
     use crate::types::SidechainProposal;
 
+    #[test]
+    fn test_decode_op_drivechain() {
+        let valid = create_m5_deposit_output(SidechainNumber(7), Amount::ZERO, Amount::ONE_SAT)
+            .script_pubkey
+            .to_bytes();
+        let decoded = decode_op_drivechain(&valid).unwrap();
+        assert_eq!(decoded.sidechain_number, SidechainNumber(7));
+        assert!(decoded.remaining.is_empty());
+
+        let cases: [(&[u8], _); 4] = [
+            (&[], "script ended at byte 0"),
+            (&[OP_RETURN.to_u8()], "at byte 0"),
+            (
+                &[OP_DRIVECHAIN.to_u8(), OP_PUSHBYTES_1.to_u8(), 7],
+                "script ended at byte 3",
+            ),
+            (
+                &[
+                    OP_DRIVECHAIN.to_u8(),
+                    OP_PUSHBYTES_1.to_u8(),
+                    7,
+                    OP_RETURN.to_u8(),
+                ],
+                "expected `OP_TRUE` at byte 3",
+            ),
+        ];
+        for (script, expected_err) in cases {
+            let err = decode_op_drivechain(script).unwrap_err();
+            assert!(err.to_string().contains(expected_err), "{err}");
+            // Must agree with the parser used by the enforcer
+            assert!(parse_op_drivechain(script).is_err());
+        }
+        assert!(parse_op_drivechain(&valid).is_ok());
+    }
+
     #[test]
     fn test_parse_m8_bmm_request() {
         // This data was given to /cusf.mainchain.v1.WalletService/CreateBmmCriticalDataTransaction
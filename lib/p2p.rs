@@ -2,7 +2,8 @@
 
 use std::net::{Ipv4Addr, SocketAddrV4};
 
-use bitcoin::p2p::Magic;
+use bitcoin::{ScriptBuf, p2p::Magic};
+use thiserror::Error;
 
 pub const SIGNET_MAGIC_BYTES: [u8; 4] = [0xd1, 0xf5, 0x77, 0x6b];
 
@@ -39,3 +40,56 @@ pub fn compute_signet_magic(signet_challenge: &bitcoin::Script) -> Magic {
     let hash = sha256d::Hash::from_engine(hasher);
     Magic::from_bytes(hash[..=3].try_into().unwrap())
 }
+
+#[derive(Debug, Error)]
+pub enum ParseSignetChallengeError {
+    #[error("signet challenge is empty")]
+    Empty,
+    #[error("signet challenge is not valid hex")]
+    Hex(#[from] hex::FromHexError),
+    #[error("signet challenge is not a valid script")]
+    Script(#[from] bitcoin::script::Error),
+}
+
+/// Parse a hex-encoded signet challenge, checking that it decodes to a
+/// well-formed, non-empty script.
+pub fn parse_signet_challenge(challenge_hex: &str) -> Result<ScriptBuf, ParseSignetChallengeError> {
+    let challenge = ScriptBuf::from_bytes(hex::decode(challenge_hex.trim())?);
+    if challenge.is_empty() {
+        return Err(ParseSignetChallengeError::Empty);
+    }
+    for instruction in challenge.instructions() {
+        let _: bitcoin::script::Instruction = instruction?;
+    }
+    Ok(challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseSignetChallengeError, compute_signet_magic, parse_signet_challenge};
+
+    #[test]
+    fn test_compute_standard_signet_magic() {
+        const STANDARD_SIGNET_CHALLENGE_HEX: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+        let challenge = parse_signet_challenge(STANDARD_SIGNET_CHALLENGE_HEX).unwrap();
+        let magic = compute_signet_magic(&challenge);
+        assert_eq!(magic, bitcoin::Network::Signet.magic());
+    }
+
+    #[test]
+    fn test_parse_invalid_signet_challenge() {
+        assert!(matches!(
+            parse_signet_challenge(""),
+            Err(ParseSignetChallengeError::Empty)
+        ));
+        assert!(matches!(
+            parse_signet_challenge("zz"),
+            Err(ParseSignetChallengeError::Hex(_))
+        ));
+        // OP_PUSHBYTES_2 with only one byte of data
+        assert!(matches!(
+            parse_signet_challenge("0201"),
+            Err(ParseSignetChallengeError::Script(_))
+        ));
+    }
+}
@@ -914,7 +914,9 @@ pub mod mainchain {
             Self {
                 txid: Some(ReverseHex::encode(&tx.txid)),
                 raw_transaction: Some(ConsensusHex::encode(&tx.tx)),
-                fee_sats: tx.fee.to_sat(),
+                // TODO: `fee_sats` is not optional in the wallet proto, so an
+                // unknown fee is reported as zero until it is made optional
+                fee_sats: tx.fee.unwrap_or_default().to_sat(),
                 received_sats: tx.received.to_sat(),
                 sent_sats: tx.sent.to_sat(),
                 confirmation_info: Some((&tx.chain_position).into()),
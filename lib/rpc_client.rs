@@ -1,47 +1,101 @@
+use std::{future::Future, sync::Arc};
+
 use bitcoin_jsonrpsee::{
     MainClient,
-    jsonrpsee::{core::ClientError, http_client::HttpClient},
+    client::BlockTemplateRequest,
+    jsonrpsee::{
+        core::{
+            ClientError,
+            client::{BatchResponse, ClientT},
+            params::BatchRequestBuilder,
+            traits::ToRpcParams,
+        },
+        http_client::HttpClient,
+        rpc_params,
+    },
 };
-use miette::miette;
+use miette::{Diagnostic, miette};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
 
 use crate::{cli::NodeRpcConfig, errors::ErrorChain};
 
-pub fn create_client(
-    conf: &NodeRpcConfig,
-    enable_mempool: bool,
-) -> Result<HttpClient, miette::Report> {
-    if conf.user.is_none() != conf.pass.is_none() {
-        return Err(miette!("RPC user and password must be set together"));
-    }
+#[cfg(any(test, feature = "mock-main-client"))]
+pub mod mock;
 
-    if conf.user.is_none() == conf.cookie_path.is_none() {
-        return Err(miette!("precisely one of RPC user and cookie must be set"));
-    }
+/// Bitcoin Core RPC error code, returned while the node is starting up.
+/// From Bitcoin Core src/rpc/protocol.h
+pub const RPC_IN_WARMUP: i32 = -28;
 
-    let mut conf_user = conf.user.clone().unwrap_or_default();
-    let mut conf_pass = conf.pass.clone().unwrap_or_default();
+/// Bitcoin Core JSON-RPC client. Credentials are replaced when the bitcoind
+/// cookie is rotated, see [`watch_cookie_file`]. Clones share credentials.
+#[derive(Clone)]
+pub struct MainchainClient(Arc<parking_lot::RwLock<HttpClient>>);
 
-    if conf.cookie_path.is_some() {
-        let cookie_path = conf.cookie_path.clone().unwrap();
-        let auth = std::fs::read_to_string(cookie_path.clone())
-            .map_err(|err| miette!("unable to read bitcoind cookie at {}: {}", cookie_path, err))?;
+impl MainchainClient {
+    fn http_client(&self) -> HttpClient {
+        self.0.read().clone()
+    }
+}
 
-        let mut auth = auth.split(':');
+impl ClientT for MainchainClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), ClientError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        self.http_client().notification(method, params).await
+    }
 
-        conf_user = auth
-            .next()
-            .ok_or(miette!("failed to get rpcuser"))?
-            .to_string()
-            .clone();
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        self.http_client().request(method, params).await
+    }
 
-        conf_pass = auth
-            .next()
-            .ok_or(miette!("failed to get rpcpassword"))?
-            .to_string()
-            .to_string()
-            .clone();
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, ClientError>
+    where
+        R: DeserializeOwned + std::fmt::Debug + 'a,
+    {
+        self.http_client().batch_request(batch).await
     }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum CookieFileError {
+    #[error("unable to read bitcoind cookie at `{path}`")]
+    #[diagnostic(code(cookie_file::read))]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid bitcoind cookie at `{path}`, expected `user:password`")]
+    #[diagnostic(code(cookie_file::invalid))]
+    Invalid { path: String },
+}
 
+/// Parse the contents of a bitcoind cookie file into a user and password
+fn parse_cookie(path: &str, cookie: &str) -> Result<(String, String), CookieFileError> {
+    let (user, pass) =
+        cookie
+            .trim_end()
+            .split_once(':')
+            .ok_or_else(|| CookieFileError::Invalid {
+                path: path.to_owned(),
+            })?;
+    Ok((user.to_owned(), pass.to_owned()))
+}
+
+fn create_http_client(
+    conf: &NodeRpcConfig,
+    enable_mempool: bool,
+    user: &str,
+    pass: &str,
+) -> Result<HttpClient, miette::Report> {
     let client_builder = if enable_mempool {
         // A mempool of default size might contain >300k txs.
         // batch Requesting 300k txs requires ~30MiB,
@@ -60,10 +114,188 @@ pub fn create_client(
         None
     };
 
-    bitcoin_jsonrpsee::client(conf.addr, client_builder, &conf_pass, &conf_user)
+    bitcoin_jsonrpsee::client(conf.addr, client_builder, pass, user)
         .map_err(|err| miette!("failed to create mainchain RPC client: {err:#}"))
 }
 
+pub fn create_client(
+    conf: &NodeRpcConfig,
+    enable_mempool: bool,
+) -> Result<MainchainClient, miette::Report> {
+    if conf.user.is_none() != conf.pass.is_none() {
+        return Err(miette!("RPC user and password must be set together"));
+    }
+
+    if conf.user.is_none() == conf.cookie_path.is_none() {
+        return Err(miette!("precisely one of RPC user and cookie must be set"));
+    }
+
+    let (user, pass) = match &conf.cookie_path {
+        Some(cookie_path) => {
+            let cookie =
+                std::fs::read_to_string(cookie_path).map_err(|err| CookieFileError::Read {
+                    path: cookie_path.clone(),
+                    source: err,
+                })?;
+            parse_cookie(cookie_path, &cookie)?
+        }
+        None => (
+            conf.user.clone().unwrap_or_default(),
+            conf.pass.clone().unwrap_or_default(),
+        ),
+    };
+
+    let http_client = create_http_client(conf, enable_mempool, &user, &pass)?;
+    Ok(MainchainClient(Arc::new(parking_lot::RwLock::new(
+        http_client,
+    ))))
+}
+
+/// Watch the bitcoind cookie file, and update the client's credentials each
+/// time the cookie is rotated, eg. when Bitcoin Core restarts. While the
+/// cookie is missing or invalid, the current credentials are kept.
+/// `conf` and `enable_mempool` must be the same as were used to create the
+/// client. Returns immediately if no cookie path is set, or once the
+/// shutdown signal resolves.
+pub async fn watch_cookie_file<F>(
+    client: MainchainClient,
+    conf: NodeRpcConfig,
+    enable_mempool: bool,
+    shutdown_signal: F,
+) where
+    F: Future<Output = ()>,
+{
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+    let Some(path) = conf.cookie_path.clone() else {
+        return;
+    };
+    // Cookie that the client's credentials were last set from. `None` if the
+    // cookie could not be read when the watcher started, in which case the
+    // credentials are refreshed as soon as it can be read.
+    let mut current_cookie = tokio::fs::read_to_string(&path).await.ok();
+    let mut cookie_missing = false;
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    futures::pin_mut!(shutdown_signal);
+    loop {
+        tokio::select! {
+            biased;  // Prioritize shutdown
+            () = &mut shutdown_signal => {
+                tracing::info!("shutting down bitcoind cookie watcher");
+                return;
+            }
+            _ = interval.tick() => (),
+        }
+        let cookie = match tokio::fs::read_to_string(&path).await {
+            Ok(cookie) => cookie,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if !cookie_missing {
+                    tracing::warn!(
+                        %path,
+                        "bitcoind cookie was removed, keeping current RPC credentials until it is recreated"
+                    );
+                    cookie_missing = true;
+                }
+                continue;
+            }
+            Err(err) => {
+                let err = CookieFileError::Read {
+                    path: path.clone(),
+                    source: err,
+                };
+                tracing::warn!("{:#}", ErrorChain::new(&err));
+                continue;
+            }
+        };
+        cookie_missing = false;
+        if current_cookie.as_ref() == Some(&cookie) {
+            continue;
+        }
+        let http_client = match parse_cookie(&path, &cookie) {
+            Ok((user, pass)) => create_http_client(&conf, enable_mempool, &user, &pass),
+            Err(err) => Err(err.into()),
+        };
+        match http_client {
+            Ok(http_client) => {
+                *client.0.write() = http_client;
+                current_cookie = Some(cookie);
+                tracing::info!(%path, "bitcoind cookie changed, updated RPC credentials");
+            }
+            Err(err) => {
+                tracing::warn!("failed to update RPC credentials from bitcoind cookie: {err:#}");
+            }
+        }
+    }
+}
+
+/// Subset of the `getdeploymentinfo` response
+#[derive(serde::Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct DeploymentInfo {
+    deployments: std::collections::HashMap<String, Deployment>,
+}
+
+#[derive(serde::Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct Deployment {
+    #[serde(rename = "type")]
+    kind: String,
+    active: bool,
+}
+
+/// Names of active BIP9 deployments. Bitcoin Core rejects `getblocktemplate`
+/// requests that do not include rules for these.
+async fn get_active_bip9_deployments<RpcClient>(
+    rpc_client: &RpcClient,
+) -> Result<Vec<String>, ClientError>
+where
+    RpcClient: MainClient + Sync,
+{
+    let deployment_info: DeploymentInfo = rpc_client
+        .request("getdeploymentinfo", rpc_params![])
+        .await?;
+    let mut res: Vec<_> = deployment_info
+        .deployments
+        .into_iter()
+        .filter_map(|(name, deployment)| {
+            (deployment.kind == "bip9" && deployment.active).then_some(name)
+        })
+        .collect();
+    res.sort();
+    Ok(res)
+}
+
+/// Rules to request in `getblocktemplate`. These are the defaults, `signet`
+/// on signet, rules for active BIP9 deployments, and `extra_rules`.
+pub async fn block_template_rules<RpcClient>(
+    rpc_client: &RpcClient,
+    network: bitcoin::Network,
+    extra_rules: &[String],
+) -> Vec<String>
+where
+    RpcClient: MainClient + Sync,
+{
+    let mut res = BlockTemplateRequest::default().rules;
+    if network == bitcoin::Network::Signet {
+        res.push("signet".to_owned())
+    }
+    // Older versions of Bitcoin Core do not support `getdeploymentinfo`
+    match get_active_bip9_deployments(rpc_client).await {
+        Ok(active_deployments) => res.extend(active_deployments),
+        Err(err) => tracing::warn!(
+            "unable to derive block template rules from deployment info: {:#}",
+            ErrorChain::new(&err)
+        ),
+    }
+    res.extend(extra_rules.iter().cloned());
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// `maxburnamount` to pass to `sendrawtransaction`, so that transactions
+/// that "burn" bitcoin (from a BIP-300/1 unaware perspective) are accepted.
+pub const MAX_BURN_AMOUNT: f64 = 21_000_000.0;
+
 /// Broadcasts a transaction to the Bitcoin network.
 /// Returns `Some(txid)` if broadcast successfully, `None` if the tx failed to
 /// broadcast due to the node not supporting OP_DRIVECHAIN
@@ -83,7 +315,6 @@ where
     // 1. Send the raw TX from our Electrum client
     // 2. Electrum server implements this by sending it into Bitcoin Core
     // 3. Bitcoin Core responds with an error, because we're burning money.
-    const MAX_BURN_AMOUNT: f64 = 21_000_000.0;
     let encoded_tx = bitcoin::consensus::encode::serialize_hex(tx);
     match rpc_client
         .send_raw_transaction(encoded_tx, None, Some(MAX_BURN_AMOUNT))
@@ -106,3 +337,109 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash as _;
+
+    use super::{
+        CookieFileError, Deployment, DeploymentInfo, block_template_rules, broadcast_transaction,
+        mock::MockMainClient, parse_cookie,
+    };
+
+    fn empty_tx() -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_transaction() {
+        let txid = bitcoin::Txid::all_zeros();
+        let client = MockMainClient::new().with_response("sendrawtransaction", txid);
+        let res = broadcast_transaction(&client, &empty_tx()).await.unwrap();
+        assert_eq!(res, Some(txid));
+        let requests = client.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "sendrawtransaction");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_transaction_op_drivechain_not_supported() {
+        let client = MockMainClient::new().with_error(
+            "sendrawtransaction",
+            -26,
+            "non-mandatory-script-verify-flag (NOPx reserved for soft-fork upgrades)",
+        );
+        let res = broadcast_transaction(&client, &empty_tx()).await.unwrap();
+        assert_eq!(res, None);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_transaction_rejected() {
+        let client = MockMainClient::new().with_error(
+            "sendrawtransaction",
+            -25,
+            "bad-txns-inputs-missingorspent",
+        );
+        assert!(broadcast_transaction(&client, &empty_tx()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_block_template_rules_signet() {
+        for (network, expect_signet_rule) in [
+            (bitcoin::Network::Regtest, false),
+            (bitcoin::Network::Signet, true),
+        ] {
+            let client = MockMainClient::new();
+            let rules = block_template_rules(&client, network, &[]).await;
+            assert_eq!(
+                rules.iter().any(|rule| rule == "signet"),
+                expect_signet_rule
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_template_rules_deployments() {
+        let deployment = |kind: &str, active| Deployment {
+            kind: kind.to_owned(),
+            active,
+        };
+        let client = MockMainClient::new().with_response(
+            "getdeploymentinfo",
+            DeploymentInfo {
+                deployments: [
+                    ("segwit".to_owned(), deployment("buried", true)),
+                    ("testdummy".to_owned(), deployment("bip9", true)),
+                    ("inactive".to_owned(), deployment("bip9", false)),
+                ]
+                .into_iter()
+                .collect(),
+            },
+        );
+        let rules = block_template_rules(
+            &client,
+            bitcoin::Network::Regtest,
+            &["extra".to_owned(), "testdummy".to_owned()],
+        )
+        .await;
+        assert!(rules.contains(&"extra".to_owned()));
+        assert!(!rules.contains(&"inactive".to_owned()));
+        assert_eq!(rules.iter().filter(|rule| *rule == "testdummy").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_cookie() {
+        let (user, pass) = parse_cookie("cookie", "__cookie__:abc:def\n").unwrap();
+        assert_eq!(user, "__cookie__");
+        assert_eq!(pass, "abc:def");
+        assert!(matches!(
+            parse_cookie("cookie", "__cookie__"),
+            Err(CookieFileError::Invalid { .. })
+        ));
+    }
+}
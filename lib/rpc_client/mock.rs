@@ -0,0 +1,178 @@
+//! Mock Bitcoin Core JSON-RPC backend, for tests that should not require a
+//! running node.
+//!
+//! [`MockMainClient`] implements [`ClientT`], and therefore also
+//! [`bitcoin_jsonrpsee::MainClient`]. Responses are set per method, and are
+//! returned for every request to that method until replaced.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use bitcoin_jsonrpsee::jsonrpsee::{
+    core::{
+        client::{BatchResponse, ClientT, Error},
+        params::BatchRequestBuilder,
+        traits::ToRpcParams,
+    },
+    types::ErrorObjectOwned,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+
+/// From Bitcoin Core src/rpc/protocol.h
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+
+/// Canned `getblocktemplate` response, from a regtest node with only the
+/// genesis block
+pub fn regtest_block_template() -> serde_json::Value {
+    json!({
+        "capabilities": ["proposal"],
+        "version": 0x2000_0000,
+        "rules": ["csv", "!segwit", "taproot"],
+        "vbavailable": {},
+        "vbrequired": 0,
+        "previousblockhash": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        "transactions": [],
+        "coinbaseaux": {},
+        "coinbasevalue": 5_000_000_000u64,
+        "longpollid": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e22060",
+        "target": "7fffff0000000000000000000000000000000000000000000000000000000000",
+        "mintime": 1_296_688_603,
+        "mutable": ["time", "transactions", "prevblock"],
+        "noncerange": "00000000ffffffff",
+        "sigoplimit": 80_000,
+        "sizelimit": 4_000_000,
+        "weightlimit": 4_000_000,
+        "curtime": 1_700_000_000,
+        "bits": "207fffff",
+        "height": 1,
+        "default_witness_commitment": "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9",
+    })
+}
+
+/// Request received by a [`MockMainClient`]
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: String,
+    pub params: Option<serde_json::Value>,
+}
+
+#[derive(Default)]
+struct Inner {
+    responses: HashMap<String, Result<serde_json::Value, ErrorObjectOwned>>,
+    requests: Vec<MockRequest>,
+}
+
+/// Bitcoin Core JSON-RPC client that serves canned responses
+#[derive(Clone, Default)]
+pub struct MockMainClient(Arc<Mutex<Inner>>);
+
+impl MockMainClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_inner<F, Output>(&self, f: F) -> Output
+    where
+        F: FnOnce(&mut Inner) -> Output,
+    {
+        f(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Respond to requests for `method` with `response`.
+    /// Panics if `response` cannot be serialized.
+    pub fn set_response<T>(&self, method: &str, response: T)
+    where
+        T: Serialize,
+    {
+        let response =
+            serde_json::to_value(response).expect("mock response should serialize to JSON");
+        self.with_inner(|inner| inner.responses.insert(method.to_owned(), Ok(response)));
+    }
+
+    /// Respond to requests for `method` with a JSON-RPC error
+    pub fn set_error(&self, method: &str, code: i32, message: &str) {
+        let err = ErrorObjectOwned::owned(code, message, Option::<()>::None);
+        self.with_inner(|inner| inner.responses.insert(method.to_owned(), Err(err)));
+    }
+
+    /// Builder-style [`Self::set_response`]
+    pub fn with_response<T>(self, method: &str, response: T) -> Self
+    where
+        T: Serialize,
+    {
+        self.set_response(method, response);
+        self
+    }
+
+    /// Builder-style [`Self::set_error`]
+    pub fn with_error(self, method: &str, code: i32, message: &str) -> Self {
+        self.set_error(method, code, message);
+        self
+    }
+
+    /// All requests received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.with_inner(|inner| inner.requests.clone())
+    }
+
+    fn respond<R>(&self, method: &str, params: Option<serde_json::Value>) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        let response = self.with_inner(|inner| {
+            inner.requests.push(MockRequest {
+                method: method.to_owned(),
+                params,
+            });
+            inner.responses.get(method).cloned()
+        });
+        match response {
+            Some(Ok(response)) => serde_json::from_value(response).map_err(Error::ParseError),
+            Some(Err(err)) => Err(Error::Call(err)),
+            None => Err(Error::Call(ErrorObjectOwned::owned(
+                RPC_METHOD_NOT_FOUND,
+                format!("Method not found: `{method}`"),
+                Option::<()>::None,
+            ))),
+        }
+    }
+}
+
+impl ClientT for MockMainClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), Error>
+    where
+        Params: ToRpcParams + Send,
+    {
+        let _: serde_json::Value = self.request(method, params).await?;
+        Ok(())
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let params = params
+            .to_rpc_params()
+            .map_err(Error::ParseError)?
+            .map(|params| serde_json::from_str(params.get()))
+            .transpose()
+            .map_err(Error::ParseError)?;
+        self.respond(method, params)
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        _batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, Error>
+    where
+        R: DeserializeOwned + std::fmt::Debug + 'a,
+    {
+        Err(Error::Custom(
+            "batch requests are not supported by the mock client".to_owned(),
+        ))
+    }
+}
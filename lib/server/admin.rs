@@ -0,0 +1,446 @@
+//! Operator-facing control methods
+
+use std::{net::SocketAddr, num::NonZeroU32, path::PathBuf, sync::Arc};
+
+use futures::TryFutureExt as _;
+use jsonrpsee::{
+    core::{RpcResult, async_trait},
+    proc_macros::rpc,
+};
+use serde::Serialize;
+use thiserror::Error;
+use tracing_appender::non_blocking::ErrorCounter;
+use tracing_subscriber::{EnvFilter, reload};
+
+use crate::{
+    cli::{
+        self, CoinbaseRecipientPolicy, LogFileBackpressure, ServeAddr, UninitializedWalletPolicy,
+        WalletSyncSource,
+    },
+    server::custom_json_rpc_err,
+    validator::{SnapshotHeader, Validator},
+    wallet::{
+        ChainSourceDiagnostics, ExportedDescriptors, ProposalReconciliation, Wallet,
+        WalletDiagnostics,
+    },
+};
+
+/// Placeholder for secrets that are set, but not reported
+const REDACTED: &str = "<redacted>";
+
+/// Handle used to swap out the log filter of a running subscriber
+pub trait LogFilterHandle: Send + Sync {
+    fn reload(&self, filter: EnvFilter) -> Result<(), reload::Error>;
+
+    /// Directives of the active log filter
+    fn current_directives(&self) -> Result<String, reload::Error>;
+}
+
+impl<S> LogFilterHandle for reload::Handle<EnvFilter, S> {
+    fn reload(&self, filter: EnvFilter) -> Result<(), reload::Error> {
+        reload::Handle::reload(self, filter)
+    }
+
+    fn current_directives(&self) -> Result<String, reload::Error> {
+        self.with_current(|filter| filter.to_string())
+    }
+}
+
+#[derive(Debug, Error)]
+enum SetLogFilterError {
+    #[error("invalid log filter directives `{directives}`")]
+    Parse {
+        directives: String,
+        source: tracing_subscriber::filter::ParseError,
+    },
+    #[error("failed to reload log filter")]
+    Reload(#[from] reload::Error),
+}
+
+#[derive(Debug, Error)]
+#[error("`admin.{method}` is disabled, see `--enable-admin-mutations`")]
+struct AdminMutationsDisabledError {
+    method: &'static str,
+}
+
+#[derive(Debug, Error)]
+#[error("configuration is not available")]
+struct ConfigNotAvailableError;
+
+#[derive(Debug, Error)]
+#[error("log file stats are not available")]
+struct LogFileNotAvailableError;
+
+#[derive(Debug, Error)]
+#[error("validator is not available")]
+struct ValidatorNotAvailableError;
+
+#[derive(Debug, Error)]
+#[error("wallet is not enabled")]
+struct WalletNotEnabledError;
+
+#[derive(Debug, Error)]
+#[error("validator snapshot export is disabled, see `--validator-snapshot-dir`")]
+struct SnapshotExportDisabledError;
+
+#[derive(Debug, Error)]
+#[error("snapshot file name `{file_name}` must not contain path separators")]
+struct InvalidSnapshotFileNameError {
+    file_name: String,
+}
+
+#[derive(Debug, Error)]
+#[error("private descriptor export is disabled, see `--wallet-allow-private-descriptor-export`")]
+struct PrivateDescriptorExportDisabledError;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeRpcSummary {
+    pub addr: SocketAddr,
+    pub cookie_path: Option<String>,
+    pub user: Option<String>,
+    /// Redacted if set
+    pub pass: Option<&'static str>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MiningSummary {
+    pub coinbase_recipient: Option<String>,
+    pub coinbase_recipient_policy: CoinbaseRecipientPolicy,
+    pub coinbase_recipient_fallback: Option<String>,
+    pub block_template_max_txs: Option<usize>,
+    pub block_template_max_weight: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletSummary {
+    pub sync_source: WalletSyncSource,
+    pub sync_fallback_sources: Vec<WalletSyncSource>,
+    /// Effective chain source endpoints, with any credentials removed.
+    /// `None` if the wallet is not running.
+    pub chain_sources: Option<Vec<ChainSourceDiagnostics>>,
+    pub require_unlock: bool,
+    pub uninitialized_policy: UninitializedWalletPolicy,
+    pub allow_private_descriptor_export: bool,
+    /// Redacted if set
+    pub seed_file: Option<&'static str>,
+}
+
+/// Effective configuration of the running enforcer. Secrets are redacted.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigSummary {
+    pub git_hash: &'static str,
+    /// `None` if the validator is not available
+    pub network: Option<bitcoin::Network>,
+    pub data_dir: PathBuf,
+    pub enable_wallet: bool,
+    pub enable_mempool: bool,
+    pub enable_admin_mutations: bool,
+    pub max_reorg_depth: Option<NonZeroU32>,
+    pub deposit_finality_depth: Option<NonZeroU32>,
+    pub node_rpc: NodeRpcSummary,
+    pub node_zmq_addr_sequence: Option<String>,
+    pub node_zmq_addr_hashblock: Option<String>,
+    pub serve_rpc_addr: SocketAddr,
+    pub serve_json_rpc_addr: SocketAddr,
+    pub serve_grpc_addr: ServeAddr,
+    pub log_level: String,
+    /// Active log filter directives, including any set via
+    /// `admin.set_log_filter`
+    pub log_filter: Option<String>,
+    pub mining: MiningSummary,
+    pub wallet: WalletSummary,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LogFileStats {
+    pub backpressure: LogFileBackpressure,
+    /// Log lines dropped because the log file buffer was full. Always zero
+    /// if backpressure is set to `block`.
+    pub dropped_lines: usize,
+}
+
+#[rpc(namespace = "admin", namespace_separator = ".", server)]
+pub trait Rpc {
+    /// Replace the active log filter. The provided directives are applied
+    /// on top of the startup directives (the defaults for `--log-level`,
+    /// followed by `RUST_LOG`, if set), using the same syntax as the
+    /// `RUST_LOG` environment variable (e.g. `bip300301_enforcer=trace`).
+    /// Directives set by a previous call are discarded, and an empty string
+    /// restores the startup directives.
+    /// Requires `--enable-admin-mutations`.
+    #[method(name = "set_log_filter")]
+    fn set_log_filter(&self, directives: String) -> RpcResult<()>;
+
+    /// Effective configuration, as parsed from the command line. Passwords
+    /// and the wallet seed file are redacted, and credentials are removed
+    /// from chain source URLs.
+    #[method(name = "get_config")]
+    fn get_config(&self) -> RpcResult<ConfigSummary>;
+
+    /// Log file writer settings, and the number of log lines dropped since
+    /// startup
+    #[method(name = "get_log_file_stats")]
+    fn get_log_file_stats(&self) -> RpcResult<LogFileStats>;
+
+    /// Snapshot of wallet state for troubleshooting: chain sources, last
+    /// sync time, wallet tip, balances, address and UTXO counts, and pending
+    /// proposal counts. Descriptors and key material are not included.
+    #[method(name = "dump_wallet_diagnostics")]
+    async fn dump_wallet_diagnostics(&self) -> RpcResult<WalletDiagnostics>;
+
+    /// Write a snapshot of the validator database to `file_name` in
+    /// `--validator-snapshot-dir` on the enforcer's host, for backups or for
+    /// provisioning new instances with `--import-validator-snapshot`. The
+    /// file must not already exist. Block processing continues while the
+    /// snapshot is written; if the tip changes during the copy, the copy is
+    /// retried. Requires `--enable-admin-mutations`.
+    #[method(name = "export_validator_snapshot")]
+    async fn export_validator_snapshot(&self, file_name: String) -> RpcResult<SnapshotHeader>;
+
+    /// Delete wallet bundle and sidechain proposals that should no longer be
+    /// pending according to the validator's current state, and report what
+    /// was deleted. Bundle proposals are stale if their sidechain is
+    /// inactive, or if they were finalized with at least
+    /// `--wallet-bundle-deletion-confirmations` confirmations. Sidechain
+    /// proposals are stale once they have been included in a block.
+    /// Requires `--enable-admin-mutations`.
+    #[method(name = "reconcile_proposals")]
+    async fn reconcile_proposals(&self) -> RpcResult<ProposalReconciliation>;
+
+    /// Export the wallet's external and internal descriptors, including
+    /// private keys, for recovering the wallet in any descriptor wallet.
+    /// Requires `--wallet-allow-private-descriptor-export`, and an unlocked
+    /// wallet. Request and response logging is redacted for this method.
+    #[method(name = "export_private_descriptors")]
+    async fn export_private_descriptors(&self) -> RpcResult<ExportedDescriptors>;
+}
+
+#[derive(Clone)]
+pub struct Server {
+    config: Option<cli::Config>,
+    /// Directives that the log filter was created with at startup. Always
+    /// applied, before any provided directives.
+    base_directives: String,
+    log_filter_handle: Arc<dyn LogFilterHandle>,
+    log_file: Option<(LogFileBackpressure, ErrorCounter)>,
+    validator: Option<Validator>,
+    wallet: Option<Wallet>,
+}
+
+impl Server {
+    pub fn new<H>(base_directives: String, log_filter_handle: H) -> Self
+    where
+        H: LogFilterHandle + 'static,
+    {
+        Self {
+            config: None,
+            base_directives,
+            log_filter_handle: Arc::new(log_filter_handle),
+            log_file: None,
+            validator: None,
+            wallet: None,
+        }
+    }
+
+    /// Enable config methods
+    pub fn with_config(self, config: cli::Config) -> Self {
+        Self {
+            config: Some(config),
+            ..self
+        }
+    }
+
+    /// Enable log file methods
+    pub fn with_log_file(
+        self,
+        backpressure: LogFileBackpressure,
+        error_counter: ErrorCounter,
+    ) -> Self {
+        Self {
+            log_file: Some((backpressure, error_counter)),
+            ..self
+        }
+    }
+
+    /// Enable validator methods
+    pub fn with_validator(self, validator: Validator) -> Self {
+        Self {
+            validator: Some(validator),
+            ..self
+        }
+    }
+
+    /// Enable wallet methods
+    pub fn with_wallet(self, wallet: Wallet) -> Self {
+        Self {
+            wallet: Some(wallet),
+            ..self
+        }
+    }
+
+    fn check_mutations_enabled(&self, method: &'static str) -> RpcResult<()> {
+        if self
+            .config
+            .as_ref()
+            .is_some_and(|config| config.enable_admin_mutations)
+        {
+            Ok(())
+        } else {
+            Err(custom_json_rpc_err(AdminMutationsDisabledError { method }))
+        }
+    }
+}
+
+#[async_trait]
+impl RpcServer for Server {
+    fn set_log_filter(&self, directives: String) -> RpcResult<()> {
+        let () = self.check_mutations_enabled("set_log_filter")?;
+        let directives_str = if directives.is_empty() {
+            self.base_directives.clone()
+        } else {
+            format!("{},{directives}", self.base_directives)
+        };
+        let filter = EnvFilter::builder()
+            .parse(directives_str)
+            .map_err(|source| {
+                custom_json_rpc_err(SetLogFilterError::Parse {
+                    directives: directives.clone(),
+                    source,
+                })
+            })?;
+        self.log_filter_handle
+            .reload(filter)
+            .map_err(|err| custom_json_rpc_err(SetLogFilterError::from(err)))?;
+        tracing::info!(%directives, "reloaded log filter");
+        Ok(())
+    }
+
+    fn get_config(&self) -> RpcResult<ConfigSummary> {
+        let Some(config) = &self.config else {
+            return Err(custom_json_rpc_err(ConfigNotAvailableError));
+        };
+        let redact = |secret: &Option<_>| secret.as_ref().map(|_| REDACTED);
+        let node_rpc_opts = &config.node_rpc_opts;
+        let mining_opts = &config.mining_opts;
+        let wallet_opts = &config.wallet_opts;
+        Ok(ConfigSummary {
+            git_hash: config.git_hash(),
+            network: self.validator.as_ref().map(|validator| validator.network()),
+            data_dir: config.data_dir.clone(),
+            enable_wallet: config.enable_wallet,
+            enable_mempool: config.enable_mempool,
+            enable_admin_mutations: config.enable_admin_mutations,
+            max_reorg_depth: config.max_reorg_depth,
+            deposit_finality_depth: config.deposit_finality_depth(),
+            node_rpc: NodeRpcSummary {
+                addr: node_rpc_opts.addr,
+                cookie_path: node_rpc_opts.cookie_path.clone(),
+                user: node_rpc_opts.user.clone(),
+                pass: redact(&node_rpc_opts.pass),
+            },
+            node_zmq_addr_sequence: config.node_zmq_addr_sequence.clone(),
+            node_zmq_addr_hashblock: config.node_zmq_addr_hashblock.clone(),
+            serve_rpc_addr: config.serve_rpc_addr,
+            serve_json_rpc_addr: config.serve_json_rpc_addr,
+            serve_grpc_addr: config.serve_grpc_addr.clone(),
+            log_level: config.logger_opts.level.to_string(),
+            log_filter: self.log_filter_handle.current_directives().ok(),
+            mining: MiningSummary {
+                coinbase_recipient: mining_opts
+                    .coinbase_recipient
+                    .as_ref()
+                    .map(|addr| addr.to_string()),
+                coinbase_recipient_policy: mining_opts.coinbase_recipient_policy,
+                coinbase_recipient_fallback: mining_opts
+                    .coinbase_recipient_fallback
+                    .as_ref()
+                    .map(|addr| addr.to_string()),
+                block_template_max_txs: mining_opts.block_template_max_txs,
+                block_template_max_weight: mining_opts.block_template_max_weight,
+            },
+            wallet: WalletSummary {
+                sync_source: wallet_opts.sync_source,
+                sync_fallback_sources: wallet_opts.sync_fallback_sources.clone(),
+                chain_sources: self
+                    .wallet
+                    .as_ref()
+                    .map(|wallet| wallet.chain_source_diagnostics()),
+                require_unlock: wallet_opts.require_unlock,
+                allow_private_descriptor_export: wallet_opts.allow_private_descriptor_export,
+                uninitialized_policy: wallet_opts.uninitialized_policy,
+                seed_file: redact(&wallet_opts.mnemonic_path),
+            },
+        })
+    }
+
+    fn get_log_file_stats(&self) -> RpcResult<LogFileStats> {
+        let Some((backpressure, error_counter)) = &self.log_file else {
+            return Err(custom_json_rpc_err(LogFileNotAvailableError));
+        };
+        Ok(LogFileStats {
+            backpressure: *backpressure,
+            dropped_lines: error_counter.dropped_lines(),
+        })
+    }
+
+    async fn dump_wallet_diagnostics(&self) -> RpcResult<WalletDiagnostics> {
+        let Some(wallet) = &self.wallet else {
+            return Err(custom_json_rpc_err(WalletNotEnabledError));
+        };
+        wallet.dump_diagnostics().map_err(custom_json_rpc_err).await
+    }
+
+    async fn export_validator_snapshot(&self, file_name: String) -> RpcResult<SnapshotHeader> {
+        let () = self.check_mutations_enabled("export_validator_snapshot")?;
+        let Some(validator) = &self.validator else {
+            return Err(custom_json_rpc_err(ValidatorNotAvailableError));
+        };
+        let Some(snapshot_dir) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.validator_snapshot_dir.as_deref())
+        else {
+            return Err(custom_json_rpc_err(SnapshotExportDisabledError));
+        };
+        // Only plain file names are accepted, so that snapshots cannot be
+        // written outside of the snapshot directory
+        let mut components = std::path::Path::new(&file_name).components();
+        let (Some(std::path::Component::Normal(_)), None) = (components.next(), components.next())
+        else {
+            return Err(custom_json_rpc_err(InvalidSnapshotFileNameError {
+                file_name,
+            }));
+        };
+        let path = snapshot_dir.join(&file_name);
+        tokio::task::block_in_place(|| validator.export_snapshot(&path))
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn reconcile_proposals(&self) -> RpcResult<ProposalReconciliation> {
+        let () = self.check_mutations_enabled("reconcile_proposals")?;
+        let Some(wallet) = &self.wallet else {
+            return Err(custom_json_rpc_err(WalletNotEnabledError));
+        };
+        wallet
+            .reconcile_proposals()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn export_private_descriptors(&self) -> RpcResult<ExportedDescriptors> {
+        let Some(wallet) = &self.wallet else {
+            return Err(custom_json_rpc_err(WalletNotEnabledError));
+        };
+        if !self
+            .config
+            .as_ref()
+            .is_some_and(|config| config.wallet_opts.allow_private_descriptor_export)
+        {
+            return Err(custom_json_rpc_err(PrivateDescriptorExportDisabledError));
+        }
+        wallet
+            .export_descriptors(true)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+}
@@ -0,0 +1,43 @@
+//! Chain parameters in use by the enforcer
+
+use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use serde::Serialize;
+
+/// Network and network magic that the enforcer is running with
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainInfo {
+    pub network: bitcoin::Network,
+    /// Hex-encoded 4-byte network magic
+    pub magic: String,
+    /// `true` if the magic was derived from a signet challenge, rather than
+    /// being the default magic for the network
+    pub signet_challenge_derived: bool,
+}
+
+impl ChainInfo {
+    pub fn new(network: bitcoin::Network, signet_challenge: Option<&bitcoin::Script>) -> Self {
+        let magic = match signet_challenge {
+            Some(signet_challenge) => crate::p2p::compute_signet_magic(signet_challenge),
+            None => network.magic(),
+        };
+        Self {
+            network,
+            magic: hex::encode(magic.to_bytes()),
+            signet_challenge_derived: signet_challenge.is_some(),
+        }
+    }
+}
+
+#[rpc(namespace = "chain", namespace_separator = ".", server)]
+pub trait Rpc {
+    /// Network, network magic, and whether the magic was derived from a
+    /// signet challenge
+    #[method(name = "get_chain_info")]
+    fn get_chain_info(&self) -> RpcResult<ChainInfo>;
+}
+
+impl RpcServer for ChainInfo {
+    fn get_chain_info(&self) -> RpcResult<ChainInfo> {
+        Ok(self.clone())
+    }
+}
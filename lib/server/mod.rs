@@ -1,7 +1,18 @@
+pub mod admin;
+pub mod chain;
 pub mod crypto;
+pub mod subscription_limit;
 pub mod validator;
 pub mod wallet;
 
+/// JSON-RPC methods whose params or results contain secrets, such as
+/// mnemonics or private keys. Request and response logging must not include
+/// the params or results of these methods.
+pub const REDACTED_JSON_RPC_METHODS: &[&str] = &[
+    "admin.export_private_descriptors",
+    "wallet.validate_mnemonic",
+];
+
 fn custom_json_rpc_err<Error>(error: Error) -> jsonrpsee::types::ErrorObject<'static>
 where
     Error: std::error::Error,
@@ -10,6 +21,18 @@ where
     jsonrpsee::types::ErrorObject::owned(-1, err_msg, Option::<()>::None)
 }
 
+fn invalid_params_json_rpc_err<Error>(error: Error) -> jsonrpsee::types::ErrorObject<'static>
+where
+    Error: std::error::Error,
+{
+    let err_msg = format!("{:#}", crate::errors::ErrorChain::new(&error));
+    jsonrpsee::types::ErrorObject::owned(
+        jsonrpsee::types::error::INVALID_PARAMS_CODE,
+        err_msg,
+        Option::<()>::None,
+    )
+}
+
 pub(crate) fn invalid_field_value<Message, Error>(
     field_name: &str,
     value: &str,
@@ -0,0 +1,162 @@
+//! Limits on concurrent server-streaming subscriptions
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt as _};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::cli::ServerConnectionConfig;
+
+#[derive(Debug, Error)]
+pub enum SubscriptionLimitExceeded {
+    #[error("too many concurrent subscriptions (limit: {limit})")]
+    Total { limit: usize },
+    #[error("too many concurrent subscriptions for this client (limit: {limit})")]
+    PerClient { limit: usize },
+}
+
+impl From<SubscriptionLimitExceeded> for tonic::Status {
+    fn from(err: SubscriptionLimitExceeded) -> Self {
+        Self::resource_exhausted(err.to_string())
+    }
+}
+
+/// Limits the number of concurrent subscriptions, in total and for each
+/// client. Clients with an unknown address share a limit.
+#[derive(Debug)]
+pub struct SubscriptionLimiter {
+    /// `None` if the total number of subscriptions is not limited
+    total: Option<(usize, Arc<Semaphore>)>,
+    max_per_client: Option<usize>,
+    /// Number of active subscriptions for each client
+    per_client: Mutex<HashMap<Option<IpAddr>, usize>>,
+}
+
+impl SubscriptionLimiter {
+    pub fn new(config: &ServerConnectionConfig) -> Self {
+        Self {
+            total: config.max_subscriptions.map(|limit| {
+                let limit = limit.get();
+                (limit, Arc::new(Semaphore::new(limit)))
+            }),
+            max_per_client: config.max_subscriptions_per_client.map(|limit| limit.get()),
+            per_client: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserve a subscription slot for a client. The slot is released when
+    /// the permit is dropped.
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        client: Option<IpAddr>,
+    ) -> Result<SubscriptionPermit, SubscriptionLimitExceeded> {
+        let total_permit = match &self.total {
+            Some((limit, semaphore)) => Some(
+                semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| SubscriptionLimitExceeded::Total { limit: *limit })?,
+            ),
+            None => None,
+        };
+        let mut per_client = self
+            .per_client
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let active = per_client.entry(client).or_default();
+        if let Some(limit) = self.max_per_client
+            && *active >= limit
+        {
+            return Err(SubscriptionLimitExceeded::PerClient { limit });
+        }
+        *active += 1;
+        Ok(SubscriptionPermit {
+            _total_permit: total_permit,
+            client,
+            limiter: self.clone(),
+        })
+    }
+
+    fn release(&self, client: Option<IpAddr>) {
+        let mut per_client = self
+            .per_client
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(active) = per_client.get_mut(&client) {
+            *active = active.saturating_sub(1);
+            if *active == 0 {
+                per_client.remove(&client);
+            }
+        }
+    }
+}
+
+/// A reserved subscription slot, released on drop
+#[derive(Debug)]
+pub struct SubscriptionPermit {
+    _total_permit: Option<OwnedSemaphorePermit>,
+    client: Option<IpAddr>,
+    limiter: Arc<SubscriptionLimiter>,
+}
+
+impl SubscriptionPermit {
+    /// Hold the permit until the stream is dropped, eg. when it ends or the
+    /// client disconnects
+    pub fn attach<S>(self, stream: S) -> impl Stream<Item = S::Item> + use<S>
+    where
+        S: Stream,
+    {
+        stream.map(move |item| {
+            let _permit = &self;
+            item
+        })
+    }
+}
+
+impl Drop for SubscriptionPermit {
+    fn drop(&mut self) {
+        self.limiter.release(self.client);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, num::NonZeroUsize, sync::Arc};
+
+    use crate::cli::ServerConnectionConfig;
+
+    use super::{SubscriptionLimitExceeded, SubscriptionLimiter};
+
+    #[test]
+    fn test_subscription_limiter() {
+        let limiter = Arc::new(SubscriptionLimiter::new(&ServerConnectionConfig {
+            tcp_keepalive_secs: None,
+            keepalive_interval_secs: None,
+            keepalive_timeout_secs: None,
+            max_concurrent_streams: None,
+            max_subscriptions: NonZeroUsize::new(3),
+            max_subscriptions_per_client: NonZeroUsize::new(2),
+        }));
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+        let other_client = Some(IpAddr::from([127, 0, 0, 2]));
+        let first = limiter.try_acquire(client).unwrap();
+        let _second = limiter.try_acquire(client).unwrap();
+        assert!(matches!(
+            limiter.try_acquire(client),
+            Err(SubscriptionLimitExceeded::PerClient { limit: 2 })
+        ));
+        let _third = limiter.try_acquire(other_client).unwrap();
+        assert!(matches!(
+            limiter.try_acquire(None),
+            Err(SubscriptionLimitExceeded::Total { limit: 3 })
+        ));
+        // Dropping a permit releases the slot
+        drop(first);
+        let _fourth = limiter.try_acquire(client).unwrap();
+    }
+}
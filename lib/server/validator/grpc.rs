@@ -421,6 +421,7 @@ impl ValidatorService for Server {
         &self,
         request: tonic::Request<SubscribeEventsRequest>,
     ) -> Result<tonic::Response<Self::SubscribeEventsStream>, tonic::Status> {
+        let client = request.remote_addr().map(|addr| addr.ip());
         let SubscribeEventsRequest { sidechain_id } = request.into_inner();
 
         let sidechain_id = {
@@ -436,16 +437,14 @@ impl ValidatorService for Server {
             })?
         };
 
-        let stream = self
-            .validator
-            .subscribe_events()
-            .map(move |res| match res {
-                Ok(event) => Ok(SubscribeEventsResponse {
-                    event: Some(event.into_proto(sidechain_id).into()),
-                }),
-                Err(err) => Err(err.builder().to_status()),
-            })
-            .boxed();
+        let permit = self.subscription_limiter.try_acquire(client)?;
+        let stream = self.validator.subscribe_events().map(move |res| match res {
+            Ok(event) => Ok(SubscribeEventsResponse {
+                event: Some(event.into_proto(sidechain_id).into()),
+            }),
+            Err(err) => Err(err.builder().to_status()),
+        });
+        let stream = permit.attach(stream).boxed();
         Ok(tonic::Response::new(stream))
     }
 
@@ -456,13 +455,15 @@ impl ValidatorService for Server {
         &self,
         request: tonic::Request<SubscribeHeaderSyncProgressRequest>,
     ) -> Result<tonic::Response<Self::SubscribeHeaderSyncProgressStream>, tonic::Status> {
+        let client = request.remote_addr().map(|addr| addr.ip());
         let SubscribeHeaderSyncProgressRequest {} = request.into_inner();
         let Some(rx) = self.validator.subscribe_header_sync_progress() else {
             return Err(tonic::Status::unavailable("No header sync in progress"));
         };
-        let stream = tokio_stream::wrappers::WatchStream::new(rx)
-            .map(|progress| Ok(progress.into()))
-            .boxed();
+        let permit = self.subscription_limiter.try_acquire(client)?;
+        let stream =
+            tokio_stream::wrappers::WatchStream::new(rx).map(|progress| Ok(progress.into()));
+        let stream = permit.attach(stream).boxed();
         Ok(tonic::Response::new(stream))
     }
 
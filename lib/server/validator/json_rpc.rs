@@ -1,10 +1,18 @@
-use bitcoin::BlockHash;
-use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use bitcoin::{BlockHash, Txid};
+use jsonrpsee::{
+    core::{RpcResult, async_trait},
+    proc_macros::rpc,
+};
 use serde::{Serialize, Serializer};
 
 use crate::{
-    server::custom_json_rpc_err,
-    types::{Ctip, HeaderInfo, SidechainBlockInfo, SidechainNumber},
+    messages::DecodedOpDrivechain,
+    server::{custom_json_rpc_err, invalid_params_json_rpc_err},
+    types::{
+        BlindedM6, Ctip, HeaderInfo, M6id, SidechainBlockInfo, SidechainDeclaration,
+        SidechainNumber,
+    },
+    validator::{DepositFinality, DepositProof, IdentifiedTransaction, SyncStatus},
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -27,6 +35,56 @@ pub struct BlockInfoItem {
 
 type BlockInfoResponse = Vec<BlockInfoItem>;
 
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SidechainActivation {
+    pub sidechain_number: SidechainNumber,
+    pub active: bool,
+    /// Height of the block in which the sidechain activated.
+    /// `None` if the sidechain is inactive.
+    pub activation_height: Option<u32>,
+}
+
+/// Fields of a BIP300 M1 v1 sidechain declaration
+#[derive(Clone, Debug, Serialize)]
+pub struct SidechainDeclarationInfo {
+    pub title: String,
+    pub description: String,
+    #[serde(with = "hex::serde")]
+    pub hash_id_1: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub hash_id_2: [u8; 20],
+}
+
+impl From<SidechainDeclaration> for SidechainDeclarationInfo {
+    fn from(declaration: SidechainDeclaration) -> Self {
+        let SidechainDeclaration {
+            title,
+            description,
+            hash_id_1,
+            hash_id_2,
+        } = declaration;
+        Self {
+            title,
+            description,
+            hash_id_1,
+            hash_id_2,
+        }
+    }
+}
+
+/// Metadata for an active sidechain, from the proposal that activated it
+#[derive(Clone, Debug, Serialize)]
+pub struct SidechainMetadata {
+    pub sidechain_number: SidechainNumber,
+    /// `None` if the proposal description is not a valid M1 v1 declaration
+    pub declaration: Option<SidechainDeclarationInfo>,
+    /// Raw proposal description
+    #[serde(with = "hex::serde")]
+    pub description: Vec<u8>,
+    pub description_sha256d_hash: bitcoin::hashes::sha256d::Hash,
+    pub activation_height: Option<u32>,
+}
+
 #[rpc(namespace = "validator", namespace_separator = ".", server)]
 pub trait Rpc {
     #[method(name = "ping")]
@@ -42,8 +100,78 @@ pub trait Rpc {
         sidechain_id: SidechainNumber,
         max_ancestors: Option<usize>,
     ) -> RpcResult<BlockInfoResponse>;
+
+    /// Whether the sidechain slot is active, and if so, the height at which
+    /// it activated. Inactive and unused slots are reported as inactive.
+    #[method(name = "is_sidechain_active")]
+    fn is_sidechain_active(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> RpcResult<SidechainActivation>;
+
+    /// Title, description, and hashes of an active sidechain, from the
+    /// proposal that activated it. `null` if the sidechain is not active.
+    #[method(name = "get_sidechain_metadata")]
+    fn get_sidechain_metadata(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> RpcResult<Option<SidechainMetadata>>;
+
+    /// Compute the hex-encoded network magic for a hex-encoded signet
+    /// challenge script
+    #[method(name = "compute_signet_magic")]
+    fn compute_signet_magic(&self, challenge_hex: String) -> RpcResult<String>;
+
+    /// Decode a hex-encoded OP_DRIVECHAIN script, using the same rules as
+    /// the enforcer
+    #[method(name = "decode_op_drivechain")]
+    fn decode_op_drivechain(&self, script_hex: String) -> RpcResult<DecodedOpDrivechain>;
+
+    /// Compute the M6ID for a hex-encoded blinded M6 transaction (as
+    /// submitted with `broadcast_withdrawal_bundle`), without storing or
+    /// broadcasting it
+    #[method(name = "compute_m6id")]
+    fn compute_m6id(&self, tx_hex: String) -> RpcResult<M6id>;
+
+    /// Block sync status relative to the mainchain tip, with a rate and ETA
+    /// estimate if a block sync is in progress
+    #[method(name = "get_sync_status")]
+    async fn get_sync_status(&self) -> RpcResult<SyncStatus>;
+
+    /// Raw deposit transaction and a Merkle proof of its inclusion in a
+    /// block (as returned by `gettxoutproof`), so that deposits can be
+    /// verified against a headers chain. Fails if the transaction is not a
+    /// deposit, is unconfirmed, or is in a block that is not in the
+    /// enforcer's processed chain. Unless Bitcoin Core has `-txindex`
+    /// enabled, `block_hash` must be the hash of the block containing the
+    /// deposit.
+    #[method(name = "get_deposit_proof")]
+    async fn get_deposit_proof(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> RpcResult<DepositProof>;
+
+    /// Number of confirmations after which the enforcer treats a deposit as
+    /// final, as set by `--deposit-finality-depth`, or one more than
+    /// `--max-reorg-depth`
+    #[method(name = "get_deposit_finality_depth")]
+    fn get_deposit_finality_depth(&self) -> RpcResult<DepositFinality>;
+
+    /// Classify a transaction as `coinbase`, `deposit` (M5), `withdrawal_bundle`
+    /// (M6), or `other`, using the same rules as the enforcer, with the
+    /// sidechain number, deposit address and value, or M6ID as applicable.
+    /// Unless Bitcoin Core has `-txindex` enabled, `block_hash` must be the
+    /// hash of the block containing the transaction, if it is confirmed.
+    #[method(name = "identify_transaction")]
+    async fn identify_transaction(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> RpcResult<IdentifiedTransaction>;
 }
 
+#[async_trait]
 impl RpcServer for crate::validator::Validator {
     fn ping(&self) -> RpcResult<Pong> {
         Ok(Pong)
@@ -77,4 +205,89 @@ impl RpcServer for crate::validator::Validator {
         };
         Ok(res)
     }
+
+    fn is_sidechain_active(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> RpcResult<SidechainActivation> {
+        let sidechain = self
+            .try_get_active_sidechain(sidechain_number)
+            .map_err(custom_json_rpc_err)?;
+        let activation_height = sidechain.and_then(|sidechain| sidechain.status.activation_height);
+        Ok(SidechainActivation {
+            sidechain_number,
+            active: activation_height.is_some(),
+            activation_height,
+        })
+    }
+
+    fn get_sidechain_metadata(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> RpcResult<Option<SidechainMetadata>> {
+        let Some(sidechain) = self
+            .try_get_active_sidechain(sidechain_number)
+            .map_err(custom_json_rpc_err)?
+        else {
+            return Ok(None);
+        };
+        let description = sidechain.proposal.description;
+        Ok(Some(SidechainMetadata {
+            sidechain_number,
+            declaration: SidechainDeclaration::try_from(&description)
+                .map(SidechainDeclarationInfo::from)
+                .ok(),
+            description_sha256d_hash: description.sha256d_hash(),
+            description: description.0,
+            activation_height: sidechain.status.activation_height,
+        }))
+    }
+
+    fn compute_signet_magic(&self, challenge_hex: String) -> RpcResult<String> {
+        let challenge =
+            crate::p2p::parse_signet_challenge(&challenge_hex).map_err(custom_json_rpc_err)?;
+        let magic = crate::p2p::compute_signet_magic(&challenge);
+        Ok(hex::encode(magic.to_bytes()))
+    }
+
+    fn decode_op_drivechain(&self, script_hex: String) -> RpcResult<DecodedOpDrivechain> {
+        let script = hex::decode(script_hex).map_err(custom_json_rpc_err)?;
+        crate::messages::decode_op_drivechain(&script).map_err(custom_json_rpc_err)
+    }
+
+    fn compute_m6id(&self, tx_hex: String) -> RpcResult<M6id> {
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_hex)
+            .map_err(invalid_params_json_rpc_err)?;
+        let blinded_m6 = BlindedM6::try_from(std::borrow::Cow::Owned(tx))
+            .map_err(invalid_params_json_rpc_err)?;
+        Ok(blinded_m6.compute_m6id())
+    }
+
+    async fn get_sync_status(&self) -> RpcResult<SyncStatus> {
+        self.get_sync_status().await.map_err(custom_json_rpc_err)
+    }
+
+    async fn get_deposit_proof(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> RpcResult<DepositProof> {
+        self.get_deposit_proof(txid, block_hash)
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    fn get_deposit_finality_depth(&self) -> RpcResult<DepositFinality> {
+        Ok(self.deposit_finality())
+    }
+
+    async fn identify_transaction(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> RpcResult<IdentifiedTransaction> {
+        self.identify_transaction(txid, block_hash)
+            .await
+            .map_err(custom_json_rpc_err)
+    }
 }
@@ -1,4 +1,6 @@
-use crate::validator::Validator;
+use std::sync::Arc;
+
+use crate::{server::subscription_limit::SubscriptionLimiter, validator::Validator};
 
 mod grpc;
 pub mod json_rpc;
@@ -7,13 +9,19 @@ pub mod json_rpc;
 pub struct Server {
     validator: Validator,
     shutdown_tx: futures::channel::mpsc::Sender<()>,
+    subscription_limiter: Arc<SubscriptionLimiter>,
 }
 
 impl Server {
-    pub fn new(validator: Validator, shutdown_tx: futures::channel::mpsc::Sender<()>) -> Self {
+    pub fn new(
+        validator: Validator,
+        shutdown_tx: futures::channel::mpsc::Sender<()>,
+        subscription_limiter: Arc<SubscriptionLimiter>,
+    ) -> Self {
         Self {
             validator,
             shutdown_tx,
+            subscription_limiter,
         }
     }
 }
@@ -34,7 +34,7 @@ use crate::{
     },
     server::{invalid_field_value, missing_field},
     types::{BlindedM6, Event, SidechainNumber},
-    wallet::{CreateTransactionParams, error::WalletInitialization},
+    wallet::{CreateTransactionParams, SidechainDepositKind, error::WalletInitialization},
 };
 
 /// Stream (non-)confirmations for a sidechain proposal
@@ -192,7 +192,10 @@ impl WalletService for crate::wallet::Wallet {
             .propose_sidechain(&sidechain_proposal)
             .await
             .map_err(|err| {
-                if let rusqlite::Error::SqliteFailure(sqlite_err, _) = err {
+                if let crate::wallet::error::ProposeSidechain::Rusqlite(
+                    rusqlite::Error::SqliteFailure(sqlite_err, _),
+                ) = err
+                {
                     tracing::error!("SQLite error: {:#}", ErrorChain::new(&sqlite_err));
 
                     if sqlite_err.code == rusqlite::ErrorCode::ConstraintViolation {
@@ -300,7 +303,7 @@ impl WalletService for crate::wallet::Wallet {
         let _m6id = self
             .put_withdrawal_bundle(sidechain_id, &transaction)
             .await
-            .map_err(|err| StatusBuilder::new(&err).to_status())?;
+            .map_err(|err| err.builder().to_status())?;
         /*
         self.broadcast_transaction(transaction.tx().into_owned())
             .await
@@ -496,7 +499,13 @@ impl WalletService for crate::wallet::Wallet {
         }
 
         let txid = self
-            .create_deposit(sidechain_number, address, value, Some(fee))
+            .create_deposit(
+                sidechain_number,
+                address,
+                value,
+                Some(crate::types::FeePolicy::Absolute(fee)),
+                None,
+            )
             .await
             .map_err(|err| err.builder().to_status())?;
 
@@ -600,6 +609,13 @@ impl WalletService for crate::wallet::Wallet {
             .await
             .map_err(|err| err.builder().to_status())?
             .into_iter()
+            // The gRPC response cannot distinguish address payments from
+            // treasury deposits, so only treasury deposits are included.
+            // Address payments are only listed by the JSON-RPC
+            // `wallet.list_sidechain_deposit_transactions`, with their kind.
+            .filter(|sidechain_deposit_tx| {
+                sidechain_deposit_tx.kind == SidechainDepositKind::TreasuryDeposit
+            })
             .map(|sidechain_deposit_tx| SidechainDepositTransaction {
                 sidechain_number: Some(sidechain_deposit_tx.sidechain_number.0.into()),
                 tx: Some(WalletTransaction::from(&sidechain_deposit_tx.wallet_tx)),
@@ -711,6 +727,7 @@ impl WalletService for crate::wallet::Wallet {
                     op_return_message,
                     required_utxos,
                     drain_wallet_to,
+                    change_address: None,
                 },
             )
             .await
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use bitcoin::{BlockHash, Txid};
 use futures::TryFutureExt as _;
 use jsonrpsee::{
@@ -7,22 +9,139 @@ use jsonrpsee::{
 use thiserror::Error;
 
 use crate::{
-    server::custom_json_rpc_err,
-    types::{BmmCommitment, SidechainNumber},
-    wallet::SidechainDepositTransaction,
+    server::{custom_json_rpc_err, invalid_params_json_rpc_err},
+    types::{BDKWalletTransaction, BlindedM6, BmmCommitment, FeePolicy, M6id, SidechainNumber},
+    wallet::{
+        BlockFeeRates, BlockFees, BlockHashOrHeight, BundleValidation, ChangeAddress,
+        CoinSelectionPreview, ConfirmationEstimate, CreateTransactionParams, CtipStatus,
+        DefaultFeeRate, DeployableBalance, DepositPolicy, EnforcerBlockTemplate,
+        ExportedDescriptors, FeeRateOrTier, FeeTier, KeychainDerivationInfo, MempoolFeeSnapshot,
+        MnemonicValidation, PendingSidechainProposal, PendingWithdrawals, ProposedWithdrawalBundle,
+        RebroadcastResult, ResolvedFeeRate, ResolvedFeeTier, RevealedAddresses, ScriptType,
+        SentTransaction, SidechainDepositTransaction, SidechainFilter, SyncSummary, UnspentOutputs,
+        WithdrawalBundleEventRecord, XpubMatch,
+    },
 };
 
 #[derive(Debug, Error)]
 #[error("BMM request with same sidechain number and previous block hash already exists")]
 struct BmmRequestAlreadyExistsError;
 
+#[derive(Debug, Error)]
+#[error("sidechain {0} is not active")]
+struct SidechainNotActiveError(SidechainNumber);
+
+#[derive(Debug, Error)]
+#[error("failed to decode transaction hex")]
+struct DecodeTransactionError(#[source] bitcoin::consensus::encode::FromHexError);
+
+#[derive(Debug, Error)]
+#[error("invalid fee rate: {0} sat/vB")]
+struct InvalidFeeRateError(u64);
+
+#[derive(Debug, Error)]
+#[error("only one of `fee_sats` and `fee_rate` can be provided")]
+struct ConflictingFeeError;
+
+#[derive(Debug, Error)]
+#[error("unsupported mnemonic language: `{0}`")]
+struct UnsupportedMnemonicLanguageError(String);
+
+/// Resolve an explicit fee rate or fee tier to a fee rate
+async fn resolve_fee_rate(
+    wallet: &crate::wallet::Wallet,
+    fee_rate: FeeRateOrTier,
+) -> RpcResult<(bdk_wallet::bitcoin::FeeRate, ResolvedFeeRate)> {
+    let resolved = match fee_rate {
+        FeeRateOrTier::SatPerVb(sat_per_vb) => ResolvedFeeRate {
+            sat_per_vb,
+            tier: None,
+        },
+        FeeRateOrTier::Tier(tier) => {
+            let resolved_tier = wallet
+                .resolve_fee_tier(tier)
+                .await
+                .map_err(custom_json_rpc_err)?;
+            ResolvedFeeRate {
+                sat_per_vb: resolved_tier.sat_per_vb,
+                tier: Some(resolved_tier),
+            }
+        }
+    };
+    let fee_rate = bdk_wallet::bitcoin::FeeRate::from_sat_per_vb(resolved.sat_per_vb)
+        .ok_or_else(|| invalid_params_json_rpc_err(InvalidFeeRateError(resolved.sat_per_vb)))?;
+    Ok((fee_rate, resolved))
+}
+
+/// Validate a change address override for the wallet's network
+fn parse_change_address(
+    network: bitcoin::Network,
+    change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+    allow_external_change: Option<bool>,
+) -> RpcResult<Option<ChangeAddress>> {
+    change_address
+        .map(|change_address| {
+            let address = change_address
+                .require_network(network)
+                .map_err(invalid_params_json_rpc_err)?;
+            Ok(ChangeAddress {
+                address,
+                allow_external: allow_external_change.unwrap_or(false),
+            })
+        })
+        .transpose()
+}
+
+/// Reusing an idempotency key with different params is an invalid params
+/// error
+fn idempotent_json_rpc_err<Err>(
+    err: crate::wallet::error::Idempotent<Err>,
+) -> jsonrpsee::types::ErrorObject<'static>
+where
+    Err: std::error::Error + 'static,
+{
+    match err {
+        crate::wallet::error::Idempotent::ParamsMismatch { .. } => invalid_params_json_rpc_err(err),
+        err => custom_json_rpc_err(err),
+    }
+}
+
 #[rpc(namespace = "wallet", namespace_separator = ".", server)]
 pub trait Rpc {
+    /// Sidechain deposits made by the wallet, with their confirmations as of
+    /// the enforcer's chain tip, and whether they are final according to the
+    /// deposit finality depth (`validator.get_deposit_finality_depth`).
+    /// Payments to the wallet's sidechain deposit addresses are included
+    /// with kind `address_payment`; only `treasury_deposit`s are credited to
+    /// the sidechain treasury.
+    /// The gRPC `ListSidechainDepositTransactions` cannot report the kind,
+    /// and returns only treasury deposits.
     #[method(name = "list_sidechain_deposit_transactions")]
     async fn list_sidechain_deposit_transactions(
         &self,
     ) -> RpcResult<Vec<SidechainDepositTransaction>>;
 
+    /// Withdrawal bundle events (submissions, successes, and failures) for a
+    /// sidechain, in blocks at or above `from_height`, in the order that they
+    /// were processed. Only events in blocks connected by the wallet are
+    /// recorded.
+    #[method(name = "list_withdrawal_bundle_events")]
+    async fn list_withdrawal_bundle_events(
+        &self,
+        sidechain_number: SidechainNumber,
+        from_height: Option<u32>,
+    ) -> RpcResult<Vec<WithdrawalBundleEventRecord>>;
+
+    /// Withdrawal bundles proposed by this wallet via
+    /// `put_withdrawal_bundle`, in the order that they were proposed, with
+    /// their latest known status (`pending`, `submitted`, `succeeded`, or
+    /// `failed`)
+    #[method(name = "list_my_withdrawal_bundles")]
+    async fn list_my_withdrawal_bundles(&self) -> RpcResult<Vec<ProposedWithdrawalBundle>>;
+
+    #[method(name = "list_wallet_transactions")]
+    async fn list_wallet_transactions(&self) -> RpcResult<Vec<BDKWalletTransaction>>;
+
     #[method(name = "create_bmm_critical_data_transaction")]
     async fn create_bmm_critical_data_transaction(
         &self,
@@ -32,6 +151,312 @@ pub trait Rpc {
         critical_hash: BmmCommitment,
         prev_block_hash: BlockHash,
     ) -> RpcResult<Txid>;
+
+    /// Get the wallet's dedicated deposit address for a sidechain. The
+    /// address is the next unrevealed address from the wallet's external
+    /// keychain when the sidechain first requests one, and is not derived
+    /// from a sidechain-specified path.
+    #[method(name = "get_sidechain_deposit_address")]
+    async fn get_sidechain_deposit_address(
+        &self,
+        sidechain_id: SidechainNumber,
+    ) -> RpcResult<String>;
+
+    /// List our sidechain proposals that have not yet been included in a
+    /// block
+    #[method(name = "list_pending_sidechain_proposals")]
+    async fn list_pending_sidechain_proposals(&self) -> RpcResult<Vec<PendingSidechainProposal>>;
+
+    /// List withdrawals in pending withdrawal bundles for a sidechain, with
+    /// the M6ID of the bundle that each withdrawal belongs to
+    #[method(name = "list_pending_withdrawals")]
+    async fn list_pending_withdrawals(
+        &self,
+        sidechain_id: SidechainNumber,
+    ) -> RpcResult<PendingWithdrawals>;
+
+    /// List unspent wallet outputs, ordered by outpoint.
+    /// `min_confirmations` defaults to zero, which includes unconfirmed
+    /// outputs. `script_type` is one of `p2pkh`, `p2sh`, `p2wpkh`, `p2wsh`,
+    /// `p2tr`, or `other`.
+    #[method(name = "list_unspent")]
+    async fn list_unspent(
+        &self,
+        min_confirmations: Option<u32>,
+        script_type: Option<ScriptType>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> RpcResult<UnspentOutputs>;
+
+    /// Check whether the wallet's descriptors derive from an xpub (eg. an
+    /// account xpub from a backup), and return the matching keychains with
+    /// their public descriptors. Returns an empty list if none match.
+    #[method(name = "verify_xpub")]
+    async fn verify_xpub(&self, xpub: String) -> RpcResult<Vec<XpubMatch>>;
+
+    /// Master key fingerprint, account derivation path and index, account
+    /// xpub, address derivation path template, and script type for each
+    /// wallet keychain, for reconstructing the wallet in other software.
+    /// Private keys are not included.
+    #[method(name = "get_derivation_info")]
+    async fn get_derivation_info(&self) -> RpcResult<Vec<KeychainDerivationInfo>>;
+
+    /// Export the wallet's external (receive) and internal (change) output
+    /// descriptors, for recovering a watch-only wallet in any descriptor
+    /// wallet. Private keys are never included, see
+    /// `admin.export_private_descriptors`.
+    #[method(name = "export_descriptors")]
+    async fn export_descriptors(&self) -> RpcResult<ExportedDescriptors>;
+
+    /// Get the next unused receive address, without revealing a new one
+    #[method(name = "get_unused_address")]
+    async fn get_unused_address(&self) -> RpcResult<String>;
+
+    /// List revealed addresses, with their keychain, derivation index, and
+    /// whether they have been used. External addresses are listed first.
+    #[method(name = "list_addresses")]
+    async fn list_addresses(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> RpcResult<RevealedAddresses>;
+
+    /// Coinbase outputs (including drivechain messages) and transactions
+    /// that the enforcer would include in a new block
+    #[method(name = "get_enforcer_block_template")]
+    async fn get_enforcer_block_template(&self) -> RpcResult<EnforcerBlockTemplate>;
+
+    /// Consensus-serialized candidate block on top of the current mainchain
+    /// tip, including the coinbase transaction with drivechain commitments,
+    /// as hex. The header is not solved, and has the target required by the
+    /// node for the next block. Sidechain acks are not persisted.
+    #[method(name = "get_candidate_block_hex")]
+    async fn get_candidate_block_hex(&self) -> RpcResult<String>;
+
+    /// Total fees, fee rate percentiles, and transaction count for a block
+    /// that has been processed by the validator, specified by hash or height
+    #[method(name = "get_block_fees")]
+    async fn get_block_fees(&self, block: BlockHashOrHeight) -> RpcResult<BlockFees>;
+
+    /// Min, median, and max fee rates (sat/vB) for up to `lookback_blocks`
+    /// recently connected blocks, in ascending order of height
+    #[method(name = "get_fee_history")]
+    async fn get_fee_history(&self, lookback_blocks: u32) -> RpcResult<Vec<BlockFeeRates>>;
+
+    /// Snapshots of mempool fee rates, taken when each of up to
+    /// `lookback_blocks` recently connected blocks was the tip, in ascending
+    /// order of height
+    #[method(name = "get_mempool_fee_history")]
+    async fn get_mempool_fee_history(
+        &self,
+        lookback_blocks: u32,
+    ) -> RpcResult<Vec<MempoolFeeSnapshot>>;
+
+    /// Check that a mnemonic is valid, without creating a wallet. If valid,
+    /// returns the public receive descriptor and first receive address.
+    /// `language` defaults to `english`. Supported languages are `english`,
+    /// `simplified-chinese`, `traditional-chinese`, `czech`, `french`,
+    /// `italian`, `japanese`, `korean`, `portuguese`, and `spanish`.
+    /// Request and response logging is redacted for this method.
+    #[method(name = "validate_mnemonic")]
+    async fn validate_mnemonic(
+        &self,
+        phrase: String,
+        language: Option<String>,
+    ) -> RpcResult<MnemonicValidation>;
+
+    /// Sync the wallet immediately, and return the number of transactions and
+    /// wallet outputs discovered. Waits for any sync in progress to complete
+    /// first.
+    #[method(name = "sync_now")]
+    async fn sync_now(&self) -> RpcResult<SyncSummary>;
+
+    /// Cancel the wallet full scan in progress. Results for the chunks of
+    /// addresses that have already been scanned are kept. Returns `false` if
+    /// there was no full scan in progress.
+    #[method(name = "cancel_full_scan")]
+    fn cancel_full_scan(&self) -> RpcResult<bool>;
+
+    /// Compact the wallet database by running `VACUUM`. Briefly holds an
+    /// exclusive lock on the wallet database.
+    #[method(name = "compact_wallet_db")]
+    async fn compact_wallet_db(&self) -> RpcResult<()>;
+
+    /// Resubmit each unconfirmed wallet transaction to Bitcoin Core, eg.
+    /// after they were dropped from the mempool. Transactions that are already
+    /// confirmed or in the mempool are treated as successfully broadcast.
+    #[method(name = "rebroadcast_unconfirmed")]
+    async fn rebroadcast_unconfirmed(&self) -> RpcResult<Vec<RebroadcastResult>>;
+
+    #[method(name = "broadcast_raw_transaction")]
+    async fn broadcast_raw_transaction(&self, tx_hex: String) -> RpcResult<Txid>;
+
+    /// Import an output that is not owned by the wallet, so that it can be
+    /// spent in PSBTs created by `create_unsigned_psbt`. Imported outputs
+    /// are never signed by the wallet. Only P2WPKH and P2TR outputs are
+    /// supported. The output must be unspent, and match the specified script
+    /// pubkey and amount.
+    #[method(name = "import_outpoint")]
+    async fn import_outpoint(
+        &self,
+        outpoint: bitcoin::OutPoint,
+        script_pubkey: bitcoin::ScriptBuf,
+        amount_sats: u64,
+    ) -> RpcResult<()>;
+
+    /// Remove an output imported via `import_outpoint`. Returns `false` if
+    /// the output was not imported.
+    #[method(name = "remove_imported_outpoint")]
+    async fn remove_imported_outpoint(&self, outpoint: bitcoin::OutPoint) -> RpcResult<bool>;
+
+    /// Create a PSBT for external signing, and return it as base64. Does
+    /// NOT sign or broadcast. Imported outputs are spent only if they are
+    /// listed in `required_utxos`. The fee rate is either in
+    /// sat/vB, or a fee tier (`economy`, `normal`, or `priority`).
+    /// If a change address is provided, change is sent to it instead of the
+    /// wallet's internal keychain. Change addresses that are not owned by the
+    /// wallet are rejected, unless `allow_external_change` is set.
+    #[method(name = "create_unsigned_psbt")]
+    async fn create_unsigned_psbt(
+        &self,
+        destinations: HashMap<bitcoin::Address<bitcoin::address::NetworkUnchecked>, u64>,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+        required_utxos: Option<Vec<bitcoin::OutPoint>>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+    ) -> RpcResult<String>;
+
+    /// Run coin selection for sending `amount_sats`, without building a
+    /// transaction, and return the UTXOs that would be spent, the total input
+    /// value, the change amount, and the fee. The fee rate is either in
+    /// sat/vB, or a fee tier.
+    #[method(name = "preview_coin_selection")]
+    async fn preview_coin_selection(
+        &self,
+        amount_sats: u64,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+    ) -> RpcResult<CoinSelectionPreview>;
+
+    /// Estimate the number of blocks and approximate time until a wallet
+    /// transaction is confirmed, by comparing its fee rate against the fee
+    /// rates of transactions in the mempool. Returns zero if the transaction
+    /// is already confirmed.
+    #[method(name = "estimate_confirmation_time")]
+    async fn estimate_confirmation_time(&self, txid: Txid) -> RpcResult<ConfirmationEstimate>;
+
+    /// Create a child-pays-for-parent transaction for an unconfirmed wallet
+    /// transaction, bringing the package up to the target fee rate.
+    /// Returns the TXID of the child transaction.
+    #[method(name = "create_cpfp")]
+    async fn create_cpfp(&self, parent_txid: Txid, fee_rate_sat_per_vb: u64) -> RpcResult<Txid>;
+
+    /// Send to an address. If `subtract_fee_from_amount` is set, the
+    /// recipient receives `amount_sats` minus the fee. The fee rate is either in
+    /// sat/vB, or a fee tier.
+    /// If a change address is provided, change is sent to it instead of the
+    /// wallet's internal keychain. Change addresses that are not owned by the
+    /// wallet are rejected, unless `allow_external_change` is set.
+    /// Returns the TXID of the sent transaction, and the fee rate and fee
+    /// tier that it was built with, if a fee rate or fee tier was requested.
+    #[method(name = "send_to_address")]
+    async fn send_to_address(
+        &self,
+        address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        amount_sats: u64,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+        subtract_fee_from_amount: Option<bool>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+    ) -> RpcResult<SentTransaction>;
+
+    /// Fee rate used for transactions built without an explicit fee, in
+    /// sat/vB, and where it comes from. If no default fee rate is set, it is
+    /// estimated by Bitcoin Core's `estimatesmartfee`. If the estimate fails
+    /// or there is not enough data for one, the transaction builder's default
+    /// fee rate is used.
+    #[method(name = "get_default_fee_rate")]
+    async fn get_default_fee_rate(&self) -> RpcResult<DefaultFeeRate>;
+
+    /// Set the fee rate used for transactions built without an explicit fee,
+    /// in sat/vB. Must not be below Bitcoin Core's minimum relay fee rate.
+    /// If not provided, the default fee rate is cleared.
+    #[method(name = "set_default_fee_rate")]
+    async fn set_default_fee_rate(&self, sat_per_vb: Option<u64>) -> RpcResult<()>;
+
+    /// Fee rate for a fee tier (`economy`, `normal`, or `priority`), in
+    /// sat/vB. Each tier is estimated by Bitcoin Core's `estimatesmartfee`
+    /// for its configured confirmation target, or from the mempool if there
+    /// is not enough data for an estimate.
+    #[method(name = "resolve_fee_tier")]
+    async fn resolve_fee_tier(&self, tier: FeeTier) -> RpcResult<ResolvedFeeTier>;
+
+    /// Current Ctip (treasury UTXO) for a sidechain, and whether the wallet
+    /// can spend it. Spending the Ctip requires the Ctip transaction to be
+    /// available, and the wallet to be unlocked. Fails if the sidechain has
+    /// no Ctip.
+    #[method(name = "get_ctip")]
+    async fn get_ctip(&self, sidechain_number: SidechainNumber) -> RpcResult<CtipStatus>;
+
+    /// Deposit amount limits for a sidechain: the dust threshold, and the
+    /// configured minimum deposit amount, if any. Deposits below either are
+    /// rejected.
+    #[method(name = "get_deposit_policy")]
+    fn get_deposit_policy(&self, sidechain_number: SidechainNumber) -> RpcResult<DepositPolicy>;
+
+    /// Confirmed spendable balance, less a reserve for fees and bundles.
+    /// The deployable amount is zero if the balance is below the reserve.
+    #[method(name = "get_deployable_balance")]
+    async fn get_deployable_balance(&self, reserve_sats: u64) -> RpcResult<DeployableBalance>;
+
+    /// Sidechains that the wallet creates deposits, withdrawal bundles, and
+    /// coinbase messages for, as configured by `--wallet-sidechain-allowlist`
+    /// and `--wallet-sidechain-denylist`
+    #[method(name = "get_sidechain_filter")]
+    fn get_sidechain_filter(&self) -> RpcResult<SidechainFilter>;
+
+    /// If an idempotency key is provided, repeating a request with the same
+    /// key returns the original result instead of creating another deposit.
+    /// If a change address is provided, change is sent to it instead of the
+    /// wallet's internal keychain. Change addresses that are not owned by the
+    /// wallet are rejected, unless `allow_external_change` is set.
+    /// The fee can be set as an absolute fee (`fee_sats`), or as a fee rate
+    /// in sat/vB or a fee tier (`fee_rate`), but not both.
+    /// Returns the TXID of the deposit transaction, and the fee rate and fee
+    /// tier that it was built with, if a fee rate or fee tier was requested.
+    #[method(name = "create_deposit_transaction")]
+    async fn create_deposit_transaction(
+        &self,
+        sidechain_id: SidechainNumber,
+        address: String,
+        value_sats: u64,
+        fee_sats: Option<u64>,
+        idempotency_key: Option<uuid::Uuid>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+        fee_rate: Option<FeeRateOrTier>,
+    ) -> RpcResult<SentTransaction>;
+
+    /// If an idempotency key is provided, repeating a request with the same
+    /// key returns the original result instead of storing the bundle again.
+    #[method(name = "broadcast_withdrawal_bundle")]
+    async fn broadcast_withdrawal_bundle(
+        &self,
+        sidechain_id: SidechainNumber,
+        tx_hex: String,
+        idempotency_key: Option<uuid::Uuid>,
+    ) -> RpcResult<M6id>;
+
+    /// Cross-check the payouts in a withdrawal bundle against the
+    /// withdrawals in one of the sidechain's pending bundles, returning
+    /// missing, extra, and wrong-amount withdrawals. Withdrawals are matched
+    /// by script pubkey.
+    #[method(name = "validate_bundle_against_pending")]
+    async fn validate_bundle_against_pending(
+        &self,
+        sidechain_id: SidechainNumber,
+        pending_m6id: M6id,
+        tx_hex: String,
+    ) -> RpcResult<BundleValidation>;
 }
 
 #[async_trait]
@@ -44,6 +469,28 @@ impl RpcServer for crate::wallet::Wallet {
             .await
     }
 
+    async fn list_withdrawal_bundle_events(
+        &self,
+        sidechain_number: SidechainNumber,
+        from_height: Option<u32>,
+    ) -> RpcResult<Vec<WithdrawalBundleEventRecord>> {
+        self.list_withdrawal_bundle_events(sidechain_number, from_height.unwrap_or(0))
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn list_my_withdrawal_bundles(&self) -> RpcResult<Vec<ProposedWithdrawalBundle>> {
+        self.list_proposed_withdrawal_bundles()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn list_wallet_transactions(&self) -> RpcResult<Vec<BDKWalletTransaction>> {
+        self.list_wallet_transactions()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
     async fn create_bmm_critical_data_transaction(
         &self,
         sidechain_id: SidechainNumber,
@@ -66,4 +513,434 @@ impl RpcServer for crate::wallet::Wallet {
             .ok_or_else(|| custom_json_rpc_err(BmmRequestAlreadyExistsError))?;
         Ok(tx.compute_txid())
     }
+
+    async fn get_sidechain_deposit_address(
+        &self,
+        sidechain_id: SidechainNumber,
+    ) -> RpcResult<String> {
+        let address = self
+            .get_sidechain_deposit_address(sidechain_id)
+            .await
+            .map_err(custom_json_rpc_err)?;
+        Ok(address.to_string())
+    }
+
+    async fn list_pending_sidechain_proposals(&self) -> RpcResult<Vec<PendingSidechainProposal>> {
+        self.list_pending_sidechain_proposals()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn list_pending_withdrawals(
+        &self,
+        sidechain_id: SidechainNumber,
+    ) -> RpcResult<PendingWithdrawals> {
+        self.list_pending_withdrawals(sidechain_id)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn list_unspent(
+        &self,
+        min_confirmations: Option<u32>,
+        script_type: Option<ScriptType>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> RpcResult<UnspentOutputs> {
+        self.list_unspent(
+            min_confirmations.unwrap_or(0),
+            script_type,
+            offset.unwrap_or(0),
+            limit,
+        )
+        .map_err(custom_json_rpc_err)
+        .await
+    }
+
+    async fn verify_xpub(&self, xpub: String) -> RpcResult<Vec<XpubMatch>> {
+        let xpub: bdk_wallet::bitcoin::bip32::Xpub =
+            xpub.parse().map_err(invalid_params_json_rpc_err)?;
+        self.verify_xpub(&xpub).map_err(custom_json_rpc_err).await
+    }
+
+    async fn get_derivation_info(&self) -> RpcResult<Vec<KeychainDerivationInfo>> {
+        self.get_derivation_info()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn export_descriptors(&self) -> RpcResult<ExportedDescriptors> {
+        self.export_descriptors(false)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn get_unused_address(&self) -> RpcResult<String> {
+        let address = self
+            .get_unused_address()
+            .await
+            .map_err(custom_json_rpc_err)?;
+        Ok(address.to_string())
+    }
+
+    async fn list_addresses(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> RpcResult<RevealedAddresses> {
+        self.list_addresses(offset.unwrap_or(0), limit)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn get_enforcer_block_template(&self) -> RpcResult<EnforcerBlockTemplate> {
+        self.get_block_template().map_err(custom_json_rpc_err).await
+    }
+
+    async fn get_candidate_block_hex(&self) -> RpcResult<String> {
+        self.get_candidate_block_hex()
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn get_block_fees(&self, block: BlockHashOrHeight) -> RpcResult<BlockFees> {
+        self.get_block_fees(block)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn get_fee_history(&self, lookback_blocks: u32) -> RpcResult<Vec<BlockFeeRates>> {
+        self.get_fee_history(lookback_blocks)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn get_mempool_fee_history(
+        &self,
+        lookback_blocks: u32,
+    ) -> RpcResult<Vec<MempoolFeeSnapshot>> {
+        self.get_mempool_fee_history(lookback_blocks)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn validate_mnemonic(
+        &self,
+        phrase: String,
+        language: Option<String>,
+    ) -> RpcResult<MnemonicValidation> {
+        let language = match language {
+            Some(language) => {
+                crate::wallet::mnemonic::parse_language(&language).ok_or_else(|| {
+                    invalid_params_json_rpc_err(UnsupportedMnemonicLanguageError(language))
+                })?
+            }
+            None => bdk_wallet::bip39::Language::English,
+        };
+        self.validate_mnemonic(&phrase, language)
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn sync_now(&self) -> RpcResult<SyncSummary> {
+        self.sync_now().map_err(custom_json_rpc_err).await
+    }
+
+    fn cancel_full_scan(&self) -> RpcResult<bool> {
+        Ok(self.cancel_full_scan())
+    }
+
+    async fn compact_wallet_db(&self) -> RpcResult<()> {
+        self.compact_wallet_db().map_err(custom_json_rpc_err).await
+    }
+
+    async fn rebroadcast_unconfirmed(&self) -> RpcResult<Vec<RebroadcastResult>> {
+        self.rebroadcast_unconfirmed()
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn broadcast_raw_transaction(&self, tx_hex: String) -> RpcResult<Txid> {
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_hex)
+            .map_err(|err| custom_json_rpc_err(DecodeTransactionError(err)))?;
+        self.broadcast_raw_transaction(&tx)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn import_outpoint(
+        &self,
+        outpoint: bitcoin::OutPoint,
+        script_pubkey: bitcoin::ScriptBuf,
+        amount_sats: u64,
+    ) -> RpcResult<()> {
+        self.import_outpoint(
+            outpoint,
+            script_pubkey,
+            bitcoin::Amount::from_sat(amount_sats),
+        )
+        .map_err(custom_json_rpc_err)
+        .await
+    }
+
+    async fn remove_imported_outpoint(&self, outpoint: bitcoin::OutPoint) -> RpcResult<bool> {
+        self.remove_imported_outpoint(outpoint)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn create_unsigned_psbt(
+        &self,
+        destinations: HashMap<bitcoin::Address<bitcoin::address::NetworkUnchecked>, u64>,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+        required_utxos: Option<Vec<bitcoin::OutPoint>>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+    ) -> RpcResult<String> {
+        let network = self.validator().network();
+        let change_address = parse_change_address(network, change_address, allow_external_change)?;
+        let destinations = destinations
+            .into_iter()
+            .map(|(address, amount_sats)| {
+                let address = address
+                    .require_network(network)
+                    .map_err(invalid_params_json_rpc_err)?;
+                Ok((address, bitcoin::Amount::from_sat(amount_sats)))
+            })
+            .collect::<RpcResult<_>>()?;
+        let fee_policy = match fee_rate_sat_per_vb {
+            Some(fee_rate) => Some(FeePolicy::Rate(resolve_fee_rate(self, fee_rate).await?.0)),
+            None => None,
+        };
+        let params = CreateTransactionParams {
+            fee_policy,
+            required_utxos: required_utxos.unwrap_or_default(),
+            change_address,
+            ..Default::default()
+        };
+        let psbt = self
+            .create_unsigned_send_psbt(destinations, params)
+            .await
+            .map_err(custom_json_rpc_err)?;
+        Ok(psbt.to_string())
+    }
+
+    async fn preview_coin_selection(
+        &self,
+        amount_sats: u64,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+    ) -> RpcResult<CoinSelectionPreview> {
+        let fee_rate = match fee_rate_sat_per_vb {
+            Some(fee_rate) => Some(resolve_fee_rate(self, fee_rate).await?.0),
+            None => None,
+        };
+        self.preview_coin_selection(bitcoin::Amount::from_sat(amount_sats), fee_rate)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn estimate_confirmation_time(&self, txid: Txid) -> RpcResult<ConfirmationEstimate> {
+        self.estimate_confirmation_time(txid)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn create_cpfp(&self, parent_txid: Txid, fee_rate_sat_per_vb: u64) -> RpcResult<Txid> {
+        let fee_rate = bdk_wallet::bitcoin::FeeRate::from_sat_per_vb(fee_rate_sat_per_vb)
+            .ok_or_else(|| invalid_params_json_rpc_err(InvalidFeeRateError(fee_rate_sat_per_vb)))?;
+        self.create_cpfp(parent_txid, fee_rate)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
+
+    async fn send_to_address(
+        &self,
+        address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        amount_sats: u64,
+        fee_rate_sat_per_vb: Option<FeeRateOrTier>,
+        subtract_fee_from_amount: Option<bool>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+    ) -> RpcResult<SentTransaction> {
+        let network = self.validator().network();
+        let address = address
+            .require_network(network)
+            .map_err(invalid_params_json_rpc_err)?;
+        let change_address = parse_change_address(network, change_address, allow_external_change)?;
+        let (fee_rate, resolved_fee_rate) = match fee_rate_sat_per_vb {
+            Some(fee_rate) => {
+                let (fee_rate, resolved_fee_rate) = resolve_fee_rate(self, fee_rate).await?;
+                (Some(fee_rate), Some(resolved_fee_rate))
+            }
+            None => (None, None),
+        };
+        let txid = self
+            .send_to_address(
+                address,
+                bitcoin::Amount::from_sat(amount_sats),
+                fee_rate,
+                subtract_fee_from_amount.unwrap_or(false),
+                change_address,
+            )
+            .map_err(custom_json_rpc_err)
+            .await?;
+        Ok(SentTransaction {
+            txid,
+            fee_rate: resolved_fee_rate,
+        })
+    }
+
+    async fn get_default_fee_rate(&self) -> RpcResult<DefaultFeeRate> {
+        self.get_default_fee_rate()
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn set_default_fee_rate(&self, sat_per_vb: Option<u64>) -> RpcResult<()> {
+        self.set_default_fee_rate(sat_per_vb)
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn resolve_fee_tier(&self, tier: FeeTier) -> RpcResult<ResolvedFeeTier> {
+        self.resolve_fee_tier(tier)
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn get_ctip(&self, sidechain_number: SidechainNumber) -> RpcResult<CtipStatus> {
+        self.get_ctip_status(sidechain_number)
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    async fn get_deployable_balance(&self, reserve_sats: u64) -> RpcResult<DeployableBalance> {
+        self.get_deployable_balance(bitcoin::Amount::from_sat(reserve_sats))
+            .await
+            .map_err(custom_json_rpc_err)
+    }
+
+    fn get_deposit_policy(&self, sidechain_number: SidechainNumber) -> RpcResult<DepositPolicy> {
+        Ok(self.get_deposit_policy(sidechain_number))
+    }
+
+    fn get_sidechain_filter(&self) -> RpcResult<SidechainFilter> {
+        Ok(self.get_sidechain_filter())
+    }
+
+    async fn create_deposit_transaction(
+        &self,
+        sidechain_id: SidechainNumber,
+        address: String,
+        value_sats: u64,
+        fee_sats: Option<u64>,
+        idempotency_key: Option<uuid::Uuid>,
+        change_address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+        allow_external_change: Option<bool>,
+        fee_rate: Option<FeeRateOrTier>,
+    ) -> RpcResult<SentTransaction> {
+        if !self
+            .is_sidechain_active(sidechain_id)
+            .map_err(custom_json_rpc_err)?
+        {
+            return Err(invalid_params_json_rpc_err(SidechainNotActiveError(
+                sidechain_id,
+            )));
+        }
+        let value = bitcoin::Amount::from_sat(value_sats);
+        let (fee_policy, resolved_fee_rate) = match (fee_sats, fee_rate) {
+            (Some(_), Some(_)) => return Err(invalid_params_json_rpc_err(ConflictingFeeError)),
+            (Some(fee_sats), None) => (
+                Some(FeePolicy::Absolute(bitcoin::Amount::from_sat(fee_sats))),
+                None,
+            ),
+            (None, Some(requested_fee_rate)) => {
+                let (fee_rate, resolved_fee_rate) =
+                    resolve_fee_rate(self, requested_fee_rate).await?;
+                (Some(FeePolicy::Rate(fee_rate)), Some(resolved_fee_rate))
+            }
+            (None, None) => (None, None),
+        };
+        let change_address = parse_change_address(
+            self.validator().network(),
+            change_address,
+            allow_external_change,
+        )?;
+        let params = serde_json::json!([
+            sidechain_id.0,
+            address,
+            value_sats,
+            fee_sats,
+            change_address.as_ref().map(|change_address| {
+                (
+                    change_address.address.to_string(),
+                    change_address.allow_external,
+                )
+            }),
+            fee_rate,
+        ]);
+        self.with_idempotency_key(
+            "create_deposit_transaction",
+            idempotency_key,
+            &params,
+            self.create_deposit(sidechain_id, address, value, fee_policy, change_address)
+                .map_ok(|txid| SentTransaction {
+                    txid,
+                    fee_rate: resolved_fee_rate,
+                }),
+        )
+        .map_err(idempotent_json_rpc_err)
+        .await
+    }
+
+    async fn broadcast_withdrawal_bundle(
+        &self,
+        sidechain_id: SidechainNumber,
+        tx_hex: String,
+        idempotency_key: Option<uuid::Uuid>,
+    ) -> RpcResult<M6id> {
+        if !self
+            .is_sidechain_active(sidechain_id)
+            .map_err(custom_json_rpc_err)?
+        {
+            return Err(invalid_params_json_rpc_err(SidechainNotActiveError(
+                sidechain_id,
+            )));
+        }
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_hex)
+            .map_err(|err| custom_json_rpc_err(DecodeTransactionError(err)))?;
+        let blinded_m6 =
+            BlindedM6::try_from(std::borrow::Cow::Owned(tx)).map_err(custom_json_rpc_err)?;
+        let params = serde_json::json!([sidechain_id.0, tx_hex]);
+        self.with_idempotency_key(
+            "broadcast_withdrawal_bundle",
+            idempotency_key,
+            &params,
+            self.put_withdrawal_bundle(sidechain_id, &blinded_m6),
+        )
+        .map_err(idempotent_json_rpc_err)
+        .await
+    }
+
+    async fn validate_bundle_against_pending(
+        &self,
+        sidechain_id: SidechainNumber,
+        pending_m6id: M6id,
+        tx_hex: String,
+    ) -> RpcResult<BundleValidation> {
+        if !self
+            .is_sidechain_active(sidechain_id)
+            .map_err(custom_json_rpc_err)?
+        {
+            return Err(invalid_params_json_rpc_err(SidechainNotActiveError(
+                sidechain_id,
+            )));
+        }
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_hex)
+            .map_err(|err| custom_json_rpc_err(DecodeTransactionError(err)))?;
+        let blinded_m6 =
+            BlindedM6::try_from(std::borrow::Cow::Owned(tx)).map_err(custom_json_rpc_err)?;
+        self.validate_bundle_against_pending(sidechain_id, pending_m6id, &blinded_m6)
+            .map_err(custom_json_rpc_err)
+            .await
+    }
 }
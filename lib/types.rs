@@ -1,8 +1,12 @@
-use std::{borrow::Cow, num::TryFromIntError, sync::Arc};
+use std::{
+    borrow::Cow,
+    num::{NonZeroU32, TryFromIntError},
+    sync::Arc,
+};
 
 use bdk_wallet::chain::{ChainPosition, ConfirmationBlockTime};
 use bitcoin::{
-    Amount, BlockHash, Opcode, OutPoint, ScriptBuf, Transaction, Txid, Work,
+    Amount, BlockHash, Opcode, OutPoint, ScriptBuf, Transaction, TxOut, Txid, Work,
     amount::CheckedSum as _,
     hashes::{Hash as _, sha256d},
     opcodes::{
@@ -376,6 +380,25 @@ pub enum WithdrawalBundleEventKind {
     },
 }
 
+/// Status of a withdrawal bundle, as of a withdrawal bundle event
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalBundleStatus {
+    Submitted,
+    Failed,
+    Succeeded,
+}
+
+impl From<&WithdrawalBundleEventKind> for WithdrawalBundleStatus {
+    fn from(kind: &WithdrawalBundleEventKind) -> Self {
+        match kind {
+            WithdrawalBundleEventKind::Submitted => Self::Submitted,
+            WithdrawalBundleEventKind::Failed => Self::Failed,
+            WithdrawalBundleEventKind::Succeeded { .. } => Self::Succeeded,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WithdrawalBundleEvent {
     pub sidechain_id: SidechainNumber,
@@ -510,7 +533,14 @@ pub struct BDKWalletTransaction {
     pub txid: bitcoin::Txid,
     pub tx: Arc<Transaction>,
     pub chain_position: ChainPosition<ConfirmationBlockTime>,
-    pub fee: Amount,
+    /// `None` if the fee could not be determined, eg. for coinbase
+    /// transactions, or if prevouts are missing
+    pub fee: Option<Amount>,
+    /// Virtual size of the transaction, in vbytes
+    pub vsize: u64,
+    /// Effective fee rate in sat/vB, computed from `fee` and `vsize`.
+    /// `None` if the fee could not be determined.
+    pub fee_rate_sat_per_vb: Option<f64>,
     pub received: Amount,
     pub sent: Amount,
 }
@@ -533,6 +563,56 @@ impl From<bitcoin::FeeRate> for FeePolicy {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+#[error(
+    "coinbase reward share `{value}` for `{script_pubkey}` is below the dust threshold `{dust_threshold}`"
+)]
+pub struct CoinbaseRewardShareBelowDust {
+    pub script_pubkey: ScriptBuf,
+    pub value: Amount,
+    pub dust_threshold: Amount,
+}
+
+/// Split of the coinbase reward across multiple recipients, proportional to
+/// their weights
+#[derive(Clone, Debug)]
+pub struct CoinbaseRewardSplit(pub NonEmpty<(ScriptBuf, NonZeroU32)>);
+
+impl CoinbaseRewardSplit {
+    /// Coinbase outputs paying each recipient its share of `value`.
+    /// Any remainder from rounding is paid to the last recipient.
+    pub fn outputs(&self, value: Amount) -> Result<Vec<TxOut>, CoinbaseRewardShareBelowDust> {
+        let total_weight: u128 = self.0.iter().map(|(_, weight)| weight.get() as u128).sum();
+        let last_idx = self.0.len() - 1;
+        let mut remaining = value;
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(idx, (script_pubkey, weight))| {
+                let share = if idx == last_idx {
+                    remaining
+                } else {
+                    let share_sats = value.to_sat() as u128 * weight.get() as u128 / total_weight;
+                    Amount::from_sat(share_sats as u64)
+                };
+                remaining -= share;
+                let dust_threshold = script_pubkey.minimal_non_dust();
+                if share < dust_threshold {
+                    return Err(CoinbaseRewardShareBelowDust {
+                        script_pubkey: script_pubkey.clone(),
+                        value: share,
+                        dust_threshold,
+                    });
+                }
+                Ok(TxOut {
+                    script_pubkey: script_pubkey.clone(),
+                    value: share,
+                })
+            })
+            .collect()
+    }
+}
+
 pub const OP_DRIVECHAIN: Opcode = OP_NOP5;
 
 /// Create an OP_DRIVECHAIN script for the specified sidechain
@@ -780,6 +860,7 @@ impl PendingM6idInfo {
 
 #[cfg(test)]
 mod tests {
+    use bitcoin::hashes::Hash as _;
     use miette::Diagnostic as _;
 
     use crate::types::{SidechainDeclaration, SidechainNumber, SidechainProposal};
@@ -890,4 +971,40 @@ mod tests {
             "sidechain_proposal::unknown_version"
         );
     }
+
+    fn reward_split(weights: &[u32]) -> crate::types::CoinbaseRewardSplit {
+        let recipients = weights
+            .iter()
+            .enumerate()
+            .map(|(idx, weight)| {
+                let script_pubkey = bitcoin::ScriptBuf::new_p2wpkh(
+                    &bitcoin::WPubkeyHash::from_byte_array([idx as u8; 20]),
+                );
+                (script_pubkey, std::num::NonZeroU32::new(*weight).unwrap())
+            })
+            .collect::<Vec<_>>();
+        crate::types::CoinbaseRewardSplit(nonempty::NonEmpty::from_vec(recipients).unwrap())
+    }
+
+    #[test]
+    fn test_coinbase_reward_split_proportional() {
+        let outputs = reward_split(&[1, 1, 2])
+            .outputs(bitcoin::Amount::from_sat(100_001))
+            .unwrap();
+        let values: Vec<_> = outputs.iter().map(|output| output.value.to_sat()).collect();
+        // Rounding remainder goes to the last recipient
+        assert_eq!(values, [25_000, 25_000, 50_001]);
+    }
+
+    #[test]
+    fn test_coinbase_reward_split_below_dust() {
+        let split = reward_split(&[1, 1_000_000]);
+        let err = split
+            .outputs(bitcoin::Amount::from_sat(100_000))
+            .unwrap_err();
+        // The first recipient's share rounds down to zero
+        assert_eq!(err.script_pubkey, split.0.head.0);
+        assert_eq!(err.value, bitcoin::Amount::ZERO);
+        assert_eq!(err.dust_threshold, split.0.head.0.minimal_non_dust());
+    }
 }
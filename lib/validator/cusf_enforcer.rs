@@ -4,6 +4,7 @@ use std::{
     borrow::Borrow,
     collections::{HashMap, HashSet},
     future::Future,
+    sync::atomic,
 };
 
 use async_broadcast::TrySendError;
@@ -11,7 +12,6 @@ use bitcoin::{Block, BlockHash, Transaction, Txid, hashes::Hash as _};
 use cusf_enforcer_mempool::cusf_enforcer::{ConnectBlockAction, CusfEnforcer, TxAcceptAction};
 use fallible_iterator::FallibleIterator;
 use fatality::Nested as _;
-use futures::TryFutureExt as _;
 use miette::Diagnostic;
 use ouroboros::self_referencing;
 use sneed::{RoTxn, RwTxn, db, env, rwtxn};
@@ -44,6 +44,8 @@ enum ConnectBlockErrorInner {
     DbTryGet(#[from] db::error::TryGet),
     #[error(transparent)]
     DbRange(Box<db::error::Range>),
+    #[error("Block processing is halted, operator intervention is required")]
+    Halted,
     #[error(transparent)]
     NestedWriteTxn(#[from] env::error::NestedWriteTxn),
     #[error(transparent)]
@@ -82,6 +84,10 @@ enum DisconnectBlockErrorInner {
     CommitWriteTxn(#[from] rwtxn::error::Commit),
     #[error(transparent)]
     DisconnectBlock(#[from] task::error::DisconnectBlock),
+    #[error("Block processing is halted, operator intervention is required")]
+    Halted,
+    #[error(transparent)]
+    ReorgTooDeep(#[from] task::error::ReorgTooDeep),
     #[error(transparent)]
     WriteTxn(#[from] env::error::WriteTxn),
 }
@@ -354,6 +360,9 @@ impl CusfEnforcer for Validator {
     where
         Signal: Future<Output = ()> + Send,
     {
+        if self.is_halted() {
+            return Err(task::error::Sync::Halted.into());
+        }
         let header_sync_progress_tx = {
             let mut header_sync_progress_rx_write = self.header_sync_progress_rx.write();
             if header_sync_progress_rx_write.is_some() {
@@ -371,13 +380,19 @@ impl CusfEnforcer for Validator {
             &self.dbs,
             &self.events_tx,
             &header_sync_progress_tx,
+            &self.block_sync_progress_rx,
+            self.max_reorg_depth,
             &self.mainchain_client,
             &self.mainchain_rest_client,
             tip,
             shutdown_signal,
         )
-        .map_err(SyncError)
-        .await?;
+        .await
+        .inspect_err(|err| {
+            if let task::error::Sync::ReorgTooDeep(err) = err {
+                self.halt(err)
+            }
+        })?;
         *self.header_sync_progress_rx.write() = None;
         Ok(())
     }
@@ -388,7 +403,14 @@ impl CusfEnforcer for Validator {
         &mut self,
         block: &Block,
     ) -> Result<ConnectBlockAction, Self::ConnectBlockError> {
-        ConnectBlockCommit.connect_block(self, block)
+        if self.is_halted() {
+            return Err(ConnectBlockErrorInner::Halted.into());
+        }
+        let res = ConnectBlockCommit.connect_block(self, block)?;
+        if let ConnectBlockAction::Accept { .. } = res {
+            self.reorg_depth.store(0, atomic::Ordering::SeqCst);
+        }
+        Ok(res)
     }
 
     type DisconnectBlockError = DisconnectBlockError;
@@ -397,9 +419,26 @@ impl CusfEnforcer for Validator {
         &mut self,
         block_hash: BlockHash,
     ) -> Result<(), Self::DisconnectBlockError> {
+        if self.is_halted() {
+            return Err(DisconnectBlockErrorInner::Halted.into());
+        }
+        // Blocks are disconnected one at a time, so the reorg depth is
+        // checked before each disconnect.
+        if let Some(max_reorg_depth) = self.max_reorg_depth {
+            let depth = self.reorg_depth.load(atomic::Ordering::SeqCst) + 1;
+            if depth > max_reorg_depth.get() {
+                let err = task::error::ReorgTooDeep {
+                    depth,
+                    max_reorg_depth,
+                };
+                self.halt(&err);
+                return Err(err.into());
+            }
+        }
         let mut rwtxn = self.dbs.write_txn()?;
         let () = task::disconnect_block(&mut rwtxn, &self.dbs, &self.events_tx, block_hash)?;
         rwtxn.commit()?;
+        self.reorg_depth.fetch_add(1, atomic::Ordering::SeqCst);
         Ok(())
     }
 
@@ -306,8 +306,18 @@ pub(super) struct Dbs {
 impl Dbs {
     const NUM_DBS: u32 = ActiveSidechainDbs::NUM_DBS + BlockHashDbs::NUM_DBS + 4;
 
+    /// Directory containing the LMDB environment
+    pub fn db_dir(data_dir: &Path, network: bitcoin::Network) -> PathBuf {
+        data_dir.join(format!("{network}.mdb"))
+    }
+
+    /// Path to the LMDB data file in `db_dir`
+    pub fn data_file_path(db_dir: &Path) -> PathBuf {
+        db_dir.join("data.mdb")
+    }
+
     pub fn new(data_dir: &Path, network: bitcoin::Network) -> Result<Self, CreateDbsError> {
-        let db_dir = data_dir.join(format!("{network}.mdb"));
+        let db_dir = Self::db_dir(data_dir, network);
         if let Err(err) = std::fs::create_dir_all(&db_dir) {
             let err = CreateDbsError::CreateDirectory {
                 path: db_dir,
@@ -346,6 +356,13 @@ impl Dbs {
         })
     }
 
+    /// Write a compacted copy of the LMDB environment to `path`, which must
+    /// not already exist. LMDB copies the environment within its own read
+    /// txn, so the copy is consistent, and writers are not blocked.
+    pub fn copy_to_file(&self, path: &Path) -> Result<std::fs::File, heed::Error> {
+        self.env.copy_to_file(path, heed::CompactionOption::Enabled)
+    }
+
     pub fn read_txn(&self) -> Result<RoTxn<'_>, env::error::ReadTxn> {
         self.env.read_txn()
     }
@@ -1,7 +1,9 @@
 use std::{
     collections::{HashMap, HashSet},
+    num::NonZeroU32,
     path::Path,
-    sync::Arc,
+    sync::{Arc, atomic::AtomicU32},
+    time::Instant,
 };
 
 use async_broadcast::{InactiveReceiver, Sender as BroadcastSender, broadcast};
@@ -11,12 +13,14 @@ use fallible_iterator::{FallibleIterator, IteratorExt};
 use futures::{StreamExt, stream::FusedStream};
 use miette::{Diagnostic, IntoDiagnostic};
 use nonempty::NonEmpty;
-use sneed::{db, env};
+use serde::Serialize;
+use sneed::{RoTxn, db, env};
 use thiserror::Error;
 use tokio::sync::watch::Receiver as WatchReceiver;
 
 use crate::{
     proto::{StatusBuilder, ToStatus, mainchain::HeaderSyncProgress},
+    rpc_client::MainchainClient,
     types::{
         BlockInfo, BmmCommitment, BmmCommitments, Ctip, Event, HeaderInfo, Sidechain,
         SidechainNumber, SidechainProposalId, TreasuryUtxo, TwoWayPegData,
@@ -27,9 +31,17 @@ use crate::{
 pub mod cusf_enforcer;
 mod dbs;
 pub mod main_rest_client;
+mod snapshot;
 mod task;
+mod transaction_identification;
+mod webhook;
 
 use self::dbs::{Dbs, PendingM6ids};
+pub use self::snapshot::{ExportSnapshotError, ImportSnapshotError, SnapshotHeader};
+pub use self::transaction_identification::{
+    IdentifiedTransaction, IdentifyTransactionError, TransactionKind,
+};
+pub use self::webhook::{WebhookBlockContext, WebhookEvent, WebhookPayload};
 
 #[derive(Debug, Error)]
 pub enum InitError {
@@ -59,6 +71,23 @@ impl ToStatus for GetCtipSequenceNumberError {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum TryGetActiveSidechainError {
+    #[error(transparent)]
+    ReadTxn(#[from] env::error::ReadTxn),
+    #[error(transparent)]
+    TryGet(#[from] db::error::TryGet),
+}
+
+impl ToStatus for TryGetActiveSidechainError {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::ReadTxn(err) => StatusBuilder::new(err),
+            Self::TryGet(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 pub enum TryGetCtipError {
     #[error(transparent)]
@@ -252,6 +281,28 @@ where
     }
 }
 
+#[derive(Debug, Error)]
+enum CheckTxErrorInner {
+    #[error(transparent)]
+    ValidateTransaction(#[from] task::error::ValidateTransaction),
+    #[error(transparent)]
+    WriteTxn(#[from] env::error::WriteTxn),
+}
+
+#[derive(Debug, Error)]
+#[error(transparent)]
+#[repr(transparent)]
+pub struct CheckTxError(CheckTxErrorInner);
+
+impl<T> From<T> for CheckTxError
+where
+    CheckTxErrorInner: From<T>,
+{
+    fn from(err: T) -> Self {
+        Self(err.into())
+    }
+}
+
 #[derive(Debug, Error)]
 enum GetTwoWayPegDataRangeErrorInner {
     #[error(transparent)]
@@ -336,6 +387,26 @@ impl ToStatus for TryGetMainchainTipHeightError {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum TryGetBlockHashAtHeightError {
+    #[error(transparent)]
+    DbGet(#[from] db::error::Get),
+    #[error(transparent)]
+    DbTryGet(#[from] db::error::TryGet),
+    #[error(transparent)]
+    ReadTxn(#[from] env::error::ReadTxn),
+}
+
+impl ToStatus for TryGetBlockHashAtHeightError {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::DbGet(err) => StatusBuilder::new(err),
+            Self::DbTryGet(err) => StatusBuilder::new(err),
+            Self::ReadTxn(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TryGetBmmCommitmentsError {
     #[error(transparent)]
@@ -400,23 +471,238 @@ impl ToStatus for GetSidechainsError {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetSyncStatusError {
+    #[error("JSON RPC error (`{method}`)")]
+    JsonRpc {
+        method: String,
+        source: jsonrpsee::core::ClientError,
+    },
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] TryGetMainchainTipHeightError),
+}
+
+impl ToStatus for GetSyncStatusError {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::JsonRpc { .. } => StatusBuilder::new(self),
+            Self::TryGetMainchainTipHeight(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetDepositProofError {
+    #[error("failed to decode `{method}` response")]
+    Decode {
+        method: String,
+        source: bitcoin::consensus::encode::FromHexError,
+    },
+    #[error("JSON RPC error (`{method}`)")]
+    JsonRpc {
+        method: String,
+        source: jsonrpsee::core::ClientError,
+    },
+    #[error(transparent)]
+    IdentifyTransaction(#[from] IdentifyTransactionError),
+    #[error("Merkle proof for transaction `{txid}` is invalid")]
+    InvalidProof {
+        txid: Txid,
+        source: Option<bitcoin::merkle_tree::MerkleBlockError>,
+    },
+    #[error("transaction `{txid}` is not a deposit")]
+    NotADeposit { txid: Txid },
+    #[error(
+        "block `{block_hash}` containing transaction `{txid}` is not in the enforcer's processed chain"
+    )]
+    NotProcessed { txid: Txid, block_hash: BlockHash },
+    #[error(transparent)]
+    ReadTxn(#[from] env::error::ReadTxn),
+    #[error(transparent)]
+    TryGet(#[from] db::error::TryGet),
+    #[error("deposit transaction `{txid}` is unconfirmed")]
+    Unconfirmed { txid: Txid },
+}
+
+impl ToStatus for GetDepositProofError {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Decode { .. } | Self::JsonRpc { .. } | Self::InvalidProof { .. } => {
+                StatusBuilder::new(self)
+            }
+            Self::IdentifyTransaction(err) => err.builder(),
+            Self::NotADeposit { .. } => StatusBuilder::new(self).code(tonic::Code::InvalidArgument),
+            Self::NotProcessed { .. } | Self::Unconfirmed { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
+            }
+            Self::ReadTxn(err) => StatusBuilder::new(err),
+            Self::TryGet(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
+/// Raw deposit transaction, with a Merkle proof of inclusion in a block
+#[derive(Clone, Debug, Serialize)]
+pub struct DepositProof {
+    pub txid: Txid,
+    pub sidechain_number: SidechainNumber,
+    pub block_hash: BlockHash,
+    /// Consensus-encoded transaction
+    #[serde(with = "hex::serde")]
+    pub transaction: Vec<u8>,
+    /// Consensus-encoded `MerkleBlock`, as returned by `gettxoutproof`
+    #[serde(with = "hex::serde")]
+    pub proof: Vec<u8>,
+}
+
+/// Subset of the verbose `getrawtransaction` response
+#[derive(serde::Deserialize)]
+struct RawTransactionInfo {
+    hex: String,
+    blockhash: Option<BlockHash>,
+}
+
+/// Progress of an in-flight block sync
+#[derive(Clone, Copy, Debug)]
+pub struct BlockSyncProgress {
+    /// Enforcer tip height when the block sync started.
+    /// `None` if syncing from genesis.
+    pub start_height: Option<u32>,
+    pub started_at: Instant,
+    /// Height of the last connected block
+    pub current_height: Option<u32>,
+    /// Height of the mainchain tip being synced to
+    pub target_height: u32,
+}
+
+impl BlockSyncProgress {
+    /// Number of blocks connected since the sync started
+    fn blocks_connected(&self) -> u32 {
+        match (self.start_height, self.current_height) {
+            (_, None) => 0,
+            (None, Some(current_height)) => current_height + 1,
+            (Some(start_height), Some(current_height)) => {
+                current_height.saturating_sub(start_height)
+            }
+        }
+    }
+
+    /// Blocks connected per second since the sync started.
+    /// `None` if no blocks have been connected yet.
+    pub fn blocks_per_sec(&self) -> Option<f64> {
+        let blocks_connected = self.blocks_connected();
+        let elapsed_secs = self.started_at.elapsed().as_secs_f64();
+        if blocks_connected == 0 || elapsed_secs <= 0. {
+            return None;
+        }
+        Some(blocks_connected as f64 / elapsed_secs)
+    }
+
+    /// Number of blocks left to connect
+    pub fn blocks_remaining(&self) -> u32 {
+        match self.current_height {
+            Some(current_height) => self.target_height.saturating_sub(current_height),
+            None => self.target_height + 1,
+        }
+    }
+
+    /// Estimated time until the sync completes, at the rate since the sync
+    /// started. `None` if no blocks have been connected yet.
+    pub fn eta(&self) -> Option<jiff::SignedDuration> {
+        let blocks_per_sec = self.blocks_per_sec()?;
+        // Float to int casts saturate, so this cannot overflow
+        let eta_secs = (self.blocks_remaining() as f64 / blocks_per_sec).ceil() as i64;
+        Some(jiff::SignedDuration::from_secs(eta_secs))
+    }
+}
+
+/// Number of confirmations after which the enforcer treats a deposit as
+/// final
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DepositFinality {
+    /// `None` if deposits are never considered final, ie. if neither a
+    /// deposit finality depth nor a maximum reorg depth is configured
+    pub confirmations: Option<NonZeroU32>,
+    /// Block processing halts instead of processing a reorg deeper than
+    /// this, so deposits with more confirmations are never reorged away
+    pub max_reorg_depth: Option<NonZeroU32>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SyncStatus {
+    /// Height of the enforcer tip. `None` if no blocks have been processed.
+    pub processed_height: Option<u32>,
+    /// Height of the mainchain tip
+    pub target_height: u32,
+    pub percent_complete: f64,
+    /// `None` if there is no block sync in progress
+    pub blocks_per_sec: Option<f64>,
+    /// Estimated seconds until the sync completes.
+    /// `None` if there is no rate estimate available.
+    pub eta_secs: Option<u64>,
+}
+
+impl SyncStatus {
+    fn new(
+        processed_height: Option<u32>,
+        target_height: u32,
+        progress: Option<&BlockSyncProgress>,
+    ) -> Self {
+        let percent_complete = match processed_height {
+            Some(processed_height) => {
+                let processed = processed_height as f64 + 1.;
+                let target = target_height as f64 + 1.;
+                (processed / target * 100.).min(100.)
+            }
+            None => 0.,
+        };
+        let blocks_per_sec = progress.and_then(BlockSyncProgress::blocks_per_sec);
+        let eta_secs = if processed_height.is_some_and(|height| height >= target_height) {
+            Some(0)
+        } else {
+            progress
+                .and_then(BlockSyncProgress::eta)
+                .map(|eta| eta.as_secs().unsigned_abs())
+        };
+        Self {
+            processed_height,
+            target_height,
+            percent_complete,
+            blocks_per_sec,
+            eta_secs,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Validator {
     dbs: Dbs,
     events_rx: InactiveReceiver<Event>,
     events_tx: BroadcastSender<Event>,
     header_sync_progress_rx: Arc<parking_lot::RwLock<Option<WatchReceiver<HeaderSyncProgress>>>>,
-    mainchain_client: jsonrpsee::http_client::HttpClient,
+    block_sync_progress_rx: Arc<parking_lot::RwLock<Option<WatchReceiver<BlockSyncProgress>>>>,
+    mainchain_client: MainchainClient,
     mainchain_rest_client: MainRestClient,
     network: bitcoin::Network,
+    /// Block processing halts if a reorg deeper than this is detected
+    max_reorg_depth: Option<NonZeroU32>,
+    /// Number of confirmations after which a deposit is considered final
+    deposit_finality_depth: Option<NonZeroU32>,
+    /// Number of consecutive blocks disconnected since the last block was
+    /// connected
+    reorg_depth: Arc<AtomicU32>,
+    /// Set to `true` if block processing has halted
+    halted: Arc<tokio::sync::watch::Sender<bool>>,
 }
 
 impl Validator {
     pub fn new(
-        mainchain_client: jsonrpsee::http_client::HttpClient,
+        mainchain_client: MainchainClient,
         mainchain_rest_client: MainRestClient,
         data_dir: &Path,
         network: bitcoin::Network,
+        max_reorg_depth: Option<NonZeroU32>,
+        deposit_finality_depth: Option<NonZeroU32>,
     ) -> Result<Self, InitError> {
         const EVENTS_CHANNEL_CAPACITY: usize = 256;
 
@@ -430,9 +716,14 @@ impl Validator {
             events_rx: events_rx.deactivate(),
             events_tx,
             header_sync_progress_rx: Arc::new(parking_lot::RwLock::new(None)),
+            block_sync_progress_rx: Arc::new(parking_lot::RwLock::new(None)),
             mainchain_client,
             mainchain_rest_client,
             network,
+            max_reorg_depth,
+            deposit_finality_depth,
+            reorg_depth: Arc::new(AtomicU32::new(0)),
+            halted: Arc::new(tokio::sync::watch::Sender::new(false)),
         })
     }
 
@@ -440,6 +731,34 @@ impl Validator {
         self.network
     }
 
+    /// Number of confirmations after which a deposit is considered final
+    pub fn deposit_finality(&self) -> DepositFinality {
+        DepositFinality {
+            confirmations: self.deposit_finality_depth,
+            max_reorg_depth: self.max_reorg_depth,
+        }
+    }
+
+    /// Returns `true` if block processing has halted due to a reorg deeper
+    /// than the maximum reorg depth
+    pub fn is_halted(&self) -> bool {
+        *self.halted.borrow()
+    }
+
+    /// Receiver for whether block processing has halted
+    pub fn subscribe_halted(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.halted.subscribe()
+    }
+
+    /// Halt block processing. Operator intervention is required to resume.
+    fn halt(&self, err: &task::error::ReorgTooDeep) {
+        tracing::error!(
+            "CRITICAL: {err}, halting block processing. \
+             Operator intervention is required"
+        );
+        self.halted.send_replace(true);
+    }
+
     pub fn subscribe_events(
         &self,
     ) -> impl FusedStream<Item = Result<Event, EventsStreamError>> + use<> {
@@ -458,6 +777,136 @@ impl Validator {
         self.header_sync_progress_rx.read().clone()
     }
 
+    /// Returns `None` if there is not a block sync in progress
+    pub fn block_sync_progress(&self) -> Option<BlockSyncProgress> {
+        self.block_sync_progress_rx
+            .read()
+            .as_ref()
+            .map(|rx| *rx.borrow())
+    }
+
+    /// Get the status of the block sync, relative to the mainchain tip.
+    pub async fn get_sync_status(&self) -> Result<SyncStatus, GetSyncStatusError> {
+        use bitcoin_jsonrpsee::client::MainClient as _;
+        let processed_height = self.try_get_block_height()?;
+        let progress = self.block_sync_progress();
+        let target_height = match progress {
+            Some(progress) => progress.target_height,
+            None => {
+                self.mainchain_client
+                    .get_blockchain_info()
+                    .await
+                    .map_err(|err| GetSyncStatusError::JsonRpc {
+                        method: "getblockchaininfo".to_owned(),
+                        source: err,
+                    })?
+                    .blocks
+            }
+        };
+        Ok(SyncStatus::new(
+            processed_height,
+            target_height,
+            progress.as_ref(),
+        ))
+    }
+
+    /// Get a deposit transaction and a Merkle proof of its inclusion in a
+    /// block. The transaction must be an M5 deposit in a block in the
+    /// enforcer's processed chain. Unless Bitcoin Core has `-txindex` enabled, `block_hash` must
+    /// be the hash of the block containing the deposit.
+    pub async fn get_deposit_proof(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> Result<DepositProof, GetDepositProofError> {
+        use jsonrpsee::{core::client::ClientT as _, rpc_params};
+        let params = match block_hash {
+            Some(block_hash) => rpc_params![txid, true, block_hash],
+            None => rpc_params![txid, true],
+        };
+        let tx_info: RawTransactionInfo = self
+            .mainchain_client
+            .request("getrawtransaction", params)
+            .await
+            .map_err(|err| GetDepositProofError::JsonRpc {
+                method: "getrawtransaction".to_owned(),
+                source: err,
+            })?;
+        let transaction: bitcoin::Transaction =
+            bitcoin::consensus::encode::deserialize_hex(&tx_info.hex).map_err(|err| {
+                GetDepositProofError::Decode {
+                    method: "getrawtransaction".to_owned(),
+                    source: err,
+                }
+            })?;
+        let block_hash = tx_info
+            .blockhash
+            .ok_or(GetDepositProofError::Unconfirmed { txid })?;
+        {
+            let rotxn = self.dbs.read_txn()?;
+            if !self.is_in_processed_chain(&rotxn, block_hash)? {
+                return Err(GetDepositProofError::NotProcessed { txid, block_hash });
+            }
+            // Block processing stores the treasury output of every valid
+            // M5/M6 as a Ctip
+            let treasury_outpoint = OutPoint { txid, vout: 0 };
+            if self
+                .dbs
+                .active_sidechains
+                .ctip_outpoint_to_value_seq()
+                .try_get(&rotxn, &treasury_outpoint)?
+                .is_none()
+            {
+                return Err(GetDepositProofError::NotADeposit { txid });
+            }
+        }
+        let sidechain_number = match self.classify_treasury_spend(transaction.clone())? {
+            TransactionKind::Deposit {
+                sidechain_number, ..
+            } => sidechain_number,
+            TransactionKind::Coinbase
+            | TransactionKind::WithdrawalBundle { .. }
+            | TransactionKind::Other => {
+                return Err(GetDepositProofError::NotADeposit { txid });
+            }
+        };
+        let proof_hex: String = self
+            .mainchain_client
+            .request("gettxoutproof", rpc_params![[txid], block_hash])
+            .await
+            .map_err(|err| GetDepositProofError::JsonRpc {
+                method: "gettxoutproof".to_owned(),
+                source: err,
+            })?;
+        let merkle_block: bitcoin::MerkleBlock =
+            bitcoin::consensus::encode::deserialize_hex(&proof_hex).map_err(|err| {
+                GetDepositProofError::Decode {
+                    method: "gettxoutproof".to_owned(),
+                    source: err,
+                }
+            })?;
+        // Check that the proof commits to the transaction, against the
+        // Merkle root in the block header
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        merkle_block
+            .extract_matches(&mut matches, &mut indexes)
+            .map_err(|err| GetDepositProofError::InvalidProof {
+                txid,
+                source: Some(err),
+            })?;
+        if merkle_block.header.block_hash() != block_hash || !matches.contains(&txid) {
+            return Err(GetDepositProofError::InvalidProof { txid, source: None });
+        }
+        Ok(DepositProof {
+            txid,
+            sidechain_number,
+            block_hash,
+            transaction: bitcoin::consensus::serialize(&transaction),
+            proof: bitcoin::consensus::serialize(&merkle_block),
+        })
+    }
+
     /// Get (possibly unactivated) sidechains
     pub fn get_sidechains(
         &self,
@@ -490,6 +939,21 @@ impl Validator {
         Ok(res)
     }
 
+    /// Returns `Some` with the sidechain in the specified slot, if the slot
+    /// is active. `None` if the slot is inactive.
+    pub fn try_get_active_sidechain(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Result<Option<Sidechain>, TryGetActiveSidechainError> {
+        let rotxn = self.dbs.read_txn()?;
+        let sidechain = self
+            .dbs
+            .active_sidechains
+            .sidechain()
+            .try_get(&rotxn, &sidechain_number)?;
+        Ok(sidechain)
+    }
+
     pub fn get_ctip_sequence_number(
         &self,
         sidechain_number: SidechainNumber,
@@ -691,6 +1155,66 @@ impl Validator {
         Ok(res)
     }
 
+    /// Check a transaction against the enforcer rules, without applying any
+    /// of its effects.
+    /// Returns the reason that the transaction was rejected, if it is invalid.
+    pub fn check_tx(&self, tx: &bitcoin::Transaction) -> Result<Option<String>, CheckTxError> {
+        let mut rwtxn = self.dbs.write_txn()?;
+        let res = task::check_tx(&self.dbs, &mut rwtxn, tx)?;
+        rwtxn.abort();
+        Ok(res)
+    }
+
+    /// Returns `true` if the block is the enforcer tip, or one of its
+    /// ancestors
+    fn is_in_processed_chain(
+        &self,
+        rotxn: &RoTxn,
+        block_hash: BlockHash,
+    ) -> Result<bool, db::error::TryGet> {
+        let Some(tip) = self.dbs.current_chain_tip.try_get(rotxn, &())? else {
+            return Ok(false);
+        };
+        let Some(height) = self.dbs.block_hashes.height().try_get(rotxn, &block_hash)? else {
+            return Ok(false);
+        };
+        let Some(tip_height) = self.dbs.block_hashes.height().try_get(rotxn, &tip)? else {
+            return Ok(false);
+        };
+        if height > tip_height {
+            return Ok(false);
+        }
+        let ancestor = self
+            .dbs
+            .block_hashes
+            .ancestor_headers(rotxn, tip)
+            .nth((tip_height - height) as usize)?;
+        Ok(ancestor.is_some_and(|(ancestor, _)| ancestor == block_hash))
+    }
+
+    /// Get the block at the specified height in the chain ending at the
+    /// enforcer tip. Returns `None` if the height is above the tip, or if not
+    /// synced.
+    pub fn try_get_block_hash_at_height(
+        &self,
+        height: u32,
+    ) -> Result<Option<BlockHash>, TryGetBlockHashAtHeightError> {
+        let rotxn = self.dbs.read_txn()?;
+        let Some(tip) = self.dbs.current_chain_tip.try_get(&rotxn, &())? else {
+            return Ok(None);
+        };
+        let tip_height = self.dbs.block_hashes.height().get(&rotxn, &tip)?;
+        let Some(depth) = tip_height.checked_sub(height) else {
+            return Ok(None);
+        };
+        let ancestor = self
+            .dbs
+            .block_hashes
+            .ancestor_headers(&rotxn, tip)
+            .nth(depth as usize)?;
+        Ok(ancestor.map(|(block_hash, _)| block_hash))
+    }
+
     /// Get the mainchain tip. Returns `None` if not synced
     pub fn try_get_mainchain_tip(&self) -> Result<Option<BlockHash>, TryGetMainchainTipError> {
         let rotxn = self.dbs.read_txn()?;
@@ -0,0 +1,379 @@
+//! Portable snapshots of the validator database, for backups and for
+//! provisioning new enforcer instances without syncing from genesis.
+//!
+//! A snapshot file consists of:
+//! * An 8-byte magic value
+//! * The snapshot format version, as a little-endian `u32`
+//! * The bincode-encoded network, tip, and tip height
+//! * A compacted copy of the LMDB data file
+
+use std::{
+    fs::File,
+    io::{Read, Seek as _, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use bitcoin::BlockHash;
+use miette::Diagnostic;
+use serde::Serialize;
+use sneed::{db, env};
+use thiserror::Error;
+
+use crate::validator::{Validator, dbs::Dbs};
+
+const MAGIC: [u8; 8] = *b"ENFSNAP\0";
+
+/// Current snapshot format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SnapshotHeader {
+    pub version: u32,
+    pub network: bitcoin::Network,
+    /// Tip that the validator was synced to. `None` if no blocks had been
+    /// processed.
+    pub tip: Option<BlockHash>,
+    pub tip_height: Option<u32>,
+}
+
+impl SnapshotHeader {
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), bincode::Error> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        bincode::serialize_into(writer, &(self.network, self.tip, self.tip_height))
+    }
+
+    fn read<R: Read>(reader: &mut R) -> Result<Self, ImportSnapshotError> {
+        let mut magic = [0; MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| ImportSnapshotError::InvalidMagic)?;
+        if magic != MAGIC {
+            return Err(ImportSnapshotError::InvalidMagic);
+        }
+        let mut version = [0; 4];
+        reader
+            .read_exact(&mut version)
+            .map_err(|_| ImportSnapshotError::InvalidMagic)?;
+        let version = u32::from_le_bytes(version);
+        if version != SNAPSHOT_VERSION {
+            return Err(ImportSnapshotError::UnsupportedVersion { version });
+        }
+        let (network, tip, tip_height) = bincode::deserialize_from(reader)?;
+        Ok(Self {
+            version,
+            network,
+            tip,
+            tip_height,
+        })
+    }
+}
+
+/// Number of times to retry copying the database, if the tip changes while
+/// it is copied
+const MAX_COPY_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ExportSnapshotError {
+    #[error("failed to copy validator database to `{}`", .path.display())]
+    CopyEnv { path: PathBuf, source: heed::Error },
+    #[error("failed to write snapshot header")]
+    Header(#[from] bincode::Error),
+    #[error("I/O error exporting snapshot (`{}`)", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    ReadTxn(#[from] env::error::ReadTxn),
+    #[error(
+        "validator tip changed during each of {MAX_COPY_ATTEMPTS} attempts to copy the database"
+    )]
+    TipChanged,
+    #[error(transparent)]
+    TryGet(#[from] db::error::TryGet),
+}
+
+/// Current tip and tip height
+fn read_tip(dbs: &Dbs) -> Result<(Option<BlockHash>, Option<u32>), ExportSnapshotError> {
+    let rotxn = dbs.read_txn()?;
+    let tip = dbs.current_chain_tip.try_get(&rotxn, &())?;
+    let tip_height = match tip {
+        Some(tip) => dbs.block_hashes.height().try_get(&rotxn, &tip)?,
+        None => None,
+    };
+    Ok((tip, tip_height))
+}
+
+/// Database copy created by [`write_snapshot`], removed when dropped
+struct DbCopy {
+    path: PathBuf,
+    file: File,
+}
+
+impl DbCopy {
+    /// Copy `dbs` to `path`, which must not already exist
+    fn create(dbs: &Dbs, path: &Path) -> Result<Self, ExportSnapshotError> {
+        match dbs.copy_to_file(path) {
+            Ok(file) => Ok(Self {
+                path: path.to_owned(),
+                file,
+            }),
+            Err(source) => {
+                // The file is created with `create_new`. Unless it already
+                // existed, a partial copy was created by this export.
+                let created = !matches!(
+                    &source,
+                    heed::Error::Io(err) if err.kind() == std::io::ErrorKind::AlreadyExists
+                );
+                if created && let Err(err) = std::fs::remove_file(path) {
+                    tracing::warn!(
+                        path = %path.display(),
+                        "failed to remove partial database copy: {err:#}"
+                    );
+                }
+                Err(ExportSnapshotError::CopyEnv {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        }
+    }
+}
+
+impl Drop for DbCopy {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            tracing::warn!(
+                path = %self.path.display(),
+                "failed to remove database copy: {err:#}"
+            );
+        }
+    }
+}
+
+/// Write a snapshot of `dbs` to `path`, which must not already exist.
+/// Block processing is not blocked while the snapshot is written.
+fn write_snapshot(
+    dbs: &Dbs,
+    network: bitcoin::Network,
+    path: &Path,
+) -> Result<(SnapshotHeader, u64), ExportSnapshotError> {
+    let io_err = |path: &Path| {
+        let path = path.to_owned();
+        move |source| ExportSnapshotError::Io { path, source }
+    };
+    let copy_path = path.with_extension("mdb.tmp");
+    let mut attempt = 0;
+    // The tip is read before and after the copy. LMDB commits are atomic,
+    // so if the tip is unchanged, the copy is consistent with it.
+    let ((tip, tip_height), mut copy) = loop {
+        if attempt == MAX_COPY_ATTEMPTS {
+            return Err(ExportSnapshotError::TipChanged);
+        }
+        attempt += 1;
+        let tip_before = read_tip(dbs)?;
+        let copy = DbCopy::create(dbs, &copy_path)?;
+        if read_tip(dbs)? == tip_before {
+            break (tip_before, copy);
+        }
+        drop(copy);
+        tracing::debug!(
+            attempt,
+            "validator tip changed while copying database, retrying"
+        );
+    };
+    let header = SnapshotHeader {
+        version: SNAPSHOT_VERSION,
+        network,
+        tip,
+        tip_height,
+    };
+    let mut snapshot_file = File::create_new(path).map_err(io_err(path))?;
+    let res = (|| {
+        let () = header.write(&mut snapshot_file)?;
+        let () = copy
+            .file
+            .seek(SeekFrom::Start(0))
+            .map(|_| ())
+            .map_err(io_err(&copy_path))?;
+        let bytes = std::io::copy(&mut copy.file, &mut snapshot_file).map_err(io_err(path))?;
+        let () = snapshot_file.sync_all().map_err(io_err(path))?;
+        Ok(bytes)
+    })();
+    match res {
+        Ok(bytes) => Ok((header, bytes)),
+        Err(err) => {
+            // Do not leave a partial snapshot behind
+            drop(snapshot_file);
+            if let Err(err) = std::fs::remove_file(path) {
+                tracing::warn!(
+                    path = %path.display(),
+                    "failed to remove partial snapshot: {err:#}"
+                );
+            }
+            Err(err)
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ImportSnapshotError {
+    #[error(
+        "validator database already exists at `{}`. Snapshots can only be imported into an empty data directory",
+        .path.display()
+    )]
+    ExistingDatabase { path: PathBuf },
+    #[error("failed to read snapshot header")]
+    Header(#[from] bincode::Error),
+    #[error("not a validator snapshot")]
+    InvalidMagic,
+    #[error("I/O error importing snapshot (`{}`)", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("snapshot is for network `{snapshot}`, expected `{expected}`")]
+    NetworkMismatch {
+        expected: bitcoin::Network,
+        snapshot: bitcoin::Network,
+    },
+    #[error("unsupported snapshot version {version} (supported: {SNAPSHOT_VERSION})")]
+    UnsupportedVersion { version: u32 },
+}
+
+impl Validator {
+    /// Write a snapshot of the validator database to `path`, which must not
+    /// already exist. The database is copied within a read txn, so block
+    /// processing continues while the snapshot is written.
+    pub fn export_snapshot(&self, path: &Path) -> Result<SnapshotHeader, ExportSnapshotError> {
+        let (header, bytes) = write_snapshot(&self.dbs, self.network, path)?;
+        tracing::info!(
+            path = %path.display(),
+            bytes,
+            tip = ?header.tip,
+            tip_height = ?header.tip_height,
+            "Exported validator snapshot"
+        );
+        Ok(header)
+    }
+
+    /// Restore the validator database from a snapshot. Must be called
+    /// before creating the validator, and fails if a validator database
+    /// already exists in `data_dir`.
+    pub fn import_snapshot(
+        snapshot_path: &Path,
+        data_dir: &Path,
+        network: bitcoin::Network,
+    ) -> Result<SnapshotHeader, ImportSnapshotError> {
+        let db_dir = Dbs::db_dir(data_dir, network);
+        let data_file_path = Dbs::data_file_path(&db_dir);
+        if data_file_path.exists() {
+            return Err(ImportSnapshotError::ExistingDatabase { path: db_dir });
+        }
+        let snapshot_io_err = |source| ImportSnapshotError::Io {
+            path: snapshot_path.to_owned(),
+            source,
+        };
+        let mut snapshot_file = File::open(snapshot_path).map_err(snapshot_io_err)?;
+        let header = SnapshotHeader::read(&mut snapshot_file)?;
+        if header.network != network {
+            return Err(ImportSnapshotError::NetworkMismatch {
+                expected: network,
+                snapshot: header.network,
+            });
+        }
+        let db_io_err = |source| ImportSnapshotError::Io {
+            path: db_dir.clone(),
+            source,
+        };
+        std::fs::create_dir_all(&db_dir).map_err(db_io_err)?;
+        // Write to a temporary file first, so that an interrupted import
+        // does not leave a partial database behind
+        let tmp_path = db_dir.join("data.mdb.tmp");
+        let mut tmp_file = File::create(&tmp_path).map_err(db_io_err)?;
+        std::io::copy(&mut snapshot_file, &mut tmp_file).map_err(snapshot_io_err)?;
+        let () = tmp_file.sync_all().map_err(db_io_err)?;
+        std::fs::rename(&tmp_path, &data_file_path).map_err(db_io_err)?;
+        tracing::info!(
+            path = %snapshot_path.display(),
+            tip = ?header.tip,
+            tip_height = ?header.tip_height,
+            "Imported validator snapshot"
+        );
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash as _;
+
+    use super::{ExportSnapshotError, SNAPSHOT_VERSION, write_snapshot};
+    use crate::validator::{Validator, dbs::Dbs};
+
+    /// A snapshot restores the exported tip into a fresh data directory
+    #[test]
+    fn test_snapshot_round_trip() {
+        let network = bitcoin::Network::Regtest;
+        let test_dir = std::env::temp_dir().join(format!(
+            "bip300301_enforcer_test_snapshot_{}",
+            std::process::id()
+        ));
+        if test_dir.exists() {
+            std::fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let source_dir = test_dir.join("source");
+        let target_dir = test_dir.join("target");
+        let snapshot_path = test_dir.join("validator.snapshot");
+
+        let tip = bitcoin::BlockHash::from_byte_array([1; 32]);
+        let dbs = Dbs::new(&source_dir, network).unwrap();
+        let mut rwtxn = dbs.write_txn().unwrap();
+        dbs.current_chain_tip.put(&mut rwtxn, &(), &tip).unwrap();
+        rwtxn.commit().unwrap();
+        let (header, _bytes) = write_snapshot(&dbs, network, &snapshot_path).unwrap();
+        assert_eq!(header.version, SNAPSHOT_VERSION);
+        assert_eq!(header.tip, Some(tip));
+
+        let imported_header =
+            Validator::import_snapshot(&snapshot_path, &target_dir, network).unwrap();
+        assert_eq!(imported_header.tip, Some(tip));
+        let dbs = Dbs::new(&target_dir, network).unwrap();
+        let rotxn = dbs.read_txn().unwrap();
+        assert_eq!(
+            dbs.current_chain_tip.try_get(&rotxn, &()).unwrap(),
+            Some(tip)
+        );
+        drop(rotxn);
+
+        // Importing over an existing database is rejected
+        assert!(Validator::import_snapshot(&snapshot_path, &target_dir, network).is_err());
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    /// An existing file at the temporary copy path is not removed or
+    /// overwritten
+    #[test]
+    fn test_snapshot_existing_copy_path() {
+        let network = bitcoin::Network::Regtest;
+        let test_dir = std::env::temp_dir().join(format!(
+            "bip300301_enforcer_test_snapshot_copy_path_{}",
+            std::process::id()
+        ));
+        if test_dir.exists() {
+            std::fs::remove_dir_all(&test_dir).unwrap();
+        }
+        let dbs = Dbs::new(&test_dir.join("source"), network).unwrap();
+        let snapshot_path = test_dir.join("validator.snapshot");
+        let copy_path = snapshot_path.with_extension("mdb.tmp");
+        std::fs::write(&copy_path, b"existing").unwrap();
+        assert!(matches!(
+            write_snapshot(&dbs, network, &snapshot_path),
+            Err(ExportSnapshotError::CopyEnv { .. })
+        ));
+        assert_eq!(std::fs::read(&copy_path).unwrap(), b"existing");
+        assert!(!snapshot_path.exists());
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
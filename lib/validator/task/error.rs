@@ -1,3 +1,5 @@
+use std::num::NonZeroU32;
+
 use bitcoin::hashes::sha256d;
 use bitcoin_jsonrpsee::jsonrpsee;
 use fatality::fatality;
@@ -263,6 +265,13 @@ impl fatality::Fatality for DisconnectBlock {
     }
 }
 
+#[derive(Debug, Error)]
+#[error("Reorg depth ({depth}) exceeds the maximum reorg depth ({max_reorg_depth})")]
+pub(in crate::validator) struct ReorgTooDeep {
+    pub depth: u32,
+    pub max_reorg_depth: NonZeroU32,
+}
+
 #[derive(Transitive)]
 #[fatality(splitable)]
 #[transitive(
@@ -294,6 +303,9 @@ pub(in crate::validator) enum Sync {
     #[error(transparent)]
     #[fatal]
     GetHeaderInfo(#[from] dbs::block_hash_dbs_error::GetHeaderInfo),
+    #[error("Block processing is halted, operator intervention is required")]
+    #[fatal]
+    Halted,
     #[error("Header sync already in progress")]
     HeaderSyncInProgress,
     #[error("JSON RPC error (`{method}`)")]
@@ -307,6 +319,9 @@ pub(in crate::validator) enum Sync {
     LastCommonAncestor(#[from] dbs::block_hash_dbs_error::LastCommonAncestor),
     #[error(transparent)]
     #[fatal]
+    ReorgTooDeep(#[from] ReorgTooDeep),
+    #[error(transparent)]
+    #[fatal]
     Rest(#[from] MainRestClientError),
     #[error("Shutdown signal received")]
     #[fatal]
@@ -1,4 +1,11 @@
-use std::{borrow::Cow, cmp::Ordering, collections::HashMap, future::Future, time::Instant};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::HashMap,
+    future::Future,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
 
 use async_broadcast::{Sender, TrySendError};
 use bitcoin::{
@@ -12,9 +19,11 @@ use fatality::Split as _;
 use futures::{FutureExt as _, TryFutureExt as _};
 use hashlink::LinkedHashSet;
 use sneed::{RwTxn, db};
+use tokio::sync::watch::Receiver as WatchReceiver;
 
 use super::main_rest_client::MainRestClient;
 use crate::{
+    errors::ErrorChain,
     messages::{
         CoinbaseMessage, CoinbaseMessages, M1ProposeSidechain, M2AckSidechain, M3ProposeBundle,
         M4AckBundles, M7BmmAccept, compute_m6id, parse_m8_tx, parse_op_drivechain,
@@ -26,7 +35,7 @@ use crate::{
         WITHDRAWAL_BUNDLE_INCLUSION_THRESHOLD, WITHDRAWAL_BUNDLE_MAX_AGE, WithdrawalBundleEvent,
         WithdrawalBundleEventKind,
     },
-    validator::dbs::Dbs,
+    validator::{BlockSyncProgress, dbs::Dbs},
 };
 
 pub mod error;
@@ -565,15 +574,25 @@ pub fn validate_tx(
     parent_rwtxn: &mut RwTxn,
     transaction: &Transaction,
 ) -> Result<bool, error::ValidateTransaction> {
+    check_tx(dbs, parent_rwtxn, transaction).map(|reject_reason| reject_reason.is_none())
+}
+
+/// Check a transaction against the current chain tip.
+/// Returns the reason that the transaction was rejected, if it is invalid.
+pub fn check_tx(
+    dbs: &Dbs,
+    parent_rwtxn: &mut RwTxn,
+    transaction: &Transaction,
+) -> Result<Option<String>, error::ValidateTransaction> {
     let mut child_rwtxn = dbs.nested_write_txn(parent_rwtxn)?;
     let tip_hash = dbs
         .current_chain_tip
         .try_get(&child_rwtxn, &())?
         .ok_or(error::ValidateTransactionInner::NoChainTip)?;
     match handle_transaction(&mut child_rwtxn, dbs, None, &tip_hash, transaction) {
-        Ok(_) => Ok(true),
+        Ok(_) => Ok(None),
         Err(err) => match err.split() {
-            Ok(_jfyi) => Ok(false),
+            Ok(jfyi) => Ok(Some(ErrorChain::new(&jfyi).to_string())),
             Err(err) => Err(err.into()),
         },
     }
@@ -983,6 +1002,8 @@ async fn sync_blocks<MainRpcClient, Signal>(
     event_tx: &Sender<Event>,
     main_rpc_client: &MainRpcClient,
     main_tip: BlockHash,
+    block_sync_progress_rx: &parking_lot::RwLock<Option<WatchReceiver<BlockSyncProgress>>>,
+    max_reorg_depth: Option<NonZeroU32>,
     shutdown_signal: Signal,
 ) -> Result<(), error::Sync>
 where
@@ -1001,6 +1022,23 @@ where
                 dbs.block_hashes
                     .last_common_ancestor(&rwtxn, current_enforcer_tip, main_tip)?;
             if current_enforcer_tip != last_common_ancestor {
+                if let Some(max_reorg_depth) = max_reorg_depth {
+                    let block_heights = dbs.block_hashes.height();
+                    let tip_height = block_heights.get(&rwtxn, &current_enforcer_tip)?;
+                    let depth = if last_common_ancestor == BlockHash::all_zeros() {
+                        tip_height + 1
+                    } else {
+                        tip_height - block_heights.get(&rwtxn, &last_common_ancestor)?
+                    };
+                    if depth > max_reorg_depth.get() {
+                        rwtxn.abort();
+                        return Err(error::ReorgTooDeep {
+                            depth,
+                            max_reorg_depth,
+                        }
+                        .into());
+                    }
+                }
                 tracing::info!(
                     "Disconnecting tip {current_enforcer_tip} -> {last_common_ancestor}"
                 );
@@ -1039,8 +1077,57 @@ where
         start.elapsed()
     );
 
+    let (start_height, target_height) = {
+        let rotxn = dbs.read_txn()?;
+        let block_heights = dbs.block_hashes.height();
+        let target_height = block_heights.get(&rotxn, &main_tip)?;
+        // Missing blocks are ordered from the tip backwards
+        let first_missing_block = missing_blocks[missing_blocks.len() - 1];
+        let first_missing_height = block_heights.get(&rotxn, &first_missing_block)?;
+        (first_missing_height.checked_sub(1), target_height)
+    };
+    let (progress_tx, progress_rx) = tokio::sync::watch::channel(BlockSyncProgress {
+        start_height,
+        started_at: Instant::now(),
+        current_height: start_height,
+        target_height,
+    });
+    *block_sync_progress_rx.write() = Some(progress_rx);
+    let res = connect_missing_blocks(
+        dbs,
+        event_tx,
+        main_rpc_client,
+        main_tip,
+        missing_blocks,
+        &progress_tx,
+        shutdown_signal,
+    )
+    .await;
+    *block_sync_progress_rx.write() = None;
+    res
+}
+
+/// Fetch and connect missing blocks, in ascending order of height.
+/// `missing_blocks` MUST be ordered from the tip backwards.
+async fn connect_missing_blocks<MainRpcClient, Signal>(
+    dbs: &Dbs,
+    event_tx: &Sender<Event>,
+    main_rpc_client: &MainRpcClient,
+    main_tip: BlockHash,
+    missing_blocks: Vec<BlockHash>,
+    progress_tx: &tokio::sync::watch::Sender<BlockSyncProgress>,
+    shutdown_signal: Signal,
+) -> Result<(), error::Sync>
+where
+    MainRpcClient: bitcoin_jsonrpsee::client::MainClient + Sync,
+    Signal: Future<Output = ()> + Send,
+{
+    const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+    let start = Instant::now();
     let shutdown_signal = shutdown_signal.shared();
     let mut total_blocks_fetched = 0;
+    let mut last_progress_log = Instant::now();
     for missing_block in missing_blocks.into_iter().rev() {
         tokio::select! {
             biased;
@@ -1077,6 +1164,21 @@ where
         // Events should only ever be sent after committing DB txs, see
         // https://github.com/LayerTwo-Labs/bip300301_enforcer/pull/185
         let _send_err: Result<Option<_>, TrySendError<_>> = event_tx.try_broadcast(event);
+
+        progress_tx.send_modify(|progress| progress.current_height = Some(height));
+        if last_progress_log.elapsed() >= PROGRESS_LOG_INTERVAL {
+            last_progress_log = Instant::now();
+            let progress = *progress_tx.borrow();
+            let blocks_per_sec = progress.blocks_per_sec().unwrap_or_default();
+            let eta = progress
+                .eta()
+                .map_or_else(|| "unknown".to_owned(), |eta| eta.to_string());
+            tracing::info!(
+                "Block sync progress: #{height}/{} ({:.2}%), {blocks_per_sec:.2} blocks/s, ETA {eta}",
+                progress.target_height,
+                (height as f64 + 1.) / (progress.target_height as f64 + 1.) * 100.,
+            );
+        }
     }
     tracing::info!(
         "Synced {total_blocks_fetched} blocks in {:?}",
@@ -1089,6 +1191,8 @@ pub(in crate::validator) async fn sync_to_tip<MainClient, Signal>(
     dbs: &Dbs,
     event_tx: &Sender<Event>,
     header_sync_progress_tx: &tokio::sync::watch::Sender<HeaderSyncProgress>,
+    block_sync_progress_rx: &parking_lot::RwLock<Option<WatchReceiver<BlockSyncProgress>>>,
+    max_reorg_depth: Option<NonZeroU32>,
     main_rpc_client: &MainClient,
     main_rest_client: &MainRestClient,
     main_tip: BlockHash,
@@ -1115,6 +1219,8 @@ where
         event_tx,
         main_rpc_client,
         main_tip,
+        block_sync_progress_rx,
+        max_reorg_depth,
         shutdown_signal.clone(),
     )
     .await?;
@@ -0,0 +1,179 @@
+//! Classify mainchain transactions as coinbase, deposit (M5), withdrawal
+//! bundle (M6), or other, using the same rules as block processing
+
+use bitcoin::{Amount, BlockHash, Transaction, Txid};
+use bitcoin_jsonrpsee::jsonrpsee;
+use miette::Diagnostic;
+use serde::Serialize;
+use sneed::{db, env};
+use thiserror::Error;
+
+use crate::{
+    messages::{compute_m6id, parse_op_drivechain, try_parse_op_return_address},
+    proto::{StatusBuilder, ToStatus},
+    types::{M6id, SidechainNumber},
+    validator::{RawTransactionInfo, Validator},
+};
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum IdentifyTransactionError {
+    #[error("failed to decode `{method}` response")]
+    Decode {
+        method: String,
+        source: bitcoin::consensus::encode::FromHexError,
+    },
+    #[error("JSON RPC error (`{method}`)")]
+    JsonRpc {
+        method: String,
+        source: jsonrpsee::core::ClientError,
+    },
+    #[error(transparent)]
+    ReadTxn(#[from] env::error::ReadTxn),
+    #[error(transparent)]
+    TryGet(#[from] db::error::TryGet),
+}
+
+impl ToStatus for IdentifyTransactionError {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Decode { .. } | Self::JsonRpc { .. } => StatusBuilder::new(self),
+            Self::ReadTxn(err) => StatusBuilder::new(err),
+            Self::TryGet(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum TransactionKind {
+    Coinbase,
+    /// M5 deposit to a sidechain
+    Deposit {
+        sidechain_number: SidechainNumber,
+        /// Sidechain address, from the deposit's OP_RETURN output
+        #[serde(with = "hex::serde")]
+        address: Vec<u8>,
+        value: Amount,
+    },
+    /// M6 withdrawal bundle payout
+    WithdrawalBundle {
+        sidechain_number: SidechainNumber,
+        m6id: M6id,
+        /// `true` if the M6ID is a pending withdrawal bundle. Withdrawal
+        /// bundles are no longer pending once they are paid out.
+        pending: bool,
+    },
+    Other,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct IdentifiedTransaction {
+    pub txid: Txid,
+    /// `None` if the transaction is unconfirmed
+    pub block_hash: Option<BlockHash>,
+    #[serde(flatten)]
+    pub kind: TransactionKind,
+}
+
+impl Validator {
+    /// Classify a transaction as an M5 deposit or M6 withdrawal bundle, by
+    /// its treasury output and the value of the treasury UTXO that it spends
+    pub(super) fn classify_treasury_spend(
+        &self,
+        transaction: Transaction,
+    ) -> Result<TransactionKind, IdentifyTransactionError> {
+        let Some(treasury_output) = transaction.output.first() else {
+            return Ok(TransactionKind::Other);
+        };
+        let Ok((_, sidechain_number)) =
+            parse_op_drivechain(treasury_output.script_pubkey.as_bytes())
+        else {
+            return Ok(TransactionKind::Other);
+        };
+        let new_treasury_value = treasury_output.value;
+        let rotxn = self.dbs.read_txn()?;
+        let mut old_treasury_value = Amount::ZERO;
+        for input in &transaction.input {
+            if let Some((ctip_sidechain_number, value, _)) = self
+                .dbs
+                .active_sidechains
+                .ctip_outpoint_to_value_seq()
+                .try_get(&rotxn, &input.previous_output)?
+                && ctip_sidechain_number == sidechain_number
+            {
+                old_treasury_value = value;
+                break;
+            }
+        }
+        if new_treasury_value > old_treasury_value {
+            let Some(address) = transaction
+                .output
+                .get(1)
+                .and_then(|output| try_parse_op_return_address(&output.script_pubkey))
+            else {
+                return Ok(TransactionKind::Other);
+            };
+            return Ok(TransactionKind::Deposit {
+                sidechain_number,
+                address,
+                value: new_treasury_value - old_treasury_value,
+            });
+        }
+        if new_treasury_value == old_treasury_value || transaction.input.len() != 1 {
+            return Ok(TransactionKind::Other);
+        }
+        let Ok((m6id, _)) = compute_m6id(transaction, old_treasury_value) else {
+            return Ok(TransactionKind::Other);
+        };
+        let pending = self
+            .dbs
+            .active_sidechains
+            .pending_m6ids()
+            .try_get(&rotxn, &sidechain_number)?
+            .is_some_and(|pending_m6ids| pending_m6ids.contains_key(&m6id));
+        Ok(TransactionKind::WithdrawalBundle {
+            sidechain_number,
+            m6id,
+            pending,
+        })
+    }
+
+    /// Classify a transaction as a coinbase, M5 deposit, M6 withdrawal
+    /// bundle, or other. Unless Bitcoin Core has `-txindex` enabled,
+    /// `block_hash` must be the hash of the block containing the
+    /// transaction, if it is confirmed.
+    pub async fn identify_transaction(
+        &self,
+        txid: Txid,
+        block_hash: Option<BlockHash>,
+    ) -> Result<IdentifiedTransaction, IdentifyTransactionError> {
+        use jsonrpsee::{core::client::ClientT as _, rpc_params};
+        let params = match block_hash {
+            Some(block_hash) => rpc_params![txid, true, block_hash],
+            None => rpc_params![txid, true],
+        };
+        let tx_info: RawTransactionInfo = self
+            .mainchain_client
+            .request("getrawtransaction", params)
+            .await
+            .map_err(|err| IdentifyTransactionError::JsonRpc {
+                method: "getrawtransaction".to_owned(),
+                source: err,
+            })?;
+        let transaction: Transaction = bitcoin::consensus::encode::deserialize_hex(&tx_info.hex)
+            .map_err(|err| IdentifyTransactionError::Decode {
+                method: "getrawtransaction".to_owned(),
+                source: err,
+            })?;
+        let kind = if transaction.is_coinbase() {
+            TransactionKind::Coinbase
+        } else {
+            self.classify_treasury_spend(transaction)?
+        };
+        Ok(IdentifiedTransaction {
+            txid,
+            block_hash: tx_info.blockhash,
+            kind,
+        })
+    }
+}
@@ -0,0 +1,334 @@
+//! HTTP webhooks for deposit and withdrawal bundle events, and disconnected
+//! blocks
+
+use std::{future::Future, time::Duration};
+
+use bitcoin::{Amount, BlockHash, Txid};
+use futures::StreamExt as _;
+use reqwest::{Client, Url};
+use serde::Serialize;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{
+    backoff,
+    errors::ErrorChain,
+    types::{
+        BlockEvent, BlockInfo, Deposit, Event, HeaderInfo, M6id, SidechainNumber,
+        WithdrawalBundleEvent, WithdrawalBundleEventKind, WithdrawalBundleStatus,
+    },
+    validator::{EventsStreamError, Validator},
+};
+
+/// Maximum number of webhooks waiting to be delivered. Further webhooks are
+/// dropped until the queue drains.
+const QUEUE_CAPACITY: usize = 1024;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct WebhookBlockContext {
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub timestamp: u32,
+}
+
+impl From<&HeaderInfo> for WebhookBlockContext {
+    fn from(header_info: &HeaderInfo) -> Self {
+        Self {
+            block_hash: header_info.block_hash,
+            height: header_info.height,
+            timestamp: header_info.timestamp,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "event_type")]
+pub enum WebhookEvent {
+    Deposit {
+        sidechain_number: SidechainNumber,
+        sequence_number: u64,
+        txid: Txid,
+        vout: u32,
+        /// Hex-encoded sidechain address
+        address: String,
+        value_sats: u64,
+    },
+    WithdrawalBundle {
+        sidechain_number: SidechainNumber,
+        m6id: M6id,
+        status: WithdrawalBundleStatus,
+        /// Only set if the bundle succeeded
+        sequence_number: Option<u64>,
+        /// M6 txid. Only set if the bundle succeeded.
+        txid: Option<Txid>,
+        /// Total value paid out by the M6, excluding the treasury output.
+        /// Only set if the bundle succeeded.
+        payout_sats: Option<u64>,
+    },
+    /// Events in the block were reverted
+    BlockDisconnected,
+}
+
+impl From<&Deposit> for WebhookEvent {
+    fn from(deposit: &Deposit) -> Self {
+        Self::Deposit {
+            sidechain_number: deposit.sidechain_id,
+            sequence_number: deposit.sequence_number,
+            txid: deposit.outpoint.txid,
+            vout: deposit.outpoint.vout,
+            address: hex::encode(&deposit.address),
+            value_sats: deposit.value.to_sat(),
+        }
+    }
+}
+
+impl From<&WithdrawalBundleEvent> for WebhookEvent {
+    fn from(bundle_event: &WithdrawalBundleEvent) -> Self {
+        let (sequence_number, transaction) = match &bundle_event.kind {
+            WithdrawalBundleEventKind::Submitted | WithdrawalBundleEventKind::Failed => {
+                (None, None)
+            }
+            WithdrawalBundleEventKind::Succeeded {
+                sequence_number,
+                transaction,
+            } => (Some(*sequence_number), Some(transaction)),
+        };
+        Self::WithdrawalBundle {
+            sidechain_number: bundle_event.sidechain_id,
+            m6id: bundle_event.m6id,
+            status: WithdrawalBundleStatus::from(&bundle_event.kind),
+            sequence_number,
+            txid: transaction.map(|tx| tx.compute_txid()),
+            // The first output of an M6 is the treasury output
+            payout_sats: transaction.map(|tx| {
+                tx.output
+                    .iter()
+                    .skip(1)
+                    .map(|output| output.value)
+                    .sum::<Amount>()
+                    .to_sat()
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookPayload {
+    #[serde(flatten)]
+    pub event: WebhookEvent,
+    pub block: WebhookBlockContext,
+}
+
+impl WebhookPayload {
+    /// Payloads for the deposit and withdrawal bundle events in a connected
+    /// block
+    fn from_connect_block(header_info: &HeaderInfo, block_info: &BlockInfo) -> Vec<Self> {
+        let block = WebhookBlockContext::from(header_info);
+        block_info
+            .events
+            .iter()
+            .filter_map(|block_event| {
+                let event = match block_event {
+                    BlockEvent::Deposit(deposit) => WebhookEvent::from(deposit),
+                    BlockEvent::WithdrawalBundle(bundle_event) => WebhookEvent::from(bundle_event),
+                    BlockEvent::SidechainProposal { .. } => return None,
+                };
+                Some(Self { event, block })
+            })
+            .collect()
+    }
+}
+
+/// Delivers queued webhooks, retrying failed deliveries with exponential
+/// backoff
+struct Deliverer {
+    client: Client,
+    url: Url,
+    max_retries: u32,
+    initial_backoff: Duration,
+}
+
+impl Deliverer {
+    async fn deliver(&self, payload: &WebhookPayload) -> Result<(), reqwest::Error> {
+        let mut failed_attempts: u32 = 0;
+        loop {
+            let res = self
+                .client
+                .post(self.url.clone())
+                .timeout(REQUEST_TIMEOUT)
+                .json(payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            let err = match res {
+                Ok(_) => return Ok(()),
+                Err(err) => err,
+            };
+            if failed_attempts >= self.max_retries {
+                return Err(err);
+            }
+            let backoff =
+                backoff::exponential_backoff(self.initial_backoff, failed_attempts, MAX_BACKOFF);
+            failed_attempts += 1;
+            tracing::debug!(
+                attempt = failed_attempts,
+                max_retries = self.max_retries,
+                ?backoff,
+                "webhook delivery failed, retrying after backoff: {:#}",
+                ErrorChain::new(&err)
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    async fn run(self, mut queue_rx: mpsc::Receiver<WebhookPayload>) {
+        while let Some(payload) = queue_rx.recv().await {
+            if let Err(err) = self.deliver(&payload).await {
+                tracing::warn!(
+                    block_hash = %payload.block.block_hash,
+                    "failed to deliver webhook, dropping: {:#}",
+                    ErrorChain::new(&err)
+                );
+            }
+        }
+    }
+}
+
+/// Queues webhooks for delivery, counting webhooks that are dropped while
+/// the queue is full
+struct Queue {
+    tx: mpsc::Sender<WebhookPayload>,
+    /// Webhooks dropped since the queue was last not full
+    dropped: u64,
+}
+
+impl Queue {
+    /// Returns `false` if the deliverer has stopped
+    fn push(&mut self, payload: WebhookPayload) -> bool {
+        match self.tx.try_send(payload) {
+            Ok(()) => {
+                if self.dropped > 0 {
+                    tracing::warn!(
+                        dropped = self.dropped,
+                        "webhook queue is no longer full, {} webhook(s) were dropped",
+                        self.dropped
+                    );
+                    self.dropped = 0;
+                }
+                true
+            }
+            Err(TrySendError::Full(payload)) => {
+                if self.dropped == 0 {
+                    tracing::warn!(
+                        block_hash = %payload.block.block_hash,
+                        "webhook queue is full, dropping webhooks until it drains"
+                    );
+                }
+                self.dropped += 1;
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Number of webhooks waiting to be delivered
+    fn len(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+}
+
+impl Validator {
+    /// Payloads for an event. Returns no payloads for a disconnected block
+    /// if its header is unknown.
+    fn webhook_payloads(&self, event: &Event) -> Vec<WebhookPayload> {
+        match event {
+            Event::ConnectBlock {
+                header_info,
+                block_info,
+            } => WebhookPayload::from_connect_block(header_info, block_info),
+            Event::DisconnectBlock { block_hash } => match self.get_header_info(block_hash) {
+                Ok(header_info) => vec![WebhookPayload {
+                    event: WebhookEvent::BlockDisconnected,
+                    block: WebhookBlockContext::from(&header_info),
+                }],
+                Err(err) => {
+                    tracing::warn!(
+                        %block_hash,
+                        "failed to get header info for disconnected block webhook: {:#}",
+                        ErrorChain::new(&err)
+                    );
+                    Vec::new()
+                }
+            },
+        }
+    }
+
+    /// Spawn a task that POSTs a [`WebhookPayload`] to `url` for each deposit
+    /// and withdrawal bundle event in connected blocks, and for each
+    /// disconnected block, until the shutdown signal resolves. Webhooks that
+    /// have not been delivered by then are discarded.
+    /// Events are subscribed to before this function returns, so no events
+    /// are missed after it is called. Webhooks are delivered in order, from
+    /// a bounded queue, so that slow or unreachable endpoints do not delay
+    /// block processing.
+    pub fn spawn_webhook_task<F>(
+        &self,
+        url: Url,
+        max_retries: u32,
+        initial_backoff: Duration,
+        shutdown_signal: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (queue_tx, queue_rx) = mpsc::channel(QUEUE_CAPACITY);
+        let deliverer = Deliverer {
+            client: Client::new(),
+            url,
+            max_retries,
+            initial_backoff,
+        };
+        let deliverer_task = tokio::spawn(deliverer.run(queue_rx));
+        let validator = self.clone();
+        let mut events = Box::pin(self.subscribe_events());
+        tokio::spawn(async move {
+            futures::pin_mut!(shutdown_signal);
+            let mut queue = Queue {
+                tx: queue_tx,
+                dropped: 0,
+            };
+            loop {
+                let event = tokio::select! {
+                    biased;  // Prioritize shutdown
+                    () = &mut shutdown_signal => {
+                        tracing::info!(
+                            undelivered = queue.len(),
+                            "shutting down webhook task"
+                        );
+                        deliverer_task.abort();
+                        return;
+                    }
+                    event = events.next() => event,
+                };
+                let event = match event {
+                    Some(Ok(event)) => event,
+                    Some(Err(EventsStreamError::Overflow)) => {
+                        tracing::warn!("webhook events stream overflowed, some events were missed");
+                        events = Box::pin(validator.subscribe_events());
+                        continue;
+                    }
+                    None => return,
+                };
+                for payload in validator.webhook_payloads(&event) {
+                    if !queue.push(payload) {
+                        tracing::warn!("webhook deliverer stopped, shutting down webhook task");
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,120 @@
+//! Trigger wallet syncs from Bitcoin Core's ZMQ `hashblock` notifications,
+//! rather than waiting for the next periodic sync
+
+use std::{future::Future, time::Duration};
+
+use bitcoin::{BlockHash, hashes::Hash as _};
+use zeromq::{Socket as _, SocketRecv as _, SubSocket, ZmqError};
+
+use crate::wallet::Wallet;
+
+const HASHBLOCK_TOPIC: &str = "hashblock";
+
+/// Delay before resubscribing after the ZMQ subscription fails
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// Parse the block hash from a `hashblock` notification.
+/// The block hash is sent in display (reversed) byte order.
+fn parse_hashblock(message: &zeromq::ZmqMessage) -> Option<BlockHash> {
+    let body = message.get(1)?;
+    let mut bytes: [u8; 32] = body.as_ref().try_into().ok()?;
+    bytes.reverse();
+    Some(BlockHash::from_byte_array(bytes))
+}
+
+impl Wallet {
+    /// Wake the periodic sync task, so that the wallet syncs immediately
+    fn request_sync(&self) {
+        self.inner.sync_requested.notify_one();
+    }
+
+    /// Subscribe to `hashblock` notifications until the ZMQ subscription
+    /// fails, requesting a wallet sync for each new block
+    async fn hashblock_subscription(&self, zmq_addr: &str) -> Result<(), ZmqError> {
+        let mut socket = SubSocket::new();
+        socket.connect(zmq_addr).await?;
+        socket.subscribe(HASHBLOCK_TOPIC).await?;
+        tracing::info!(%zmq_addr, "subscribed to ZMQ `{HASHBLOCK_TOPIC}` notifications");
+        loop {
+            let message = socket.recv().await?;
+            match parse_hashblock(&message) {
+                Some(block_hash) => {
+                    tracing::debug!(%block_hash, "new block notification, requesting wallet sync");
+                }
+                None => {
+                    tracing::warn!(
+                        "invalid ZMQ `{HASHBLOCK_TOPIC}` notification, requesting wallet sync"
+                    );
+                }
+            }
+            self.request_sync();
+        }
+    }
+
+    /// Request a wallet sync for each ZMQ `hashblock` notification, until the
+    /// shutdown signal resolves. If the ZMQ subscription fails, it is
+    /// re-established after a delay. Periodic syncs continue regardless.
+    pub async fn hashblock_sync_trigger_task<F: Future<Output = ()>>(
+        &self,
+        zmq_addr: String,
+        shutdown_signal: F,
+    ) {
+        futures::pin_mut!(shutdown_signal);
+        loop {
+            tokio::select! {
+                biased;  // Prioritize shutdown
+                () = &mut shutdown_signal => {
+                    tracing::info!("shutting down ZMQ `{HASHBLOCK_TOPIC}` subscription");
+                    return;
+                }
+                res = self.hashblock_subscription(&zmq_addr) => {
+                    if let Err(err) = res {
+                        tracing::warn!(
+                            %zmq_addr,
+                            "ZMQ `{HASHBLOCK_TOPIC}` subscription failed, resubscribing in {RESUBSCRIBE_DELAY:?}: {err:#}"
+                        );
+                    }
+                }
+            }
+            tokio::select! {
+                biased;  // Prioritize shutdown
+                () = &mut shutdown_signal => return,
+                () = tokio::time::sleep(RESUBSCRIBE_DELAY) => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use bitcoin::BlockHash;
+    use zeromq::ZmqMessage;
+
+    use super::{HASHBLOCK_TOPIC, parse_hashblock};
+
+    /// Genesis block hash, in display byte order
+    const GENESIS_BLOCK_HASH: &str =
+        "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    fn hashblock_message(body: Vec<u8>) -> ZmqMessage {
+        let mut message = ZmqMessage::from(HASHBLOCK_TOPIC);
+        message.push_back(body.into());
+        message
+    }
+
+    #[test]
+    fn test_parse_hashblock_byte_order() {
+        let message = hashblock_message(hex::decode(GENESIS_BLOCK_HASH).unwrap());
+        let expected = BlockHash::from_str(GENESIS_BLOCK_HASH).unwrap();
+        assert_eq!(parse_hashblock(&message), Some(expected));
+    }
+
+    #[test]
+    fn test_parse_hashblock_invalid() {
+        assert_eq!(parse_hashblock(&ZmqMessage::from(HASHBLOCK_TOPIC)), None);
+        let message = hashblock_message(vec![0; 31]);
+        assert_eq!(parse_hashblock(&message), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Cross-check withdrawal bundles against a sidechain's pending withdrawals
+
+use std::collections::BTreeMap;
+
+use bitcoin::{Amount, ScriptBuf};
+use serde::Serialize;
+
+use crate::{
+    types::{BlindedM6, M6id, SidechainNumber},
+    wallet::{PendingWithdrawals, Wallet, error},
+};
+
+/// Withdrawal that is in only one of the bundle or the pending withdrawals
+#[derive(Clone, Debug, Serialize)]
+pub struct BundleWithdrawal {
+    pub script_pubkey: ScriptBuf,
+    /// `None` if the script pubkey does not correspond to an address
+    pub address: Option<bitcoin::Address>,
+    pub amount: Amount,
+}
+
+/// Withdrawal to the same script pubkey in both the bundle and the pending
+/// withdrawals, with different amounts
+#[derive(Clone, Debug, Serialize)]
+pub struct WrongAmountWithdrawal {
+    pub script_pubkey: ScriptBuf,
+    /// `None` if the script pubkey does not correspond to an address
+    pub address: Option<bitcoin::Address>,
+    /// Amount in the pending withdrawals
+    pub expected: Amount,
+    /// Amount in the bundle
+    pub actual: Amount,
+}
+
+/// Differences between a withdrawal bundle's payouts and the withdrawals in
+/// a pending bundle
+#[derive(Clone, Debug, Serialize)]
+pub struct BundleValidation {
+    pub m6id: M6id,
+    pub pending_m6id: M6id,
+    /// `true` if there are no missing, extra, or wrong-amount withdrawals
+    pub matches: bool,
+    /// Withdrawals in the pending bundle that are not paid out by the bundle
+    pub missing: Vec<BundleWithdrawal>,
+    /// Payouts in the bundle that are not in the pending bundle
+    pub extra: Vec<BundleWithdrawal>,
+    pub wrong_amount: Vec<WrongAmountWithdrawal>,
+}
+
+/// Match `actual` against `expected` amounts for the same script pubkey.
+/// Exact matches are removed first, then the remaining amounts are paired
+/// in ascending order. Returns the unpaired expected amounts, the unpaired
+/// actual amounts, and the mismatched pairs.
+fn match_amounts(
+    mut expected: Vec<Amount>,
+    mut actual: Vec<Amount>,
+) -> (Vec<Amount>, Vec<Amount>, Vec<(Amount, Amount)>) {
+    expected.sort_unstable();
+    actual.sort_unstable();
+    let mut unmatched_expected = Vec::new();
+    let mut unmatched_actual = Vec::new();
+    let (mut expected, mut actual) = (
+        expected.into_iter().peekable(),
+        actual.into_iter().peekable(),
+    );
+    loop {
+        match (expected.peek().copied(), actual.peek().copied()) {
+            (Some(e), Some(a)) if e == a => {
+                expected.next();
+                actual.next();
+            }
+            (Some(e), Some(a)) if e < a => unmatched_expected.extend(expected.next()),
+            (Some(_), Some(_)) => unmatched_actual.extend(actual.next()),
+            (Some(_), None) => unmatched_expected.extend(expected.next()),
+            (None, Some(_)) => unmatched_actual.extend(actual.next()),
+            (None, None) => break,
+        }
+    }
+    let wrong_amounts = unmatched_expected
+        .iter()
+        .copied()
+        .zip(unmatched_actual.iter().copied())
+        .collect::<Vec<_>>();
+    let paired = wrong_amounts.len();
+    (
+        unmatched_expected.split_off(paired),
+        unmatched_actual.split_off(paired),
+        wrong_amounts,
+    )
+}
+
+impl Wallet {
+    /// Cross-check the payouts in a withdrawal bundle against the withdrawals
+    /// in one of a sidechain's pending bundles. Withdrawals are matched by
+    /// script pubkey.
+    pub async fn validate_bundle_against_pending(
+        &self,
+        sidechain_number: SidechainNumber,
+        pending_m6id: M6id,
+        blinded_m6: &BlindedM6<'_>,
+    ) -> Result<BundleValidation, error::ValidateBundleAgainstPending> {
+        let PendingWithdrawals {
+            withdrawals,
+            unknown_bundles,
+        } = self.list_pending_withdrawals(sidechain_number).await?;
+        if unknown_bundles.contains(&pending_m6id) {
+            return Err(error::ValidateBundleAgainstPending::UnknownBundle { m6id: pending_m6id });
+        }
+        let withdrawals: Vec<_> = withdrawals
+            .into_iter()
+            .filter(|withdrawal| withdrawal.m6id == pending_m6id)
+            .collect();
+        // Pending bundles always contain at least one payout
+        if withdrawals.is_empty() {
+            return Err(error::ValidateBundleAgainstPending::NotPending {
+                m6id: pending_m6id,
+                sidechain_number,
+            });
+        }
+        // Amounts by script pubkey, in the pending bundle and the bundle
+        let mut amounts = BTreeMap::<ScriptBuf, (Vec<Amount>, Vec<Amount>)>::new();
+        for withdrawal in withdrawals {
+            amounts
+                .entry(withdrawal.script_pubkey)
+                .or_default()
+                .0
+                .push(withdrawal.amount);
+        }
+        // The first output is the fee output
+        for output in blinded_m6.tx().into_owned().output.into_iter().skip(1) {
+            amounts
+                .entry(output.script_pubkey)
+                .or_default()
+                .1
+                .push(output.value);
+        }
+        let network = self.validator().network();
+        let mut res = BundleValidation {
+            m6id: blinded_m6.compute_m6id(),
+            pending_m6id,
+            matches: false,
+            missing: Vec::new(),
+            extra: Vec::new(),
+            wrong_amount: Vec::new(),
+        };
+        for (script_pubkey, (expected, actual)) in amounts {
+            let address = bitcoin::Address::from_script(&script_pubkey, network).ok();
+            let (missing, extra, wrong_amount) = match_amounts(expected, actual);
+            let withdrawal = |amount| BundleWithdrawal {
+                script_pubkey: script_pubkey.clone(),
+                address: address.clone(),
+                amount,
+            };
+            res.missing.extend(missing.into_iter().map(withdrawal));
+            res.extra.extend(extra.into_iter().map(withdrawal));
+            res.wrong_amount
+                .extend(
+                    wrong_amount
+                        .into_iter()
+                        .map(|(expected, actual)| WrongAmountWithdrawal {
+                            script_pubkey: script_pubkey.clone(),
+                            address: address.clone(),
+                            expected,
+                            actual,
+                        }),
+                );
+        }
+        res.matches = res.missing.is_empty() && res.extra.is_empty() && res.wrong_amount.is_empty();
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Amount;
+
+    use super::match_amounts;
+
+    fn sats(amounts: &[u64]) -> Vec<Amount> {
+        amounts.iter().copied().map(Amount::from_sat).collect()
+    }
+
+    #[test]
+    fn exact_matches() {
+        let (missing, extra, wrong_amount) = match_amounts(sats(&[3, 1, 2]), sats(&[2, 3, 1]));
+        assert!(missing.is_empty());
+        assert!(extra.is_empty());
+        assert!(wrong_amount.is_empty());
+    }
+
+    #[test]
+    fn wrong_amounts_paired_in_ascending_order() {
+        let (missing, extra, wrong_amount) = match_amounts(sats(&[5, 1, 3]), sats(&[3, 6, 2]));
+        assert!(missing.is_empty());
+        assert!(extra.is_empty());
+        assert_eq!(
+            wrong_amount,
+            vec![
+                (Amount::from_sat(1), Amount::from_sat(2)),
+                (Amount::from_sat(5), Amount::from_sat(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unpaired_amounts() {
+        let (missing, extra, wrong_amount) = match_amounts(sats(&[4, 2, 1]), sats(&[2]));
+        assert_eq!(missing, sats(&[1, 4]));
+        assert!(extra.is_empty());
+        assert!(wrong_amount.is_empty());
+        let (missing, extra, wrong_amount) = match_amounts(sats(&[]), sats(&[7, 7]));
+        assert!(missing.is_empty());
+        assert_eq!(extra, sats(&[7, 7]));
+        assert!(wrong_amount.is_empty());
+    }
+}
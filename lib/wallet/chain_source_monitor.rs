@@ -0,0 +1,102 @@
+//! Background connectivity monitor for the wallet chain sources
+
+use std::{future::Future, time::Duration};
+
+use crate::{
+    backoff,
+    errors::ErrorChain,
+    wallet::{Wallet, WalletInner},
+};
+
+/// Delay before the first retry after a failed probe. Doubled after each
+/// consecutive failure, up to the probe interval.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A chain source that does not respond within this time is treated as
+/// unreachable. Blocking Electrum requests are also bounded by the Electrum
+/// client's socket timeout.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl WalletInner {
+    /// Returns `true` if any chain source responds with its tip height
+    /// within [`PROBE_TIMEOUT`]
+    async fn probe_chain_sources(&self) -> bool {
+        for (sync_source, chain_source) in &self.chain_sources {
+            match tokio::time::timeout(PROBE_TIMEOUT, Self::chain_source_tip_height(chain_source))
+                .await
+            {
+                Ok(Ok(_)) => return true,
+                Ok(Err(err)) => {
+                    tracing::debug!(
+                        "{sync_source:?} chain source probe failed: {:#}",
+                        ErrorChain::new(&err)
+                    );
+                }
+                Err(_) => {
+                    tracing::debug!(
+                        "{sync_source:?} chain source probe timed out after {PROBE_TIMEOUT:?}"
+                    );
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Wallet {
+    /// Receiver for whether any chain source is reachable, as of the last
+    /// probe. Always `true` if the chain sources are not monitored.
+    pub fn subscribe_chain_source_reachable(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.inner.chain_source_reachable.subscribe()
+    }
+
+    /// Probe the chain sources every `probe_interval`, until the shutdown
+    /// signal resolves. The chain sources are reported as unreachable after
+    /// the configured number of consecutive failed probes. Failed probes are
+    /// retried with exponential backoff, so that recovery is detected
+    /// quickly.
+    pub async fn chain_source_monitor_task<F: Future<Output = ()>>(
+        &self,
+        probe_interval: Duration,
+        shutdown_signal: F,
+    ) {
+        let failure_threshold = self
+            .inner
+            .config
+            .wallet_opts
+            .chain_source_failure_threshold
+            .get();
+        tracing::debug!(?probe_interval, "chain source monitor: starting");
+        futures::pin_mut!(shutdown_signal);
+        let mut consecutive_failures: u32 = 0;
+        loop {
+            let delay = match consecutive_failures {
+                0 => probe_interval,
+                n => backoff::exponential_backoff(INITIAL_RETRY_DELAY, n - 1, probe_interval),
+            };
+            tokio::select! {
+                biased;  // Prioritize shutdown
+                () = &mut shutdown_signal => {
+                    tracing::info!("shutting down chain source monitor");
+                    return;
+                }
+                () = tokio::time::sleep(delay) => (),
+            }
+            if self.inner.probe_chain_sources().await {
+                consecutive_failures = 0;
+                if !self.inner.chain_source_reachable.send_replace(true) {
+                    tracing::info!("chain source is reachable again");
+                }
+            } else {
+                consecutive_failures = consecutive_failures.saturating_add(1);
+                if consecutive_failures >= failure_threshold
+                    && self.inner.chain_source_reachable.send_replace(false)
+                {
+                    tracing::warn!(
+                        "no chain source reachable after {consecutive_failures} consecutive probes"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Preview of the UTXOs that would be selected to send an amount
+
+use bdk_wallet::{
+    KeychainKind,
+    bitcoin::{Amount, FeeRate, OutPoint},
+};
+use serde::Serialize;
+
+use crate::wallet::{Wallet, error};
+
+/// Result of running coin selection, without signing or broadcasting
+#[derive(Clone, Debug, Serialize)]
+pub struct CoinSelectionPreview {
+    /// Selected UTXOs
+    pub outpoints: Vec<OutPoint>,
+    pub total_input_value: Amount,
+    /// Zero if no change output would be created
+    pub change_amount: Amount,
+    pub fee: Amount,
+}
+
+impl Wallet {
+    /// Run coin selection for sending `amount` at `fee_rate`, and return the
+    /// UTXOs that would be spent. The recipient is assumed to have the same
+    /// script type as the wallet. Nothing is signed, broadcast, or persisted.
+    #[allow(
+        clippy::significant_drop_tightening,
+        reason = "false positive for `bitcoin_wallet`"
+    )]
+    pub async fn preview_coin_selection(
+        &self,
+        amount: Amount,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<CoinSelectionPreview, error::PreviewCoinSelection> {
+        let mut wallet_write = self.inner.try_write_wallet().await?;
+        tokio::task::block_in_place(|| {
+            wallet_write.with_mut(|wallet| {
+                // Placeholder recipient, for estimating the transaction size.
+                // Peeking does not reveal the address.
+                let recipient = wallet
+                    .peek_address(KeychainKind::External, 0)
+                    .script_pubkey();
+                let mut builder = wallet.build_tx();
+                builder.add_recipient(recipient, amount);
+                if let Some(fee_rate) = fee_rate {
+                    builder.fee_rate(fee_rate);
+                }
+                let psbt = builder.finish().map_err(|err| match err {
+                    bdk_wallet::error::CreateTxError::CoinSelection(err) => {
+                        error::PreviewCoinSelection::InsufficientFunds(err)
+                    }
+                    err => error::PreviewCoinSelection::CreateTx(err),
+                })?;
+                // Unmark the change address as used
+                wallet.cancel_tx(&psbt.unsigned_tx);
+                let fee = psbt.fee().map_err(error::PreviewCoinSelection::PsbtFee)?;
+                let outpoints: Vec<_> = psbt
+                    .unsigned_tx
+                    .input
+                    .iter()
+                    .map(|input| input.previous_output)
+                    .collect();
+                let total_input_value = outpoints
+                    .iter()
+                    .filter_map(|outpoint| wallet.get_utxo(*outpoint))
+                    .map(|utxo| utxo.txout.value)
+                    .sum();
+                let change_amount = psbt
+                    .unsigned_tx
+                    .output
+                    .iter()
+                    .filter(|txout| {
+                        matches!(
+                            wallet.derivation_of_spk(txout.script_pubkey.clone()),
+                            Some((KeychainKind::Internal, _))
+                        )
+                    })
+                    .map(|txout| txout.value)
+                    .sum();
+                Ok(CoinSelectionPreview {
+                    outpoints,
+                    total_input_value,
+                    change_amount,
+                    fee,
+                })
+            })
+        })
+    }
+}
@@ -0,0 +1,97 @@
+//! Consolidation of mature coinbase outputs into a single output
+
+use std::{collections::HashMap, num::NonZeroUsize};
+
+use bdk_chain::ChainPosition;
+use bitcoin::{FeeRate, constants::COINBASE_MATURITY};
+
+use crate::wallet::{ChangeAddress, CreateTransactionParams, Wallet, error};
+
+impl Wallet {
+    /// Unspent coinbase outputs that are spendable
+    async fn mature_coinbase_outputs(
+        &self,
+    ) -> Result<Vec<bdk_wallet::bitcoin::OutPoint>, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let tip_height = wallet_read.local_chain().tip().height();
+        let res = wallet_read
+            .list_unspent()
+            .filter(|utxo| {
+                let ChainPosition::Confirmed { anchor, .. } = utxo.chain_position else {
+                    return false;
+                };
+                let confirmations = tip_height.saturating_sub(anchor.block_id.height) + 1;
+                confirmations >= COINBASE_MATURITY
+                    && wallet_read
+                        .get_tx(utxo.outpoint.txid)
+                        .is_some_and(|tx| tx.tx_node.tx.is_coinbase())
+            })
+            .map(|utxo| utxo.outpoint)
+            .collect();
+        drop(wallet_read);
+        Ok(res)
+    }
+
+    /// If the wallet has more than `threshold` mature coinbase outputs, and
+    /// the fee rate required for inclusion in the next block is at most
+    /// `max_fee_rate` sat/vB, consolidate them into a single output.
+    /// Returns the consolidation TXID, if a consolidation transaction was
+    /// broadcast.
+    pub(in crate::wallet) async fn consolidate_coinbase_outputs(
+        &self,
+        threshold: NonZeroUsize,
+        max_fee_rate: u64,
+    ) -> Result<Option<bitcoin::Txid>, error::ConsolidateCoinbaseOutputs> {
+        let outpoints = self.mature_coinbase_outputs().await?;
+        if outpoints.len() <= threshold.get() {
+            return Ok(None);
+        }
+        let next_block_fee_rate = self
+            .inner
+            .fetch_fee_histogram(None)
+            .await?
+            .next_block_fee_rate()
+            .max(1);
+        if next_block_fee_rate > max_fee_rate {
+            tracing::debug!(
+                coinbase_outputs = outpoints.len(),
+                next_block_fee_rate,
+                max_fee_rate,
+                "Not consolidating coinbase outputs, fee rate is too high"
+            );
+            return Ok(None);
+        }
+        // The outputs are consolidated by sending all of their value, less
+        // the fee, as change
+        let change_address = match &self.inner.config.wallet_opts.coinbase_consolidation_address {
+            Some(address) => ChangeAddress {
+                address: address.clone(),
+                allow_external: true,
+            },
+            None => ChangeAddress {
+                address: self.get_new_address().await?,
+                allow_external: false,
+            },
+        };
+        let coinbase_outputs = outpoints.len();
+        let txid = self
+            .send_wallet_transaction(
+                HashMap::new(),
+                CreateTransactionParams {
+                    fee_policy: FeeRate::from_sat_per_vb(next_block_fee_rate)
+                        .map(crate::types::FeePolicy::Rate),
+                    required_utxos: outpoints,
+                    change_address: Some(change_address),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        tracing::info!(
+            %txid,
+            coinbase_outputs,
+            fee_rate = next_block_fee_rate,
+            "Consolidated coinbase outputs"
+        );
+        Ok(Some(txid))
+    }
+}
@@ -0,0 +1,167 @@
+//! Estimated time until a wallet transaction is confirmed, based on the
+//! fee rates of transactions in the mempool
+
+use std::collections::HashMap;
+
+use bitcoin::{Amount, Txid, Weight};
+use bitcoin_jsonrpsee::jsonrpsee::{core::client::ClientT as _, rpc_params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    convert,
+    wallet::{Wallet, WalletInner, error},
+};
+
+/// Subset of the fields of a verbose `getrawmempool` entry
+#[derive(Debug, Deserialize)]
+pub(in crate::wallet) struct MempoolEntry {
+    pub vsize: u64,
+    pub weight: u64,
+    pub fees: MempoolEntryFees,
+    /// Unconfirmed parent transactions
+    pub depends: Vec<Txid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(in crate::wallet) struct MempoolEntryFees {
+    /// Fee including any prioritisation
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub modified: Amount,
+}
+
+/// Total vsize of mempool transactions in each fee rate bucket, in
+/// descending order of fee rate (sat/vB)
+#[derive(Debug)]
+pub(in crate::wallet) struct FeeHistogram(Vec<(u64, u64)>);
+
+impl FeeHistogram {
+    fn new<Entries>(entries: Entries) -> Self
+    where
+        Entries: IntoIterator<Item = MempoolEntry>,
+    {
+        let mut buckets = HashMap::<u64, u64>::new();
+        for entry in entries {
+            let fee_rate = entry.fees.modified.to_sat() / entry.vsize.max(1);
+            *buckets.entry(fee_rate).or_default() += entry.vsize;
+        }
+        let mut buckets: Vec<_> = buckets.into_iter().collect();
+        buckets.sort_unstable_by(|(lhs, _), (rhs, _)| rhs.cmp(lhs));
+        Self(buckets)
+    }
+
+    /// Fee rate buckets, as `(fee_rate, vsize)`, in descending order of fee
+    /// rate
+    pub(in crate::wallet) fn buckets(&self) -> &[(u64, u64)] {
+        &self.0
+    }
+
+    /// Total vsize of transactions that pay a higher fee rate than
+    /// `fee_rate`, and would be mined first
+    fn vsize_ahead(&self, fee_rate: u64) -> u64 {
+        self.0
+            .iter()
+            .take_while(|(bucket_fee_rate, _)| *bucket_fee_rate > fee_rate)
+            .map(|(_, vsize)| vsize)
+            .sum()
+    }
+
+    /// Lowest fee rate of the transactions that would be included in the
+    /// next block. Zero if all transactions fit in the next block.
+    pub(in crate::wallet) fn next_block_fee_rate(&self) -> u64 {
+        self.fee_rate_within_blocks(1)
+    }
+
+    /// Lowest fee rate of the transactions that would be included in the
+    /// next `blocks` blocks, if no other transactions arrive. Zero if all
+    /// transactions fit in the next `blocks` blocks.
+    pub(in crate::wallet) fn fee_rate_within_blocks(&self, blocks: u64) -> u64 {
+        let max_vsize = Weight::MAX_BLOCK.to_vbytes_floor() * blocks.max(1);
+        let mut total_vsize = 0;
+        for (fee_rate, vsize) in &self.0 {
+            total_vsize += vsize;
+            if total_vsize >= max_vsize {
+                return *fee_rate;
+            }
+        }
+        0
+    }
+}
+
+/// Estimated time until a wallet transaction is confirmed
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfirmationEstimate {
+    pub txid: Txid,
+    /// Fee rate paid by the transaction, in sat/vB.
+    /// `None` if the transaction is confirmed.
+    pub fee_rate: Option<u64>,
+    /// Estimated number of blocks until the transaction is confirmed.
+    /// Zero if the transaction is confirmed.
+    pub blocks: u64,
+    /// Approximate time until the transaction is confirmed, in seconds,
+    /// assuming that blocks are mined at the target interval
+    pub seconds: u64,
+}
+
+impl WalletInner {
+    pub(in crate::wallet) async fn fetch_mempool_entries(
+        &self,
+    ) -> Result<HashMap<Txid, MempoolEntry>, error::BitcoinCoreRPC> {
+        self.main_client
+            .request("getrawmempool", rpc_params![true])
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "getrawmempool".to_string(),
+                error: err,
+            })
+    }
+
+    pub(in crate::wallet) async fn fetch_fee_histogram(
+        &self,
+        exclude_txid: Option<Txid>,
+    ) -> Result<FeeHistogram, error::BitcoinCoreRPC> {
+        let mempool = self.fetch_mempool_entries().await?;
+        Ok(FeeHistogram::new(mempool.into_iter().filter_map(
+            |(txid, entry)| (Some(txid) != exclude_txid).then_some(entry),
+        )))
+    }
+}
+
+impl Wallet {
+    /// Estimate the number of blocks until a wallet transaction is
+    /// confirmed, by comparing its fee rate against the fee rates of
+    /// transactions in the mempool. Assumes that no transactions are added to
+    /// the mempool with a higher fee rate, so this is a lower bound.
+    pub async fn estimate_confirmation_time(
+        &self,
+        txid: Txid,
+    ) -> Result<ConfirmationEstimate, error::EstimateConfirmationTime> {
+        let tx = {
+            let wallet_read = self.inner.try_read_wallet().await?;
+            let Some(wallet_tx) = wallet_read.get_tx(convert::bitcoin_txid_to_bdk_txid(txid))
+            else {
+                return Err(error::EstimateConfirmationTime::UnknownTransaction { txid });
+            };
+            if wallet_tx.chain_position.is_confirmed() {
+                return Ok(ConfirmationEstimate {
+                    txid,
+                    fee_rate: None,
+                    blocks: 0,
+                    seconds: 0,
+                });
+            }
+            wallet_tx.tx_node.tx.as_ref().clone()
+        };
+        let fee = self.fetch_transaction_fee(&tx).await?;
+        let fee_rate = fee.to_sat() / tx.vsize().max(1) as u64;
+        let fee_histogram = self.inner.fetch_fee_histogram(Some(txid)).await?;
+        let max_block_vsize = Weight::MAX_BLOCK.to_vbytes_floor();
+        let blocks = fee_histogram.vsize_ahead(fee_rate) / max_block_vsize + 1;
+        let target_spacing_secs = self.validator().network().params().pow_target_spacing;
+        Ok(ConfirmationEstimate {
+            txid,
+            fee_rate: Some(fee_rate),
+            blocks,
+            seconds: target_spacing_secs * blocks,
+        })
+    }
+}
@@ -1,7 +1,15 @@
-use std::{borrow::Cow, collections::HashMap, future::Future};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    future::Future,
+    time::Duration,
+};
 
 use bitcoin::{BlockHash, Transaction, Txid, hashes::Hash as _};
-use bitcoin_jsonrpsee::client::{GetBlockClient, U8Witness};
+use bitcoin_jsonrpsee::{
+    client::{GetBlockClient, U8Witness},
+    jsonrpsee::core::client::Error as JsonRpcError,
+};
 use cusf_enforcer_mempool::{
     cusf_block_producer::{
         BlockTemplateSuffix, CoinbaseTxn, CoinbaseTxouts, CusfBlockProducer, InitialBlockTemplate,
@@ -12,11 +20,74 @@ use cusf_enforcer_mempool::{
 use tracing::instrument;
 
 use crate::{
+    backoff,
+    errors::ErrorChain,
     messages::{CoinbaseBuilder, parse_m8_tx},
+    rpc_client,
     validator::Validator,
-    wallet::{Wallet, error},
+    wallet::{Wallet, WalletInner, error},
 };
 
+/// Maximum backoff between block fetch retries
+const BLOCK_FETCH_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Whether a failed Bitcoin Core RPC request is likely to succeed if retried.
+/// Errors returned by Bitcoin Core (eg. block not found, or pruned) are not
+/// retryable, unless the node is warming up.
+fn is_transient_rpc_error(err: &JsonRpcError) -> bool {
+    match err {
+        JsonRpcError::Call(err) => err.code() == rpc_client::RPC_IN_WARMUP,
+        JsonRpcError::Transport(_)
+        | JsonRpcError::RestartNeeded(_)
+        | JsonRpcError::RequestTimeout => true,
+        _ => false,
+    }
+}
+
+impl WalletInner {
+    /// Send a Bitcoin Core RPC request while connecting blocks, retrying with
+    /// exponential backoff if the request fails with a transient error.
+    pub(in crate::wallet) async fn request_with_retry<T, Request, Fut>(
+        &self,
+        method: &'static str,
+        mut request: Request,
+    ) -> Result<T, error::BitcoinCoreRPC>
+    where
+        Request: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError>>,
+    {
+        let max_retries = self.config.block_fetch_max_retries;
+        let initial_backoff = Duration::from_millis(self.config.block_fetch_retry_backoff_ms);
+        let mut failed_attempts: u32 = 0;
+        loop {
+            let err = match request().await {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+            if failed_attempts >= max_retries || !is_transient_rpc_error(&err) {
+                return Err(error::BitcoinCoreRPC {
+                    method: method.to_string(),
+                    error: err,
+                });
+            }
+            let backoff = backoff::exponential_backoff(
+                initial_backoff,
+                failed_attempts,
+                BLOCK_FETCH_MAX_BACKOFF,
+            );
+            failed_attempts += 1;
+            tracing::warn!(
+                attempt = failed_attempts,
+                max_retries,
+                ?backoff,
+                "`{method}` request failed, retrying after backoff: {:#}",
+                ErrorChain::new(&err)
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
 #[derive(Debug, miette::Diagnostic, thiserror::Error)]
 pub enum SyncError {
     #[error(transparent)]
@@ -138,16 +209,11 @@ impl CusfEnforcer for Wallet {
                     // Fetch the ancestor block if needed
                     let fetched_block = self
                         .inner
-                        .main_client
-                        .get_block(block_hash, U8Witness::<0>)
+                        .request_with_retry("getblock", || {
+                            self.inner.main_client.get_block(block_hash, U8Witness::<0>)
+                        })
                         .await
-                        .map_err(|err| {
-                            let error = error::BitcoinCoreRPC {
-                                method: "getblock".to_string(),
-                                error: err,
-                            };
-                            error::ConnectBlock::GetBlock(error)
-                        })?;
+                        .map_err(error::ConnectBlock::GetBlock)?;
                     Cow::Owned(fetched_block.0) // Cow now owns the fetched block
                 };
 
@@ -213,12 +279,35 @@ impl CusfEnforcer for Wallet {
         &mut self,
         block_hash: BlockHash,
     ) -> std::result::Result<(), Self::DisconnectBlockError> {
-        self.inner
+        let block_height = self
+            .inner
+            .validator
+            .get_header_info(&block_hash)
+            .map(|header_info| header_info.height);
+        let () = self
+            .inner
             .validator
             .clone()
             .disconnect_block(block_hash)
-            .await
+            .await?;
+        // Withdrawal bundle event records are best-effort, and should not
+        // prevent the block from being disconnected
+        let res = match block_height {
+            Ok(block_height) => self
+                .inner
+                .delete_withdrawal_bundle_events_from(block_height)
+                .await
+                .map_err(|err| format!("{:#}", ErrorChain::new(&err))),
+            Err(err) => Err(format!("{:#}", ErrorChain::new(&err))),
+        };
+        if let Err(err) = res {
+            tracing::warn!(
+                %block_hash,
+                "failed to delete withdrawal bundle events for disconnected block: {err}"
+            );
+        }
         // FIXME: disconnect block for wallet
+        Ok(())
     }
 
     type AcceptTxError = <Validator as CusfEnforcer>::AcceptTxError;
@@ -275,9 +364,12 @@ impl CusfBlockProducer for Wallet {
             );
 
             const ACK_ALL_PROPOSALS: bool = true;
-            let () = self
+            let ack_changes = self
                 .extend_coinbase_txouts(ACK_ALL_PROPOSALS, mainchain_tip, coinbase_txouts)
                 .await?;
+            self.persist_sidechain_ack_changes(ack_changes)
+                .await
+                .map_err(error::InitialBlockTemplateInner::PersistSidechainAcks)?;
             tracing::debug!(
                 "Initial coinbase txouts post-extension: {:?}",
                 coinbase_txouts
@@ -306,6 +398,9 @@ impl CusfBlockProducer for Wallet {
             }
         }
         // FIXME: set prefix txns and exclude mempool txs
+        let excluded: HashSet<Txid> = template.exclude_mempool_txs.iter().copied().collect();
+        let exclude = self.inner.template_limit_exclusions(&excluded).await?;
+        template.exclude_mempool_txs.extend(exclude);
         Ok(template)
     }
 
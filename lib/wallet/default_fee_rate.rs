@@ -0,0 +1,192 @@
+//! Default fee rate for wallet transactions built without an explicit fee
+
+use bitcoin::{Amount, FeeRate};
+use bitcoin_jsonrpsee::jsonrpsee::{core::client::ClientT as _, rpc_params};
+use rusqlite::{Connection, OptionalExtension as _};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::ErrorChain,
+    wallet::{Wallet, WalletInner, error},
+};
+
+/// Confirmation target for `estimatesmartfee`, if no default fee rate is set
+const ESTIMATE_SMART_FEE_CONF_TARGET: u16 = 6;
+
+/// Subset of the fields of the `getmempoolinfo` response
+#[derive(Debug, Deserialize)]
+struct MempoolInfo {
+    /// Minimum relay fee rate, in BTC/kvB
+    #[serde(rename = "minrelaytxfee", with = "bitcoin::amount::serde::as_btc")]
+    min_relay_tx_fee: Amount,
+}
+
+/// Subset of the fields of the `estimatesmartfee` response
+#[derive(Debug, Deserialize)]
+struct SmartFeeEstimate {
+    /// Estimated fee rate, in BTC/kvB. Not set if there is not enough data
+    /// for an estimate.
+    #[serde(
+        default,
+        rename = "feerate",
+        with = "bitcoin::amount::serde::as_btc::opt"
+    )]
+    fee_rate: Option<Amount>,
+}
+
+/// Convert a fee rate in sats/kvB to sat/vB, rounding up
+fn sat_per_kvb_to_sat_per_vb(fee_rate: Amount) -> u64 {
+    fee_rate.to_sat().div_ceil(1000)
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultFeeRateSource {
+    /// Set via `set_default_fee_rate`
+    Configured,
+    /// Estimated by Bitcoin Core's `estimatesmartfee`
+    Estimated,
+    /// Neither set nor estimated, because `estimatesmartfee` failed or did
+    /// not have enough data for an estimate. The transaction builder's
+    /// default fee rate is used.
+    Unavailable,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DefaultFeeRate {
+    /// Fee rate in sat/vB
+    pub sat_per_vb: Option<u64>,
+    pub source: DefaultFeeRateSource,
+}
+
+impl WalletInner {
+    fn read_default_fee_rate(connection: &Connection) -> Result<Option<u64>, rusqlite::Error> {
+        connection
+            .query_row(
+                "SELECT sat_per_vb FROM default_fee_rate WHERE id = 0;",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Fee rate estimate in sat/vB, for confirmation within `conf_target`
+    /// blocks. `None` if Bitcoin Core does not have enough data for an
+    /// estimate.
+    pub(in crate::wallet) async fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+    ) -> Result<Option<u64>, error::BitcoinCoreRPC> {
+        let estimate: SmartFeeEstimate = self
+            .main_client
+            .request("estimatesmartfee", rpc_params![conf_target])
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "estimatesmartfee".to_string(),
+                error: err,
+            })?;
+        Ok(estimate.fee_rate.map(sat_per_kvb_to_sat_per_vb))
+    }
+
+    pub(in crate::wallet) async fn get_default_fee_rate(
+        &self,
+    ) -> Result<DefaultFeeRate, error::GetDefaultFeeRate> {
+        if let Some(sat_per_vb) = Self::read_default_fee_rate(&self.self_db_readers.lock().await)? {
+            return Ok(DefaultFeeRate {
+                sat_per_vb: Some(sat_per_vb),
+                source: DefaultFeeRateSource::Configured,
+            });
+        }
+        let unavailable = DefaultFeeRate {
+            sat_per_vb: None,
+            source: DefaultFeeRateSource::Unavailable,
+        };
+        let res = match self
+            .estimate_smart_fee(ESTIMATE_SMART_FEE_CONF_TARGET)
+            .await
+        {
+            Ok(Some(sat_per_vb)) => DefaultFeeRate {
+                sat_per_vb: Some(sat_per_vb),
+                source: DefaultFeeRateSource::Estimated,
+            },
+            Ok(None) => {
+                tracing::warn!(
+                    conf_target = ESTIMATE_SMART_FEE_CONF_TARGET,
+                    "Not enough data for a fee rate estimate, using the transaction builder's default fee rate"
+                );
+                unavailable
+            }
+            Err(err) => {
+                tracing::warn!(
+                    conf_target = ESTIMATE_SMART_FEE_CONF_TARGET,
+                    "Failed to estimate fee rate, using the transaction builder's default fee rate: {:#}",
+                    ErrorChain::new(&err)
+                );
+                unavailable
+            }
+        };
+        Ok(res)
+    }
+
+    /// Fee rate to use for transactions built without an explicit fee.
+    /// `None` if the transaction builder's default should be used.
+    pub(in crate::wallet) async fn default_fee_rate(
+        &self,
+    ) -> Result<Option<FeeRate>, error::GetDefaultFeeRate> {
+        let DefaultFeeRate { sat_per_vb, .. } = self.get_default_fee_rate().await?;
+        Ok(sat_per_vb.and_then(FeeRate::from_sat_per_vb))
+    }
+}
+
+impl Wallet {
+    /// Fee rate used for transactions built without an explicit fee, and
+    /// where it comes from
+    pub async fn get_default_fee_rate(&self) -> Result<DefaultFeeRate, error::GetDefaultFeeRate> {
+        self.inner.get_default_fee_rate().await
+    }
+
+    /// Set the fee rate used for transactions built without an explicit fee,
+    /// in sat/vB. If `None`, the default fee rate is estimated by Bitcoin
+    /// Core's `estimatesmartfee`.
+    pub async fn set_default_fee_rate(
+        &self,
+        sat_per_vb: Option<u64>,
+    ) -> Result<(), error::SetDefaultFeeRate> {
+        let Some(sat_per_vb) = sat_per_vb else {
+            let _: usize = self
+                .inner
+                .self_db
+                .lock()
+                .await
+                .execute("DELETE FROM default_fee_rate;", [])?;
+            tracing::info!("Cleared default fee rate");
+            return Ok(());
+        };
+        let mempool_info: MempoolInfo = self
+            .inner
+            .main_client
+            .request("getmempoolinfo", rpc_params![])
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "getmempoolinfo".to_string(),
+                error: err,
+            })?;
+        if FeeRate::from_sat_per_vb(sat_per_vb).is_none() {
+            return Err(error::SetDefaultFeeRate::Overflow { sat_per_vb });
+        }
+        let min_relay_fee_rate = sat_per_kvb_to_sat_per_vb(mempool_info.min_relay_tx_fee);
+        if sat_per_vb < min_relay_fee_rate {
+            return Err(error::SetDefaultFeeRate::BelowMinRelayFee {
+                sat_per_vb,
+                min_relay_fee_rate,
+            });
+        }
+        let _: usize = self.inner.self_db.lock().await.execute(
+            "INSERT INTO default_fee_rate (id, sat_per_vb) VALUES (0, ?1)
+             ON CONFLICT(id) DO UPDATE SET sat_per_vb = excluded.sat_per_vb;",
+            [sat_per_vb],
+        )?;
+        tracing::info!(sat_per_vb, "Set default fee rate");
+        Ok(())
+    }
+}
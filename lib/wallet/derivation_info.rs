@@ -0,0 +1,94 @@
+//! BIP32 derivation paths and account information for the wallet's
+//! keychains, for reconstructing the wallet in other software
+
+use bdk_wallet::{
+    KeychainKind,
+    bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub},
+    miniscript::descriptor::{DescriptorPublicKey, Wildcard},
+};
+use serde::Serialize;
+
+use crate::wallet::{ScriptType, Wallet, error};
+
+/// Derivation information for a wallet keychain. Does not include private
+/// keys.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeychainDerivationInfo {
+    pub keychain: KeychainKind,
+    pub master_fingerprint: Fingerprint,
+    /// Derivation path of the account key from the master key,
+    /// eg. `m/84'/1'/0'`
+    pub account_path: DerivationPath,
+    /// Index of the account, if the account path ends with a hardened step
+    pub account_index: Option<u32>,
+    pub account_xpub: Xpub,
+    /// Derivation path template for addresses, eg. `m/84'/1'/0'/0/*`
+    pub path_template: String,
+    pub script_type: ScriptType,
+    /// Public descriptor for the keychain
+    pub descriptor: String,
+}
+
+/// Derivation path template for a descriptor key, from the master key
+fn path_template(
+    account_path: &DerivationPath,
+    key_path: &DerivationPath,
+    wildcard: Wildcard,
+) -> String {
+    let mut template = String::from("m");
+    for child in account_path.into_iter().chain(key_path) {
+        template.push_str(&format!("/{child}"));
+    }
+    match wildcard {
+        Wildcard::None => (),
+        Wildcard::Unhardened => template.push_str("/*"),
+        Wildcard::Hardened => template.push_str("/*'"),
+    }
+    template
+}
+
+impl Wallet {
+    /// Derivation path templates, account information, and script types for
+    /// the wallet's keychains, read from the persisted descriptors. Keychains
+    /// whose descriptor does not have a single extended key are omitted.
+    pub async fn get_derivation_info(
+        &self,
+    ) -> Result<Vec<KeychainDerivationInfo>, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let res = [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .filter_map(|keychain| {
+                let descriptor = wallet_read.public_descriptor(keychain);
+                let mut keys = descriptor.iter_pk();
+                let (Some(DescriptorPublicKey::XPub(xkey)), None) = (keys.next(), keys.next())
+                else {
+                    return None;
+                };
+                let (master_fingerprint, account_path) = match &xkey.origin {
+                    Some((fingerprint, path)) => (*fingerprint, path.clone()),
+                    None => (xkey.xkey.fingerprint(), DerivationPath::master()),
+                };
+                let account_index = match account_path.into_iter().next_back() {
+                    Some(ChildNumber::Hardened { index }) => Some(*index),
+                    _ => None,
+                };
+                let script_pubkey = wallet_read.peek_address(keychain, 0).script_pubkey();
+                Some(KeychainDerivationInfo {
+                    keychain,
+                    master_fingerprint,
+                    path_template: path_template(
+                        &account_path,
+                        &xkey.derivation_path,
+                        xkey.wildcard,
+                    ),
+                    account_path,
+                    account_index,
+                    account_xpub: xkey.xkey,
+                    script_type: ScriptType::of(&script_pubkey),
+                    descriptor: descriptor.to_string(),
+                })
+            })
+            .collect();
+        Ok(res)
+    }
+}
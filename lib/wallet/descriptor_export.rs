@@ -0,0 +1,50 @@
+//! Export the wallet's output descriptors, for recovering the wallet in any
+//! descriptor wallet
+
+use bdk_wallet::{KeychainKind, bitcoin::secp256k1::Secp256k1};
+use serde::Serialize;
+
+use crate::wallet::{Wallet, error};
+
+/// External (receive) and internal (change) output descriptors, with
+/// checksums
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedDescriptors {
+    pub external: String,
+    pub internal: String,
+    /// `true` if the descriptors include private keys
+    pub include_private: bool,
+}
+
+impl Wallet {
+    /// Export the wallet's external and internal descriptors. If
+    /// `include_private` is set, the descriptors include the wallet's
+    /// private keys, and the export is logged.
+    pub async fn export_descriptors(
+        &self,
+        include_private: bool,
+    ) -> Result<ExportedDescriptors, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let secp = Secp256k1::new();
+        let export = |keychain: KeychainKind| {
+            let descriptor = wallet_read.public_descriptor(keychain);
+            if include_private {
+                let key_map = wallet_read.get_signers(keychain).as_key_map(&secp);
+                descriptor.to_string_with_secret(&key_map)
+            } else {
+                descriptor.to_string()
+            }
+        };
+        let res = ExportedDescriptors {
+            external: export(KeychainKind::External),
+            internal: export(KeychainKind::Internal),
+            include_private,
+        };
+        if include_private {
+            tracing::warn!("exported wallet descriptors with private keys");
+        } else {
+            tracing::debug!("exported public wallet descriptors");
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,164 @@
+//! Snapshot of wallet state, for diagnosing wallet issues
+
+use std::time::UNIX_EPOCH;
+
+use bdk_wallet::KeychainKind;
+use bitcoin::{Amount, BlockHash};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::{
+    cli::WalletSyncSource,
+    wallet::{Wallet, WalletInner, error},
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainSourceDiagnostics {
+    pub sync_source: WalletSyncSource,
+    /// Server address, with any credentials removed.
+    /// `None` if the sync source is disabled.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletBalanceDiagnostics {
+    pub confirmed: Amount,
+    pub immature: Amount,
+    pub trusted_pending: Amount,
+    pub untrusted_pending: Amount,
+}
+
+impl From<bdk_wallet::Balance> for WalletBalanceDiagnostics {
+    fn from(balance: bdk_wallet::Balance) -> Self {
+        Self {
+            confirmed: balance.confirmed,
+            immature: balance.immature,
+            trusted_pending: balance.trusted_pending,
+            untrusted_pending: balance.untrusted_pending,
+        }
+    }
+}
+
+/// Wallet state, as reported by [`Wallet::dump_diagnostics`].
+/// Does not include descriptors, keys, or other secret material.
+/// Fields that require the wallet to be unlocked are `None` if it is not.
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletDiagnostics {
+    pub network: bitcoin::Network,
+    pub unlocked: bool,
+    /// Chain sources in priority order, primary first
+    pub chain_sources: Vec<ChainSourceDiagnostics>,
+    /// `false` if no chain source was reachable as of the last chain source
+    /// probe
+    pub chain_source_reachable: bool,
+    /// Unix timestamp of the last successful sync, in seconds
+    pub last_sync: Option<u64>,
+    /// `true` if the last successful sync is older than the sync staleness
+    /// threshold
+    pub sync_stale: bool,
+    pub tip_height: Option<u32>,
+    pub tip_hash: Option<BlockHash>,
+    pub balance: Option<WalletBalanceDiagnostics>,
+    pub revealed_address_count: Option<u32>,
+    pub utxo_count: Option<usize>,
+    pub bundle_proposal_count: usize,
+    pub pending_sidechain_proposal_count: usize,
+}
+
+impl WalletInner {
+    fn chain_source_diagnostics(&self) -> Vec<ChainSourceDiagnostics> {
+        let network = self.validator.network();
+        let wallet_opts = &self.config.wallet_opts;
+        self.chain_sources
+            .iter()
+            .map(|(sync_source, _)| {
+                let endpoint = match sync_source {
+                    WalletSyncSource::Electrum => Self::electrum_url(wallet_opts, network).ok(),
+                    WalletSyncSource::Esplora => {
+                        Self::esplora_url(wallet_opts, network).ok().map(|mut url| {
+                            let _: Result<(), ()> = url.set_username("");
+                            let _: Result<(), ()> = url.set_password(None);
+                            url.set_query(None);
+                            url.to_string()
+                        })
+                    }
+                    WalletSyncSource::Disabled => None,
+                };
+                ChainSourceDiagnostics {
+                    sync_source: *sync_source,
+                    endpoint,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Wallet {
+    /// Chain sources used for wallet sync, with any credentials removed
+    pub fn chain_source_diagnostics(&self) -> Vec<ChainSourceDiagnostics> {
+        self.inner.chain_source_diagnostics()
+    }
+
+    /// Snapshot of wallet state, for diagnosing wallet issues
+    pub async fn dump_diagnostics(&self) -> Result<WalletDiagnostics, error::DumpDiagnostics> {
+        let last_sync = self
+            .inner
+            .last_sync
+            .read()
+            .await
+            .and_then(|last_sync| last_sync.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs());
+        let mut res = WalletDiagnostics {
+            network: self.inner.validator.network(),
+            unlocked: false,
+            chain_sources: self.inner.chain_source_diagnostics(),
+            chain_source_reachable: *self.inner.chain_source_reachable.borrow(),
+            last_sync,
+            sync_stale: *self.inner.sync_stale.borrow(),
+            tip_height: None,
+            tip_hash: None,
+            balance: None,
+            revealed_address_count: None,
+            utxo_count: None,
+            bundle_proposal_count: 0,
+            pending_sidechain_proposal_count: 0,
+        };
+        match self.inner.try_read_wallet().await {
+            Ok(wallet_read) => {
+                let tip = wallet_read.local_chain().tip();
+                res.unlocked = true;
+                res.tip_height = Some(tip.height());
+                res.tip_hash = Some(tip.hash());
+                res.balance = Some(wallet_read.balance().into());
+                res.revealed_address_count = Some(
+                    [KeychainKind::External, KeychainKind::Internal]
+                        .into_iter()
+                        .filter_map(|keychain| wallet_read.derivation_index(keychain))
+                        .map(|last_revealed| last_revealed + 1)
+                        .sum(),
+                );
+                res.utxo_count = Some(wallet_read.list_unspent().count());
+            }
+            Err(error::LockWallet::NotUnlocked(error::NotUnlocked)) => (),
+            Err(error::LockWallet::WalletBusy(err)) => return Err(err.into()),
+        }
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let bundle_proposal_count: usize =
+                connection.query_row("SELECT COUNT(*) FROM bundle_proposals", [], |row| {
+                    row.get(0)
+                })?;
+            let pending_sidechain_proposal_count: usize =
+                connection.query_row("SELECT COUNT(*) FROM sidechain_proposals", [], |row| {
+                    row.get(0)
+                })?;
+            Ok((bundle_proposal_count, pending_sidechain_proposal_count))
+        };
+        let connection = self.inner.self_db_readers.lock().await;
+        (
+            res.bundle_proposal_count,
+            res.pending_sidechain_proposal_count,
+        ) = with_connection(&connection)?;
+        Ok(res)
+    }
+}
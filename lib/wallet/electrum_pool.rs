@@ -0,0 +1,92 @@
+//! Pool of Electrum connections
+
+use std::{
+    num::NonZeroUsize,
+    ops::Deref,
+    sync::{Mutex, PoisonError},
+};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::ElectrumClient;
+
+/// Fixed-size pool of Electrum connections, so that a long-running sync does
+/// not block other requests to the Electrum server.
+pub(in crate::wallet) struct ElectrumPool {
+    /// Connections that are not currently leased
+    idle: Mutex<Vec<ElectrumClient>>,
+    /// One permit per connection in the pool
+    permits: Semaphore,
+}
+
+impl ElectrumPool {
+    /// Create a pool of `max_connections` connections, using `connect` to
+    /// open each connection.
+    pub(in crate::wallet) fn new<F, Err>(
+        max_connections: NonZeroUsize,
+        mut connect: F,
+    ) -> Result<Self, Err>
+    where
+        F: FnMut() -> Result<ElectrumClient, Err>,
+    {
+        let idle = (0..max_connections.get())
+            .map(|_| connect())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            idle: Mutex::new(idle),
+            permits: Semaphore::new(max_connections.get()),
+        })
+    }
+
+    /// Lease a connection from the pool, waiting until one is available.
+    /// The connection is returned to the pool when the lease is dropped.
+    pub(in crate::wallet) async fn lease(&self) -> ElectrumLease<'_> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("Electrum pool semaphore should never be closed");
+        let client = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .expect("Electrum pool should have an idle connection for each permit");
+        ElectrumLease {
+            pool: self,
+            client: Some(client),
+            _permit: permit,
+        }
+    }
+}
+
+/// Connection leased from an [`ElectrumPool`]
+pub(in crate::wallet) struct ElectrumLease<'a> {
+    pool: &'a ElectrumPool,
+    // Always `Some`, until dropped
+    client: Option<ElectrumClient>,
+    // Released after `drop` has returned the client to the pool
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for ElectrumLease<'_> {
+    type Target = ElectrumClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.client
+            .as_ref()
+            .expect("Leased Electrum connection should be Some")
+    }
+}
+
+impl Drop for ElectrumLease<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(client);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt::Debug, path::PathBuf};
+use std::{fmt::Debug, path::PathBuf, time::Duration};
 
 use bdk_chain::CheckPoint;
 use bdk_esplora::esplora_client;
@@ -14,7 +14,7 @@ use crate::{
     errors::ErrorChain,
     messages::CoinbaseMessagesError,
     proto::{StatusBuilder, ToStatus},
-    types::SidechainNumber,
+    types::{M6id, SidechainNumber},
     validator::{self, Validator},
 };
 
@@ -88,6 +88,60 @@ impl ToStatus for NotUnlocked {
     }
 }
 
+/// Sidechain excluded by the wallet's sidechain allowlist or denylist
+#[derive(Debug, Diagnostic, Error)]
+#[diagnostic(
+    code(sidechain_not_allowed),
+    help("see `--wallet-sidechain-allowlist` and `--wallet-sidechain-denylist`")
+)]
+#[error("sidechain {sidechain_number} is not allowed by the wallet's sidechain filter")]
+pub struct SidechainNotAllowed {
+    pub sidechain_number: SidechainNumber,
+}
+
+impl ToStatus for SidechainNotAllowed {
+    fn builder(&self) -> StatusBuilder {
+        StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
+    }
+}
+
+/// Timed out waiting to acquire a wallet lock
+#[derive(Debug, Diagnostic, Error)]
+#[diagnostic(
+    code(wallet_busy),
+    help("the wallet may be syncing, retry the request later")
+)]
+#[error("enforcer wallet busy, timed out after {timeout:?} waiting to acquire {lock} lock")]
+pub struct WalletBusy {
+    pub lock: &'static str,
+    pub timeout: Duration,
+}
+
+impl ToStatus for WalletBusy {
+    fn builder(&self) -> StatusBuilder {
+        StatusBuilder::new(self).code(tonic::Code::Unavailable)
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum LockWallet {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    NotUnlocked(#[from] NotUnlocked),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    WalletBusy(#[from] WalletBusy),
+}
+
+impl ToStatus for LockWallet {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::NotUnlocked(err) => err.builder(),
+            Self::WalletBusy(err) => err.builder(),
+        }
+    }
+}
+
 /// Wallet data mismatch
 #[derive(Debug, Diagnostic, Error)]
 #[diagnostic(code(wallet_data_mismatch))]
@@ -145,7 +199,7 @@ impl ToStatus for WalletInitialization {
 #[derive(Debug, Diagnostic, Error)]
 pub enum WalletSignTransaction {
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
     #[error(transparent)]
     SignerError(#[from] bdk_wallet::signer::SignerError),
     #[error(transparent)]
@@ -158,7 +212,7 @@ pub enum WalletSignTransaction {
 impl ToStatus for WalletSignTransaction {
     fn builder(&self) -> StatusBuilder {
         match self {
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
             Self::SignerError(err) => StatusBuilder::new(err),
             Self::ExtractTx(err) => StatusBuilder::new(err),
             Self::UnableToSign => StatusBuilder::new(self),
@@ -191,6 +245,28 @@ pub enum WalletSync {
     #[error(transparent)]
     #[diagnostic(code(wallet_not_unlocked))]
     WalletNotUnlocked(#[from] NotUnlocked),
+    #[error("failed to sync wallet via {sync_source:?} chain source")]
+    #[diagnostic(code(chain_source_sync))]
+    ChainSource {
+        sync_source: WalletSyncSource,
+        source: Box<WalletSync>,
+    },
+    #[error(
+        "chain sources disagree on the block at height {height}: {sync_source:?} has {hash}, {other_sync_source:?} has {other_hash}"
+    )]
+    #[diagnostic(code(chain_source_tip_mismatch))]
+    TipMismatch {
+        height: u32,
+        sync_source: WalletSyncSource,
+        hash: bitcoin::BlockHash,
+        other_sync_source: WalletSyncSource,
+        other_hash: bitcoin::BlockHash,
+    },
+    #[error(
+        "tip cross-check requires at least 2 chain sources with a block hash at height {height}, but only {sources} responded"
+    )]
+    #[diagnostic(code(chain_source_tip_cross_check_insufficient))]
+    TipCrossCheckInsufficientSources { height: u32, sources: usize },
 }
 
 #[derive(Debug, Diagnostic, Error)]
@@ -375,6 +451,16 @@ pub enum InitDbConnection {
     Migration(#[from] rusqlite_migration::Error),
     #[error(transparent)]
     Rusqlite(#[from] rusqlite::Error),
+    #[error(
+        "wallet database schema version {version} is newer than the latest supported version {supported_version}"
+    )]
+    #[diagnostic(help(
+        "the wallet database was created by a newer version of the enforcer, downgrading is not supported"
+    ))]
+    SchemaTooNew {
+        version: usize,
+        supported_version: usize,
+    },
 }
 
 type Persistence = <crate::wallet::Persistence as bdk_wallet::AsyncWalletPersister>::Error;
@@ -416,6 +502,29 @@ impl ToStatus for InitWalletFromMnemonic {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum ValidateMnemonic {
+    #[error("failed to create wallet from mnemonic")]
+    CreateWallet(#[from] bdk_wallet::descriptor::DescriptorError),
+    #[error(transparent)]
+    InitFromMnemonic(Box<InitWalletFromMnemonic>),
+}
+
+impl From<InitWalletFromMnemonic> for ValidateMnemonic {
+    fn from(err: InitWalletFromMnemonic) -> Self {
+        Self::InitFromMnemonic(Box::new(err))
+    }
+}
+
+impl ToStatus for ValidateMnemonic {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CreateWallet(_) => StatusBuilder::new(self),
+            Self::InitFromMnemonic(err) => err.builder(),
+        }
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 pub enum CreateNewWallet {
     #[error(transparent)]
@@ -453,6 +562,10 @@ impl ToStatus for CreateNewWallet {
 
 #[derive(Debug, Diagnostic, Error)]
 pub enum InitWallet {
+    /// The supplied mnemonic does not match the wallet data directory
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    DataMismatch(#[from] DataMismatch),
     #[error("failed to initialize DB connection")]
     InitDbConnection(#[from] InitDbConnection),
     #[error("failed to initialize electrum client")]
@@ -461,12 +574,24 @@ pub enum InitWallet {
     InitEsploraClient(#[from] InitEsploraClient),
     #[error("failed to initialize wallet from mnemonic")]
     InitFromMnemonic(Box<InitWalletFromMnemonic>),
+    #[error("invalid fallback chain sync source: {:?}", .sync_source)]
+    #[diagnostic(code(invalid_fallback_sync_source))]
+    InvalidFallbackSyncSource { sync_source: WalletSyncSource },
+    #[error("duplicate chain sync source: {:?}", .sync_source)]
+    #[diagnostic(code(duplicate_sync_source))]
+    DuplicateSyncSource { sync_source: WalletSyncSource },
     #[error("failed to open connection to wallet DB")]
     OpenConnection(#[source] tokio_rusqlite::Error),
     #[error(transparent)]
     ParseNetwork(#[from] bitcoin::network::ParseNetworkError),
     #[error(transparent)]
     ReadDbMnemonic(#[from] ReadDbMnemonic),
+    #[error("wallet mnemonic is not encrypted, so the wallet cannot be explicitly unlocked")]
+    #[diagnostic(
+        code(wallet_require_unlock_not_encrypted),
+        help("either disable `--wallet-require-unlock`, or recreate the wallet with a password")
+    )]
+    RequireUnlockNotEncrypted,
 }
 
 impl From<InitWalletFromMnemonic> for InitWallet {
@@ -475,11 +600,33 @@ impl From<InitWalletFromMnemonic> for InitWallet {
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum InitialSync {
+    #[error(transparent)]
+    FullScan(#[from] FullScan),
+    #[error(transparent)]
+    NotUnlocked(#[from] NotUnlocked),
+    #[error(transparent)]
+    WalletSync(#[from] WalletSync),
+}
+
 #[derive(Debug, Diagnostic, Error)]
 pub enum FullScan {
     #[error(transparent)]
     WalletNotUnlocked(#[from] NotUnlocked),
 
+    #[error("wallet full scan was cancelled")]
+    #[diagnostic(code(full_scan_cancelled))]
+    Cancelled,
+
+    #[error("a wallet full scan is already in progress")]
+    #[diagnostic(code(full_scan_in_progress))]
+    InProgress,
+
+    #[error("no wallet chain sources are configured")]
+    #[diagnostic(code(full_scan_no_chain_sources))]
+    NoChainSources,
+
     #[error("failed to check for bitcoin address transactions")]
     #[diagnostic(code(check_address_transactions))]
     CheckAddressTransactions {
@@ -511,6 +658,13 @@ pub enum FullScan {
     #[error("chain sync source does not support full scan: {:?}", .sync_source)]
     #[diagnostic(code(invalid_sync_source))]
     InvalidSyncSource { sync_source: WalletSyncSource },
+
+    #[error("wallet full scan failed via {sync_source:?} chain source")]
+    #[diagnostic(code(chain_source_full_scan))]
+    ChainSource {
+        sync_source: WalletSyncSource,
+        source: Box<FullScan>,
+    },
 }
 
 #[derive(Debug, Diagnostic, Error)]
@@ -621,19 +775,39 @@ impl ToStatus for FetchTransaction {
     }
 }
 
+/// Change address is not owned by the wallet
+#[derive(Debug, Diagnostic, Error)]
+#[diagnostic(code(external_change_address))]
+#[error(
+    "change address `{address}` is not owned by the wallet, and external change is not allowed"
+)]
+pub struct ExternalChangeAddress {
+    pub address: bdk_wallet::bitcoin::Address,
+}
+
+impl ToStatus for ExternalChangeAddress {
+    fn builder(&self) -> StatusBuilder {
+        StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 pub enum CreateDepositPsbt {
     #[error(transparent)]
     AddForeignUtxo(#[from] bdk_wallet::AddForeignUtxoError),
     #[error("failed to create tx")]
     CreateTx(#[from] bdk_wallet::error::CreateTxError),
+    #[error(transparent)]
+    DefaultFeeRate(#[from] GetDefaultFeeRate),
+    #[error(transparent)]
+    ExternalChangeAddress(#[from] ExternalChangeAddress),
     #[error("failed to fetch transaction (`{txid}`)")]
     FetchTransaction {
         txid: bitcoin::Txid,
         source: FetchTransaction,
     },
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
     #[error("failed to parse sidechain number")]
     ParseSidechainNumber,
 }
@@ -642,10 +816,12 @@ impl ToStatus for CreateDepositPsbt {
     fn builder(&self) -> StatusBuilder {
         match self {
             Self::AddForeignUtxo(err) => StatusBuilder::new(err),
+            Self::DefaultFeeRate(err) => err.builder(),
+            Self::ExternalChangeAddress(err) => err.builder(),
             Self::FetchTransaction { source, .. } => {
                 StatusBuilder::with_code(self, source.builder())
             }
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
             Self::CreateTx(_) | Self::ParseSidechainNumber => StatusBuilder::new(self),
         }
     }
@@ -661,9 +837,24 @@ pub enum CreateDeposit {
     BroadcastUnsuccessful { txid: bitcoin::Txid },
     #[error("failed to convert sidechain address to PushBytesBuf")]
     ConvertSidechainAddress(#[source] bitcoin::script::PushBytesError),
+    #[error("deposit amount `{value}` is below the dust threshold `{dust_threshold}`")]
+    DepositBelowDust {
+        value: bitcoin::Amount,
+        dust_threshold: bitcoin::Amount,
+    },
+    #[error(
+        "deposit amount `{value}` is below the minimum `{minimum}` for sidechain {sidechain_number}"
+    )]
+    DepositBelowMinimum {
+        sidechain_number: SidechainNumber,
+        value: bitcoin::Amount,
+        minimum: bitcoin::Amount,
+    },
     #[error(transparent)]
     Psbt(#[from] CreateDepositPsbt),
     #[error(transparent)]
+    SidechainNotAllowed(#[from] SidechainNotAllowed),
+    #[error(transparent)]
     SignTransaction(#[from] WalletSignTransaction),
     #[error(transparent)]
     TryGetCtip(#[from] validator::TryGetCtipError),
@@ -678,7 +869,11 @@ impl ToStatus for CreateDeposit {
             | Self::BroadcastNonstandardTx(_)
             | Self::BroadcastUnsuccessful { .. }
             | Self::ConvertSidechainAddress(_) => StatusBuilder::new(self),
+            Self::DepositBelowDust { .. } | Self::DepositBelowMinimum { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
             Self::Psbt(err) => err.builder(),
+            Self::SidechainNotAllowed(err) => err.builder(),
             Self::SignTransaction(err) => err.builder(),
             Self::TryGetCtip(err) => err.builder(),
             Self::TryGetMainchainTipHeight(err) => err.builder(),
@@ -808,6 +1003,8 @@ impl From<rusqlite::Error> for ConnectBlock {
 
 #[derive(Debug, Diagnostic, Error)]
 pub(in crate::wallet) enum InitialBlockTemplateInner {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
     #[error(transparent)]
     CoinbaseMessages(#[from] CoinbaseMessagesError),
     #[error(transparent)]
@@ -818,6 +1015,8 @@ pub(in crate::wallet) enum InitialBlockTemplateInner {
     ),
     #[error(transparent)]
     GenerateCoinbaseTxouts(#[from] GenerateCoinbaseTxouts),
+    #[error("failed to persist sidechain acks")]
+    PersistSidechainAcks(#[source] rusqlite::Error),
 }
 
 #[derive(Debug, Diagnostic, Error)]
@@ -867,13 +1066,35 @@ where
 }
 
 #[derive(Debug, Diagnostic, Error)]
-pub enum FinalizeBlock {
+pub enum FinalizeCoinbase {
+    #[error("invalid coinbase reward split address")]
+    CoinbaseRewardAddress(#[source] bitcoin::address::ParseError),
     #[error(transparent)]
-    GetHeaderInfo(#[from] validator::GetHeaderInfoError),
-    #[error(transparent)]
-    GetMainchainTip(#[from] validator::GetMainchainTipError),
+    CoinbaseRewardShareBelowDust(#[from] crate::types::CoinbaseRewardShareBelowDust),
     #[error(transparent)]
     GetNewAddress(#[from] GetNewAddress),
+}
+
+impl ToStatus for FinalizeCoinbase {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CoinbaseRewardAddress(_) => {
+                StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
+            }
+            Self::CoinbaseRewardShareBelowDust(err) => {
+                StatusBuilder::new(err).code(tonic::Code::FailedPrecondition)
+            }
+            Self::GetNewAddress(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum FinalizeBlock {
+    #[error(transparent)]
+    FinalizeCoinbase(#[from] FinalizeCoinbase),
+    #[error(transparent)]
+    GetHeaderInfo(#[from] validator::GetHeaderInfoError),
     #[error(transparent)]
     Script(#[from] bitcoin::script::PushBytesError),
     #[error(transparent)]
@@ -883,9 +1104,8 @@ pub enum FinalizeBlock {
 impl ToStatus for FinalizeBlock {
     fn builder(&self) -> StatusBuilder {
         match self {
+            Self::FinalizeCoinbase(err) => err.builder(),
             Self::GetHeaderInfo(err) => err.builder(),
-            Self::GetMainchainTip(err) => err.builder(),
-            Self::GetNewAddress(err) => err.builder(),
             Self::Script(err) => StatusBuilder::new(err),
             Self::SystemTime(err) => StatusBuilder::new(err),
         }
@@ -1011,22 +1231,40 @@ impl ToStatus for GenerateSignetBlock {
 }
 
 #[derive(Diagnostic, Debug, Error)]
-pub enum GenerateBlock {
+pub enum BlockTemplate {
     #[error(transparent)]
     CoinbaseBuilder(#[from] CoinbaseMessagesError),
-    #[error("failed to delete BMM requests")]
-    DeleteBmmRequests(#[source] rusqlite::Error),
     #[error(transparent)]
     GenerateCoinbaseTxouts(#[from] GenerateCoinbaseTxouts),
     #[error(transparent)]
+    SelectBlockTxs(#[from] SelectBlockTxs),
+}
+
+impl ToStatus for BlockTemplate {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CoinbaseBuilder(err) => err.builder(),
+            Self::GenerateCoinbaseTxouts(err) => err.builder(),
+            Self::SelectBlockTxs(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Diagnostic, Debug, Error)]
+pub enum GenerateBlock {
+    #[error(transparent)]
+    BlockTemplate(#[from] BlockTemplate),
+    #[error("failed to delete BMM requests")]
+    DeleteBmmRequests(#[source] rusqlite::Error),
+    #[error(transparent)]
     GenerateSignetBlock(#[from] GenerateSignetBlock),
     #[error(transparent)]
     Mine(#[from] Mine),
+    #[error("failed to persist sidechain acks")]
+    PersistSidechainAcks(#[source] rusqlite::Error),
     #[error(transparent)]
     PushBytesBuf(#[from] bitcoin::script::PushBytesError),
     #[error(transparent)]
-    SelectBlockTxs(#[from] SelectBlockTxs),
-    #[error(transparent)]
     TryGetMainchainTip(#[from] validator::TryGetMainchainTipError),
     #[error("validator is not synced")]
     ValidatorNotSynced,
@@ -1035,32 +1273,102 @@ pub enum GenerateBlock {
 impl ToStatus for GenerateBlock {
     fn builder(&self) -> StatusBuilder {
         match self {
-            Self::CoinbaseBuilder(err) => err.builder(),
-            Self::GenerateCoinbaseTxouts(err) => err.builder(),
+            Self::BlockTemplate(err) => err.builder(),
             Self::GenerateSignetBlock(err) => err.builder(),
             Self::Mine(err) => err.builder(),
-            Self::SelectBlockTxs(err) => err.builder(),
             Self::TryGetMainchainTip(err) => err.builder(),
-            Self::DeleteBmmRequests(_) | Self::PushBytesBuf(_) | Self::ValidatorNotSynced => {
-                StatusBuilder::new(self)
+            Self::DeleteBmmRequests(_)
+            | Self::PersistSidechainAcks(_)
+            | Self::PushBytesBuf(_)
+            | Self::ValidatorNotSynced => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Diagnostic, Debug, Error)]
+pub enum GetBlockTemplate {
+    #[error(transparent)]
+    BlockTemplate(#[from] BlockTemplate),
+    #[error(transparent)]
+    TryGetMainchainTip(#[from] validator::TryGetMainchainTipError),
+    #[error("validator is not synced")]
+    ValidatorNotSynced,
+}
+
+impl ToStatus for GetBlockTemplate {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BlockTemplate(err) => err.builder(),
+            Self::TryGetMainchainTip(err) => err.builder(),
+            Self::ValidatorNotSynced => {
+                StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
             }
         }
     }
 }
 
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetNextBlockBits {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error("invalid compact target `{bits}` in block template")]
+    InvalidBits {
+        bits: String,
+        source: std::num::ParseIntError,
+    },
+    #[error("Bitcoin Core tip ({node_tip}) does not match the enforcer tip ({enforcer_tip})")]
+    TipMismatch {
+        enforcer_tip: bitcoin::BlockHash,
+        node_tip: bitcoin::BlockHash,
+    },
+}
+
+impl ToStatus for GetNextBlockBits {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::InvalidBits { .. } => StatusBuilder::new(self),
+            Self::TipMismatch { .. } => StatusBuilder::new(self).code(tonic::Code::Unavailable),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetCandidateBlock {
+    #[error(transparent)]
+    EncodeBlock(#[from] EncodeBlock),
+    #[error(transparent)]
+    FinalizeBlock(#[from] FinalizeBlock),
+    #[error(transparent)]
+    GetBlockTemplate(#[from] GetBlockTemplate),
+    #[error(transparent)]
+    GetNextBlockBits(#[from] GetNextBlockBits),
+}
+
+impl ToStatus for GetCandidateBlock {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::EncodeBlock(err) => err.builder(),
+            Self::FinalizeBlock(err) => err.builder(),
+            Self::GetBlockTemplate(err) => err.builder(),
+            Self::GetNextBlockBits(err) => err.builder(),
+        }
+    }
+}
+
 #[derive(Diagnostic, Debug, Error)]
 pub enum GetWalletBalance {
     #[error(transparent)]
     NotSynced(#[from] NotSynced),
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
 }
 
 impl ToStatus for GetWalletBalance {
     fn builder(&self) -> StatusBuilder {
         match self {
             Self::NotSynced(err) => err.builder(),
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
         }
     }
 }
@@ -1075,7 +1383,7 @@ pub enum ListWalletTransactions {
     #[error(transparent)]
     DeserializeHex(#[from] bitcoin::consensus::encode::FromHexError),
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
 }
 
 impl ToStatus for ListWalletTransactions {
@@ -1084,7 +1392,7 @@ impl ToStatus for ListWalletTransactions {
             Self::FetchTransaction { txid, source } => StatusBuilder::new(source)
                 .message(move |f| write!(f, "unable to fetch wallet transaction `{txid:?}`")),
             Self::DeserializeHex(err) => StatusBuilder::new(err),
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
         }
     }
 }
@@ -1095,10 +1403,14 @@ pub enum ListSidechainDepositTransactions {
     GetTreasuryUtxo(#[from] validator::GetTreasuryUtxoError),
     #[error(transparent)]
     ListWalletTransactions(#[from] ListWalletTransactions),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
     #[error(transparent)]
     TryGetCtip(#[from] validator::TryGetCtipError),
     #[error(transparent)]
     TryGetCtipValueSeq(#[from] validator::TryGetCtipValueSeqError),
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] validator::TryGetMainchainTipHeightError),
 }
 
 impl ToStatus for ListSidechainDepositTransactions {
@@ -1106,18 +1418,29 @@ impl ToStatus for ListSidechainDepositTransactions {
         match self {
             Self::GetTreasuryUtxo(err) => err.builder(),
             Self::ListWalletTransactions(err) => err.builder(),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
             Self::TryGetCtip(err) => err.builder(),
             Self::TryGetCtipValueSeq(err) => err.builder(),
+            Self::TryGetMainchainTipHeight(err) => err.builder(),
         }
     }
 }
 
 #[derive(Diagnostic, Debug, Error)]
 pub enum CreateSendPsbt {
+    #[error(transparent)]
+    AddForeignUtxo(#[from] bdk_wallet::AddForeignUtxoError),
+    #[diagnostic(code(create_send_transaction_change_address_with_drain_wallet))]
+    #[error("cannot set a change address when draining the wallet")]
+    ChangeAddressWithDrainWallet,
     #[error(transparent)]
     CreateTx(#[from] bdk_wallet::error::CreateTxError),
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    DefaultFeeRate(#[from] GetDefaultFeeRate),
+    #[error(transparent)]
+    ExternalChangeAddress(#[from] ExternalChangeAddress),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
     #[error(transparent)]
     Script(#[from] bitcoin::script::PushBytesError),
     #[diagnostic(code(create_send_transaction_add_utxo))]
@@ -1128,14 +1451,36 @@ pub enum CreateSendPsbt {
 impl ToStatus for CreateSendPsbt {
     fn builder(&self) -> StatusBuilder {
         match self {
-            Self::UnknownUTXO(_) => StatusBuilder::new(self).code(tonic::Code::InvalidArgument),
-            Self::NotUnlocked(err) => err.builder(),
+            Self::ChangeAddressWithDrainWallet | Self::UnknownUTXO(_) => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+            Self::DefaultFeeRate(err) => err.builder(),
+            Self::ExternalChangeAddress(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
+            Self::AddForeignUtxo(err) => StatusBuilder::new(err),
             Self::CreateTx(err) => StatusBuilder::new(err),
             Self::Script(err) => StatusBuilder::new(err),
         }
     }
 }
 
+#[derive(Diagnostic, Debug, Error)]
+pub enum CreateUnsignedSendPsbt {
+    #[error(transparent)]
+    CreateSendPsbt(#[from] CreateSendPsbt),
+    #[error("failed to read imported outputs")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for CreateUnsignedSendPsbt {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CreateSendPsbt(err) => err.builder(),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
 #[derive(Diagnostic, Debug, Error)]
 pub enum SendWalletTransaction {
     #[error("failed to broadcast tx")]
@@ -1149,7 +1494,7 @@ pub enum SendWalletTransaction {
     )]
     OpDrivechainNotSupported,
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
     #[error(transparent)]
     Persistence(#[from] Persistence),
 }
@@ -1160,39 +1505,201 @@ impl ToStatus for SendWalletTransaction {
             Self::CreateSendPsbt(err) => err.builder(),
             Self::SignTransaction(err) => err.builder(),
             Self::BroadcastTx(_) | Self::OpDrivechainNotSupported => StatusBuilder::new(self),
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
             Self::Persistence(err) => StatusBuilder::new(err),
         }
     }
 }
 
 #[derive(Debug, Diagnostic, Error)]
-pub enum BuildBmmTx {
-    #[error(transparent)]
-    CreateTx(#[from] bdk_wallet::error::CreateTxError),
-    #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
-    #[error(transparent)]
-    Script(#[from] bitcoin::script::PushBytesError),
+pub enum FetchTransactionFee {
+    #[error("failed to fetch previous transaction `{txid}`")]
+    FetchTransaction {
+        txid: bitcoin::Txid,
+        source: FetchTransaction,
+    },
+    #[error("missing previous output `{outpoint}`")]
+    MissingPrevout { outpoint: bitcoin::OutPoint },
+    #[error("output value `{output_value}` exceeds input value `{input_value}`")]
+    NegativeFee {
+        input_value: bitcoin::Amount,
+        output_value: bitcoin::Amount,
+    },
 }
 
-impl ToStatus for BuildBmmTx {
+impl ToStatus for FetchTransactionFee {
     fn builder(&self) -> StatusBuilder {
         match self {
-            Self::CreateTx(err) => StatusBuilder::new(err),
-            Self::NotUnlocked(err) => err.builder(),
-            Self::Script(err) => StatusBuilder::new(err),
+            Self::FetchTransaction { source, .. } => {
+                StatusBuilder::with_code(self, source.builder())
+            }
+            Self::MissingPrevout { .. } | Self::NegativeFee { .. } => StatusBuilder::new(self),
         }
     }
 }
 
 #[derive(Debug, Diagnostic, Error)]
-enum CreateBmmRequestInner {
-    #[error("failed to build BMM tx")]
-    BuildBmmTx(#[from] BuildBmmTx),
-    #[error("rusqlite error")]
-    Rusqlite(#[from] rusqlite::Error),
-    #[error("failed to sign BMM tx")]
+pub enum SendToAddress {
+    #[error("amount ({amount}) does not exceed the fee ({fee})")]
+    AmountNotAboveFee {
+        amount: bitcoin::Amount,
+        fee: bitcoin::Amount,
+    },
+    #[error(transparent)]
+    CreateSendPsbt(#[from] CreateSendPsbt),
+    #[error("fee did not converge after {builds} transaction builds")]
+    FeeDidNotConverge { builds: usize },
+    #[error("failed to compute send PSBT fee")]
+    PsbtFee(#[source] bdk_wallet::bitcoin::psbt::Error),
+    #[error(transparent)]
+    SendWalletTransaction(#[from] SendWalletTransaction),
+}
+
+impl ToStatus for SendToAddress {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::AmountNotAboveFee { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+            Self::CreateSendPsbt(err) => err.builder(),
+            Self::FeeDidNotConverge { .. } => StatusBuilder::new(self),
+            Self::PsbtFee(_) => StatusBuilder::new(self),
+            Self::SendWalletTransaction(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum CreateCpfp {
+    #[error("failed to broadcast tx")]
+    BroadcastTx(#[source] jsonrpsee::core::ClientError),
+    #[error("broadcast CPFP transaction failed: {txid}")]
+    BroadcastUnsuccessful { txid: bitcoin::Txid },
+    #[error(transparent)]
+    CreateTx(#[from] bdk_wallet::error::CreateTxError),
+    #[error("fee for parent transaction `{txid}` overflows at the target fee rate")]
+    FeeOverflow { txid: bitcoin::Txid },
+    #[error(transparent)]
+    FetchTransactionFee(#[from] FetchTransactionFee),
+    #[error("wallet has no spendable output from parent transaction `{txid}`")]
+    NoSpendableOutput { txid: bitcoin::Txid },
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error("parent transaction `{txid}` is already confirmed")]
+    ParentConfirmed { txid: bitcoin::Txid },
+    #[error("parent transaction `{txid}` already pays at least the target fee rate")]
+    ParentFeeRateSufficient { txid: bitcoin::Txid },
+    #[error(transparent)]
+    Persistence(#[from] Persistence),
+    #[error("failed to compute CPFP PSBT fee")]
+    PsbtFee(#[source] bdk_wallet::bitcoin::psbt::Error),
+    #[error(transparent)]
+    SignTransaction(#[from] WalletSignTransaction),
+    #[error("parent transaction `{txid}` not found in wallet")]
+    UnknownParentTx { txid: bitcoin::Txid },
+    #[error("UTXO is not in wallet (`{}:{}`)", .0.txid, .0.vout)]
+    UnknownUtxo(bitcoin::OutPoint),
+}
+
+impl ToStatus for CreateCpfp {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BroadcastTx(_)
+            | Self::BroadcastUnsuccessful { .. }
+            | Self::FeeOverflow { .. }
+            | Self::PsbtFee(_)
+            | Self::UnknownUtxo(_) => StatusBuilder::new(self),
+            Self::CreateTx(err) => StatusBuilder::new(err),
+            Self::FetchTransactionFee(err) => err.builder(),
+            Self::NoSpendableOutput { .. }
+            | Self::ParentConfirmed { .. }
+            | Self::ParentFeeRateSufficient { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
+            }
+            Self::LockWallet(err) => err.builder(),
+            Self::Persistence(err) => StatusBuilder::new(err),
+            Self::SignTransaction(err) => err.builder(),
+            Self::UnknownParentTx { .. } => StatusBuilder::new(self).code(tonic::Code::NotFound),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum EstimateConfirmationTime {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error(transparent)]
+    FetchTransactionFee(#[from] FetchTransactionFee),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error("transaction `{txid}` not found in wallet")]
+    UnknownTransaction { txid: bitcoin::Txid },
+}
+
+impl ToStatus for EstimateConfirmationTime {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::FetchTransactionFee(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
+            Self::UnknownTransaction { .. } => StatusBuilder::new(self).code(tonic::Code::NotFound),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum PreviewCoinSelection {
+    #[error(transparent)]
+    CreateTx(bdk_wallet::error::CreateTxError),
+    #[diagnostic(code(preview_coin_selection_insufficient_funds))]
+    #[error(transparent)]
+    InsufficientFunds(bdk_wallet::coin_selection::InsufficientFunds),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error("failed to compute PSBT fee")]
+    PsbtFee(#[source] bdk_wallet::bitcoin::psbt::Error),
+}
+
+impl ToStatus for PreviewCoinSelection {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CreateTx(err) => StatusBuilder::new(err),
+            Self::InsufficientFunds(_) => {
+                StatusBuilder::new(self).code(tonic::Code::FailedPrecondition)
+            }
+            Self::LockWallet(err) => err.builder(),
+            Self::PsbtFee(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum BuildBmmTx {
+    #[error(transparent)]
+    CreateTx(#[from] bdk_wallet::error::CreateTxError),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error(transparent)]
+    Script(#[from] bitcoin::script::PushBytesError),
+}
+
+impl ToStatus for BuildBmmTx {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::CreateTx(err) => StatusBuilder::new(err),
+            Self::LockWallet(err) => err.builder(),
+            Self::Script(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+enum CreateBmmRequestInner {
+    #[error("failed to build BMM tx")]
+    BuildBmmTx(#[from] BuildBmmTx),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error("failed to sign BMM tx")]
     SignTx(#[from] WalletSignTransaction),
 }
 
@@ -1229,7 +1736,7 @@ impl ToStatus for CreateBmmRequest {
 #[derive(Debug, Diagnostic, Error)]
 pub enum GetNewAddress {
     #[error(transparent)]
-    NotUnlocked(#[from] NotUnlocked),
+    LockWallet(#[from] LockWallet),
     #[error(transparent)]
     Persistence(#[from] Persistence),
 }
@@ -1237,8 +1744,477 @@ pub enum GetNewAddress {
 impl ToStatus for GetNewAddress {
     fn builder(&self) -> StatusBuilder {
         match self {
-            Self::NotUnlocked(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
+            Self::Persistence(err) => StatusBuilder::new(err),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetUnusedAddress {
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for GetUnusedAddress {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::LockWallet(err) => err.builder(),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetSidechainDepositAddress {
+    #[error("invalid deposit address `{address}` stored for sidechain {sidechain_number}")]
+    InvalidStoredAddress {
+        sidechain_number: SidechainNumber,
+        address: String,
+    },
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error(transparent)]
+    Persistence(#[from] Persistence),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for GetSidechainDepositAddress {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::InvalidStoredAddress { .. } => StatusBuilder::new(self),
+            Self::LockWallet(err) => err.builder(),
             Self::Persistence(err) => StatusBuilder::new(err),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+/// Transaction rejected by Bitcoin Core
+#[derive(Debug, Diagnostic, Error)]
+#[diagnostic(
+    code(bitcoin_core_reject),
+    help("The transaction was rejected by Bitcoin Core. Check the message for more details.")
+)]
+#[error("Bitcoin Core rejected transaction `{code}`: `{message}`")]
+pub struct BitcoinCoreReject {
+    pub code: i32,
+    pub message: String,
+}
+
+impl ToStatus for BitcoinCoreReject {
+    fn builder(&self) -> StatusBuilder {
+        StatusBuilder::new(self).code(match self.code {
+            // https://github.com/bitcoin/bitcoin/blob/e8f72aefd20049eac81b150e7f0d33709acd18ed/src/rpc/protocol.h
+            // RPC_VERIFY_ERROR, RPC_VERIFY_REJECTED
+            -25 | -26 => tonic::Code::InvalidArgument,
+            // RPC_VERIFY_ALREADY_IN_UTXO_SET
+            -27 => tonic::Code::AlreadyExists,
+            _ => tonic::Code::Unknown,
+        })
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum BroadcastRawTransaction {
+    #[error(transparent)]
+    BitcoinCoreReject(#[from] BitcoinCoreReject),
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error(transparent)]
+    CheckTx(#[from] validator::CheckTxError),
+    #[error("transaction rejected by enforcer: `{reason}`")]
+    RejectedByEnforcer { reason: String },
+}
+
+impl ToStatus for BroadcastRawTransaction {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BitcoinCoreReject(err) => err.builder(),
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::CheckTx(err) => StatusBuilder::new(err),
+            Self::RejectedByEnforcer { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+        }
+    }
+}
+
+/// Errors from operations run with an idempotency key
+#[derive(Debug, Error)]
+pub enum Idempotent<Err> {
+    #[error(transparent)]
+    Operation(Err),
+    #[error(
+        "idempotency key `{idempotency_key}` was already used for `{method}` with different params"
+    )]
+    ParamsMismatch {
+        method: &'static str,
+        idempotency_key: String,
+    },
+    #[error("a `{method}` request with idempotency key `{idempotency_key}` is already in progress")]
+    InProgress {
+        method: &'static str,
+        idempotency_key: String,
+    },
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error("failed to (de)serialize idempotent operation result")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+impl<Err> ToStatus for Idempotent<Err>
+where
+    Err: ToStatus,
+{
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Operation(err) => err.builder(),
+            Self::ParamsMismatch { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+            Self::InProgress { .. } => StatusBuilder::new(self).code(tonic::Code::Aborted),
+            Self::Rusqlite(_) | Self::SerdeJson(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ProposeSidechain {
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] validator::TryGetMainchainTipHeightError),
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ListPendingWithdrawals {
+    #[error(transparent)]
+    GetBundleProposals(#[from] GetBundleProposals),
+    #[error(transparent)]
+    GetPendingWithdrawals(#[from] validator::GetPendingWithdrawalsError),
+    #[error(transparent)]
+    GetSidechains(#[from] validator::GetSidechainsError),
+}
+
+impl ToStatus for ListPendingWithdrawals {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::GetBundleProposals(err) => err.builder(),
+            Self::GetPendingWithdrawals(err) => err.builder(),
+            Self::GetSidechains(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ValidateBundleAgainstPending {
+    #[error(transparent)]
+    ListPendingWithdrawals(#[from] ListPendingWithdrawals),
+    #[error("`{m6id}` is not a pending bundle for sidechain {sidechain_number}")]
+    NotPending {
+        m6id: M6id,
+        sidechain_number: SidechainNumber,
+    },
+    #[error("the wallet does not have the bundle transaction for pending bundle `{m6id}`")]
+    UnknownBundle { m6id: M6id },
+}
+
+impl ToStatus for ValidateBundleAgainstPending {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::ListPendingWithdrawals(err) => err.builder(),
+            Self::NotPending { .. } | Self::UnknownBundle { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::NotFound)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum RecordBlockFeeRates {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error("failed to serialize mempool fee rate buckets")]
+    SerializeFeeRateBuckets(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetBlockFees {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error("block `{block_hash}` has not been processed by the validator")]
+    BlockNotProcessed { block_hash: bitcoin::BlockHash },
+    #[error(
+        "block at height {height} has not been processed by the validator (tip height: {})",
+        .tip_height.map_or("none".to_owned(), |tip_height| tip_height.to_string())
+    )]
+    HeightNotProcessed {
+        height: u32,
+        tip_height: Option<u32>,
+    },
+    #[error(transparent)]
+    TryGetBlockHashAtHeight(#[from] validator::TryGetBlockHashAtHeightError),
+    #[error(transparent)]
+    TryGetBlockInfos(#[from] validator::TryGetBlockInfosError),
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] validator::TryGetMainchainTipHeightError),
+}
+
+impl ToStatus for GetBlockFees {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::BlockNotProcessed { .. } | Self::HeightNotProcessed { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::NotFound)
+            }
+            Self::TryGetBlockHashAtHeight(err) => err.builder(),
+            Self::TryGetBlockInfos(err) => StatusBuilder::new(err),
+            Self::TryGetMainchainTipHeight(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetFeeHistory {
+    #[error("failed to deserialize mempool fee rate buckets")]
+    DeserializeFeeRateBuckets(#[from] serde_json::Error),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for GetFeeHistory {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::DeserializeFeeRateBuckets(_) | Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ConsolidateCoinbaseOutputs {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error(transparent)]
+    GetNewAddress(#[from] GetNewAddress),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[error(transparent)]
+    SendWalletTransaction(#[from] SendWalletTransaction),
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetDefaultFeeRate {
+    #[error("failed to read default fee rate")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for GetDefaultFeeRate {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum SetDefaultFeeRate {
+    #[error(
+        "default fee rate `{sat_per_vb}` sat/vB is below the minimum relay fee rate `{min_relay_fee_rate}` sat/vB"
+    )]
+    BelowMinRelayFee {
+        sat_per_vb: u64,
+        min_relay_fee_rate: u64,
+    },
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error("default fee rate `{sat_per_vb}` sat/vB is too large")]
+    Overflow { sat_per_vb: u64 },
+    #[error("failed to write default fee rate")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for SetDefaultFeeRate {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BelowMinRelayFee { .. } | Self::Overflow { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum GetCtipStatus {
+    #[error("sidechain {sidechain_number} has no Ctip")]
+    NoCtip { sidechain_number: SidechainNumber },
+    #[error(transparent)]
+    TryGetCtip(#[from] validator::TryGetCtipError),
+}
+
+impl ToStatus for GetCtipStatus {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::NoCtip { .. } => StatusBuilder::new(self).code(tonic::Code::NotFound),
+            Self::TryGetCtip(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ListWithdrawalBundleEvents {
+    #[error("invalid withdrawal bundle status in DB (`{status}`)")]
+    InvalidStatus { status: String },
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+impl ToStatus for ListWithdrawalBundleEvents {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::InvalidStatus { .. } | Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ListPendingSidechainProposals {
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] validator::TryGetMainchainTipHeightError),
+}
+
+impl ToStatus for ListPendingSidechainProposals {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+            Self::TryGetMainchainTipHeight(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum DumpDiagnostics {
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    WalletBusy(#[from] WalletBusy),
+}
+
+impl ToStatus for DumpDiagnostics {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+            Self::WalletBusy(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ImportOutpoint {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error(transparent)]
+    LockWallet(#[from] LockWallet),
+    #[diagnostic(code(import_outpoint_mismatch))]
+    #[error(
+        "output `{outpoint}` does not match the imported output: it pays {amount} to `{script_pubkey}`"
+    )]
+    Mismatch {
+        outpoint: bitcoin::OutPoint,
+        script_pubkey: bitcoin::ScriptBuf,
+        amount: bitcoin::Amount,
+    },
+    #[diagnostic(code(import_outpoint_not_unspent))]
+    #[error("output `{outpoint}` does not exist or is spent")]
+    NotUnspent { outpoint: bitcoin::OutPoint },
+    #[diagnostic(code(import_outpoint_owned_by_wallet))]
+    #[error("output `{outpoint}` belongs to the wallet, and cannot be imported")]
+    OwnedByWallet { outpoint: bitcoin::OutPoint },
+    #[error("failed to store imported output")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[diagnostic(
+        code(import_outpoint_unsupported_script),
+        help("only P2WPKH and P2TR outputs can be imported")
+    )]
+    #[error("unsupported script pubkey for imported output (`{script_pubkey}`)")]
+    UnsupportedScript { script_pubkey: bitcoin::ScriptBuf },
+}
+
+impl ToStatus for ImportOutpoint {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::BitcoinCoreRPC(err) => err.builder(),
+            Self::LockWallet(err) => err.builder(),
+            Self::Mismatch { .. }
+            | Self::NotUnspent { .. }
+            | Self::OwnedByWallet { .. }
+            | Self::UnsupportedScript { .. } => {
+                StatusBuilder::new(self).code(tonic::Code::InvalidArgument)
+            }
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum PruneImportedUtxos {
+    #[error(transparent)]
+    BitcoinCoreRPC(#[from] BitcoinCoreRPC),
+    #[error("failed to prune imported outputs")]
+    Rusqlite(#[from] rusqlite::Error),
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum PutWithdrawalBundle {
+    #[error("failed to write withdrawal bundle")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    SidechainNotAllowed(#[from] SidechainNotAllowed),
+}
+
+impl ToStatus for PutWithdrawalBundle {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+            Self::SidechainNotAllowed(err) => err.builder(),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ReconcileProposals {
+    #[error(transparent)]
+    GetPendingWithdrawals(#[from] validator::GetPendingWithdrawalsError),
+    #[error(transparent)]
+    GetSidechains(#[from] validator::GetSidechainsError),
+    #[error(transparent)]
+    ListPendingSidechainProposals(#[from] ListPendingSidechainProposals),
+    #[error("rusqlite error")]
+    Rusqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    TryGetActiveSidechain(#[from] validator::TryGetActiveSidechainError),
+    #[error(transparent)]
+    TryGetMainchainTipHeight(#[from] validator::TryGetMainchainTipHeightError),
+}
+
+impl ToStatus for ReconcileProposals {
+    fn builder(&self) -> StatusBuilder {
+        match self {
+            Self::GetPendingWithdrawals(err) => err.builder(),
+            Self::GetSidechains(err) => err.builder(),
+            Self::ListPendingSidechainProposals(err) => err.builder(),
+            Self::Rusqlite(_) => StatusBuilder::new(self),
+            Self::TryGetActiveSidechain(err) => err.builder(),
+            Self::TryGetMainchainTipHeight(err) => err.builder(),
         }
     }
 }
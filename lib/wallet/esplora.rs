@@ -0,0 +1,295 @@
+//! Rate-limited Esplora client
+
+use std::{borrow::Borrow, future::Future, num::NonZeroU32, ops::Deref, time::Duration};
+
+use bdk_chain::bdk_core::{
+    CheckPoint,
+    spk_client::{SyncRequest, SyncResponse},
+};
+use bdk_esplora::{EsploraAsyncExt as _, esplora_client};
+use bdk_wallet::KeychainKind;
+use tokio::time::Instant;
+
+use crate::{backoff, errors::ErrorChain};
+
+/// Retry requests that are rejected with 429 (Too Many Requests) up to this
+/// many times, after the Esplora client's own retries are exhausted
+const MAX_RATE_LIMITED_RETRIES: u32 = 5;
+
+/// Wait before retrying a rate limited request, if the server does not send
+/// a `Retry-After` header. Doubled after each retry, up to
+/// [`RATE_LIMITED_MAX_BACKOFF`].
+const RATE_LIMITED_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const RATE_LIMITED_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn is_rate_limited(err: &esplora_client::Error) -> bool {
+    matches!(err, esplora_client::Error::HttpResponse { status: 429, .. })
+}
+
+/// Token bucket rate limiter, implemented as a generic cell rate algorithm.
+/// Allows bursts of up to one second's worth of requests.
+struct RateLimiter {
+    /// Time between requests, at the sustained rate
+    interval: Duration,
+    /// Maximum time that requests may run ahead of the sustained rate
+    burst: Duration,
+    /// Time at which the bucket will be full again, if no more requests
+    /// are made
+    theoretical_arrival: tokio::sync::Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: NonZeroU32) -> Self {
+        let interval = Duration::from_secs(1) / requests_per_second.get();
+        Self {
+            interval,
+            burst: interval * requests_per_second.get(),
+            theoretical_arrival: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until `requests` requests can be made
+    async fn acquire(&self, requests: u32) {
+        let ready_at = {
+            let mut theoretical_arrival = self.theoretical_arrival.lock().await;
+            let now = Instant::now();
+            let new_theoretical_arrival =
+                std::cmp::max(*theoretical_arrival, now) + self.interval * requests;
+            *theoretical_arrival = new_theoretical_arrival;
+            new_theoretical_arrival
+                .checked_sub(self.burst)
+                .unwrap_or(now)
+        };
+        if ready_at > Instant::now() {
+            tracing::trace!(
+                requests,
+                wait = ?ready_at - Instant::now(),
+                "esplora: waiting for rate limiter"
+            );
+            tokio::time::sleep_until(ready_at).await;
+        }
+    }
+
+    /// Delay all requests for at least `duration`, eg. after the server
+    /// rejected a request with `Retry-After`
+    async fn pause(&self, duration: Duration) {
+        let mut theoretical_arrival = self.theoretical_arrival.lock().await;
+        let paused_until = Instant::now() + duration + self.burst;
+        *theoretical_arrival = std::cmp::max(*theoretical_arrival, paused_until);
+    }
+}
+
+/// Esplora client, with an optional rate limit shared by all requests made
+/// via the client
+pub(in crate::wallet) struct EsploraClient {
+    client: bdk_esplora::esplora_client::AsyncClient,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl EsploraClient {
+    pub(in crate::wallet) fn new(
+        client: bdk_esplora::esplora_client::AsyncClient,
+        requests_per_second: Option<NonZeroU32>,
+    ) -> Self {
+        Self {
+            client,
+            rate_limiter: requests_per_second.map(RateLimiter::new),
+        }
+    }
+
+    /// Wait until `requests` requests can be made without exceeding the
+    /// rate limit. Returns immediately if there is no rate limit.
+    async fn rate_limit(&self, requests: usize) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let requests = u32::try_from(requests).unwrap_or(u32::MAX);
+            rate_limiter.acquire(requests).await
+        }
+    }
+
+    /// Time to wait before retrying a request that was rejected with 429,
+    /// according to the server's `Retry-After` header, in seconds.
+    /// The Esplora client does not expose response headers, so this makes
+    /// another request to the server. Returns zero if that request is not
+    /// rate limited, and `None` if the server does not send `Retry-After`.
+    async fn retry_after(&self) -> Option<Duration> {
+        let url = format!("{}/blocks/tip/height", self.client.url());
+        let response = self.client.client().get(url).send().await.ok()?;
+        if response.status().as_u16() != 429 {
+            return Some(Duration::ZERO);
+        }
+        let retry_after_secs = response
+            .headers()
+            .get("retry-after")?
+            .to_str()
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        Some(Duration::from_secs(retry_after_secs))
+    }
+
+    /// Run `request`, retrying if it is rejected with 429 (Too Many
+    /// Requests). Before each retry, waits for the server's `Retry-After`,
+    /// or with exponential backoff if the server does not send one. If
+    /// there is a rate limit, all requests via the client are paused while
+    /// waiting.
+    async fn retry_rate_limited<T, E, F, Fut>(&self, mut request: F) -> Result<T, E>
+    where
+        E: Borrow<esplora_client::Error>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut retries: u32 = 0;
+        loop {
+            let err = match request().await {
+                Err(err) if retries < MAX_RATE_LIMITED_RETRIES && is_rate_limited(err.borrow()) => {
+                    err
+                }
+                res => return res,
+            };
+            let err: &esplora_client::Error = err.borrow();
+            let wait = match self.retry_after().await {
+                Some(retry_after) => retry_after,
+                None => backoff::exponential_backoff(
+                    RATE_LIMITED_INITIAL_BACKOFF,
+                    retries,
+                    RATE_LIMITED_MAX_BACKOFF,
+                ),
+            };
+            retries += 1;
+            tracing::debug!(
+                attempt = retries,
+                ?wait,
+                "esplora: rate limited, retrying after wait: {:#}",
+                ErrorChain::new(err)
+            );
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.pause(wait).await;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Make a single request, waiting for the rate limit first. Requests
+    /// rejected with 429 are retried, see [`Self::retry_rate_limited`].
+    pub(in crate::wallet) async fn rate_limited<T, F, Fut>(
+        &self,
+        request: F,
+    ) -> Result<T, esplora_client::Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, esplora_client::Error>>,
+    {
+        let request = &request;
+        self.retry_rate_limited(move || async move {
+            self.rate_limit(1).await;
+            request().await
+        })
+        .await
+    }
+
+    /// Sync via Esplora, making up to `max_parallel_requests` requests at
+    /// once. If there is a rate limit, the request is split into chunks that
+    /// fit in the rate limit burst size, and tokens are acquired for each
+    /// chunk before it is synced, so that requests are spread out at the
+    /// sustained rate instead of being made in a single burst.
+    /// Chunks rejected with 429 are retried, see
+    /// [`Self::retry_rate_limited`].
+    pub(in crate::wallet) async fn rate_limited_sync(
+        &self,
+        mut request: SyncRequest<(KeychainKind, u32)>,
+        max_parallel_requests: usize,
+    ) -> Result<SyncResponse, Box<esplora_client::Error>> {
+        let parallel_requests = self.parallel_requests(max_parallel_requests);
+        // Without a rate limit, the request is synced in a single chunk
+        let chunk_size = if self.rate_limiter.is_some() {
+            parallel_requests
+        } else {
+            usize::MAX
+        };
+        let chain_tip = request.chain_tip();
+        let spks: Vec<_> = std::iter::from_fn(|| request.next_spk_with_index()).collect();
+        let txids: Vec<_> = std::iter::from_fn(|| request.next_txid()).collect();
+        let outpoints: Vec<_> = std::iter::from_fn(|| request.next_outpoint()).collect();
+        let mut spks = spks.into_iter();
+        let mut txids = txids.into_iter();
+        let mut outpoints = outpoints.into_iter();
+        let mut response: Option<SyncResponse> = None;
+        loop {
+            // Fill the chunk with script pubkeys first, then txids, then
+            // outpoints. Each needs at least one request.
+            let chunk_spks: Vec<_> = spks.by_ref().take(chunk_size).collect();
+            let remaining = chunk_size - chunk_spks.len();
+            let chunk_txids: Vec<_> = txids.by_ref().take(remaining).collect();
+            let remaining = remaining - chunk_txids.len();
+            let chunk_outpoints: Vec<_> = outpoints.by_ref().take(remaining).collect();
+            let chunk_items = chunk_spks.len() + chunk_txids.len() + chunk_outpoints.len();
+            let mut chunk_response = self
+                .retry_rate_limited(|| {
+                    let mut chunk_request = SyncRequest::builder()
+                        .spks_with_indexes(chunk_spks.clone())
+                        .txids(chunk_txids.clone())
+                        .outpoints(chunk_outpoints.clone());
+                    if let Some(chain_tip) = &chain_tip {
+                        chunk_request = chunk_request.chain_tip(chain_tip.clone());
+                    }
+                    async move {
+                        // One more request for the chain tip
+                        self.rate_limit(chunk_items + 1).await;
+                        self.client
+                            .sync(chunk_request.build(), parallel_requests)
+                            .await
+                    }
+                })
+                .await?;
+            if let Some(response) = response {
+                chunk_response.tx_update.extend(response.tx_update);
+                chunk_response.chain_update =
+                    merge_chain_updates(response.chain_update, chunk_response.chain_update);
+            }
+            if chunk_items < chunk_size {
+                return Ok(chunk_response);
+            }
+            response = Some(chunk_response);
+        }
+    }
+
+    /// Number of parallel requests to make, no greater than `max`, or the
+    /// rate limit burst size
+    pub(in crate::wallet) fn parallel_requests(&self, max: usize) -> usize {
+        match &self.rate_limiter {
+            Some(rate_limiter) => {
+                let burst_size = rate_limiter.burst.as_nanos() / rate_limiter.interval.as_nanos();
+                std::cmp::min(max, burst_size as usize).max(1)
+            }
+            None => max,
+        }
+    }
+}
+
+/// Merge chain updates from successive chunks of a sync. Blocks from the
+/// later update take precedence, so that a reorg between chunks is reflected.
+fn merge_chain_updates(
+    earlier: Option<CheckPoint>,
+    later: Option<CheckPoint>,
+) -> Option<CheckPoint> {
+    match (earlier, later) {
+        (Some(earlier), Some(later)) => Some(earlier.iter().fold(later, |merged, checkpoint| {
+            if merged.get(checkpoint.height()).is_some() {
+                merged
+            } else {
+                merged.insert(checkpoint.block_id())
+            }
+        })),
+        (earlier, later) => later.or(earlier),
+    }
+}
+
+impl Deref for EsploraClient {
+    type Target = bdk_esplora::esplora_client::AsyncClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
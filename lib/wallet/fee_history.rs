@@ -0,0 +1,316 @@
+//! Fee rates of connected blocks, and of the mempool when they were
+//! connected
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::{Amount, BlockHash, hashes::Hash as _};
+use bitcoin_jsonrpsee::jsonrpsee::{core::client::ClientT as _, rpc_params};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::wallet::{Wallet, WalletInner, error};
+
+/// Subset of the `getblockstats` response.
+/// Fee rates are in sat/vB.
+#[derive(Debug, Deserialize)]
+struct BlockStats {
+    blockhash: BlockHash,
+    height: u32,
+    /// Excludes the coinbase transaction
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    totalfee: Amount,
+    /// Includes the coinbase transaction
+    txs: u64,
+    minfeerate: u64,
+    maxfeerate: u64,
+    /// 10th, 25th, 50th, 75th, and 90th percentiles
+    feerate_percentiles: [u64; 5],
+}
+
+/// Block specified by either hash or height
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BlockHashOrHeight {
+    Hash(BlockHash),
+    Height(u32),
+}
+
+/// Fees paid by the non-coinbase transactions in a block.
+/// Fee rates are in sat/vB, and are zero if the block contains no
+/// transactions other than the coinbase.
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockFees {
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub total_fee: Amount,
+    /// Number of non-coinbase transactions
+    pub tx_count: u64,
+    pub min_fee_rate: u64,
+    /// 10th, 25th, 50th, 75th, and 90th percentiles
+    pub fee_rate_percentiles: [u64; 5],
+    pub max_fee_rate: u64,
+}
+
+/// Fee rates paid by the non-coinbase transactions in a block, in sat/vB.
+/// Fee rates are zero if the block contains no transactions other than the
+/// coinbase.
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockFeeRates {
+    pub block_hash: BlockHash,
+    pub height: u32,
+    pub min_fee_rate: u64,
+    pub median_fee_rate: u64,
+    pub max_fee_rate: u64,
+}
+
+/// Total vsize of mempool transactions paying a fee rate, in sat/vB
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct FeeRateBucket {
+    pub fee_rate: u64,
+    pub vsize: u64,
+}
+
+/// Fee rates of mempool transactions when a block was connected
+#[derive(Clone, Debug, Serialize)]
+pub struct MempoolFeeSnapshot {
+    pub block_hash: BlockHash,
+    pub height: u32,
+    /// Unix timestamp, in seconds
+    pub recorded_at: u64,
+    /// In descending order of fee rate
+    pub fee_rate_buckets: Vec<FeeRateBucket>,
+}
+
+impl WalletInner {
+    async fn fetch_block_stats(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<BlockStats, error::BitcoinCoreRPC> {
+        self.main_client
+            .request(
+                "getblockstats",
+                rpc_params![
+                    block_hash,
+                    [
+                        "blockhash",
+                        "height",
+                        "totalfee",
+                        "txs",
+                        "minfeerate",
+                        "maxfeerate",
+                        "feerate_percentiles"
+                    ]
+                ],
+            )
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "getblockstats".to_string(),
+                error: err,
+            })
+    }
+
+    /// Record the fee rates for a connected block, and a snapshot of the
+    /// mempool fee rates if the block is the mainchain tip. Records that are
+    /// outside of the fee history window are deleted.
+    /// Blocks that are outside of the fee history window of the mainchain
+    /// tip, eg. during initial sync, are skipped.
+    pub(in crate::wallet) async fn record_block_fee_rates(
+        &self,
+        block_hash: BlockHash,
+        block_height: u32,
+    ) -> Result<(), error::RecordBlockFeeRates> {
+        let window = self.config.wallet_opts.fee_history_window.get();
+        let sync_target_height = self
+            .validator
+            .block_sync_progress()
+            .map(|progress| progress.target_height);
+        if let Some(sync_target_height) = sync_target_height
+            && sync_target_height.saturating_sub(block_height) >= window
+        {
+            return Ok(());
+        }
+        let stats = self.fetch_block_stats(block_hash).await?;
+        // The mempool is only relevant to the tip
+        let mempool_snapshot = if sync_target_height.is_none_or(|height| height == block_height) {
+            let fee_rate_buckets: Vec<_> = self
+                .fetch_fee_histogram(None)
+                .await?
+                .buckets()
+                .iter()
+                .map(|(fee_rate, vsize)| FeeRateBucket {
+                    fee_rate: *fee_rate,
+                    vsize: *vsize,
+                })
+                .collect();
+            Some(serde_json::to_string(&fee_rate_buckets)?)
+        } else {
+            None
+        };
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let tx = connection.unchecked_transaction()?;
+            // Records at or above this height have been reorged out
+            for table in ["block_fee_rates", "mempool_fee_snapshots"] {
+                let _ = tx.execute(
+                    &format!("DELETE FROM {table} WHERE height >= ?1;"),
+                    [block_height],
+                )?;
+            }
+            let _ = tx.execute(
+                "INSERT INTO block_fee_rates
+                 (height, block_hash, min_fee_rate, median_fee_rate, max_fee_rate)
+                 VALUES (?1, ?2, ?3, ?4, ?5);",
+                (
+                    block_height,
+                    block_hash.as_byte_array(),
+                    stats.minfeerate,
+                    stats.feerate_percentiles[2],
+                    stats.maxfeerate,
+                ),
+            )?;
+            if let Some(fee_rate_buckets) = &mempool_snapshot {
+                let _ = tx.execute(
+                    "INSERT INTO mempool_fee_snapshots
+                     (height, block_hash, recorded_at, fee_rate_buckets)
+                     VALUES (?1, ?2, ?3, ?4);",
+                    (
+                        block_height,
+                        block_hash.as_byte_array(),
+                        recorded_at,
+                        fee_rate_buckets,
+                    ),
+                )?;
+            }
+            if let Some(max_expired_height) = block_height.checked_sub(window) {
+                for table in ["block_fee_rates", "mempool_fee_snapshots"] {
+                    let _ = tx.execute(
+                        &format!("DELETE FROM {table} WHERE height <= ?1;"),
+                        [max_expired_height],
+                    )?;
+                }
+            }
+            tx.commit()
+        };
+        let connection = self.self_db.lock().await;
+        with_connection(&connection)?;
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Fees paid in a block that has been processed by the validator
+    pub async fn get_block_fees(
+        &self,
+        block: BlockHashOrHeight,
+    ) -> Result<BlockFees, error::GetBlockFees> {
+        let block_hash = match block {
+            BlockHashOrHeight::Hash(block_hash) => {
+                if self
+                    .validator()
+                    .try_get_block_infos(&block_hash, 0)?
+                    .is_none()
+                {
+                    return Err(error::GetBlockFees::BlockNotProcessed { block_hash });
+                }
+                block_hash
+            }
+            // Core's block at this height may differ from the block that
+            // was processed by the validator
+            BlockHashOrHeight::Height(height) => {
+                match self.validator().try_get_block_hash_at_height(height)? {
+                    Some(block_hash) => block_hash,
+                    None => {
+                        let tip_height = self.validator().try_get_block_height()?;
+                        return Err(error::GetBlockFees::HeightNotProcessed { height, tip_height });
+                    }
+                }
+            }
+        };
+        let stats = self.inner.fetch_block_stats(block_hash).await?;
+        Ok(BlockFees {
+            block_hash: stats.blockhash,
+            height: stats.height,
+            total_fee: stats.totalfee,
+            tx_count: stats.txs.saturating_sub(1),
+            min_fee_rate: stats.minfeerate,
+            fee_rate_percentiles: stats.feerate_percentiles,
+            max_fee_rate: stats.maxfeerate,
+        })
+    }
+
+    /// Fee rates for up to `lookback_blocks` of the most recent connected
+    /// blocks, in ascending order of height
+    pub async fn get_fee_history(
+        &self,
+        lookback_blocks: u32,
+    ) -> Result<Vec<BlockFeeRates>, error::GetFeeHistory> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, error::GetFeeHistory> {
+            let mut statement = connection.prepare(
+                "SELECT height, block_hash, min_fee_rate, median_fee_rate, max_fee_rate
+                 FROM block_fee_rates ORDER BY height DESC LIMIT ?1;",
+            )?;
+            let rows = statement.query_map([lookback_blocks], |row| {
+                let block_hash: [u8; 32] = row.get(1)?;
+                Ok(BlockFeeRates {
+                    height: row.get(0)?,
+                    block_hash: BlockHash::from_byte_array(block_hash),
+                    min_fee_rate: row.get(2)?,
+                    median_fee_rate: row.get(3)?,
+                    max_fee_rate: row.get(4)?,
+                })
+            })?;
+            let mut res = rows.collect::<Result<Vec<_>, _>>()?;
+            res.reverse();
+            Ok(res)
+        };
+        let connection = self.inner.self_db_readers.lock().await;
+        with_connection(&connection)
+    }
+
+    /// Snapshots of mempool fee rates, taken when each of up to
+    /// `lookback_blocks` of the most recent blocks was connected, in
+    /// ascending order of height. Blocks that were connected during initial
+    /// sync do not have snapshots.
+    pub async fn get_mempool_fee_history(
+        &self,
+        lookback_blocks: u32,
+    ) -> Result<Vec<MempoolFeeSnapshot>, error::GetFeeHistory> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, error::GetFeeHistory> {
+            let mut statement = connection.prepare(
+                "SELECT height, block_hash, recorded_at, fee_rate_buckets
+                 FROM mempool_fee_snapshots ORDER BY height DESC LIMIT ?1;",
+            )?;
+            let rows = statement.query_map([lookback_blocks], |row| {
+                let block_hash: [u8; 32] = row.get(1)?;
+                let fee_rate_buckets: String = row.get(3)?;
+                Ok((
+                    row.get(0)?,
+                    BlockHash::from_byte_array(block_hash),
+                    row.get(2)?,
+                    fee_rate_buckets,
+                ))
+            })?;
+            let mut res = rows
+                .map(|row| {
+                    let (height, block_hash, recorded_at, fee_rate_buckets) = row?;
+                    Ok(MempoolFeeSnapshot {
+                        block_hash,
+                        height,
+                        recorded_at,
+                        fee_rate_buckets: serde_json::from_str(&fee_rate_buckets)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, error::GetFeeHistory>>()?;
+            res.reverse();
+            Ok(res)
+        };
+        let connection = self.inner.self_db_readers.lock().await;
+        with_connection(&connection)
+    }
+}
@@ -0,0 +1,103 @@
+//! Named fee tiers, resolved to a fee rate when a transaction is built
+
+use serde::{Deserialize, Serialize};
+
+use crate::wallet::{Wallet, error};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeTier {
+    Economy,
+    Normal,
+    Priority,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeTierSource {
+    /// Estimated by Bitcoin Core's `estimatesmartfee`
+    Estimated,
+    /// Fee rate required for inclusion within the tier's confirmation
+    /// target, estimated from the mempool. Used if Bitcoin Core does not
+    /// have enough data for an estimate.
+    Mempool,
+}
+
+/// Fee rate for a fee tier, as of when it was resolved
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ResolvedFeeTier {
+    pub tier: FeeTier,
+    /// Confirmation target in blocks, as configured for the tier
+    pub conf_target: u16,
+    pub sat_per_vb: u64,
+    pub source: FeeTierSource,
+}
+
+/// Explicit fee rate in sat/vB, or a fee tier
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FeeRateOrTier {
+    SatPerVb(u64),
+    Tier(FeeTier),
+}
+
+/// Fee rate that a transaction was built with, as requested by the caller
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ResolvedFeeRate {
+    pub sat_per_vb: u64,
+    /// Fee tier that the fee rate was resolved from. `None` if an explicit
+    /// fee rate was requested.
+    pub tier: Option<ResolvedFeeTier>,
+}
+
+/// Broadcast wallet transaction
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SentTransaction {
+    pub txid: bitcoin::Txid,
+    /// Fee rate that the transaction was built with. `None` if no fee rate
+    /// was requested.
+    pub fee_rate: Option<ResolvedFeeRate>,
+}
+
+impl Wallet {
+    /// Confirmation target in blocks for a fee tier
+    fn fee_tier_conf_target(&self, tier: FeeTier) -> u16 {
+        let wallet_opts = &self.inner.config.wallet_opts;
+        match tier {
+            FeeTier::Economy => wallet_opts.fee_tier_economy_conf_target,
+            FeeTier::Normal => wallet_opts.fee_tier_normal_conf_target,
+            FeeTier::Priority => wallet_opts.fee_tier_priority_conf_target,
+        }
+        .get()
+    }
+
+    /// Resolve a fee tier to a fee rate, via Bitcoin Core's
+    /// `estimatesmartfee`, or the mempool if there is not enough data for an
+    /// estimate. The mempool fee rate is the lowest fee rate that would be
+    /// included within the tier's confirmation target.
+    pub async fn resolve_fee_tier(
+        &self,
+        tier: FeeTier,
+    ) -> Result<ResolvedFeeTier, error::BitcoinCoreRPC> {
+        let conf_target = self.fee_tier_conf_target(tier);
+        let (sat_per_vb, source) = match self.inner.estimate_smart_fee(conf_target).await? {
+            Some(sat_per_vb) => (sat_per_vb, FeeTierSource::Estimated),
+            None => {
+                let sat_per_vb = self
+                    .inner
+                    .fetch_fee_histogram(None)
+                    .await?
+                    .fee_rate_within_blocks(u64::from(conf_target))
+                    .max(1);
+                (sat_per_vb, FeeTierSource::Mempool)
+            }
+        };
+        tracing::debug!(?tier, conf_target, sat_per_vb, ?source, "Resolved fee tier");
+        Ok(ResolvedFeeTier {
+            tier,
+            conf_target,
+            sat_per_vb,
+            source,
+        })
+    }
+}
@@ -0,0 +1,256 @@
+//! External outputs imported as watch-only, for building PSBTs that are
+//! signed externally (e.g. by a hardware wallet)
+
+use std::collections::HashMap;
+
+use bdk_wallet::bitcoin::{Address, Amount, OutPoint, ScriptBuf, TxOut, Txid, Weight, psbt::Psbt};
+use bitcoin::hashes::Hash as _;
+use bitcoin_jsonrpsee::jsonrpsee::{core::client::ClientT as _, rpc_params};
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::wallet::{CreateTransactionParams, Wallet, error};
+
+/// Subset of the fields of the `scriptPubKey` field of the `gettxout`
+/// response
+#[derive(Debug, Deserialize)]
+struct TxOutScriptPubKey {
+    hex: ScriptBuf,
+}
+
+/// Subset of the fields of the `gettxout` response
+#[derive(Debug, Deserialize)]
+struct UnspentTxOut {
+    /// Output value, in BTC
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    value: Amount,
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: TxOutScriptPubKey,
+}
+
+/// Output that is not owned by the wallet, imported so that it can be spent
+/// in PSBTs created by the wallet. Imported outputs are never signed by the
+/// wallet.
+#[derive(Clone, Debug)]
+pub(in crate::wallet) struct ImportedUtxo {
+    pub script_pubkey: ScriptBuf,
+    pub amount: Amount,
+    /// Maximum weight of the witness needed to spend the output
+    pub satisfaction_weight: Weight,
+}
+
+impl ImportedUtxo {
+    /// Maximum satisfaction weight for spending an output with the
+    /// specified script pubkey, or `None` if the script type is unsupported
+    fn satisfaction_weight(script_pubkey: &ScriptBuf) -> Option<Weight> {
+        if script_pubkey.is_p2wpkh() {
+            // Witness item count, signature with sighash flag, and pubkey
+            Some(Weight::from_wu(1 + 1 + 72 + 1 + 33))
+        } else if script_pubkey.is_p2tr() {
+            // Witness item count, and key path signature with sighash flag
+            Some(Weight::from_wu(1 + 1 + 65))
+        } else {
+            None
+        }
+    }
+
+    pub fn txout(&self) -> TxOut {
+        TxOut {
+            value: self.amount,
+            script_pubkey: self.script_pubkey.clone(),
+        }
+    }
+}
+
+impl Wallet {
+    /// Import an output that is not owned by the wallet, so that it can be
+    /// spent in PSBTs created by [`Self::create_unsigned_send_psbt`], by
+    /// listing it in the required UTXOs.
+    /// Only P2WPKH and P2TR outputs are supported. The output must be
+    /// unspent according to Bitcoin Core (including the mempool), with the
+    /// specified script pubkey and amount.
+    /// Re-importing an output replaces the previous import.
+    pub async fn import_outpoint(
+        &self,
+        outpoint: OutPoint,
+        script_pubkey: ScriptBuf,
+        amount: Amount,
+    ) -> Result<(), error::ImportOutpoint> {
+        if ImportedUtxo::satisfaction_weight(&script_pubkey).is_none() {
+            return Err(error::ImportOutpoint::UnsupportedScript { script_pubkey });
+        }
+        {
+            let wallet_read = self.inner.try_read_wallet().await?;
+            if wallet_read.get_utxo(outpoint).is_some()
+                || wallet_read.is_mine(script_pubkey.clone())
+            {
+                return Err(error::ImportOutpoint::OwnedByWallet { outpoint });
+            }
+        }
+        const INCLUDE_MEMPOOL: bool = true;
+        let unspent_txout: Option<UnspentTxOut> = self
+            .inner
+            .main_client
+            .request(
+                "gettxout",
+                rpc_params![outpoint.txid, outpoint.vout, INCLUDE_MEMPOOL],
+            )
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "gettxout".to_string(),
+                error: err,
+            })?;
+        let Some(unspent_txout) = unspent_txout else {
+            return Err(error::ImportOutpoint::NotUnspent { outpoint });
+        };
+        if unspent_txout.script_pubkey.hex != script_pubkey || unspent_txout.value != amount {
+            return Err(error::ImportOutpoint::Mismatch {
+                outpoint,
+                script_pubkey: unspent_txout.script_pubkey.hex,
+                amount: unspent_txout.value,
+            });
+        }
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            connection.execute(
+                "INSERT OR REPLACE INTO imported_utxos
+                 (txid, vout, script_pubkey, amount)
+                 VALUES (?1, ?2, ?3, ?4);",
+                (
+                    outpoint.txid.as_byte_array(),
+                    outpoint.vout,
+                    script_pubkey.as_bytes(),
+                    amount.to_sat(),
+                ),
+            )
+        };
+        let connection = self.inner.self_db.lock().await;
+        let _: usize = with_connection(&connection)?;
+        tracing::info!(%outpoint, %amount, "Imported external output");
+        Ok(())
+    }
+
+    /// Remove an imported output. Returns `false` if the output was not
+    /// imported.
+    pub async fn remove_imported_outpoint(
+        &self,
+        outpoint: OutPoint,
+    ) -> Result<bool, rusqlite::Error> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            connection.execute(
+                "DELETE FROM imported_utxos WHERE txid = ?1 AND vout = ?2;",
+                (outpoint.txid.as_byte_array(), outpoint.vout),
+            )
+        };
+        let connection = self.inner.self_db.lock().await;
+        let removed = with_connection(&connection)? != 0;
+        drop(connection);
+        if removed {
+            tracing::info!(%outpoint, "Removed imported external output");
+        }
+        Ok(removed)
+    }
+
+    pub(in crate::wallet) async fn get_imported_utxos(
+        &self,
+    ) -> Result<HashMap<OutPoint, ImportedUtxo>, rusqlite::Error> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let mut statement = connection
+                .prepare("SELECT txid, vout, script_pubkey, amount FROM imported_utxos;")?;
+            let rows = statement.query_map([], |row| {
+                let txid: [u8; 32] = row.get(0)?;
+                let outpoint = OutPoint {
+                    txid: Txid::from_byte_array(txid),
+                    vout: row.get(1)?,
+                };
+                let script_pubkey = ScriptBuf::from_bytes(row.get(2)?);
+                let amount = Amount::from_sat(row.get(3)?);
+                Ok((outpoint, script_pubkey, amount))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()
+        };
+        let rows = {
+            let connection = self.inner.self_db_readers.lock().await;
+            with_connection(&connection)?
+        };
+        Ok(rows
+            .into_iter()
+            .filter_map(|(outpoint, script_pubkey, amount)| {
+                let satisfaction_weight = ImportedUtxo::satisfaction_weight(&script_pubkey)?;
+                let imported_utxo = ImportedUtxo {
+                    script_pubkey,
+                    amount,
+                    satisfaction_weight,
+                };
+                Some((outpoint, imported_utxo))
+            })
+            .collect())
+    }
+
+    /// Remove imported outputs that are no longer unspent according to
+    /// Bitcoin Core (including the mempool), eg. after a PSBT spending them
+    /// was signed and broadcast. Returns the number of outputs removed.
+    pub(in crate::wallet) async fn prune_spent_imported_utxos(
+        &self,
+    ) -> Result<usize, error::PruneImportedUtxos> {
+        const INCLUDE_MEMPOOL: bool = true;
+        let imported_utxos = self.get_imported_utxos().await?;
+        let mut spent = Vec::new();
+        for outpoint in imported_utxos.into_keys() {
+            let unspent_txout: Option<UnspentTxOut> = self
+                .inner
+                .main_client
+                .request(
+                    "gettxout",
+                    rpc_params![outpoint.txid, outpoint.vout, INCLUDE_MEMPOOL],
+                )
+                .await
+                .map_err(|err| error::BitcoinCoreRPC {
+                    method: "gettxout".to_string(),
+                    error: err,
+                })?;
+            if unspent_txout.is_none() {
+                spent.push(outpoint);
+            }
+        }
+        if spent.is_empty() {
+            return Ok(0);
+        }
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let mut statement =
+                connection.prepare("DELETE FROM imported_utxos WHERE txid = ?1 AND vout = ?2;")?;
+            spent.iter().try_fold(0, |removed, outpoint| {
+                let rows = statement.execute((outpoint.txid.as_byte_array(), outpoint.vout))?;
+                Ok(removed + rows)
+            })
+        };
+        let connection = self.inner.self_db.lock().await;
+        let removed = with_connection(&connection)?;
+        drop(connection);
+        for outpoint in &spent {
+            tracing::info!(%outpoint, "Removed spent imported external output");
+        }
+        Ok(removed)
+    }
+
+    /// Create a PSBT for external signing. Does NOT sign or broadcast.
+    /// Required UTXOs may include imported outputs, which are left unsigned
+    /// for the external signer.
+    /// Imported outputs are NOT considered by coin selection, and are only
+    /// spent if they are listed in the required UTXOs. Spent imported
+    /// outputs are removed by the wallet sync task.
+    pub async fn create_unsigned_send_psbt(
+        &self,
+        destinations: HashMap<Address, Amount>,
+        params: CreateTransactionParams,
+    ) -> Result<Psbt, error::CreateUnsignedSendPsbt> {
+        let imported_utxos = self.get_imported_utxos().await?;
+        let psbt = self
+            .create_send_psbt(destinations, params, &imported_utxos)
+            .await?;
+        Ok(psbt)
+    }
+}
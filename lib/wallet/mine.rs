@@ -6,8 +6,8 @@ use std::{
 };
 
 use bitcoin::{
-    Amount, Block, BlockHash, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
-    Txid, Witness,
+    Amount, Block, BlockHash, CompactTarget, Network, OutPoint, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
     absolute::{Height, LockTime},
     block::Version as BlockVersion,
     consensus::Encodable as _,
@@ -22,17 +22,25 @@ use bitcoin::{
 use bitcoin_jsonrpsee::{
     MainClient as _,
     client::{BlockTemplateRequest, BoolWitness, GetRawMempoolClient as _},
+    jsonrpsee::{core::client::ClientT as _, rpc_params},
 };
 use futures::{
     StreamExt as _,
     stream::{self, FusedStream},
 };
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
     bins::{self, CommandExt as _},
     errors::ErrorChain,
     messages::{CoinbaseBuilder, M4AckBundles},
-    types::{Ctip, SidechainAck, SidechainNumber, WITHDRAWAL_BUNDLE_INCLUSION_THRESHOLD},
+    rpc_client,
+    types::{
+        CoinbaseRewardSplit, Ctip, SidechainAck, SidechainNumber,
+        WITHDRAWAL_BUNDLE_INCLUSION_THRESHOLD,
+    },
     wallet::{
         Wallet,
         error::{self, BitcoinCoreRPC},
@@ -55,15 +63,47 @@ fn get_block_value(height: u32, fees: Amount, network: Network) -> Amount {
 
 const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
 
+/// Coinbase outputs and transactions for a new block
+#[derive(Clone, Debug, Serialize)]
+pub struct EnforcerBlockTemplate {
+    /// Coinbase outputs, including drivechain messages such as sidechain
+    /// proposals and acks, bundle proposals and votes, and BMM accepts
+    pub coinbase_outputs: Vec<TxOut>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Subset of the fields of the `getblocktemplate` response
+#[derive(Debug, Deserialize)]
+struct NodeBlockTemplate {
+    #[serde(rename = "previousblockhash")]
+    prev_block_hash: BlockHash,
+    /// Compact target, as hex
+    bits: String,
+}
+
+/// Changes to the stored sidechain acks, found while building a block
+/// template
+#[derive(Debug, Default)]
+pub(in crate::wallet) struct SidechainAckChanges {
+    /// Acks for active proposals that were not stored, if acking all
+    /// proposals
+    added: Vec<SidechainAck>,
+    /// Stored acks that do not match an active proposal
+    invalid: Vec<SidechainAck>,
+}
+
 impl Wallet {
-    /// Extend coinbase txouts for a new block
+    /// Extend coinbase txouts for a new block.
+    /// Returns the ack changes to persist if the txouts are used in a block,
+    /// see [`Self::persist_sidechain_ack_changes`].
     pub(in crate::wallet) async fn extend_coinbase_txouts(
         &self,
         ack_all_proposals: bool,
         mainchain_tip: BlockHash,
         coinbase_txouts: &mut Vec<TxOut>,
-    ) -> Result<(), error::GenerateCoinbaseTxouts> {
+    ) -> Result<SidechainAckChanges, error::GenerateCoinbaseTxouts> {
         let mut coinbase_builder = CoinbaseBuilder::new(coinbase_txouts)?;
+        let wallet_opts = &self.inner.config.wallet_opts;
         tracing::debug!(
             message = "Extending coinbase txouts",
             ack_all_proposals = ack_all_proposals,
@@ -93,12 +133,20 @@ impl Wallet {
             .collect::<HashSet<_>>();
 
         for sidechain_proposal in sidechain_proposals {
+            if !wallet_opts.is_sidechain_allowed(sidechain_proposal.sidechain_number) {
+                tracing::debug!(
+                    sidechain_number = %sidechain_proposal.sidechain_number,
+                    "Not proposing sidechain excluded by sidechain filter"
+                );
+                continue;
+            }
             if !proposed_sidechains.contains(&sidechain_proposal.compute_id()) {
                 coinbase_builder.propose_sidechain(sidechain_proposal)?;
             }
         }
 
         let mut sidechain_acks = self.get_sidechain_acks().await?;
+        let mut ack_changes = SidechainAckChanges::default();
 
         // This is a map of pending sidechain proposals from the /validator/, i.e.
         // proposals broadcasted by (potentially) someone else, and already active.
@@ -111,6 +159,9 @@ impl Wallet {
 
             for (sidechain_number, sidechain_proposal) in &active_sidechain_proposals {
                 let sidechain_number = *sidechain_number;
+                if !wallet_opts.is_sidechain_allowed(sidechain_number) {
+                    continue;
+                }
 
                 if !sidechain_acks
                     .iter()
@@ -120,26 +171,31 @@ impl Wallet {
                         "Handle sidechain ACK: adding 'fake' ACK for {}",
                         sidechain_number
                     );
-                    self.ack_sidechain(
-                        sidechain_number,
-                        sidechain_proposal.description.sha256d_hash(),
-                    )
-                    .await?;
-                    sidechain_acks.push(SidechainAck {
+                    let ack = SidechainAck {
                         sidechain_number,
                         description_hash: sidechain_proposal.description.sha256d_hash(),
-                    });
+                    };
+                    ack_changes.added.push(ack.clone());
+                    sidechain_acks.push(ack);
                 }
             }
         }
 
         for sidechain_ack in sidechain_acks {
+            if !wallet_opts.is_sidechain_allowed(sidechain_ack.sidechain_number) {
+                tracing::debug!(
+                    sidechain_number = %sidechain_ack.sidechain_number,
+                    "Not acking sidechain excluded by sidechain filter"
+                );
+                continue;
+            }
             if !self.validate_sidechain_ack(&sidechain_ack, &active_sidechain_proposals) {
-                self.delete_sidechain_ack(&sidechain_ack).await?;
                 tracing::info!(
-                    "Unable to handle sidechain ack, deleted: {}",
+                    "Unable to handle sidechain ack, skipping: {}",
                     sidechain_ack.sidechain_number
                 );
+                // An ack added above is always valid, so this is a stored ack
+                ack_changes.invalid.push(sidechain_ack);
                 continue;
             }
             if coinbase_builder
@@ -163,6 +219,9 @@ impl Wallet {
 
         let bmm_hashes = self.get_bmm_requests(&mainchain_tip).await?;
         for (sidechain_number, bmm_hash) in bmm_hashes {
+            if !wallet_opts.is_sidechain_allowed(sidechain_number) {
+                continue;
+            }
             if coinbase_builder
                 .messages()
                 .m7_bmm_accept_slot_vout(&sidechain_number)
@@ -178,6 +237,9 @@ impl Wallet {
             coinbase_builder.bmm_accept(sidechain_number, bmm_hash)?;
         }
         for (sidechain_id, m6ids) in self.get_bundle_proposals().await? {
+            if !wallet_opts.is_sidechain_allowed(sidechain_id) {
+                continue;
+            }
             for (m6id, _blinded_m6, m6id_info) in m6ids {
                 if m6id_info.is_none() {
                     coinbase_builder.propose_bundle(sidechain_id, m6id)?;
@@ -192,11 +254,13 @@ impl Wallet {
             let upvotes = active_sidechains
                 .into_iter()
                 .map(|sidechain| {
-                    if self
-                        .inner
-                        .validator
-                        .get_pending_withdrawals(&sidechain.proposal.sidechain_number)?
-                        .is_empty()
+                    let sidechain_number = sidechain.proposal.sidechain_number;
+                    if !wallet_opts.is_sidechain_allowed(sidechain_number)
+                        || self
+                            .inner
+                            .validator
+                            .get_pending_withdrawals(&sidechain_number)?
+                            .is_empty()
                     {
                         Ok(M4AckBundles::ABSTAIN_ONE_BYTE)
                     } else {
@@ -207,6 +271,26 @@ impl Wallet {
             coinbase_builder.ack_bundles(M4AckBundles::OneByte { upvotes })?;
         }
         let () = coinbase_builder.build()?;
+        Ok(ack_changes)
+    }
+
+    /// Store acks added while building a block template, and delete stored
+    /// acks that were found to be invalid. Only called when the template is
+    /// used to produce a block, so that inspecting a template has no side
+    /// effects.
+    pub(in crate::wallet) async fn persist_sidechain_ack_changes(
+        &self,
+        ack_changes: SidechainAckChanges,
+    ) -> Result<(), rusqlite::Error> {
+        let SidechainAckChanges { added, invalid } = ack_changes;
+        for ack in added {
+            self.ack_sidechain(ack.sidechain_number, ack.description_hash)
+                .await?;
+        }
+        for ack in invalid {
+            self.delete_sidechain_ack(&ack).await?;
+            tracing::info!("Deleted invalid sidechain ack: {}", ack.sidechain_number);
+        }
         Ok(())
     }
 
@@ -274,26 +358,52 @@ impl Wallet {
         Ok(res)
     }
 
+    /// Configured split of the coinbase reward, if any
+    fn coinbase_reward_split(
+        &self,
+    ) -> Result<Option<CoinbaseRewardSplit>, error::FinalizeCoinbase> {
+        let network = self.validator().network();
+        let recipients = self
+            .inner
+            .config
+            .mining_opts
+            .coinbase_reward_split
+            .iter()
+            .map(|recipient| {
+                let address = recipient
+                    .address
+                    .clone()
+                    .require_network(network)
+                    .map_err(error::FinalizeCoinbase::CoinbaseRewardAddress)?;
+                Ok((address.script_pubkey(), recipient.weight))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(NonEmpty::from_vec(recipients).map(CoinbaseRewardSplit))
+    }
+
     /// Construct a coinbase tx from txouts
     async fn finalize_coinbase(
         &self,
         best_block_height: u32,
         coinbase_outputs: &[TxOut],
-    ) -> Result<Transaction, error::GetNewAddress> {
-        let coinbase_addr = self.get_new_address().await?;
-        tracing::trace!(%coinbase_addr, "Fetched address");
-        let coinbase_spk = coinbase_addr.script_pubkey();
-
+    ) -> Result<Transaction, error::FinalizeCoinbase> {
         let script_sig = bitcoin::blockdata::script::Builder::new()
             .push_int((best_block_height + 1) as i64)
             .push_opcode(OP_0)
             .into_script();
         let value = get_block_value(best_block_height + 1, Amount::ZERO, Network::Regtest);
         let output = if value > Amount::ZERO {
-            vec![TxOut {
-                script_pubkey: coinbase_spk,
-                value,
-            }]
+            match self.coinbase_reward_split()? {
+                Some(reward_split) => reward_split.outputs(value)?,
+                None => {
+                    let coinbase_addr = self.get_new_address().await?;
+                    tracing::trace!(%coinbase_addr, "Fetched address");
+                    vec![TxOut {
+                        script_pubkey: coinbase_addr.script_pubkey(),
+                        value,
+                    }]
+                }
+            }
         } else {
             vec![TxOut {
                 script_pubkey: ScriptBuf::builder().push_opcode(OP_RETURN).into_script(),
@@ -319,23 +429,21 @@ impl Wallet {
     /// Finalize a new block by constructing the coinbase tx
     async fn finalize_block(
         &self,
+        prev_blockhash: BlockHash,
         coinbase_outputs: &[TxOut],
         transactions: Vec<Transaction>,
+        bits: CompactTarget,
     ) -> Result<Block, error::FinalizeBlock> {
-        let best_block_hash = self.validator().get_mainchain_tip()?;
-        let best_block_height = self.validator().get_header_info(&best_block_hash)?.height;
-        tracing::trace!(%best_block_hash, %best_block_height, "Found mainchain tip");
+        let prev_block_height = self.validator().get_header_info(&prev_blockhash)?.height;
 
         let coinbase_tx = self
-            .finalize_coinbase(best_block_height, coinbase_outputs)
+            .finalize_coinbase(prev_block_height, coinbase_outputs)
             .await?;
         let txdata = std::iter::once(coinbase_tx).chain(transactions).collect();
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as u32;
-        let genesis_block = genesis_block(bitcoin::Network::Regtest);
-        let bits = genesis_block.header.bits;
         let header = bitcoin::block::Header {
             version: BlockVersion::NO_SOFT_FORK_SIGNALLING,
-            prev_blockhash: best_block_hash,
+            prev_blockhash,
             // merkle root is computed after the witness commitment is added to coinbase
             merkle_root: TxMerkleNode::all_zeros(),
             time: timestamp,
@@ -366,15 +474,20 @@ impl Wallet {
         Ok(block)
     }
 
-    /// Mine a block
+    /// Mine a block on top of `prev_blockhash`
     async fn mine(
         &self,
+        prev_blockhash: BlockHash,
         coinbase_outputs: &[TxOut],
         transactions: Vec<Transaction>,
     ) -> Result<BlockHash, error::Mine> {
         let transaction_count = transactions.len();
 
-        let mut block = self.finalize_block(coinbase_outputs, transactions).await?;
+        // Only used on regtest, which has no difficulty adjustment
+        let bits = genesis_block(Network::Regtest).header.bits;
+        let mut block = self
+            .finalize_block(prev_blockhash, coinbase_outputs, transactions, bits)
+            .await?;
         loop {
             block.header.nonce += 1;
             if block.header.validate_pow(block.header.target()).is_ok() {
@@ -448,7 +561,12 @@ impl Wallet {
             .inner
             .main_client
             .get_block_template(BlockTemplateRequest {
-                rules: vec!["signet".to_string(), "segwit".to_string()],
+                rules: rpc_client::block_template_rules(
+                    &self.inner.main_client,
+                    Network::Signet,
+                    &self.inner.config.mining_opts.block_template_rules,
+                )
+                .await,
                 capabilities: HashSet::new(),
             })
             .await
@@ -628,22 +746,16 @@ impl Wallet {
         Ok(block_hash)
     }
 
-    /// Build and mine a single block
-    async fn generate_block(
+    /// Coinbase outputs and transactions for a new block on top of
+    /// `mainchain_tip`, and the ack changes to persist if the template is
+    /// used to produce a block
+    async fn block_template(
         &self,
         ack_all_proposals: bool,
-    ) -> Result<BlockHash, error::GenerateBlock> {
-        let Some(mainchain_tip) = self.inner.validator.try_get_mainchain_tip()? else {
-            return Err(error::GenerateBlock::ValidatorNotSynced);
-        };
-        if self.inner.validator.network() == Network::Signet {
-            return self
-                .generate_signet_block(self.inner.config.mining_opts.coinbase_recipient.clone())
-                .await
-                .map_err(error::GenerateBlock::GenerateSignetBlock);
-        }
+        mainchain_tip: BlockHash,
+    ) -> Result<(EnforcerBlockTemplate, SidechainAckChanges), error::BlockTemplate> {
         let mut coinbase_outputs = Vec::new();
-        let () = self
+        let ack_changes = self
             .extend_coinbase_txouts(ack_all_proposals, mainchain_tip, &mut coinbase_outputs)
             .await?;
         let transactions = self.select_block_txs().await?;
@@ -662,6 +774,126 @@ impl Wallet {
             }
         }
         let () = coinbase_builder.build()?;
+        let template = EnforcerBlockTemplate {
+            coinbase_outputs,
+            transactions,
+        };
+        Ok((template, ack_changes))
+    }
+
+    /// Get the coinbase outputs and transactions that the enforcer would
+    /// include in a new block on top of the current mainchain tip.
+    /// All active sidechain proposals are acked, as when serving
+    /// `getblocktemplate`. Acks are not persisted, so the wallet is not
+    /// modified.
+    pub async fn get_block_template(
+        &self,
+    ) -> Result<EnforcerBlockTemplate, error::GetBlockTemplate> {
+        let (_mainchain_tip, template) = self.block_template_at_tip().await?;
+        Ok(template)
+    }
+
+    /// Current mainchain tip, and the block template on top of it, acking
+    /// all active sidechain proposals. Acks are not persisted.
+    async fn block_template_at_tip(
+        &self,
+    ) -> Result<(BlockHash, EnforcerBlockTemplate), error::GetBlockTemplate> {
+        let Some(mainchain_tip) = self.inner.validator.try_get_mainchain_tip()? else {
+            return Err(error::GetBlockTemplate::ValidatorNotSynced);
+        };
+        const ACK_ALL_PROPOSALS: bool = true;
+        let (template, _ack_changes) = self
+            .block_template(ACK_ALL_PROPOSALS, mainchain_tip)
+            .await?;
+        Ok((mainchain_tip, template))
+    }
+
+    /// Compact target for a new block, from Bitcoin Core's
+    /// `getblocktemplate`. Fails if Bitcoin Core's tip is not
+    /// `mainchain_tip`, since the target may differ.
+    async fn get_next_block_bits(
+        &self,
+        mainchain_tip: BlockHash,
+    ) -> Result<CompactTarget, error::GetNextBlockBits> {
+        let rules = rpc_client::block_template_rules(
+            &self.inner.main_client,
+            self.validator().network(),
+            &self.inner.config.mining_opts.block_template_rules,
+        )
+        .await;
+        let template: NodeBlockTemplate = self
+            .inner
+            .main_client
+            .request("getblocktemplate", rpc_params![json!({ "rules": rules })])
+            .await
+            .map_err(|err| error::BitcoinCoreRPC {
+                method: "getblocktemplate".to_string(),
+                error: err,
+            })?;
+        if template.prev_block_hash != mainchain_tip {
+            return Err(error::GetNextBlockBits::TipMismatch {
+                enforcer_tip: mainchain_tip,
+                node_tip: template.prev_block_hash,
+            });
+        }
+        let bits = u32::from_str_radix(&template.bits, 16).map_err(|source| {
+            error::GetNextBlockBits::InvalidBits {
+                bits: template.bits.clone(),
+                source,
+            }
+        })?;
+        Ok(CompactTarget::from_consensus(bits))
+    }
+
+    /// Candidate block on top of the current mainchain tip, assembled from
+    /// the enforcer block template. The compact target is taken from Bitcoin
+    /// Core. The header is not solved, and no sidechain acks are persisted.
+    /// On signet, the block does not include a signet solution.
+    pub async fn get_candidate_block_hex(&self) -> Result<String, error::GetCandidateBlock> {
+        let (
+            mainchain_tip,
+            EnforcerBlockTemplate {
+                coinbase_outputs,
+                transactions,
+            },
+        ) = self.block_template_at_tip().await?;
+        let bits = self.get_next_block_bits(mainchain_tip).await?;
+        let block = self
+            .finalize_block(mainchain_tip, &coinbase_outputs, transactions, bits)
+            .await?;
+        let mut block_bytes = vec![];
+        block
+            .consensus_encode(&mut block_bytes)
+            .map_err(error::EncodeBlock)?;
+        Ok(hex::encode(block_bytes))
+    }
+
+    /// Build and mine a single block
+    async fn generate_block(
+        &self,
+        ack_all_proposals: bool,
+    ) -> Result<BlockHash, error::GenerateBlock> {
+        let Some(mainchain_tip) = self.inner.validator.try_get_mainchain_tip()? else {
+            return Err(error::GenerateBlock::ValidatorNotSynced);
+        };
+        if self.inner.validator.network() == Network::Signet {
+            return self
+                .generate_signet_block(self.inner.config.mining_opts.coinbase_recipient.clone())
+                .await
+                .map_err(error::GenerateBlock::GenerateSignetBlock);
+        }
+        let (
+            EnforcerBlockTemplate {
+                coinbase_outputs,
+                transactions,
+            },
+            ack_changes,
+        ) = self
+            .block_template(ack_all_proposals, mainchain_tip)
+            .await?;
+        self.persist_sidechain_ack_changes(ack_changes)
+            .await
+            .map_err(error::GenerateBlock::PersistSidechainAcks)?;
 
         tracing::info!(
             coinbase_outputs = %coinbase_outputs.len(),
@@ -669,7 +901,9 @@ impl Wallet {
             "Mining block",
         );
 
-        let block_hash = self.mine(&coinbase_outputs, transactions).await?;
+        let block_hash = self
+            .mine(mainchain_tip, &coinbase_outputs, transactions)
+            .await?;
         self.delete_bmm_requests(&mainchain_tip)
             .await
             .map_err(error::GenerateBlock::DeleteBmmRequests)?;
@@ -25,6 +25,25 @@ pub(crate) fn new_mnemonic() -> Result<Mnemonic, bdk_wallet::bip39::Error> {
     Mnemonic::parse(words)
 }
 
+/// Parse a BIP39 wordlist language name, eg. `english` or
+/// `simplified-chinese`
+pub(crate) fn parse_language(language: &str) -> Option<Language> {
+    let language = match language {
+        "english" => Language::English,
+        "simplified-chinese" => Language::SimplifiedChinese,
+        "traditional-chinese" => Language::TraditionalChinese,
+        "czech" => Language::Czech,
+        "french" => Language::French,
+        "italian" => Language::Italian,
+        "japanese" => Language::Japanese,
+        "korean" => Language::Korean,
+        "portuguese" => Language::Portuguese,
+        "spanish" => Language::Spanish,
+        _ => return None,
+    };
+    Some(language)
+}
+
 fn stretch_password(password: &str, key_salt: &[u8]) -> Result<[u8; 32], error::StretchPassword> {
     let mut key_bytes = [0u8; 32];
     Argon2::default().hash_password_into(password.as_bytes(), key_salt, &mut key_bytes)?;
@@ -1,7 +1,8 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     future::Future,
-    path::Path,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
     time::{Duration, SystemTime},
@@ -25,14 +26,11 @@ use bitcoin::{
     hashes::{Hash as _, HashEngine, sha256, sha256d},
     script::PushBytesBuf,
 };
-use bitcoin_jsonrpsee::{
-    client::{GetRawTransactionClient, GetRawTransactionVerbose},
-    jsonrpsee::http_client::HttpClient,
-};
+use bitcoin_jsonrpsee::client::{GetRawTransactionClient, GetRawTransactionVerbose};
 use either::Either;
 use fallible_iterator::{FallibleIterator as _, IteratorExt as _};
 use futures::{FutureExt, TryFutureExt};
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension as _};
 use serde::{Deserialize, Serialize};
 use tokio::time::Instant;
 use tracing::instrument;
@@ -43,26 +41,73 @@ use crate::{
     convert,
     errors::ErrorChain,
     messages::{self, M8BmmRequest},
+    rpc_client::MainchainClient,
     types::{
         BDKWalletTransaction, BlindedM6, BmmCommitment, Ctip, M6id, PendingM6idInfo, SidechainAck,
-        SidechainNumber, SidechainProposal, SidechainProposalId,
+        SidechainDeclaration, SidechainNumber, SidechainProposal, SidechainProposalId,
     },
     validator::{self, Validator},
     wallet::{
+        electrum_pool::ElectrumPool,
         error::WalletInitialization,
+        esplora::EsploraClient,
         mnemonic::{EncryptedMnemonic, new_mnemonic},
         sync::NoSyncClient,
         util::{RwLockReadGuardSome, RwLockUpgradableReadGuardSome, RwLockWriteGuardSome},
     },
 };
 
+mod block_notifications;
+mod bundle_validation;
+mod chain_source_monitor;
+mod coin_selection_preview;
+mod coinbase_consolidation;
+mod confirmation_estimate;
 mod cusf_block_producer;
+mod default_fee_rate;
+mod derivation_info;
+mod descriptor_export;
+mod diagnostics;
+mod electrum_pool;
 pub mod error;
+mod esplora;
+mod fee_history;
+mod fee_tier;
+mod import;
 mod mine;
 pub mod mnemonic;
+mod proposal_reconciliation;
+mod read_connection_pool;
+mod rebroadcast;
 mod sync;
+mod sync_staleness_monitor;
+mod template_limits;
 mod thread_safe_connection;
 mod util;
+mod withdrawal_bundle_history;
+
+pub use bundle_validation::{BundleValidation, BundleWithdrawal, WrongAmountWithdrawal};
+pub use coin_selection_preview::CoinSelectionPreview;
+pub use confirmation_estimate::ConfirmationEstimate;
+pub use default_fee_rate::{DefaultFeeRate, DefaultFeeRateSource};
+pub use derivation_info::KeychainDerivationInfo;
+pub use descriptor_export::ExportedDescriptors;
+pub use diagnostics::{ChainSourceDiagnostics, WalletBalanceDiagnostics, WalletDiagnostics};
+pub use fee_history::{
+    BlockFeeRates, BlockFees, BlockHashOrHeight, FeeRateBucket, MempoolFeeSnapshot,
+};
+pub use fee_tier::{
+    FeeRateOrTier, FeeTier, FeeTierSource, ResolvedFeeRate, ResolvedFeeTier, SentTransaction,
+};
+pub use mine::EnforcerBlockTemplate;
+pub use proposal_reconciliation::{
+    DeletedBundleProposal, ProposalReconciliation, StaleBundleProposalReason,
+};
+pub use rebroadcast::{RebroadcastResult, RebroadcastStatus};
+pub use sync::SyncSummary;
+pub use withdrawal_bundle_history::{
+    ProposedWithdrawalBundle, ProposedWithdrawalBundleStatus, WithdrawalBundleEventRecord,
+};
 
 type BundleProposals = Vec<(M6id, BlindedM6<'static>, Option<PendingM6idInfo>)>;
 
@@ -70,49 +115,103 @@ pub(crate) type Persistence = thread_safe_connection::ThreadSafeConnection;
 type BdkWallet = bdk_wallet::PersistedWallet<Persistence>;
 
 type ElectrumClient = BdkElectrumClient<bdk_electrum::electrum_client::Client>;
-type EsploraClient = bdk_esplora::esplora_client::AsyncClient;
-type ChainSource = Either<ElectrumClient, Either<EsploraClient, NoSyncClient>>;
+type ChainSource = Either<ElectrumPool, Either<EsploraClient, NoSyncClient>>;
+
+/// Releases an idempotency key reservation when dropped, including if the
+/// operation is cancelled
+struct IdempotencyReservation<'a> {
+    reservations: &'a parking_lot::Mutex<HashSet<(&'static str, String)>>,
+    method: &'static str,
+    idempotency_key: String,
+}
+
+impl Drop for IdempotencyReservation<'_> {
+    fn drop(&mut self) {
+        self.reservations
+            .lock()
+            .remove(&(self.method, std::mem::take(&mut self.idempotency_key)));
+    }
+}
 
 struct WalletInner {
-    main_client: HttpClient,
+    main_client: MainchainClient,
     validator: Validator,
     magic: bitcoin::p2p::Magic,
     // Unlocked, ready-to-go wallet: Some
     // Locked wallet: None
     bitcoin_wallet: async_lock::RwLock<Option<BdkWallet>>,
+    /// Set to `true` once `bitcoin_wallet` is unlocked
+    unlocked: tokio::sync::watch::Sender<bool>,
+    /// Set to `true` while the balance is below the low balance threshold,
+    /// as of the last sync
+    low_balance: tokio::sync::watch::Sender<bool>,
+    /// Set to `true` once a sync or full scan has completed
+    synced: tokio::sync::watch::Sender<bool>,
+    /// Set to `false` while no chain source is reachable, as of the last
+    /// chain source probe
+    chain_source_reachable: tokio::sync::watch::Sender<bool>,
+    /// Set to `true` while the last successful sync is older than the sync
+    /// staleness threshold
+    sync_stale: tokio::sync::watch::Sender<bool>,
+    /// Time of the last successful sync. Unlike `last_sync`, this is not
+    /// locked while a sync is in progress.
+    last_sync_success: tokio::sync::watch::Sender<Option<SystemTime>>,
+    /// Notified to wake the periodic sync task, eg. when a new block is
+    /// announced
+    sync_requested: tokio::sync::Notify,
+    /// Set while a full scan is in progress. Sending `true` cancels the
+    /// full scan.
+    full_scan_cancel: parking_lot::Mutex<Option<tokio::sync::watch::Sender<bool>>>,
     /// Persistence for the BDK wallet
     bdk_db: tokio::sync::Mutex<Persistence>,
     // Persistence for things /we/ care about. Wallet seed, M* messages, ++.
     self_db: tokio::sync::Mutex<rusqlite::Connection>,
-    chain_source: ChainSource,
+    /// Read-only connections to `self_db`
+    self_db_readers: read_connection_pool::ReadConnectionPool,
+    /// Method and idempotency key of each operation in progress, so that
+    /// concurrent requests with the same key can't both execute
+    idempotency_reservations: parking_lot::Mutex<HashSet<(&'static str, String)>>,
+    /// Chain sources in priority order, primary first. Never empty.
+    chain_sources: Vec<(WalletSyncSource, ChainSource)>,
     last_sync: async_lock::RwLock<Option<SystemTime>>,
     config: Config,
 }
 
 impl WalletInner {
-    async fn init_esplora_client(
+    /// Esplora server URL, from the config or the default for the network
+    fn esplora_url(
         config: &WalletConfig,
         network: Network,
-    ) -> Result<EsploraClient, error::InitEsploraClient> {
+    ) -> Result<url::Url, error::InitEsploraClient> {
+        if let Some(esplora_url) = &config.esplora_url {
+            return Ok(esplora_url.clone());
+        }
         let default_url = match network {
             Network::Signet => "https://explorer.drivechain.info/api",
             Network::Regtest => "http://localhost:3003",
             network => return Err(error::UnsupportedNetwork(network).into()),
         };
-        let default_url = url::Url::parse(default_url)?;
+        Ok(url::Url::parse(default_url)?)
+    }
 
-        let esplora_url = config.esplora_url.clone().unwrap_or(default_url);
+    async fn init_esplora_client(
+        config: &WalletConfig,
+        network: Network,
+    ) -> Result<EsploraClient, error::InitEsploraClient> {
+        let esplora_url = Self::esplora_url(config, network)?;
 
         tracing::info!(esplora_url = %esplora_url, "creating esplora client");
 
         // URLs with a port number at the end get a `/` when turned back into a string, for
         // some reason. The Esplora library doesn't like that! Remove it.
         let client = esplora_client::Builder::new(esplora_url.as_str().trim_end_matches("/"))
+            .max_retries(config.esplora_max_retries)
             .build_async()
             .map_err(error::InitEsploraClient::BuildEsploraClient)?;
+        let client = EsploraClient::new(client, config.esplora_requests_per_second);
 
         let height = client
-            .get_height()
+            .rate_limited(|| client.get_height())
             .await
             .map_err(error::InitEsploraClient::EsploraClientHeight)?;
 
@@ -120,27 +219,55 @@ impl WalletInner {
         Ok(client)
     }
 
-    /// Initialize electrum client
-    fn init_electrum_client(
+    async fn init_chain_source(
         config: &WalletConfig,
         network: Network,
-    ) -> Result<ElectrumClient, error::InitElectrumClient> {
+        sync_source: WalletSyncSource,
+    ) -> Result<ChainSource, error::InitWallet> {
+        let chain_source = match sync_source {
+            WalletSyncSource::Electrum => {
+                let electrum_pool = ElectrumPool::new(config.electrum_max_connections, || {
+                    Self::init_electrum_client(config, network)
+                })?;
+                Either::Left(electrum_pool)
+            }
+            WalletSyncSource::Esplora => {
+                let esplora_client = Self::init_esplora_client(config, network).await?;
+                Either::Right(Either::Left(esplora_client))
+            }
+            WalletSyncSource::Disabled => Either::Right(Either::Right(NoSyncClient {})),
+        };
+        Ok(chain_source)
+    }
+
+    /// Electrum server address (`host:port`), from the config or the
+    /// defaults for the network
+    fn electrum_url(
+        config: &WalletConfig,
+        network: Network,
+    ) -> Result<String, error::UnsupportedNetwork> {
         let (default_host, default_port) = match network {
             Network::Signet => ("explorer.drivechain.info", 50001),
             Network::Regtest => ("127.0.0.1", 60401), // Default for mempool/electrs
-            network => return Err(error::UnsupportedNetwork(network).into()),
+            network => return Err(error::UnsupportedNetwork(network)),
         };
-        let electrum_host = config
-            .electrum_host
-            .clone()
-            .unwrap_or(default_host.to_string());
+        let electrum_host = config.electrum_host.as_deref().unwrap_or(default_host);
         let electrum_port = config.electrum_port.unwrap_or(default_port);
-        let electrum_url = format!("{electrum_host}:{electrum_port}");
+        Ok(format!("{electrum_host}:{electrum_port}"))
+    }
+
+    /// Initialize electrum client
+    fn init_electrum_client(
+        config: &WalletConfig,
+        network: Network,
+    ) -> Result<ElectrumClient, error::InitElectrumClient> {
+        let electrum_url = Self::electrum_url(config, network)?;
         tracing::debug!(%electrum_url, "creating electrum client");
         // Apply a reasonably short timeout to prevent the wallet from hanging
         let timeout = 5;
         let config = electrum_client::ConfigBuilder::new()
             .timeout(Some(timeout))
+            .retry(config.electrum_retries)
             .build();
         let electrum_client = electrum_client::Client::from_config(&electrum_url, config)
             .map_err(error::InitElectrumClient::CreateElectrumClient)?;
@@ -157,12 +284,21 @@ impl WalletInner {
         Ok(BdkElectrumClient::new(electrum_client))
     }
 
-    fn init_db_connection(
-        data_dir: &Path,
-    ) -> Result<rusqlite::Connection, error::InitDbConnection> {
-        use rusqlite_migration::{M, Migrations};
-        // 1️⃣ Define migrations
-        let migrations = Migrations::new(vec![
+    fn self_db_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("db.sqlite")
+    }
+
+    /// Index of the migration that adds `proposed_withdrawal_bundles`, ie.
+    /// the schema version before it was added
+    const PROPOSED_WITHDRAWAL_BUNDLES_MIGRATION: usize = 14;
+
+    /// Migrations for the wallet database, in order. The schema version is
+    /// the number of migrations applied, stored as the SQLite `user_version`.
+    /// Migrations must never be modified or reordered once released; schema
+    /// changes are made by appending a migration.
+    fn db_migrations() -> Vec<rusqlite_migration::M<'static>> {
+        use rusqlite_migration::M;
+        let mut migrations = vec![
             M::up(
                 "CREATE TABLE sidechain_proposals
                (sidechain_number INTEGER NOT NULL,
@@ -214,22 +350,169 @@ impl WalletInner {
                  creation_time DATETIME NOT NULL DEFAULT (DATETIME('now')) 
                 );",
             ),
+            M::up(
+                "CREATE TABLE sidechain_deposit_addresses
+                (sidechain_number INTEGER PRIMARY KEY,
+                 address TEXT NOT NULL UNIQUE);",
+            ),
+            M::up(
+                "CREATE TABLE idempotency_keys
+                (idempotency_key TEXT PRIMARY KEY,
+                 result TEXT NOT NULL,
+                 -- unix timestamp, in seconds
+                 created_at INTEGER NOT NULL);",
+            ),
+            M::up(
+                "ALTER TABLE sidechain_proposals
+                 -- mainchain tip height when the proposal was created
+                 ADD COLUMN proposal_height INTEGER;",
+            ),
+            M::up(
+                "CREATE TABLE pending_bundle_deletions
+               (sidechain_number INTEGER NOT NULL,
+                bundle_hash BLOB NOT NULL,
+                -- height of the block that finalized the bundle
+                finalized_height INTEGER NOT NULL,
+                UNIQUE(sidechain_number, bundle_hash));",
+            ),
+            M::up(
+                "CREATE TABLE block_fee_rates
+               (height INTEGER PRIMARY KEY,
+                block_hash BLOB NOT NULL,
+                -- fee rates in sat/vB
+                min_fee_rate INTEGER NOT NULL,
+                median_fee_rate INTEGER NOT NULL,
+                max_fee_rate INTEGER NOT NULL);",
+            ),
+            M::up(
+                "CREATE TABLE imported_utxos
+               (txid BLOB NOT NULL,
+                vout INTEGER NOT NULL,
+                script_pubkey BLOB NOT NULL,
+                -- amount in sats
+                amount INTEGER NOT NULL,
+                UNIQUE(txid, vout));",
+            ),
+            M::up(
+                "CREATE TABLE withdrawal_bundle_events
+               (block_height INTEGER NOT NULL,
+                block_hash BLOB NOT NULL,
+                -- index of the event within the block's withdrawal bundle events
+                event_index INTEGER NOT NULL,
+                sidechain_number INTEGER NOT NULL,
+                bundle_hash BLOB NOT NULL,
+                -- one of 'submitted', 'failed', 'succeeded'
+                status TEXT NOT NULL,
+                -- only set for succeeded bundles
+                sequence_number INTEGER,
+                -- M6 txid, only set for succeeded bundles
+                txid BLOB,
+                UNIQUE(block_height, event_index));
+                CREATE INDEX withdrawal_bundle_events_sidechain_height
+                ON withdrawal_bundle_events (sidechain_number, block_height);",
+            ),
+            M::up(
+                "CREATE TABLE default_fee_rate
+               (id INTEGER PRIMARY KEY CHECK (id = 0),
+                -- fee rate in sat/vB
+                sat_per_vb INTEGER NOT NULL);",
+            ),
+        ];
+        debug_assert_eq!(
+            migrations.len(),
+            Self::PROPOSED_WITHDRAWAL_BUNDLES_MIGRATION
+        );
+        migrations.push(M::up(
+            "CREATE TABLE proposed_withdrawal_bundles
+               (sidechain_number INTEGER NOT NULL,
+                bundle_hash BLOB NOT NULL,
+                -- unix timestamp, in seconds. NULL for bundles proposed
+                -- before proposals were recorded.
+                proposed_at INTEGER,
+                UNIQUE(sidechain_number, bundle_hash));
+                INSERT INTO proposed_withdrawal_bundles (sidechain_number, bundle_hash)
+                SELECT sidechain_number, bundle_hash FROM bundle_proposals;",
+        ));
+        migrations.extend([
+            // Idempotency keys are scoped to a method, and bound to the
+            // request params. Existing keys are short-lived, and dropped.
+            M::up(
+                "DROP TABLE idempotency_keys;
+                CREATE TABLE idempotency_keys
+               (method TEXT NOT NULL,
+                idempotency_key TEXT NOT NULL,
+                -- SHA256 of the JSON-serialized request params
+                params_hash BLOB NOT NULL,
+                result TEXT NOT NULL,
+                -- unix timestamp, in seconds
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (method, idempotency_key));",
+            ),
+            M::up(
+                "CREATE TABLE mempool_fee_snapshots
+               (height INTEGER PRIMARY KEY,
+                block_hash BLOB NOT NULL,
+                -- unix timestamp, in seconds
+                recorded_at INTEGER NOT NULL,
+                -- JSON array of fee rate buckets, in descending order of
+                -- fee rate
+                fee_rate_buckets TEXT NOT NULL);",
+            ),
         ]);
+        migrations
+    }
+
+    /// Upgrade the wallet database schema to the latest version. Fails if
+    /// the database was created by a newer version of the enforcer.
+    fn migrate_db(db_connection: &mut Connection) -> Result<(), error::InitDbConnection> {
+        let migrations = Self::db_migrations();
+        let supported_version = migrations.len();
+        let migrations = rusqlite_migration::Migrations::new(migrations);
+        let version: usize =
+            db_connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > supported_version {
+            return Err(error::InitDbConnection::SchemaTooNew {
+                version,
+                supported_version,
+            });
+        }
+        migrations.to_latest(db_connection)?;
+        if version != supported_version {
+            tracing::info!(
+                from_version = version,
+                to_version = supported_version,
+                "Upgraded wallet database schema"
+            );
+        }
+        Ok(())
+    }
 
-        let db_name = "db.sqlite";
-        let path = data_dir.join(db_name);
+    fn init_db_connection(
+        data_dir: &Path,
+    ) -> Result<rusqlite::Connection, error::InitDbConnection> {
+        let path = Self::self_db_path(data_dir);
         let mut db_connection = Connection::open(path.clone())?;
         tracing::info!("Created database connection to {}", path.display());
-        migrations.to_latest(&mut db_connection)?;
+        // WAL mode allows reads via `self_db_readers` while writing
+        let journal_mode: String =
+            db_connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case("wal") {
+            tracing::warn!(
+                %journal_mode,
+                "failed to enable WAL mode for {}, reads may be blocked by writes",
+                path.display()
+            );
+        }
+        let () = Self::migrate_db(&mut db_connection)?;
         tracing::debug!("Ran migrations on {}", path.display());
         Ok(db_connection)
     }
 
-    async fn initialize_wallet_from_mnemonic(
+    /// External and internal descriptors for a wallet derived from `mnemonic`
+    fn descriptors_from_mnemonic(
         mnemonic: &Mnemonic,
         network: bdk_wallet::bitcoin::Network,
-        wallet_database: &mut Persistence,
-    ) -> Result<BdkWallet, error::InitWalletFromMnemonic> {
+    ) -> Result<(String, String), error::InitWalletFromMnemonic> {
         let extended_key: ExtendedKey = mnemonic.clone().into_extended_key()?;
 
         let xpriv = extended_key
@@ -239,6 +522,44 @@ impl WalletInner {
         // Create a BDK wallet structure using BIP 84 descriptor ("m/84h/1h/0h/0" and "m/84h/1h/0h/1")
         let external_desc = format!("wpkh({xpriv}/84'/1'/0'/0/*)");
         let internal_desc = format!("wpkh({xpriv}/84'/1'/0'/1/*)");
+        Ok((external_desc, internal_desc))
+    }
+
+    /// Check that the descriptors persisted in the wallet database, if any,
+    /// match the descriptors derived from `mnemonic`.
+    /// The wallet database is not modified.
+    async fn check_persisted_descriptors(
+        mnemonic: &Mnemonic,
+        network: bdk_wallet::bitcoin::Network,
+        wallet_database: &mut Persistence,
+    ) -> Result<(), error::InitWallet> {
+        let (external_desc, internal_desc) = Self::descriptors_from_mnemonic(mnemonic, network)?;
+        let loaded = bdk_wallet::Wallet::load()
+            .descriptor(KeychainKind::External, Some(external_desc))
+            .descriptor(KeychainKind::Internal, Some(internal_desc))
+            .check_network(network)
+            .load_wallet_async(wallet_database)
+            .await;
+        match loaded.map_err(error::InitWalletFromMnemonic::from) {
+            Ok(Some(_)) => {
+                tracing::debug!("persisted wallet descriptors match mnemonic");
+                Ok(())
+            }
+            Ok(None) => {
+                tracing::debug!("no persisted wallet descriptors to check against mnemonic");
+                Ok(())
+            }
+            Err(error::InitWalletFromMnemonic::DataMismatch(err)) => Err(err.into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn initialize_wallet_from_mnemonic(
+        mnemonic: &Mnemonic,
+        network: bdk_wallet::bitcoin::Network,
+        wallet_database: &mut Persistence,
+    ) -> Result<BdkWallet, error::InitWalletFromMnemonic> {
+        let (external_desc, internal_desc) = Self::descriptors_from_mnemonic(mnemonic, network)?;
 
         tracing::debug!("Attempting load of existing BDK wallet");
         let bitcoin_wallet = bdk_wallet::Wallet::load()
@@ -271,9 +592,10 @@ impl WalletInner {
     async fn new(
         data_dir: &Path,
         config: &Config,
-        main_client: HttpClient,
+        main_client: MainchainClient,
         validator: Validator,
         magic: bitcoin::p2p::Magic,
+        mnemonic: Option<&Mnemonic>,
     ) -> Result<Self, error::InitWallet> {
         let network = {
             let validator_network = validator.network();
@@ -293,19 +615,54 @@ impl WalletInner {
             .await
             .map_err(error::InitWallet::OpenConnection)?;
 
-        let chain_source = match config.wallet_opts.sync_source {
-            WalletSyncSource::Electrum => {
-                let electrum_client = Self::init_electrum_client(&config.wallet_opts, network)?;
-                Either::Left(electrum_client)
+        // Fail fast if the supplied mnemonic does not belong to this data
+        // directory, before anything is synced into the wallet database
+        if let Some(mnemonic) = mnemonic {
+            Self::check_persisted_descriptors(mnemonic, network, &mut wallet_database).await?;
+        }
+
+        let primary_sync_source = config.wallet_opts.sync_source;
+        let mut chain_sources = vec![(
+            primary_sync_source,
+            Self::init_chain_source(&config.wallet_opts, network, primary_sync_source).await?,
+        )];
+        if primary_sync_source == WalletSyncSource::Disabled {
+            if !config.wallet_opts.sync_fallback_sources.is_empty() {
+                tracing::warn!("wallet sync source is disabled, ignoring fallback sync sources");
             }
-            WalletSyncSource::Esplora => {
-                let esplora_client =
-                    Self::init_esplora_client(&config.wallet_opts, network).await?;
-                Either::Right(Either::Left(esplora_client))
+        } else {
+            for (index, &sync_source) in config.wallet_opts.sync_fallback_sources.iter().enumerate()
+            {
+                if sync_source == WalletSyncSource::Disabled {
+                    return Err(error::InitWallet::InvalidFallbackSyncSource { sync_source });
+                }
+                // Each sync source has a single endpoint, so a repeated source
+                // would query the same server twice
+                if sync_source == primary_sync_source
+                    || config.wallet_opts.sync_fallback_sources[..index].contains(&sync_source)
+                {
+                    return Err(error::InitWallet::DuplicateSyncSource { sync_source });
+                }
+                // A fallback source that is unreachable at startup must not
+                // prevent the wallet from starting with the primary source
+                match Self::init_chain_source(&config.wallet_opts, network, sync_source).await {
+                    Ok(chain_source) => chain_sources.push((sync_source, chain_source)),
+                    Err(err) => {
+                        tracing::warn!(
+                            ?sync_source,
+                            "failed to initialize fallback wallet sync source, skipping it: {:#}",
+                            ErrorChain::new(&err)
+                        );
+                    }
+                }
             }
-            WalletSyncSource::Disabled => Either::Right(Either::Right(NoSyncClient {})),
-        };
+        }
         let db_connection = Self::init_db_connection(data_dir)?;
+        let self_db_readers = read_connection_pool::ReadConnectionPool::open(
+            &Self::self_db_path(data_dir),
+            config.wallet_opts.db_read_connections,
+        )
+        .map_err(error::InitDbConnection::from)?;
 
         // If we:
         // 1. Already have an initialized wallet
@@ -314,6 +671,10 @@ impl WalletInner {
         // We can just go ahead and unlock the wallet right away.
         let bitcoin_wallet =
             if let Some(Either::Left(mnemonic)) = WalletInner::read_db_mnemonic(&db_connection)? {
+                // A plaintext wallet can never be explicitly unlocked
+                if config.wallet_opts.require_unlock {
+                    return Err(error::InitWallet::RequireUnlockNotEncrypted);
+                }
                 tracing::debug!("found plaintext mnemonic, going straight to initialization");
                 let initialized = WalletInner::initialize_wallet_from_mnemonic(
                     &mnemonic,
@@ -337,10 +698,20 @@ impl WalletInner {
             main_client,
             validator,
             magic,
+            unlocked: tokio::sync::watch::Sender::new(bitcoin_wallet.is_some()),
+            low_balance: tokio::sync::watch::Sender::new(false),
+            synced: tokio::sync::watch::Sender::new(false),
+            chain_source_reachable: tokio::sync::watch::Sender::new(true),
+            sync_stale: tokio::sync::watch::Sender::new(false),
+            last_sync_success: tokio::sync::watch::Sender::new(None),
+            sync_requested: tokio::sync::Notify::new(),
+            full_scan_cancel: parking_lot::Mutex::new(None),
             bitcoin_wallet: async_lock::RwLock::new(bitcoin_wallet),
             bdk_db: tokio::sync::Mutex::new(wallet_database),
             self_db: tokio::sync::Mutex::new(db_connection),
-            chain_source,
+            self_db_readers,
+            idempotency_reservations: parking_lot::Mutex::new(HashSet::new()),
+            chain_sources,
             last_sync: async_lock::RwLock::new(None),
         })
     }
@@ -348,75 +719,87 @@ impl WalletInner {
     /// Warn if lock takes this long to acquire
     const LOCK_WARN_DURATION: Duration = Duration::from_secs(1);
 
+    /// Await a lock acquisition, warning if it takes longer than
+    /// [`Self::LOCK_WARN_DURATION`]
     #[allow(clippy::significant_drop_in_scrutinee, reason = "false positive")]
-    async fn read_wallet(&self) -> Result<RwLockReadGuardSome<BdkWallet>, error::NotUnlocked> {
+    async fn acquire_lock<Guard>(
+        lock: &'static str,
+        acquire: impl Future<Output = Guard> + Send,
+    ) -> Guard {
         use futures::future::{Either, select};
-        tracing::trace!("wallet: acquiring read lock");
-        let read_guard = match select(
-            self.bitcoin_wallet.read().boxed(),
+        tracing::trace!("wallet: acquiring {lock} lock");
+        match select(
+            acquire.boxed(),
             tokio::time::sleep(Self::LOCK_WARN_DURATION).boxed(),
         )
         .await
         {
-            Either::Left((read_guard, _sleep)) => read_guard,
-            Either::Right(((), acquiring_read_lock)) => {
+            Either::Left((guard, _sleep)) => guard,
+            Either::Right(((), acquiring_lock)) => {
                 tracing::warn!(
-                    "wallet: waiting over {} to acquire read lock",
+                    "wallet: waiting over {} to acquire {lock} lock",
                     jiff::SignedDuration::try_from(Self::LOCK_WARN_DURATION).unwrap(),
                 );
-                acquiring_read_lock.await
+                acquiring_lock.await
             }
+        }
+    }
+
+    /// Await a lock acquisition, failing if the lock is not acquired within
+    /// the configured lock timeout.
+    /// Used for API calls, so that requests fail instead of waiting
+    /// indefinitely behind a long-running operation such as a sync.
+    async fn acquire_lock_with_timeout<Guard>(
+        &self,
+        lock: &'static str,
+        acquire: impl Future<Output = Guard> + Send,
+    ) -> Result<Guard, error::WalletBusy> {
+        let Some(timeout) = self
+            .config
+            .wallet_opts
+            .lock_timeout_secs
+            .map(Duration::from_secs)
+        else {
+            return Ok(Self::acquire_lock(lock, acquire).await);
         };
+        tokio::time::timeout(timeout, Self::acquire_lock(lock, acquire))
+            .await
+            .map_err(|_elapsed| {
+                tracing::warn!(
+                    "wallet: timed out after {} waiting to acquire {lock} lock",
+                    jiff::SignedDuration::try_from(timeout).unwrap(),
+                );
+                error::WalletBusy { lock, timeout }
+            })
+    }
+
+    async fn read_wallet(&self) -> Result<RwLockReadGuardSome<BdkWallet>, error::NotUnlocked> {
+        let read_guard = Self::acquire_lock("read", self.bitcoin_wallet.read()).await;
         RwLockReadGuardSome::new(read_guard).ok_or(error::NotUnlocked)
     }
 
+    /// Obtain a read lock on the inner wallet, subject to the lock timeout
+    async fn try_read_wallet(&self) -> Result<RwLockReadGuardSome<BdkWallet>, error::LockWallet> {
+        let read_guard = self
+            .acquire_lock_with_timeout("read", self.bitcoin_wallet.read())
+            .await?;
+        Ok(RwLockReadGuardSome::new(read_guard).ok_or(error::NotUnlocked)?)
+    }
+
     /// Obtain an upgradable read lock on the inner wallet
-    #[allow(clippy::significant_drop_in_scrutinee, reason = "false positive")]
     async fn read_wallet_upgradable(
         &self,
     ) -> Result<RwLockUpgradableReadGuardSome<BdkWallet>, error::NotUnlocked> {
-        use futures::future::{Either, select};
-        tracing::trace!("wallet: acquiring upgradable read lock");
-        let read_guard = match select(
-            self.bitcoin_wallet.upgradable_read().boxed(),
-            tokio::time::sleep(Self::LOCK_WARN_DURATION).boxed(),
-        )
-        .await
-        {
-            Either::Left((read_guard, _sleep)) => read_guard,
-            Either::Right(((), acquiring_read_lock)) => {
-                tracing::warn!(
-                    "waiting over {} to acquire read lock",
-                    jiff::SignedDuration::try_from(Self::LOCK_WARN_DURATION).unwrap(),
-                );
-                acquiring_read_lock.await
-            }
-        };
+        let read_guard =
+            Self::acquire_lock("upgradable read", self.bitcoin_wallet.upgradable_read()).await;
         RwLockUpgradableReadGuardSome::new(read_guard).ok_or(error::NotUnlocked)
     }
 
-    #[allow(clippy::significant_drop_in_scrutinee, reason = "false positive")]
     async fn write_wallet(&self) -> Result<RwLockWriteGuardSome<BdkWallet>, error::NotUnlocked> {
-        use futures::future::{Either, select};
         let start = SystemTime::now();
         let span = tracing::span!(tracing::Level::TRACE, "acquire_write_lock");
         let _guard = span.enter();
-        tracing::trace!("acquiring write lock");
-        let write_guard = match select(
-            self.bitcoin_wallet.write().boxed(),
-            tokio::time::sleep(Self::LOCK_WARN_DURATION).boxed(),
-        )
-        .await
-        {
-            Either::Left((write_guard, _sleep)) => write_guard,
-            Either::Right(((), acquiring_write_lock)) => {
-                tracing::warn!(
-                    "waiting over {} to acquire write lock",
-                    jiff::SignedDuration::try_from(Self::LOCK_WARN_DURATION).unwrap()
-                );
-                acquiring_write_lock.await
-            }
-        };
+        let write_guard = Self::acquire_lock("write", self.bitcoin_wallet.write()).await;
         tracing::trace!(
             "wallet: acquired write lock successfully in {:?}",
             start.elapsed().unwrap_or_default()
@@ -424,6 +807,14 @@ impl WalletInner {
         RwLockWriteGuardSome::new(write_guard).ok_or(error::NotUnlocked)
     }
 
+    /// Obtain a write lock on the inner wallet, subject to the lock timeout
+    async fn try_write_wallet(&self) -> Result<RwLockWriteGuardSome<BdkWallet>, error::LockWallet> {
+        let write_guard = self
+            .acquire_lock_with_timeout("write", self.bitcoin_wallet.write())
+            .await?;
+        Ok(RwLockWriteGuardSome::new(write_guard).ok_or(error::NotUnlocked)?)
+    }
+
     fn read_db_mnemonic(
         connection: &Connection,
     ) -> Result<Option<Either<Mnemonic, EncryptedMnemonic>>, error::ReadDbMnemonic> {
@@ -549,6 +940,7 @@ impl WalletInner {
         let mut write_guard = self.bitcoin_wallet.write().await;
         *write_guard = Some(wallet);
         drop(write_guard);
+        self.unlocked.send_replace(true);
         Ok(())
     }
 
@@ -597,24 +989,62 @@ impl WalletInner {
         let mut write_guard = self.bitcoin_wallet.write().await;
         *write_guard = Some(wallet);
         drop(write_guard);
+        self.unlocked.send_replace(true);
 
         tracing::info!("unlock wallet: initialized wallet");
         Ok(())
     }
 
-    // Gets wiped upon generating a new block.
-    async fn delete_bundle_proposals<I>(&self, iter: I) -> Result<(), rusqlite::Error>
+    /// Queue bundle proposals finalized in the block at `block_height` for
+    /// deletion, and delete queued bundle proposals once the finalizing block
+    /// has enough confirmations.
+    async fn delete_bundle_proposals<I>(
+        &self,
+        finalized: I,
+        block_height: u32,
+    ) -> Result<(), rusqlite::Error>
     where
         I: IntoIterator<Item = (SidechainNumber, M6id)>,
     {
+        let confirmations = self.config.wallet_opts.bundle_deletion_confirmations.get();
         // Satisfy clippy with a single function call per lock
         let with_connection = |connection: &Connection| -> Result<_, _> {
-            for (sidechain_number, m6id) in iter {
+            // Blocks at or above this height have been reorged out, so
+            // bundles finalized in those blocks are no longer final
+            let _ = connection.execute(
+                "DELETE FROM pending_bundle_deletions WHERE finalized_height >= ?1;",
+                [block_height],
+            )?;
+            for (sidechain_number, m6id) in finalized {
                 let _ = connection.execute(
-                    "DELETE FROM bundle_proposals where sidechain_number = ?1 AND bundle_hash = ?2;",
-                    (sidechain_number.0, m6id.0.as_byte_array())
+                    "INSERT OR REPLACE INTO pending_bundle_deletions
+                     (sidechain_number, bundle_hash, finalized_height) VALUES (?1, ?2, ?3);",
+                    (sidechain_number.0, m6id.0.as_byte_array(), block_height),
                 )?;
             }
+            let Some(max_finalized_height) = (block_height + 1).checked_sub(confirmations) else {
+                return Ok(());
+            };
+            let deleted = connection.execute(
+                "DELETE FROM bundle_proposals WHERE EXISTS
+                 (SELECT 1 FROM pending_bundle_deletions
+                  WHERE pending_bundle_deletions.sidechain_number = bundle_proposals.sidechain_number
+                  AND pending_bundle_deletions.bundle_hash = bundle_proposals.bundle_hash
+                  AND pending_bundle_deletions.finalized_height <= ?1);",
+                [max_finalized_height],
+            )?;
+            let _ = connection.execute(
+                "DELETE FROM pending_bundle_deletions WHERE finalized_height <= ?1;",
+                [max_finalized_height],
+            )?;
+            if deleted > 0 {
+                tracing::debug!(
+                    deleted,
+                    block_height,
+                    confirmations,
+                    "deleted finalized bundle proposals"
+                );
+            }
             Ok(())
         };
         let connection = self.self_db.lock().await;
@@ -622,7 +1052,7 @@ impl WalletInner {
     }
 
     // Gets wiped upon generating a new block.
-    async fn delete_pending_sidechain_proposals<I>(
+    pub(in crate::wallet) async fn delete_pending_sidechain_proposals<I>(
         &self,
         proposals: I,
     ) -> Result<(), rusqlite::Error>
@@ -643,15 +1073,170 @@ impl WalletInner {
     }
 }
 
+/// One of our sidechain proposals, not yet included in a block
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingSidechainProposal {
+    pub id: SidechainProposalId,
+    /// `None` if the proposal is not a valid sidechain declaration
+    pub title: Option<String>,
+    /// `None` if the proposal is not a valid sidechain declaration
+    pub description: Option<String>,
+    /// Number of blocks since the proposal was created.
+    /// `None` for proposals created before this was tracked.
+    pub blocks_pending: Option<u32>,
+}
+
+/// Address revealed by the wallet
+#[derive(Clone, Debug, Serialize)]
+pub struct RevealedAddress {
+    pub address: bitcoin::Address,
+    pub keychain: KeychainKind,
+    /// Derivation index within the keychain
+    pub index: u32,
+    /// `true` if the address has any associated transactions
+    pub used: bool,
+}
+
+/// Page of revealed addresses
+#[derive(Clone, Debug, Serialize)]
+pub struct RevealedAddresses {
+    pub addresses: Vec<RevealedAddress>,
+    /// Total number of revealed addresses, across all pages
+    pub total: usize,
+}
+
+/// Script type of a wallet output
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    fn of(script_pubkey: &bitcoin::Script) -> Self {
+        if script_pubkey.is_p2pkh() {
+            Self::P2pkh
+        } else if script_pubkey.is_p2sh() {
+            Self::P2sh
+        } else if script_pubkey.is_p2wpkh() {
+            Self::P2wpkh
+        } else if script_pubkey.is_p2wsh() {
+            Self::P2wsh
+        } else if script_pubkey.is_p2tr() {
+            Self::P2tr
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Unspent wallet output
+#[derive(Clone, Debug, Serialize)]
+pub struct UnspentOutput {
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: Amount,
+    pub script_type: ScriptType,
+    pub keychain: KeychainKind,
+    /// Derivation index within the keychain
+    pub derivation_index: u32,
+    /// Zero if the output is unconfirmed
+    pub confirmations: u32,
+}
+
+/// Page of unspent wallet outputs
+#[derive(Clone, Debug, Serialize)]
+pub struct UnspentOutputs {
+    pub outputs: Vec<UnspentOutput>,
+    /// Total number of outputs that match the filters, across all pages
+    pub total: usize,
+}
+
+/// Withdrawal in a pending withdrawal bundle
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingWithdrawal {
+    pub m6id: M6id,
+    pub script_pubkey: bitcoin::ScriptBuf,
+    /// `None` if the script pubkey does not correspond to an address
+    pub address: Option<bitcoin::Address>,
+    pub amount: Amount,
+}
+
+/// Pending withdrawals for a sidechain
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PendingWithdrawals {
+    /// Withdrawals in pending bundles that the wallet has the bundle
+    /// transaction for
+    pub withdrawals: Vec<PendingWithdrawal>,
+    /// Pending bundles that the wallet does not have the bundle transaction
+    /// for, so the withdrawals in these bundles are unknown
+    pub unknown_bundles: Vec<M6id>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SidechainDepositKind {
+    /// M5 deposit to the sidechain treasury
+    TreasuryDeposit,
+    /// Plain payment to the wallet's deposit address for the sidechain.
+    /// Nothing is paid to the sidechain treasury.
+    AddressPayment,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SidechainDepositTransaction {
     pub sidechain_number: SidechainNumber,
+    pub kind: SidechainDepositKind,
+    /// Value committed to the sidechain. For deposits to the sidechain
+    /// treasury, this excludes the value of the spent treasury UTXO.
     pub deposit_amount: Amount,
+    /// Total value of outputs that do not pay to the sidechain, eg. change
+    pub other_outputs_amount: Amount,
+    /// Sidechain address that the deposit is credited to. Empty for address
+    /// payments.
     #[serde(with = "hex::serde")]
     pub destination_address: Vec<u8>,
+    /// Payload of the first OP_RETURN output that does not carry the
+    /// destination address. Empty if there is no such output.
+    #[serde(with = "hex::serde")]
+    pub memo: Vec<u8>,
+    /// Number of confirmations as of the enforcer's chain tip. Zero if
+    /// unconfirmed.
+    pub confirmations: u32,
+    /// `true` if the deposit has at least as many confirmations as the
+    /// deposit finality depth (`--deposit-finality-depth`). Always `false`
+    /// if deposits are never considered final.
+    pub is_final: bool,
     pub wallet_tx: BDKWalletTransaction,
 }
 
+impl SidechainDepositTransaction {
+    /// Set confirmations and finality from the chain tip height and the
+    /// deposit finality depth
+    fn set_finality(&mut self, tip_height: Option<u32>, finality_depth: Option<NonZeroU32>) {
+        self.confirmations = match (&self.wallet_tx.chain_position, tip_height) {
+            (ChainPosition::Confirmed { anchor, .. }, Some(tip_height)) => {
+                (tip_height + 1).saturating_sub(anchor.block_id.height)
+            }
+            (ChainPosition::Confirmed { .. }, None) | (ChainPosition::Unconfirmed { .. }, _) => 0,
+        };
+        self.is_final = finality_depth.is_some_and(|depth| self.confirmations >= depth.get());
+    }
+
+    /// Payload of the first OP_RETURN output in `outputs`, which should not
+    /// include the destination address output
+    fn memo(outputs: &[bitcoin::TxOut]) -> Vec<u8> {
+        outputs
+            .iter()
+            .find_map(|txout| crate::messages::try_parse_op_return_address(&txout.script_pubkey))
+            .unwrap_or_default()
+    }
+}
+
 /// Optional parameters for sending a wallet transaction
 #[derive(Debug, Default)]
 pub struct CreateTransactionParams {
@@ -664,6 +1249,103 @@ pub struct CreateTransactionParams {
     // If set, sends ALL UTXOs in the wallet to this address.
     // Incompatible with `required_utxos`.
     pub drain_wallet_to: Option<bdk_wallet::bitcoin::Address>,
+    /// Optional address to send change to, instead of the wallet's internal
+    /// keychain. Incompatible with `drain_wallet_to`.
+    pub change_address: Option<ChangeAddress>,
+}
+
+/// Limits on the amount that can be deposited to a sidechain
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DepositPolicy {
+    pub sidechain_number: SidechainNumber,
+    /// Deposits below this amount are rejected as dust
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub dust_threshold: Amount,
+    /// Configured minimum deposit amount for the sidechain, if any
+    #[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+    pub minimum: Option<Amount>,
+}
+
+/// Confirmed spendable balance, less an operational reserve
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DeployableBalance {
+    /// Confirmed spendable balance
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub total: Amount,
+    /// Amount withheld from the total, at most the total
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub reserved: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub deployable: Amount,
+}
+
+/// Sidechains that the wallet creates deposits, withdrawal bundles, and
+/// coinbase messages for
+#[derive(Clone, Debug, Serialize)]
+pub struct SidechainFilter {
+    /// If set, only these sidechains are allowed
+    pub allowlist: Option<Vec<SidechainNumber>>,
+    pub denylist: Vec<SidechainNumber>,
+}
+
+/// Current Ctip (treasury UTXO) for a sidechain, and whether the wallet can
+/// spend it
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct CtipStatus {
+    pub sidechain_number: SidechainNumber,
+    pub outpoint: bitcoin::OutPoint,
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub value: Amount,
+    /// `true` if the Ctip transaction can be fetched, and the Ctip output
+    /// matches the validator's Ctip value
+    pub tracked: bool,
+    pub unlocked: bool,
+    /// `true` if the Ctip is tracked, and the wallet is unlocked
+    pub spendable: bool,
+}
+
+/// Change output override for wallet transactions
+#[derive(Clone, Debug)]
+pub struct ChangeAddress {
+    pub address: bdk_wallet::bitcoin::Address,
+    /// Must be set if the address is not owned by the wallet. Change sent to
+    /// an external address is not tracked by the wallet.
+    pub allow_external: bool,
+}
+
+impl ChangeAddress {
+    /// Script pubkey to drain change to. Fails if the address is not owned
+    /// by the wallet, unless external change is allowed.
+    fn drain_script(
+        &self,
+        wallet: &bdk_wallet::Wallet,
+    ) -> Result<bdk_wallet::bitcoin::ScriptBuf, error::ExternalChangeAddress> {
+        let script_pubkey = self.address.script_pubkey();
+        if !wallet.is_mine(script_pubkey.clone()) {
+            if !self.allow_external {
+                return Err(error::ExternalChangeAddress {
+                    address: self.address.clone(),
+                });
+            }
+            tracing::info!(
+                address = %self.address,
+                "Sending change to external address, which will not be tracked by the wallet"
+            );
+        }
+        Ok(script_pubkey)
+    }
+}
+
+/// Result of validating a mnemonic
+#[derive(Clone, Debug, Serialize)]
+pub struct MnemonicValidation {
+    pub valid: bool,
+    /// Reason that the mnemonic is invalid
+    pub error: Option<String>,
+    /// Public descriptor for receive addresses, if the mnemonic is valid
+    pub external_descriptor: Option<String>,
+    /// First receive address, if the mnemonic is valid
+    pub first_address: Option<bitcoin::Address>,
 }
 
 pub struct WalletInfo {
@@ -678,6 +1360,14 @@ pub struct WalletInfo {
     pub tip: (BlockHash, u32),
 }
 
+/// Wallet keychain whose descriptor derives from an xpub
+#[derive(Clone, Debug, Serialize)]
+pub struct XpubMatch {
+    pub keychain: KeychainKind,
+    /// Public descriptor for the keychain
+    pub descriptor: String,
+}
+
 /// Cheap to clone, since it uses Arc internally
 #[derive(Clone)]
 pub struct Wallet {
@@ -685,15 +1375,23 @@ pub struct Wallet {
 }
 
 impl Wallet {
+    /// Maximum number of transaction builds when subtracting the fee from
+    /// the amount sent
+    const SUBTRACT_FEE_MAX_BUILDS: usize = 5;
+
+    /// If `mnemonic` is provided, checks that it matches the wallet
+    /// descriptors persisted in `data_dir`, if any.
     pub async fn new(
         data_dir: &Path,
         config: &Config,
-        main_client: HttpClient,
+        main_client: MainchainClient,
         validator: Validator,
         magic: bitcoin::p2p::Magic,
+        mnemonic: Option<&Mnemonic>,
     ) -> Result<Self, error::InitWallet> {
-        let inner =
-            Arc::new(WalletInner::new(data_dir, config, main_client, validator, magic).await?);
+        let inner = Arc::new(
+            WalletInner::new(data_dir, config, main_client, validator, magic, mnemonic).await?,
+        );
         Ok(Self { inner })
     }
 
@@ -710,6 +1408,18 @@ impl Wallet {
         // Needed so we can use `tokio::select!`
         futures::pin_mut!(shutdown_signal);
 
+        let compaction_interval = self
+            .inner
+            .config
+            .wallet_opts
+            .db_compaction_interval_secs
+            .map(Duration::from_secs);
+        let coinbase_consolidation_threshold = self
+            .inner
+            .config
+            .wallet_opts
+            .coinbase_consolidation_threshold;
+        let mut last_compaction = Instant::now();
         let mut sleep = tokio::time::sleep(SYNC_INTERVAL).boxed();
         loop {
             tokio::select! {
@@ -719,20 +1429,51 @@ impl Wallet {
                     tracing::info!("shutting down sync task");
                     return Ok(res);
                 }
-                _ = &mut sleep => {
-                    let tick = Uuid::new_v4().simple();
-                    let span = tracing::span!(tracing::Level::DEBUG,
-                        "wallet_sync",
-                        %tick,
-                    );
-                    let guard = span.enter();
-                    if let Err(err) = self.inner.sync().await {
-                        tracing::error!("wallet sync error: {:#}", ErrorChain::new(&err));
-                    }
-                    drop(guard);
-                    sleep = tokio::time::sleep(SYNC_INTERVAL).boxed();
+                _ = &mut sleep => (),
+                () = self.inner.sync_requested.notified() => {
+                    tracing::debug!("wallet sync task: sync requested");
+                }
+            }
+            let tick = Uuid::new_v4().simple();
+            let span = tracing::span!(tracing::Level::DEBUG,
+                "wallet_sync",
+                %tick,
+            );
+            let guard = span.enter();
+            if let Err(err) = self.inner.sync().await {
+                tracing::error!("wallet sync error: {:#}", ErrorChain::new(&err));
+            }
+            // Compact between syncs, so that compaction does not
+            // block a sync
+            if let Some(compaction_interval) = compaction_interval
+                && last_compaction.elapsed() >= compaction_interval
+            {
+                if let Err(err) = self.compact_wallet_db().await {
+                    tracing::error!("wallet DB compaction error: {:#}", ErrorChain::new(&err));
                 }
+                last_compaction = Instant::now();
             }
+            if let Err(err) = self.prune_spent_imported_utxos().await {
+                tracing::error!(
+                    "failed to prune spent imported outputs: {:#}",
+                    ErrorChain::new(&err)
+                );
+            }
+            if let Some(threshold) = coinbase_consolidation_threshold
+                && let Err(err) = self
+                    .consolidate_coinbase_outputs(
+                        threshold,
+                        self.inner
+                            .config
+                            .wallet_opts
+                            .coinbase_consolidation_max_fee_rate,
+                    )
+                    .await
+            {
+                tracing::error!("coinbase consolidation error: {:#}", ErrorChain::new(&err));
+            }
+            drop(guard);
+            sleep = tokio::time::sleep(SYNC_INTERVAL).boxed();
         }
     }
 
@@ -755,16 +1496,110 @@ impl Wallet {
         Ok(address)
     }
 
-    pub async fn full_scan(&self) -> miette::Result<BlockHash, error::FullScan> {
-        self.inner.full_scan().await
+    /// Sync the wallet immediately. If a sync is already in progress, waits
+    /// for it to complete first.
+    pub async fn sync_now(&self) -> Result<SyncSummary, error::WalletSync> {
+        self.inner.sync().await
     }
 
-    pub async fn is_initialized(&self) -> bool {
-        self.inner.bitcoin_wallet.read().await.is_some()
+    /// Full scan the wallet. Fails if a full scan is already in progress.
+    pub async fn full_scan(&self) -> miette::Result<BlockHash, error::FullScan> {
+        self.inner.full_scan().await
     }
 
-    pub fn validator(&self) -> &Validator {
-        &self.inner.validator
+    /// Cancel the full scan in progress, if any. Results for the chunks of
+    /// addresses that have already been scanned are kept. Returns `false` if
+    /// there was no full scan in progress.
+    pub fn cancel_full_scan(&self) -> bool {
+        match &*self.inner.full_scan_cancel.lock() {
+            Some(cancel_tx) => {
+                tracing::info!("cancelling wallet full scan");
+                cancel_tx.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sync the wallet for the first time since startup. Performs a full
+    /// scan if `force_full_scan` is set, or if the wallet has never been
+    /// synced to the chain (eg. a freshly created wallet).
+    pub async fn initial_sync(&self, force_full_scan: bool) -> Result<(), error::InitialSync> {
+        let fresh = self.inner.read_wallet().await?.local_chain().tip().height() == 0;
+        if force_full_scan || fresh {
+            match self.full_scan().await {
+                Ok(_) => (),
+                Err(error::FullScan::Cancelled) => {
+                    let _: SyncSummary = self.sync_now().await?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        } else {
+            let _: SyncSummary = self.sync_now().await?;
+        }
+        Ok(())
+    }
+
+    /// Compact the BDK wallet database by running `VACUUM`.
+    /// Wallet updates are blocked while the database is being compacted, and
+    /// `VACUUM` briefly holds an exclusive lock on the database file.
+    pub async fn compact_wallet_db(&self) -> Result<(), error::SqliteError> {
+        let start = Instant::now();
+        let database = self.inner.bdk_db.lock().await;
+        let () = database.vacuum().await?;
+        tracing::info!(
+            file = %database.file_path.display(),
+            "Compacted wallet database in {:?}",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    pub async fn is_initialized(&self) -> bool {
+        self.inner.bitcoin_wallet.read().await.is_some()
+    }
+
+    /// Resolves once the wallet is unlocked, immediately if it already is.
+    pub async fn wait_until_unlocked(&self) {
+        let mut unlocked_rx = self.inner.unlocked.subscribe();
+        // The sender is owned by `self`, so the channel cannot be closed
+        // while we are waiting.
+        let _: Result<bool, _> = unlocked_rx
+            .wait_for(|unlocked| *unlocked)
+            .await
+            .map(|unlocked| *unlocked);
+    }
+
+    /// Resolves once a sync or full scan has completed, immediately if one
+    /// already has.
+    pub async fn wait_until_synced(&self) {
+        let mut synced_rx = self.inner.synced.subscribe();
+        // The sender is owned by `self`, so the channel cannot be closed
+        // while we are waiting.
+        let _: Result<bool, _> = synced_rx
+            .wait_for(|synced| *synced)
+            .await
+            .map(|synced| *synced);
+    }
+
+    /// Receiver for whether the wallet is unlocked
+    pub fn subscribe_unlocked(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.inner.unlocked.subscribe()
+    }
+
+    /// Receiver for whether a sync or full scan has completed
+    pub fn subscribe_synced(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.inner.synced.subscribe()
+    }
+
+    /// Receiver for whether the balance is below the low balance threshold,
+    /// as of the last sync
+    pub fn subscribe_low_balance(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.inner.low_balance.subscribe()
+    }
+
+    pub fn validator(&self) -> &Validator {
+        &self.inner.validator
     }
 
     /// Returns pending sidechain proposals from the wallet. These are not yet
@@ -788,7 +1623,7 @@ impl Wallet {
 
             Ok(proposals)
         };
-        let connection = self.inner.self_db.lock().await;
+        let connection = self.inner.self_db_readers.lock().await;
         with_connection(&connection)
     }
 
@@ -808,7 +1643,7 @@ impl Wallet {
                 .collect::<Result<_, _>>()?;
             Ok(rows)
         };
-        let connection = self.inner.self_db.lock().await;
+        let connection = self.inner.self_db_readers.lock().await;
         with_connection(&connection)
     }
 
@@ -842,7 +1677,7 @@ impl Wallet {
                 })?;
             Ok(bundle_proposals)
         };
-        let connection = self.inner.self_db.lock().await;
+        let connection = self.inner.self_db_readers.lock().await;
         let bundle_proposals = with_connection(&connection)?;
         drop(connection);
         // Filter out proposals that have already been created
@@ -959,7 +1794,7 @@ impl Wallet {
 
             Ok(queried)
         };
-        let connection = self.inner.self_db.lock().await;
+        let connection = self.inner.self_db_readers.lock().await;
         with_connection(&connection)
     }
 
@@ -1142,7 +1977,8 @@ impl Wallet {
         op_drivechain_output: bdk_wallet::bitcoin::TxOut,
         sidechain_address_data: bdk_wallet::bitcoin::script::PushBytesBuf,
         sidechain_ctip: Option<&Ctip>,
-        fee: Option<Amount>,
+        fee_policy: Option<crate::types::FeePolicy>,
+        change_address: Option<&ChangeAddress>,
     ) -> Result<bdk_wallet::bitcoin::psbt::Psbt, error::CreateDepositPsbt> {
         let sidechain_number = match crate::messages::parse_op_drivechain(
             op_drivechain_output.script_pubkey.as_bytes(),
@@ -1178,10 +2014,21 @@ impl Wallet {
             None => None,
         };
 
+        let fee_policy = match fee_policy {
+            Some(fee_policy) => Some(fee_policy),
+            None => self
+                .inner
+                .default_fee_rate()
+                .await?
+                .map(crate::types::FeePolicy::Rate),
+        };
         let psbt = {
-            let mut wallet_write = self.inner.write_wallet().await?;
+            let mut wallet_write = self.inner.try_write_wallet().await?;
             tokio::task::block_in_place(|| {
                 wallet_write.with_mut(|wallet| {
+                    let change_script = change_address
+                        .map(|change_address| change_address.drain_script(wallet))
+                        .transpose()?;
                     let mut builder = wallet.build_tx();
                     builder
                         // important: the M5 OP_DRIVECHAIN output must come directly before the OP_RETURN sidechain address output.
@@ -1191,8 +2038,18 @@ impl Wallet {
                         )
                         .add_data(&sidechain_address_data);
 
-                    if let Some(fee) = fee {
-                        builder.fee_absolute(fee);
+                    match fee_policy {
+                        Some(crate::types::FeePolicy::Absolute(fee)) => {
+                            builder.fee_absolute(fee);
+                        }
+                        Some(crate::types::FeePolicy::Rate(rate)) => {
+                            builder.fee_rate(rate);
+                        }
+                        None => (),
+                    }
+
+                    if let Some(change_script) = change_script {
+                        builder.drain_to(change_script);
                     }
 
                     if let Some((ctip_psbt_input, outpoint)) = ctip_foreign_utxo {
@@ -1218,6 +2075,93 @@ impl Wallet {
         Ok(psbt)
     }
 
+    /// Deposit amount limits for a sidechain
+    pub fn get_deposit_policy(&self, sidechain_number: SidechainNumber) -> DepositPolicy {
+        let dust_threshold = match self.inner.config.wallet_opts.deposit_dust_threshold_sats {
+            Some(dust_threshold_sats) => Amount::from_sat(dust_threshold_sats),
+            None => {
+                // The deposit output script does not depend on the values
+                Self::create_deposit_op_drivechain_output(
+                    sidechain_number,
+                    Amount::ZERO,
+                    Amount::ZERO,
+                )
+                .script_pubkey
+                .minimal_non_dust()
+            }
+        };
+        DepositPolicy {
+            sidechain_number,
+            dust_threshold,
+            minimum: self
+                .inner
+                .config
+                .wallet_opts
+                .sidechain_deposit_minimum(sidechain_number),
+        }
+    }
+
+    /// Sidechains that the wallet creates deposits, withdrawal bundles, and
+    /// coinbase messages for
+    pub fn get_sidechain_filter(&self) -> SidechainFilter {
+        let wallet_opts = &self.inner.config.wallet_opts;
+        SidechainFilter {
+            allowlist: wallet_opts.sidechain_allowlist.clone(),
+            denylist: wallet_opts.sidechain_denylist.clone(),
+        }
+    }
+
+    fn check_sidechain_allowed(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Result<(), error::SidechainNotAllowed> {
+        if self
+            .inner
+            .config
+            .wallet_opts
+            .is_sidechain_allowed(sidechain_number)
+        {
+            Ok(())
+        } else {
+            Err(error::SidechainNotAllowed { sidechain_number })
+        }
+    }
+
+    /// Current Ctip for a sidechain, and whether the wallet can spend it
+    pub async fn get_ctip_status(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Result<CtipStatus, error::GetCtipStatus> {
+        let Ctip { outpoint, value } = self
+            .inner
+            .validator
+            .try_get_ctip(sidechain_number)?
+            .ok_or(error::GetCtipStatus::NoCtip { sidechain_number })?;
+        let tracked = match self.fetch_transaction(outpoint.txid).await {
+            Ok(transaction) => transaction
+                .output
+                .get(outpoint.vout as usize)
+                .is_some_and(|output| output.value.to_sat() == value.to_sat()),
+            Err(err) => {
+                tracing::warn!(
+                    %outpoint,
+                    error = %ErrorChain::new(&err),
+                    "Failed to fetch Ctip transaction"
+                );
+                false
+            }
+        };
+        let unlocked = self.inner.read_wallet().await.is_ok();
+        Ok(CtipStatus {
+            sidechain_number,
+            outpoint,
+            value,
+            tracked,
+            unlocked,
+            spendable: tracked && unlocked,
+        })
+    }
+
     /// Creates a deposit transaction, persists it to the database, and returns the TXID.
     /// This is also known as a M5 message, in BIP300 nomenclature.
     ///
@@ -1227,8 +2171,10 @@ impl Wallet {
         sidechain_number: SidechainNumber,
         sidechain_address: String,
         value: Amount,
-        fee: Option<Amount>,
+        fee_policy: Option<crate::types::FeePolicy>,
+        change_address: Option<ChangeAddress>,
     ) -> Result<bitcoin::Txid, error::CreateDeposit> {
+        let () = self.check_sidechain_allowed(sidechain_number)?;
         let block_height = self
             .inner
             .validator
@@ -1250,6 +2196,26 @@ impl Wallet {
             spk = %op_drivechain_output.script_pubkey.to_asm_string(),
             "Created OP_DRIVECHAIN output",
         );
+        let DepositPolicy {
+            sidechain_number: _,
+            dust_threshold,
+            minimum,
+        } = self.get_deposit_policy(sidechain_number);
+        if value < dust_threshold {
+            return Err(error::CreateDeposit::DepositBelowDust {
+                value,
+                dust_threshold,
+            });
+        }
+        if let Some(minimum) = minimum
+            && value < minimum
+        {
+            return Err(error::CreateDeposit::DepositBelowMinimum {
+                sidechain_number,
+                value,
+                minimum,
+            });
+        }
         let sidechain_address_data =
             bdk_wallet::bitcoin::script::PushBytesBuf::try_from(sidechain_address.into_bytes())
                 .map_err(error::CreateDeposit::ConvertSidechainAddress)?;
@@ -1258,7 +2224,8 @@ impl Wallet {
                 op_drivechain_output,
                 sidechain_address_data,
                 sidechain_ctip,
-                fee,
+                fee_policy,
+                change_address.as_ref(),
             )
             .await?;
         tracing::debug!("Created deposit PSBT: {psbt}");
@@ -1302,11 +2269,26 @@ impl Wallet {
     ) -> Result<(bdk_wallet::Balance, bool), error::GetWalletBalance> {
         let has_synced = self.inner.last_sync.read().await.is_some();
 
-        let balance = self.inner.read_wallet().await?.balance();
+        let balance = self.inner.try_read_wallet().await?.balance();
 
         Ok((balance, has_synced))
     }
 
+    /// Confirmed spendable balance, less `reserve`. Zero if the balance is
+    /// below the reserve.
+    pub async fn get_deployable_balance(
+        &self,
+        reserve: Amount,
+    ) -> Result<DeployableBalance, error::LockWallet> {
+        let total = self.inner.try_read_wallet().await?.balance().confirmed;
+        let reserved = reserve.min(total);
+        Ok(DeployableBalance {
+            total,
+            reserved,
+            deployable: total - reserved,
+        })
+    }
+
     #[allow(
         clippy::significant_drop_tightening,
         reason = "false positive for `bitcoin_wallet`"
@@ -1317,7 +2299,7 @@ impl Wallet {
     ) -> Result<Vec<BDKWalletTransaction>, error::ListWalletTransactions> {
         // Massage the wallet data into a format that we can use to calculate fees, etc.
         let wallet_data = {
-            let wallet_read = self.inner.read_wallet().await?;
+            let wallet_read = self.inner.try_read_wallet().await?;
             let transactions = wallet_read.transactions();
 
             transactions
@@ -1393,7 +2375,7 @@ impl Wallet {
                     bitcoin::consensus::encode::deserialize_hex::<Transaction>(&transaction_hex)?;
 
                 let value = prev_output.output[input.previous_output.vout as usize].value;
-                if self.inner.read_wallet().await?.is_mine(
+                if self.inner.try_read_wallet().await?.is_mine(
                     prev_output.output[input.previous_output.vout as usize]
                         .script_pubkey
                         .clone(),
@@ -1403,15 +2385,24 @@ impl Wallet {
                 input_value += value;
             }
 
-            let fee = input_value
-                .checked_sub(output_value)
-                .unwrap_or(Amount::ZERO);
+            // Coinbase transactions have no fee, and an input total below
+            // the output total means that some prevout values are unknown
+            let fee = if tx.is_coinbase() {
+                None
+            } else {
+                input_value.checked_sub(output_value)
+            };
+            let vsize = tx.vsize() as u64;
+            let fee_rate_sat_per_vb = fee.map(|fee| fee.to_sat() as f64 / vsize as f64);
             // Calculate net wallet change (excluding fee)
             // We need to handle received and sent separately since Amount can't be negative
             let (final_received, final_sent) = if received >= sent {
                 (received - sent, Amount::from_sat(0)) // Net gain to wallet
             } else {
-                (Amount::from_sat(0), sent - received - fee) // Net loss from wallet
+                (
+                    Amount::from_sat(0),
+                    sent - received - fee.unwrap_or(Amount::ZERO),
+                ) // Net loss from wallet
             };
 
             txs.push(BDKWalletTransaction {
@@ -1419,6 +2410,8 @@ impl Wallet {
                 tx,
                 chain_position,
                 fee,
+                vsize,
+                fee_rate_sat_per_vb,
                 received: final_received,
                 sent: final_sent,
             });
@@ -1464,7 +2457,9 @@ impl Wallet {
     pub async fn list_sidechain_deposit_transactions(
         &self,
     ) -> Result<Vec<SidechainDepositTransaction>, error::ListSidechainDepositTransactions> {
-        self.list_wallet_transactions()
+        let deposit_addresses = self.get_sidechain_deposit_addresses().await?;
+        let mut deposit_txs = self
+            .list_wallet_transactions()
             .await?
             .into_iter()
             .map(Ok::<_, error::ListSidechainDepositTransactions>)
@@ -1476,7 +2471,10 @@ impl Wallet {
                 let Ok((_, sidechain_number)) =
                     crate::messages::parse_op_drivechain(&treasury_output.script_pubkey.to_bytes())
                 else {
-                    return Ok(None);
+                    return Ok(Self::deposit_address_transaction(
+                        &deposit_addresses,
+                        bdk_wallet_tx,
+                    ));
                 };
                 let treasury_outpoint = bitcoin::OutPoint {
                     txid: bdk_wallet_tx.txid,
@@ -1533,17 +2531,76 @@ impl Wallet {
                 ) else {
                     return Ok(None);
                 };
+                // Only the first output is credited to the treasury, even if
+                // other outputs are OP_DRIVECHAIN outputs for the same
+                // sidechain
+                let other_outputs_amount = bdk_wallet_tx.tx.output[1..]
+                    .iter()
+                    .map(|txout| txout.value)
+                    .sum();
+                let memo = SidechainDepositTransaction::memo(&bdk_wallet_tx.tx.output[2..]);
                 let deposit_tx = SidechainDepositTransaction {
                     sidechain_number,
+                    kind: SidechainDepositKind::TreasuryDeposit,
                     deposit_amount,
+                    other_outputs_amount,
                     destination_address,
+                    memo,
+                    confirmations: 0,
+                    is_final: false,
                     wallet_tx: bdk_wallet_tx,
                 };
                 Ok(Some(deposit_tx))
             })
-            .collect()
+            .collect::<Vec<_>>()?;
+        let tip_height = self.validator().try_get_block_height()?;
+        let finality_depth = self.validator().deposit_finality().confirmations;
+        for deposit_tx in &mut deposit_txs {
+            deposit_tx.set_finality(tip_height, finality_depth);
+        }
+        Ok(deposit_txs)
     }
 
+    /// Attribute a wallet transaction to a sidechain if it pays to one of the
+    /// derived sidechain deposit addresses.
+    fn deposit_address_transaction(
+        deposit_addresses: &HashMap<bitcoin::ScriptBuf, (SidechainNumber, bitcoin::Address)>,
+        bdk_wallet_tx: BDKWalletTransaction,
+    ) -> Option<SidechainDepositTransaction> {
+        let mut matched: Option<(SidechainNumber, Amount)> = None;
+        let mut other_outputs_amount = Amount::ZERO;
+        for txout in &bdk_wallet_tx.tx.output {
+            let Some((sidechain_number, _)) = deposit_addresses.get(&txout.script_pubkey) else {
+                other_outputs_amount += txout.value;
+                continue;
+            };
+            match &mut matched {
+                Some((matched_sidechain_number, amount))
+                    if *matched_sidechain_number == *sidechain_number =>
+                {
+                    *amount += txout.value;
+                }
+                // Outputs to multiple sidechains' deposit addresses are ambiguous
+                Some(_) => return None,
+                None => matched = Some((*sidechain_number, txout.value)),
+            }
+        }
+        let (sidechain_number, deposit_amount) = matched?;
+        Some(SidechainDepositTransaction {
+            sidechain_number,
+            kind: SidechainDepositKind::AddressPayment,
+            deposit_amount,
+            other_outputs_amount,
+            destination_address: Vec::new(),
+            memo: SidechainDepositTransaction::memo(&bdk_wallet_tx.tx.output),
+            confirmations: 0,
+            is_final: false,
+            wallet_tx: bdk_wallet_tx,
+        })
+    }
+
+    /// Required UTXOs that are in `imported_utxos` are spent as foreign
+    /// inputs, and will not be signed by the wallet.
     #[allow(
         clippy::significant_drop_tightening,
         reason = "false positive for `bitcoin_wallet`"
@@ -1552,12 +2609,28 @@ impl Wallet {
         &self,
         destinations: HashMap<bitcoin::Address, Amount>,
         params: CreateTransactionParams,
+        imported_utxos: &HashMap<bdk_wallet::bitcoin::OutPoint, import::ImportedUtxo>,
     ) -> Result<bdk_wallet::bitcoin::psbt::Psbt, error::CreateSendPsbt> {
+        let fee_policy = match params.fee_policy {
+            Some(fee_policy) => Some(fee_policy),
+            None => self
+                .inner
+                .default_fee_rate()
+                .await?
+                .map(crate::types::FeePolicy::Rate),
+        };
         let mut timestamp = Instant::now();
         let psbt = {
-            let mut wallet_write = self.inner.write_wallet().await?;
+            let mut wallet_write = self.inner.try_write_wallet().await?;
             tokio::task::block_in_place(|| {
                 wallet_write.with_mut(|wallet| {
+                    let change_script = match &params.change_address {
+                        Some(_) if params.drain_wallet_to.is_some() => {
+                            return Err(error::CreateSendPsbt::ChangeAddressWithDrainWallet);
+                        }
+                        Some(change_address) => Some(change_address.drain_script(wallet)?),
+                        None => None,
+                    };
                     let mut builder = wallet.build_tx();
 
                     if let Some(op_return_message) = params.op_return_message {
@@ -1588,13 +2661,35 @@ impl Wallet {
                         builder
                             .drain_to(drain_wallet_to.script_pubkey())
                             .drain_wallet();
+                    } else if let Some(change_script) = change_script {
+                        builder.drain_to(change_script);
                     }
 
                     if !params.required_utxos.is_empty() {
+                        let (imported_outpoints, wallet_outpoints): (Vec<_>, Vec<_>) = params
+                            .required_utxos
+                            .iter()
+                            .copied()
+                            .partition(|outpoint| imported_utxos.contains_key(outpoint));
+                        for outpoint in imported_outpoints {
+                            let imported_utxo = &imported_utxos[&outpoint];
+                            let psbt_input = bdk_wallet::bitcoin::psbt::Input {
+                                witness_utxo: Some(imported_utxo.txout()),
+                                ..bdk_wallet::bitcoin::psbt::Input::default()
+                            };
+                            builder.add_foreign_utxo(
+                                outpoint,
+                                psbt_input,
+                                imported_utxo.satisfaction_weight,
+                            )?;
+                            // Imported outputs are segwit, and the previous
+                            // transaction is not known
+                            builder.only_witness_utxo();
+                        }
                         builder
                             // TODO: this does not work at all for wallets past a certain scale....
                             // 25s pr. UTXO for a wallet with 40k UTXOs in total
-                            .add_utxos(&params.required_utxos)
+                            .add_utxos(&wallet_outpoints)
                             .map_err(|err| match err {
                                 bdk_wallet::tx_builder::AddUtxoError::UnknownUtxo(outpoint) => {
                                     error::CreateSendPsbt::UnknownUTXO(outpoint)
@@ -1611,7 +2706,7 @@ impl Wallet {
                         timestamp = Instant::now();
                     }
 
-                    match params.fee_policy {
+                    match fee_policy {
                         Some(crate::types::FeePolicy::Absolute(fee)) => {
                             builder.fee_absolute(fee);
                         }
@@ -1652,12 +2747,22 @@ impl Wallet {
             drain_wallet = params.drain_wallet_to.is_some(),
             "Sending wallet transaction",
         );
-        let mut timestamp = Instant::now();
-        let psbt = self.create_send_psbt(destinations, params).await?;
+        let timestamp = Instant::now();
+        let psbt = self
+            .create_send_psbt(destinations, params, &HashMap::new())
+            .await?;
 
         tracing::debug!("Created send PSBT in {:?}", timestamp.elapsed());
-        timestamp = Instant::now();
 
+        self.send_wallet_psbt(psbt).await
+    }
+
+    /// Signs a wallet PSBT, sends it, and returns the TXID.
+    async fn send_wallet_psbt(
+        &self,
+        psbt: bdk_wallet::bitcoin::psbt::Psbt,
+    ) -> Result<bitcoin::Txid, error::SendWalletTransaction> {
+        let mut timestamp = Instant::now();
         let tx = self.sign_transaction(psbt).await?;
         let txid = tx.compute_txid();
 
@@ -1683,7 +2788,8 @@ impl Wallet {
         }
         tracing::info!(%txid, "Broadcast send transaction in {:?}", timestamp.elapsed());
 
-        // Apply the unconfirmed transaction to the wallet
+        // Apply the unconfirmed transaction to the wallet. The transaction
+        // has already been broadcast, so wait for the locks without a timeout.
         let mut bdk_db_lock = self.inner.bdk_db.lock().await;
 
         let last_seen = std::time::SystemTime::now()
@@ -1693,7 +2799,8 @@ impl Wallet {
         let applied_changes = self
             .inner
             .write_wallet()
-            .await?
+            .await
+            .map_err(error::LockWallet::from)?
             .with_mut(|wallet| {
                 wallet.apply_unconfirmed_txs(vec![(tx, last_seen.as_secs())]);
                 wallet.persist_async(&mut bdk_db_lock)
@@ -1710,33 +2817,355 @@ impl Wallet {
         Ok(convert::bdk_txid_to_bitcoin_txid(txid))
     }
 
+    /// Send `amount` to `address`, and return the TXID.
+    /// If `subtract_fee_from_amount` is set, the recipient receives `amount`
+    /// minus the fee, and the fee is paid at the requested fee rate.
+    pub async fn send_to_address(
+        &self,
+        address: bdk_wallet::bitcoin::Address,
+        amount: Amount,
+        fee_rate: Option<bdk_wallet::bitcoin::FeeRate>,
+        subtract_fee_from_amount: bool,
+        change_address: Option<ChangeAddress>,
+    ) -> Result<bitcoin::Txid, error::SendToAddress> {
+        let params = || CreateTransactionParams {
+            fee_policy: fee_rate.map(crate::types::FeePolicy::Rate),
+            change_address: change_address.clone(),
+            ..Default::default()
+        };
+        if !subtract_fee_from_amount {
+            let txid = self
+                .send_wallet_transaction(HashMap::from([(address, amount)]), params())
+                .await?;
+            return Ok(txid);
+        }
+        // The fee depends on the inputs and outputs selected, which depend
+        // on the output value. Rebuild with the fee deducted from the output
+        // value until the fee no longer changes, and send the transaction
+        // from the final build, so that the recipient receives exactly
+        // `amount` minus the fee of the transaction that is sent.
+        let mut fee = Amount::ZERO;
+        for _ in 0..Self::SUBTRACT_FEE_MAX_BUILDS {
+            let Some(amount_after_fee) = amount
+                .checked_sub(fee)
+                .filter(|amount| *amount > Amount::ZERO)
+            else {
+                return Err(error::SendToAddress::AmountNotAboveFee { amount, fee });
+            };
+            let psbt = self
+                .create_send_psbt(
+                    HashMap::from([(address.clone(), amount_after_fee)]),
+                    params(),
+                    &HashMap::new(),
+                )
+                .await?;
+            let psbt_fee = psbt.fee().map_err(error::SendToAddress::PsbtFee)?;
+            if psbt_fee == fee {
+                let txid = self
+                    .send_wallet_psbt(psbt)
+                    .await
+                    .map_err(error::SendToAddress::SendWalletTransaction)?;
+                return Ok(txid);
+            }
+            fee = psbt_fee;
+        }
+        Err(error::SendToAddress::FeeDidNotConverge {
+            builds: Self::SUBTRACT_FEE_MAX_BUILDS,
+        })
+    }
+
+    /// Sum of input values minus sum of output values.
+    /// Input values are looked up via `getrawtransaction`.
+    async fn fetch_transaction_fee(
+        &self,
+        tx: &bdk_wallet::bitcoin::Transaction,
+    ) -> Result<Amount, error::FetchTransactionFee> {
+        let mut input_value = Amount::ZERO;
+        for input in &tx.input {
+            let prev_txid = convert::bdk_txid_to_bitcoin_txid(input.previous_output.txid);
+            let prev_tx = self.fetch_transaction(prev_txid).await.map_err(|err| {
+                error::FetchTransactionFee::FetchTransaction {
+                    txid: prev_txid,
+                    source: err,
+                }
+            })?;
+            let prev_output = prev_tx
+                .output
+                .get(input.previous_output.vout as usize)
+                .ok_or(error::FetchTransactionFee::MissingPrevout {
+                    outpoint: input.previous_output,
+                })?;
+            input_value += prev_output.value;
+        }
+        let output_value = tx.output.iter().map(|output| output.value).sum();
+        input_value
+            .checked_sub(output_value)
+            .ok_or(error::FetchTransactionFee::NegativeFee {
+                input_value,
+                output_value,
+            })
+    }
+
+    #[allow(
+        clippy::significant_drop_tightening,
+        reason = "false positive for `bitcoin_wallet`"
+    )]
+    async fn create_cpfp_psbt(
+        &self,
+        parent_outpoints: &[bdk_wallet::bitcoin::OutPoint],
+        fee_policy: crate::types::FeePolicy,
+    ) -> Result<bdk_wallet::bitcoin::psbt::Psbt, error::CreateCpfp> {
+        let mut wallet_write = self.inner.try_write_wallet().await?;
+        tokio::task::block_in_place(|| {
+            wallet_write.with_mut(|wallet| {
+                let change_address = wallet.next_unused_address(KeychainKind::Internal);
+                let mut builder = wallet.build_tx();
+                builder
+                    .add_utxos(parent_outpoints)
+                    .map_err(|err| match err {
+                        bdk_wallet::tx_builder::AddUtxoError::UnknownUtxo(outpoint) => {
+                            error::CreateCpfp::UnknownUtxo(outpoint)
+                        }
+                    })?
+                    // Only spend outputs from the parent, so that the child
+                    // weight does not change between builds
+                    .manually_selected_only()
+                    .drain_to(change_address.script_pubkey());
+                match fee_policy {
+                    crate::types::FeePolicy::Absolute(fee) => {
+                        builder.fee_absolute(fee);
+                    }
+                    crate::types::FeePolicy::Rate(rate) => {
+                        builder.fee_rate(rate);
+                    }
+                }
+                builder.finish().map_err(error::CreateCpfp::CreateTx)
+            })
+        })
+    }
+
+    /// Creates a child-pays-for-parent transaction for an unconfirmed
+    /// transaction, sends it, and returns the TXID of the child.
+    /// The child spends the wallet's outputs from the parent, paying a fee
+    /// high enough to bring the package up to the target fee rate.
+    pub async fn create_cpfp(
+        &self,
+        parent_txid: bitcoin::Txid,
+        fee_rate: bdk_wallet::bitcoin::FeeRate,
+    ) -> Result<bitcoin::Txid, error::CreateCpfp> {
+        let bdk_parent_txid = convert::bitcoin_txid_to_bdk_txid(parent_txid);
+        let (parent_tx, parent_outpoints) = {
+            let wallet_read = self.inner.try_read_wallet().await?;
+            let Some(parent) = wallet_read.get_tx(bdk_parent_txid) else {
+                return Err(error::CreateCpfp::UnknownParentTx { txid: parent_txid });
+            };
+            if parent.chain_position.is_confirmed() {
+                return Err(error::CreateCpfp::ParentConfirmed { txid: parent_txid });
+            }
+            let parent_tx = parent.tx_node.tx.as_ref().clone();
+            let parent_outpoints: Vec<_> = wallet_read
+                .list_unspent()
+                .filter(|utxo| utxo.outpoint.txid == bdk_parent_txid)
+                .map(|utxo| utxo.outpoint)
+                .collect();
+            (parent_tx, parent_outpoints)
+        };
+        if parent_outpoints.is_empty() {
+            return Err(error::CreateCpfp::NoSpendableOutput { txid: parent_txid });
+        }
+        let parent_fee = self.fetch_transaction_fee(&parent_tx).await?;
+        let parent_weight = parent_tx.weight();
+        let package_parent_fee = fee_rate
+            .fee_wu(parent_weight)
+            .ok_or(error::CreateCpfp::FeeOverflow { txid: parent_txid })?;
+        let Some(parent_fee_deficit) = package_parent_fee
+            .checked_sub(parent_fee)
+            .filter(|deficit| *deficit > Amount::ZERO)
+        else {
+            return Err(error::CreateCpfp::ParentFeeRateSufficient { txid: parent_txid });
+        };
+        tracing::debug!(
+            parent_txid = %parent_txid,
+            %parent_fee,
+            %parent_fee_deficit,
+            "Creating CPFP transaction",
+        );
+
+        // The fee paid by the child at the target fee rate, plus the
+        // shortfall in the parent fee
+        let child_fee = self
+            .create_cpfp_psbt(&parent_outpoints, fee_rate.into())
+            .await?
+            .fee()
+            .map_err(error::CreateCpfp::PsbtFee)?
+            + parent_fee_deficit;
+        let psbt = self
+            .create_cpfp_psbt(&parent_outpoints, child_fee.into())
+            .await?;
+        tracing::debug!("Created CPFP PSBT: {psbt}");
+
+        let tx = self.sign_transaction(psbt).await?;
+        let txid = tx.compute_txid();
+        tracing::info!(%txid, %parent_txid, %child_fee, "Signed CPFP transaction");
+
+        if crate::rpc_client::broadcast_transaction(&self.inner.main_client, &tx)
+            .await
+            .map_err(error::CreateCpfp::BroadcastTx)?
+            .is_none()
+        {
+            return Err(error::CreateCpfp::BroadcastUnsuccessful { txid });
+        }
+        tracing::info!(%txid, "Broadcast CPFP transaction successfully");
+
+        // Apply the unconfirmed transaction to the wallet. The transaction
+        // has already been broadcast, so wait for the locks without a timeout.
+        let mut bdk_db_lock = self.inner.bdk_db.lock().await;
+        let last_seen = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        let applied_changes = self
+            .inner
+            .write_wallet()
+            .await
+            .map_err(error::LockWallet::from)?
+            .with_mut(|wallet| {
+                wallet.apply_unconfirmed_txs(vec![(tx, last_seen.as_secs())]);
+                wallet.persist_async(&mut bdk_db_lock)
+            })
+            .await?;
+        if !applied_changes {
+            panic!("PROGRAMMER ERROR: no changes in wallet after applying unconfirmed transaction");
+        }
+
+        Ok(convert::bdk_txid_to_bitcoin_txid(txid))
+    }
+
     #[allow(
         clippy::significant_drop_tightening,
         reason = "false positive for `bitcoin_wallet`"
     )]
     #[instrument(skip_all)]
-    pub async fn get_utxos(&self) -> Result<Vec<bdk_wallet::LocalOutput>, error::NotUnlocked> {
-        let wallet_read = self.inner.read_wallet().await?;
+    pub async fn get_utxos(&self) -> Result<Vec<bdk_wallet::LocalOutput>, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
         let utxos = wallet_read.list_unspent().collect::<Vec<_>>();
 
         Ok(utxos)
     }
 
+    /// List unspent wallet outputs with at least `min_confirmations`
+    /// confirmations, and of the specified script type, if any.
+    /// Outputs are ordered by outpoint.
+    pub async fn list_unspent(
+        &self,
+        min_confirmations: u32,
+        script_type: Option<ScriptType>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<UnspentOutputs, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let tip_height = wallet_read.local_chain().tip().height();
+        let mut outputs: Vec<_> = wallet_read
+            .list_unspent()
+            .map(|utxo| {
+                let confirmations = match utxo.chain_position {
+                    ChainPosition::Confirmed { anchor, .. } => {
+                        tip_height.saturating_sub(anchor.block_id.height) + 1
+                    }
+                    ChainPosition::Unconfirmed { .. } => 0,
+                };
+                UnspentOutput {
+                    outpoint: utxo.outpoint,
+                    amount: utxo.txout.value,
+                    script_type: ScriptType::of(&utxo.txout.script_pubkey),
+                    keychain: utxo.keychain,
+                    derivation_index: utxo.derivation_index,
+                    confirmations,
+                }
+            })
+            .filter(|output| {
+                output.confirmations >= min_confirmations
+                    && script_type.is_none_or(|script_type| output.script_type == script_type)
+            })
+            .collect();
+        drop(wallet_read);
+        outputs.sort_unstable_by_key(|output| output.outpoint);
+        let total = outputs.len();
+        let outputs = outputs
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(UnspentOutputs { outputs, total })
+    }
+
     /// Persists a sidechain proposal into our database.
     /// On regtest: picked up by the next block generation.
     /// On signet: TBD, but needs some way of getting communicated to the miner.
     pub async fn propose_sidechain(
         &self,
         proposal: &SidechainProposal,
-    ) -> Result<(), rusqlite::Error> {
+    ) -> Result<(), error::ProposeSidechain> {
         let sidechain_number: u8 = proposal.sidechain_number.into();
+        let proposal_height = self.inner.validator.try_get_block_height()?;
         self.inner.self_db.lock().await.execute(
-            "INSERT INTO sidechain_proposals (sidechain_number, data_hash, data) VALUES (?1, ?2, ?3)",
-            (sidechain_number, proposal.description.sha256d_hash().to_byte_array(), &proposal.description.0),
+            "INSERT INTO sidechain_proposals (sidechain_number, data_hash, data, proposal_height) VALUES (?1, ?2, ?3, ?4)",
+            (sidechain_number, proposal.description.sha256d_hash().to_byte_array(), &proposal.description.0, proposal_height),
         )?;
         Ok(())
     }
 
+    /// List our sidechain proposals that have not yet been included in a
+    /// block.
+    pub async fn list_pending_sidechain_proposals(
+        &self,
+    ) -> Result<Vec<PendingSidechainProposal>, error::ListPendingSidechainProposals> {
+        let tip_height = self.inner.validator.try_get_block_height()?;
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let mut statement = connection.prepare(
+                "SELECT sidechain_number, data, proposal_height FROM sidechain_proposals",
+            )?;
+            let proposals = statement
+                .query_map([], |row| {
+                    let sidechain_number: u8 = row.get(0)?;
+                    let data: Vec<u8> = row.get(1)?;
+                    let proposal_height: Option<u32> = row.get(2)?;
+                    Ok((
+                        SidechainProposal {
+                            sidechain_number: sidechain_number.into(),
+                            description: data.into(),
+                        },
+                        proposal_height,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(proposals)
+        };
+        let proposals = {
+            let connection = self.inner.self_db_readers.lock().await;
+            with_connection(&connection)?
+        };
+        let res = proposals
+            .into_iter()
+            .map(|(proposal, proposal_height)| {
+                let declaration = SidechainDeclaration::try_from(&proposal.description).ok();
+                let blocks_pending = match (tip_height, proposal_height) {
+                    (Some(tip_height), Some(proposal_height)) => {
+                        Some(tip_height.saturating_sub(proposal_height))
+                    }
+                    _ => None,
+                };
+                PendingSidechainProposal {
+                    id: proposal.compute_id(),
+                    title: declaration.as_ref().map(|decl| decl.title.clone()),
+                    description: declaration.map(|decl| decl.description),
+                    blocks_pending,
+                }
+            })
+            .collect();
+        Ok(res)
+    }
+
     pub async fn nack_sidechain(
         &self,
         sidechain_number: u8,
@@ -1770,6 +3199,47 @@ impl Wallet {
         }
     }
 
+    /// List withdrawals in pending withdrawal bundles for a sidechain.
+    /// Returns no withdrawals if the sidechain is not active.
+    pub async fn list_pending_withdrawals(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Result<PendingWithdrawals, error::ListPendingWithdrawals> {
+        if !self.is_sidechain_active(sidechain_number)? {
+            return Ok(PendingWithdrawals::default());
+        }
+        let pending_m6ids = self
+            .inner
+            .validator
+            .get_pending_withdrawals(&sidechain_number)?;
+        let mut bundle_proposals: HashMap<M6id, BlindedM6<'static>> = self
+            .get_bundle_proposals()
+            .await?
+            .remove(&sidechain_number)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(m6id, blinded_m6, _)| (m6id, blinded_m6))
+            .collect();
+        let network = self.validator().network();
+        let mut res = PendingWithdrawals::default();
+        for m6id in pending_m6ids.keys() {
+            let Some(blinded_m6) = bundle_proposals.remove(m6id) else {
+                res.unknown_bundles.push(*m6id);
+                continue;
+            };
+            // The first output is the fee output
+            let payouts = blinded_m6.tx().into_owned().output.into_iter().skip(1);
+            res.withdrawals
+                .extend(payouts.map(|output| PendingWithdrawal {
+                    m6id: *m6id,
+                    address: bitcoin::Address::from_script(&output.script_pubkey, network).ok(),
+                    script_pubkey: output.script_pubkey,
+                    amount: output.value,
+                }));
+        }
+        Ok(res)
+    }
+
     pub fn is_sidechain_active(
         &self,
         sidechain_number: SidechainNumber,
@@ -1791,9 +3261,9 @@ impl Wallet {
 
         if !self
             .inner
-            .read_wallet()
+            .try_read_wallet()
             .await
-            .map_err(error::WalletSignTransaction::NotUnlocked)?
+            .map_err(error::WalletSignTransaction::LockWallet)?
             .sign(&mut psbt, bdk_wallet::signer::SignOptions::default())
             .map_err(error::WalletSignTransaction::SignerError)?
         {
@@ -1849,7 +3319,7 @@ impl Wallet {
 
         let psbt = {
             tracing::trace!("build_bmm_tx: acquiring wallet write lock");
-            let mut wallet_write = self.inner.write_wallet().await?;
+            let mut wallet_write = self.inner.try_write_wallet().await?;
             tokio::task::block_in_place(|| {
                 wallet_write.with_mut(|wallet| {
                     tracing::trace!("build_bmm_tx: creating transaction builder");
@@ -1949,8 +3419,8 @@ impl Wallet {
         }
     }
 
-    pub async fn get_wallet_info(&self) -> Result<WalletInfo, error::NotUnlocked> {
-        let w = self.inner.read_wallet().await?;
+    pub async fn get_wallet_info(&self) -> Result<WalletInfo, error::LockWallet> {
+        let w = self.inner.try_read_wallet().await?;
         let mut keychain_descriptors = std::collections::HashMap::new();
         for (kind, _) in w.keychains() {
             keychain_descriptors.insert(kind, w.public_descriptor(kind).clone());
@@ -1967,6 +3437,44 @@ impl Wallet {
         })
     }
 
+    /// Check whether the wallet's descriptors derive from `xpub`, and return
+    /// the matching keychains. `xpub` matches a descriptor if it is the
+    /// descriptor key (eg. an account xpub), or the key that the descriptor
+    /// derives addresses from. The network encoding of `xpub` is ignored.
+    pub async fn verify_xpub(
+        &self,
+        xpub: &bdk_wallet::bitcoin::bip32::Xpub,
+    ) -> Result<Vec<XpubMatch>, error::LockWallet> {
+        use bdk_wallet::miniscript::{ForEachKey as _, descriptor::DescriptorPublicKey};
+
+        let is_xpub = |candidate: &bdk_wallet::bitcoin::bip32::Xpub| {
+            candidate.public_key == xpub.public_key && candidate.chain_code == xpub.chain_code
+        };
+        let secp = bdk_wallet::bitcoin::secp256k1::Secp256k1::verification_only();
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let res = [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .filter_map(|keychain| {
+                let descriptor = wallet_read.public_descriptor(keychain);
+                let derives_from_xpub = descriptor.for_any_key(|key| match key {
+                    DescriptorPublicKey::XPub(xkey) => {
+                        is_xpub(&xkey.xkey)
+                            || xkey
+                                .xkey
+                                .derive_pub(&secp, &xkey.derivation_path)
+                                .is_ok_and(|derived| is_xpub(&derived))
+                    }
+                    DescriptorPublicKey::Single(_) | DescriptorPublicKey::MultiXPub(_) => false,
+                });
+                derives_from_xpub.then(|| XpubMatch {
+                    keychain,
+                    descriptor: descriptor.to_string(),
+                })
+            })
+            .collect();
+        Ok(res)
+    }
+
     #[allow(clippy::significant_drop_tightening)]
     pub async fn get_new_address(
         &self,
@@ -1974,7 +3482,7 @@ impl Wallet {
         // Using next_unused_address here means that we get a new address
         // when funds are received. Without this we'd need to take care not
         // to cross the wallet scan gap.
-        let mut wallet_write = self.inner.write_wallet().await?;
+        let mut wallet_write = self.inner.try_write_wallet().await?;
 
         let mut bdk_db_lock = self.inner.bdk_db.lock().await;
         let address = wallet_write
@@ -1988,20 +3496,338 @@ impl Wallet {
         Ok(address)
     }
 
+    /// Get the next unused receive address, without revealing a new one.
+    /// Repeated calls return the same address until it receives funds.
+    /// Sidechain deposit addresses are never returned.
+    pub async fn get_unused_address(
+        &self,
+    ) -> Result<bdk_wallet::bitcoin::Address, error::GetUnusedAddress> {
+        let sidechain_deposit_addresses = self.get_sidechain_deposit_addresses().await?;
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let keychain = bdk_wallet::KeychainKind::External;
+        let unused_address = wallet_read
+            .list_unused_addresses(keychain)
+            .map(|info| info.address)
+            .find(|address| {
+                !sidechain_deposit_addresses.contains_key(address.script_pubkey().as_script())
+            });
+        let address = match unused_address {
+            Some(address) => address,
+            None => {
+                let index = wallet_read.next_derivation_index(keychain);
+                wallet_read.peek_address(keychain, index).address
+            }
+        };
+        Ok(address)
+    }
+
+    /// List revealed addresses, external keychain first, in order of
+    /// derivation index. Skips the first `offset` addresses, and returns at
+    /// most `limit` addresses.
+    pub async fn list_addresses(
+        &self,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<RevealedAddresses, error::LockWallet> {
+        let wallet_read = self.inner.try_read_wallet().await?;
+        let keychains = [KeychainKind::External, KeychainKind::Internal];
+        let mut revealed = Vec::new();
+        for keychain in keychains {
+            let unused_indices: HashSet<u32> = wallet_read
+                .list_unused_addresses(keychain)
+                .map(|info| info.index)
+                .collect();
+            let Some(last_revealed) = wallet_read.derivation_index(keychain) else {
+                continue;
+            };
+            revealed.extend(
+                (0..=last_revealed)
+                    .map(|index| (keychain, index, !unused_indices.contains(&index))),
+            )
+        }
+        let total = revealed.len();
+        let addresses = revealed
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(keychain, index, used)| RevealedAddress {
+                address: wallet_read.peek_address(keychain, index).address,
+                keychain,
+                index,
+                used,
+            })
+            .collect();
+        Ok(RevealedAddresses { addresses, total })
+    }
+
+    /// Get the deposit addresses that have been derived for sidechains,
+    /// keyed by script pubkey.
+    async fn get_sidechain_deposit_addresses(
+        &self,
+    ) -> Result<HashMap<bitcoin::ScriptBuf, (SidechainNumber, bitcoin::Address)>, rusqlite::Error>
+    {
+        let network = self.validator().network();
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let mut statement = connection
+                .prepare("SELECT sidechain_number, address FROM sidechain_deposit_addresses")?;
+            let rows = statement
+                .query_map([], |row| {
+                    let sidechain_number = SidechainNumber(row.get(0)?);
+                    let address: String = row.get(1)?;
+                    Ok((sidechain_number, address))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        };
+        let connection = self.inner.self_db_readers.lock().await;
+        let rows = with_connection(&connection)?;
+        drop(connection);
+        let res = rows
+            .into_iter()
+            .filter_map(|(sidechain_number, address)| {
+                // Addresses are written by us, so failing to parse one means
+                // the DB was tampered with. Skip it rather than erroring out.
+                let address = bitcoin::Address::from_str(&address)
+                    .ok()?
+                    .require_network(network)
+                    .ok()?;
+                Some((address.script_pubkey(), (sidechain_number, address)))
+            })
+            .collect();
+        Ok(res)
+    }
+
+    /// Get the dedicated deposit address for a sidechain, deriving and
+    /// persisting a fresh one if none exists yet. Repeated calls for the same
+    /// sidechain return the same address.
+    /// Addresses are revealed from the wallet's external keychain, in the
+    /// order that sidechains first request them. Sidechain-specified
+    /// derivation paths are not supported, since the BDK wallet only tracks
+    /// and signs for its external and internal keychains.
+    #[allow(clippy::significant_drop_tightening)]
+    pub async fn get_sidechain_deposit_address(
+        &self,
+        sidechain_number: SidechainNumber,
+    ) -> Result<bitcoin::Address, error::GetSidechainDepositAddress> {
+        let network = self.validator().network();
+        let parse_address = |address: String| {
+            bitcoin::Address::from_str(&address)
+                .map_err(
+                    |_| error::GetSidechainDepositAddress::InvalidStoredAddress {
+                        sidechain_number,
+                        address: address.clone(),
+                    },
+                )?
+                .require_network(network)
+                .map_err(
+                    |_| error::GetSidechainDepositAddress::InvalidStoredAddress {
+                        sidechain_number,
+                        address,
+                    },
+                )
+        };
+        let existing = select_sidechain_deposit_address(
+            &*self.inner.self_db_readers.lock().await,
+            sidechain_number,
+        )?;
+        if let Some(address) = existing {
+            return parse_address(address);
+        }
+
+        // Hold the wallet write lock until the address is persisted, and check
+        // again once it is held, so that concurrent requests for the same
+        // sidechain can't derive two addresses.
+        let mut wallet_write = self.inner.try_write_wallet().await?;
+        let existing =
+            select_sidechain_deposit_address(&*self.inner.self_db.lock().await, sidechain_number)?;
+        if let Some(address) = existing {
+            return parse_address(address);
+        }
+        let mut bdk_db_lock = self.inner.bdk_db.lock().await;
+        let derived_address = wallet_write
+            .with_mut(|wallet| {
+                let info = wallet.reveal_next_address(bdk_wallet::KeychainKind::External);
+                wallet
+                    .persist_async(&mut bdk_db_lock)
+                    .map_ok(|_: bool| info.address)
+            })
+            .await?;
+        drop(bdk_db_lock);
+
+        let address = insert_sidechain_deposit_address(
+            &*self.inner.self_db.lock().await,
+            sidechain_number,
+            &derived_address.to_string(),
+        )?;
+        drop(wallet_write);
+        let address = parse_address(address)?;
+        if address == derived_address {
+            tracing::info!(
+                %sidechain_number,
+                %address,
+                "derived new sidechain deposit address"
+            );
+        }
+        Ok(address)
+    }
+
+    /// Validate a transaction against the enforcer rules, and broadcast it
+    /// via Bitcoin Core's `sendrawtransaction`.
+    pub async fn broadcast_raw_transaction(
+        &self,
+        tx: &bitcoin::Transaction,
+    ) -> Result<bitcoin::Txid, error::BroadcastRawTransaction> {
+        use bitcoin_jsonrpsee::{MainClient as _, jsonrpsee::core::client::Error as ClientError};
+
+        let txid = tx.compute_txid();
+        if let Some(reason) = self.inner.validator.check_tx(tx)? {
+            tracing::warn!(%txid, %reason, "enforcer rejected raw transaction");
+            return Err(error::BroadcastRawTransaction::RejectedByEnforcer { reason });
+        }
+        let encoded_tx = bitcoin::consensus::encode::serialize_hex(tx);
+        match self
+            .inner
+            .main_client
+            .send_raw_transaction(encoded_tx, None, Some(crate::rpc_client::MAX_BURN_AMOUNT))
+            .await
+        {
+            Ok(txid) => {
+                tracing::info!(%txid, "broadcast raw transaction successfully");
+                Ok(txid)
+            }
+            Err(ClientError::Call(err)) => {
+                let err = error::BitcoinCoreReject {
+                    code: err.code(),
+                    message: err.message().to_owned(),
+                };
+                tracing::warn!(%txid, "{err:#}");
+                Err(err.into())
+            }
+            Err(err) => Err(error::BitcoinCoreRPC {
+                method: "sendrawtransaction".to_string(),
+                error: err,
+            }
+            .into()),
+        }
+    }
+
+    /// Run `operation` at most once for each idempotency key of `method`.
+    /// If an operation with the same method and key has already succeeded,
+    /// and the key has not expired, the original result is returned instead
+    /// of running `operation` again. Reusing a key with different `params`
+    /// is an error, as is reusing a key while an operation with the same
+    /// method and key is in progress. Failed operations are not recorded, and
+    /// can be retried with the same key.
+    pub async fn with_idempotency_key<T, Err, Fut>(
+        &self,
+        method: &'static str,
+        idempotency_key: Option<uuid::Uuid>,
+        params: &serde_json::Value,
+        operation: Fut,
+    ) -> Result<T, error::Idempotent<Err>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+        Fut: Future<Output = Result<T, Err>>,
+    {
+        let Some(idempotency_key) = idempotency_key else {
+            return operation.await.map_err(error::Idempotent::Operation);
+        };
+        let idempotency_key = idempotency_key.to_string();
+        let params_hash = sha256::Hash::hash(params.to_string().as_bytes()).to_byte_array();
+        let ttl_secs = self.inner.config.wallet_opts.idempotency_key_ttl_secs;
+        if !self
+            .inner
+            .idempotency_reservations
+            .lock()
+            .insert((method, idempotency_key.clone()))
+        {
+            return Err(error::Idempotent::InProgress {
+                method,
+                idempotency_key,
+            });
+        }
+        let reservation = IdempotencyReservation {
+            reservations: &self.inner.idempotency_reservations,
+            method,
+            idempotency_key,
+        };
+        let idempotency_key = &reservation.idempotency_key;
+
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            connection.execute(
+                "DELETE FROM idempotency_keys
+                 WHERE created_at <= CAST(strftime('%s', 'now') AS INTEGER) - ?1",
+                (ttl_secs,),
+            )?;
+            connection
+                .query_row(
+                    "SELECT params_hash, result FROM idempotency_keys
+                     WHERE method = ?1 AND idempotency_key = ?2",
+                    (method, idempotency_key),
+                    |row| Ok((row.get::<_, [u8; 32]>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()
+        };
+        let existing = {
+            let connection = self.inner.self_db.lock().await;
+            with_connection(&connection)?
+        };
+        if let Some((existing_params_hash, result)) = existing {
+            if existing_params_hash != params_hash {
+                return Err(error::Idempotent::ParamsMismatch {
+                    method,
+                    idempotency_key: idempotency_key.clone(),
+                });
+            }
+            tracing::debug!(
+                %method,
+                %idempotency_key,
+                "returning result for existing idempotency key"
+            );
+            return Ok(serde_json::from_str(&result)?);
+        }
+
+        let res = operation.await.map_err(error::Idempotent::Operation)?;
+        self.inner.self_db.lock().await.execute(
+            "INSERT INTO idempotency_keys
+             (method, idempotency_key, params_hash, result, created_at)
+             VALUES (?1, ?2, ?3, ?4, CAST(strftime('%s', 'now') AS INTEGER))",
+            (
+                method,
+                idempotency_key,
+                params_hash,
+                serde_json::to_string(&res)?,
+            ),
+        )?;
+        drop(reservation);
+        Ok(res)
+    }
+
     pub async fn put_withdrawal_bundle(
         &self,
         sidechain_number: SidechainNumber,
         blinded_m6: &BlindedM6<'static>,
-    ) -> Result<M6id, rusqlite::Error> {
+    ) -> Result<M6id, error::PutWithdrawalBundle> {
+        let () = self.check_sidechain_allowed(sidechain_number)?;
         let m6id = blinded_m6.compute_m6id();
         let tx_bytes = bitcoin::consensus::serialize(blinded_m6.as_ref());
-        self.inner.self_db
-            .lock()
-            .await
-            .execute(
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let _ = connection.execute(
                 "INSERT OR IGNORE INTO bundle_proposals (sidechain_number, bundle_hash, bundle_tx) VALUES (?1, ?2, ?3)",
                 (sidechain_number.0, m6id.0.as_byte_array(), tx_bytes),
             )?;
+            let _ = connection.execute(
+                "INSERT OR IGNORE INTO proposed_withdrawal_bundles (sidechain_number, bundle_hash, proposed_at)
+                 VALUES (?1, ?2, CAST(strftime('%s', 'now') AS INTEGER))",
+                (sidechain_number.0, m6id.0.as_byte_array()),
+            )?;
+            Ok(())
+        };
+        let connection = self.inner.self_db.lock().await;
+        with_connection(&connection)?;
+        drop(connection);
         Ok(m6id)
     }
 
@@ -2018,27 +3844,23 @@ impl Wallet {
 
         let try_include_hash = self
             .inner
-            .main_client
-            .getblockhash(try_include_height as usize)
+            .request_with_retry("getblockhash", || {
+                self.inner
+                    .main_client
+                    .getblockhash(try_include_height as usize)
+            })
             .await
-            .map_err(|err| {
-                error::ConnectBlock::GetBlockHash(error::BitcoinCoreRPC {
-                    method: "getblockhash".to_string(),
-                    error: err,
-                })
-            })?;
+            .map_err(error::ConnectBlock::GetBlockHash)?;
 
         let try_include_block = self
             .inner
-            .main_client
-            .get_block(try_include_hash, U8Witness::<0>)
+            .request_with_retry("getblock", || {
+                self.inner
+                    .main_client
+                    .get_block(try_include_hash, U8Witness::<0>)
+            })
             .await
-            .map_err(|err| {
-                error::ConnectBlock::GetBlock(error::BitcoinCoreRPC {
-                    method: "getblock".to_string(),
-                    error: err,
-                })
-            })?;
+            .map_err(error::ConnectBlock::GetBlock)?;
 
         let infos = self
             .inner
@@ -2094,6 +3916,40 @@ impl Wallet {
         self.inner.unlock_existing_wallet(password).await
     }
 
+    /// Check that a mnemonic is valid, without creating a wallet or
+    /// persisting anything. If the mnemonic is valid, the public external
+    /// descriptor and first receive address are returned, so that they can be
+    /// checked against a backup.
+    pub fn validate_mnemonic(
+        &self,
+        phrase: &str,
+        language: Language,
+    ) -> Result<MnemonicValidation, error::ValidateMnemonic> {
+        let mnemonic = match Mnemonic::parse_in(language, phrase) {
+            Ok(mnemonic) => mnemonic,
+            Err(err) => {
+                return Ok(MnemonicValidation {
+                    valid: false,
+                    error: Some(format!("{err:#}")),
+                    external_descriptor: None,
+                    first_address: None,
+                });
+            }
+        };
+        let network = self.validator().network();
+        let (external_desc, internal_desc) =
+            WalletInner::descriptors_from_mnemonic(&mnemonic, network)?;
+        let wallet = bdk_wallet::Wallet::create(external_desc, internal_desc)
+            .network(network)
+            .create_wallet_no_persist()?;
+        Ok(MnemonicValidation {
+            valid: true,
+            error: None,
+            external_descriptor: Some(wallet.public_descriptor(KeychainKind::External).to_string()),
+            first_address: Some(wallet.peek_address(KeychainKind::External, 0).address),
+        })
+    }
+
     // Creates a new wallet with a given mnemonic and encryption password.
     // Note that the password is NOT a BIP39 passphrase, but is only used to
     // encrypt the mnemonic in storage.
@@ -2105,3 +3961,176 @@ impl Wallet {
         self.inner.create_new_wallet(mnemonic, password).await
     }
 }
+
+/// Get the stored deposit address for a sidechain, if any
+fn select_sidechain_deposit_address(
+    connection: &Connection,
+    sidechain_number: SidechainNumber,
+) -> Result<Option<String>, rusqlite::Error> {
+    connection
+        .query_row(
+            "SELECT address FROM sidechain_deposit_addresses WHERE sidechain_number = ?1",
+            [sidechain_number.0],
+            |row| row.get(0),
+        )
+        .optional()
+}
+
+/// Store the deposit address for a sidechain, unless one is already stored.
+/// Returns the stored address, which is `address` only if none existed.
+fn insert_sidechain_deposit_address(
+    connection: &Connection,
+    sidechain_number: SidechainNumber,
+    address: &str,
+) -> Result<String, rusqlite::Error> {
+    connection.execute(
+        "INSERT INTO sidechain_deposit_addresses (sidechain_number, address) VALUES (?1, ?2)
+         ON CONFLICT (sidechain_number) DO NOTHING",
+        (sidechain_number.0, address),
+    )?;
+    connection.query_row(
+        "SELECT address FROM sidechain_deposit_addresses WHERE sidechain_number = ?1",
+        [sidechain_number.0],
+        |row| row.get(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::{
+        WalletInner, error, insert_sidechain_deposit_address, select_sidechain_deposit_address,
+    };
+    use crate::types::SidechainNumber;
+
+    #[test]
+    fn test_db_migrations() {
+        rusqlite_migration::Migrations::new(WalletInner::db_migrations())
+            .validate()
+            .unwrap();
+        let supported_version = WalletInner::db_migrations().len();
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        WalletInner::migrate_db(&mut db_connection).unwrap();
+        // Migrating an up-to-date database is a no-op
+        WalletInner::migrate_db(&mut db_connection).unwrap();
+        let version: usize = db_connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, supported_version);
+        // Downgrades are rejected
+        db_connection
+            .pragma_update(None, "user_version", supported_version + 1)
+            .unwrap();
+        assert!(matches!(
+            WalletInner::migrate_db(&mut db_connection),
+            Err(error::InitDbConnection::SchemaTooNew { .. })
+        ));
+    }
+
+    /// A database created by an older version of the enforcer is upgraded
+    /// to the latest schema, and existing rows are migrated
+    #[test]
+    fn test_db_migrations_upgrade() {
+        let migrations = WalletInner::db_migrations();
+        let supported_version = migrations.len();
+        let old_version = WalletInner::PROPOSED_WITHDRAWAL_BUNDLES_MIGRATION;
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        rusqlite_migration::Migrations::new(migrations)
+            .to_version(&mut db_connection, old_version)
+            .unwrap();
+        db_connection
+            .execute(
+                "INSERT INTO bundle_proposals (sidechain_number, bundle_hash, bundle_tx)
+                 VALUES (1, x'01', x'02')",
+                (),
+            )
+            .unwrap();
+        WalletInner::migrate_db(&mut db_connection).unwrap();
+        let version: usize = db_connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, supported_version);
+        let (sidechain_number, bundle_hash, proposed_at): (u8, Vec<u8>, Option<u64>) =
+            db_connection
+                .query_row(
+                    "SELECT sidechain_number, bundle_hash, proposed_at
+                     FROM proposed_withdrawal_bundles",
+                    (),
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+        assert_eq!(sidechain_number, 1);
+        assert_eq!(bundle_hash, vec![0x01]);
+        assert_eq!(proposed_at, None);
+    }
+
+    /// Re-requesting a sidechain's deposit address returns the stored
+    /// address, and a second insert for the same sidechain does not replace it
+    #[test]
+    fn test_sidechain_deposit_address_reuse() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        WalletInner::migrate_db(&mut db_connection).unwrap();
+        let sidechain_number = SidechainNumber(1);
+        assert_eq!(
+            select_sidechain_deposit_address(&db_connection, sidechain_number).unwrap(),
+            None
+        );
+        let address =
+            insert_sidechain_deposit_address(&db_connection, sidechain_number, "first").unwrap();
+        assert_eq!(address, "first");
+        let address =
+            insert_sidechain_deposit_address(&db_connection, sidechain_number, "second").unwrap();
+        assert_eq!(address, "first");
+        assert_eq!(
+            select_sidechain_deposit_address(&db_connection, sidechain_number)
+                .unwrap()
+                .as_deref(),
+            Some("first")
+        );
+        // Other sidechains get their own address
+        let address =
+            insert_sidechain_deposit_address(&db_connection, SidechainNumber(2), "second").unwrap();
+        assert_eq!(address, "second");
+    }
+
+    /// Concurrent inserts for the same sidechain, from separate connections,
+    /// all return the same stored address
+    #[test]
+    fn test_sidechain_deposit_address_concurrent() {
+        let db_path = std::env::temp_dir().join(format!(
+            "bip300301_enforcer_test_deposit_address_{}.sqlite",
+            std::process::id()
+        ));
+        if db_path.exists() {
+            std::fs::remove_file(&db_path).unwrap();
+        }
+        WalletInner::migrate_db(&mut Connection::open(&db_path).unwrap()).unwrap();
+        let sidechain_number = SidechainNumber(1);
+        let addresses: Vec<String> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let db_path = &db_path;
+                    scope.spawn(move || {
+                        let db_connection = Connection::open(db_path).unwrap();
+                        db_connection
+                            .busy_timeout(std::time::Duration::from_secs(10))
+                            .unwrap();
+                        insert_sidechain_deposit_address(
+                            &db_connection,
+                            sidechain_number,
+                            &format!("address-{i}"),
+                        )
+                        .unwrap()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        std::fs::remove_file(&db_path).unwrap();
+        assert!(addresses.iter().all(|address| *address == addresses[0]));
+    }
+}
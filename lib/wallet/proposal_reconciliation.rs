@@ -0,0 +1,178 @@
+//! Reconcile the wallet's bundle and sidechain proposals against the
+//! validator, deleting proposals that should no longer be pending
+
+use std::collections::HashSet;
+
+use bitcoin::hashes::Hash as _;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::{
+    types::{M6id, SidechainNumber, SidechainProposalId},
+    wallet::{Wallet, error},
+};
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleBundleProposalReason {
+    /// The sidechain is not active
+    SidechainInactive,
+    /// The bundle failed or succeeded, in a block with at least
+    /// `--wallet-bundle-deletion-confirmations` confirmations
+    Finalized,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DeletedBundleProposal {
+    pub sidechain_number: SidechainNumber,
+    pub m6id: M6id,
+    pub reason: StaleBundleProposalReason,
+}
+
+/// Proposals deleted by proposal reconciliation
+#[derive(Clone, Debug, Serialize)]
+pub struct ProposalReconciliation {
+    pub deleted_bundle_proposals: Vec<DeletedBundleProposal>,
+    /// Sidechain proposals that have already been included in a block
+    pub deleted_sidechain_proposals: Vec<SidechainProposalId>,
+}
+
+impl Wallet {
+    /// Bundle proposals, with the height of the block that finalized the
+    /// bundle, if the latest recorded event for the bundle finalized it
+    async fn get_bundle_proposal_finalized_heights(
+        &self,
+    ) -> Result<Vec<(SidechainNumber, M6id, Option<u32>)>, rusqlite::Error> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let mut statement = connection.prepare(
+                "SELECT bundle_proposals.sidechain_number, bundle_proposals.bundle_hash,
+                        withdrawal_bundle_events.block_height
+                 FROM bundle_proposals
+                 LEFT JOIN withdrawal_bundle_events
+                 ON withdrawal_bundle_events.rowid =
+                    (SELECT rowid FROM withdrawal_bundle_events
+                     WHERE sidechain_number = bundle_proposals.sidechain_number
+                     AND bundle_hash = bundle_proposals.bundle_hash
+                     ORDER BY block_height DESC, event_index DESC LIMIT 1)
+                 AND withdrawal_bundle_events.status IN ('failed', 'succeeded');",
+            )?;
+            let rows = statement
+                .query_map([], |row| {
+                    let sidechain_number = SidechainNumber(row.get(0)?);
+                    let m6id_bytes: [u8; 32] = row.get(1)?;
+                    let finalized_height: Option<u32> = row.get(2)?;
+                    Ok((sidechain_number, M6id::from(m6id_bytes), finalized_height))
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(rows)
+        };
+        let connection = self.inner.self_db_readers.lock().await;
+        with_connection(&connection)
+    }
+
+    /// Bundle proposals that should no longer be pending. Bundles that are
+    /// pending in the validator are never stale.
+    async fn get_stale_bundle_proposals(
+        &self,
+    ) -> Result<Vec<DeletedBundleProposal>, error::ReconcileProposals> {
+        let validator = &self.inner.validator;
+        let confirmations = self
+            .inner
+            .config
+            .wallet_opts
+            .bundle_deletion_confirmations
+            .get();
+        let max_finalized_height = validator
+            .try_get_block_height()?
+            .and_then(|tip_height| (tip_height + 1).checked_sub(confirmations));
+        let mut res = Vec::new();
+        for (sidechain_number, m6id, finalized_height) in
+            self.get_bundle_proposal_finalized_heights().await?
+        {
+            let reason = if validator
+                .try_get_active_sidechain(sidechain_number)?
+                .is_none()
+            {
+                StaleBundleProposalReason::SidechainInactive
+            } else if validator
+                .get_pending_withdrawals(&sidechain_number)?
+                .contains_key(&m6id)
+            {
+                continue;
+            } else if let (Some(finalized_height), Some(max_finalized_height)) =
+                (finalized_height, max_finalized_height)
+                && finalized_height <= max_finalized_height
+            {
+                StaleBundleProposalReason::Finalized
+            } else {
+                continue;
+            };
+            res.push(DeletedBundleProposal {
+                sidechain_number,
+                m6id,
+                reason,
+            });
+        }
+        Ok(res)
+    }
+
+    /// Re-derive which bundle and sidechain proposals should still be
+    /// pending from the validator's current state, and delete any stale
+    /// proposals. Proposals are otherwise only deleted as blocks are
+    /// connected, so this repairs proposal stores that have drifted from
+    /// the validator.
+    pub async fn reconcile_proposals(
+        &self,
+    ) -> Result<ProposalReconciliation, error::ReconcileProposals> {
+        let deleted_bundle_proposals = self.get_stale_bundle_proposals().await?;
+        let included_proposal_ids: HashSet<SidechainProposalId> = self
+            .inner
+            .validator
+            .get_sidechains()?
+            .into_iter()
+            .map(|(proposal_id, _sidechain)| proposal_id)
+            .collect();
+        let deleted_sidechain_proposals: Vec<_> = self
+            .list_pending_sidechain_proposals()
+            .await?
+            .into_iter()
+            .map(|proposal| proposal.id)
+            .filter(|proposal_id| included_proposal_ids.contains(proposal_id))
+            .collect();
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            for deleted in &deleted_bundle_proposals {
+                let params = (deleted.sidechain_number.0, deleted.m6id.0.as_byte_array());
+                let _ = connection.execute(
+                    "DELETE FROM bundle_proposals
+                     WHERE sidechain_number = ?1 AND bundle_hash = ?2;",
+                    params,
+                )?;
+                let _ = connection.execute(
+                    "DELETE FROM pending_bundle_deletions
+                     WHERE sidechain_number = ?1 AND bundle_hash = ?2;",
+                    params,
+                )?;
+            }
+            Ok(())
+        };
+        {
+            let connection = self.inner.self_db.lock().await;
+            let () = with_connection(&connection)?;
+        }
+        let () = self
+            .inner
+            .delete_pending_sidechain_proposals(deleted_sidechain_proposals.iter().copied())
+            .await?;
+        tracing::info!(
+            deleted_bundle_proposals = deleted_bundle_proposals.len(),
+            deleted_sidechain_proposals = deleted_sidechain_proposals.len(),
+            "reconciled proposals"
+        );
+        Ok(ProposalReconciliation {
+            deleted_bundle_proposals,
+            deleted_sidechain_proposals,
+        })
+    }
+}
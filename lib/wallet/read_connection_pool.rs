@@ -0,0 +1,53 @@
+//! Pool of read-only SQLite connections
+
+use std::{
+    num::NonZeroUsize,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rusqlite::{Connection, OpenFlags};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Pool of read-only connections to a SQLite database, so that reads do not
+/// queue behind writes on the read-write connection.
+/// The database should use WAL mode, so that reads are not blocked by an
+/// open write transaction.
+/// As with the read-write connection, queries run synchronously on the
+/// async task that holds the connection, so they should be short.
+pub(in crate::wallet) struct ReadConnectionPool {
+    connections: Vec<Mutex<Connection>>,
+    /// Index of the connection to wait on if all connections are in use
+    next: AtomicUsize,
+}
+
+impl ReadConnectionPool {
+    pub fn open(path: &Path, size: NonZeroUsize) -> Result<Self, rusqlite::Error> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connections = (0..size.get())
+            .map(|_| Connection::open_with_flags(path, flags).map(Mutex::new))
+            .collect::<Result<_, _>>()?;
+        tracing::debug!(
+            "Opened {size} read-only database connections to {}",
+            path.display()
+        );
+        Ok(Self {
+            connections,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Lock an idle connection if one exists. Otherwise, wait for a
+    /// connection, selected in round-robin order.
+    pub async fn lock(&self) -> MutexGuard<'_, Connection> {
+        if let Some(connection) = self
+            .connections
+            .iter()
+            .find_map(|connection| connection.try_lock().ok())
+        {
+            return connection;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        self.connections[index].lock().await
+    }
+}
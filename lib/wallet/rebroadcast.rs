@@ -0,0 +1,188 @@
+//! Rebroadcast unconfirmed wallet transactions, eg. after they were dropped
+//! from the mempool
+
+use std::collections::HashMap;
+
+use bdk_chain::ChainPosition;
+use bitcoin::Txid;
+use bitcoin_jsonrpsee::jsonrpsee::core::ClientError;
+use serde::Serialize;
+
+use crate::{
+    convert,
+    errors::ErrorChain,
+    wallet::{Wallet, error},
+};
+
+const RPC_VERIFY_ALREADY_IN_UTXO_SET: i32 = -27;
+
+/// Reject reasons from older versions of Bitcoin Core, if the transaction is
+/// already in the mempool. Newer versions return the txid instead.
+const ALREADY_IN_MEMPOOL_ERR_MSGS: [&str; 2] = ["txn-already-in-mempool", "txn-already-known"];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum RebroadcastStatus {
+    /// Accepted by Bitcoin Core, or already in its mempool
+    Broadcast,
+    AlreadyConfirmed,
+    /// Bitcoin Core does not support OP_DRIVECHAIN
+    OpDrivechainNotSupported,
+    Failed {
+        error: String,
+    },
+}
+
+impl RebroadcastStatus {
+    fn is_success(&self) -> bool {
+        match self {
+            Self::Broadcast | Self::AlreadyConfirmed => true,
+            Self::OpDrivechainNotSupported | Self::Failed { .. } => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RebroadcastResult {
+    pub txid: Txid,
+    #[serde(flatten)]
+    pub status: RebroadcastStatus,
+}
+
+/// Sort transactions so that each transaction comes after its parents in
+/// `txs`, since Bitcoin Core rejects transactions with missing inputs.
+/// Otherwise, the order of `txs` is preserved.
+fn sort_parents_first(
+    txs: Vec<bdk_wallet::bitcoin::Transaction>,
+) -> Vec<bdk_wallet::bitcoin::Transaction> {
+    let index_by_txid: HashMap<_, _> = txs
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| (tx.compute_txid(), index))
+        .collect();
+    let mut visited = vec![false; txs.len()];
+    let mut order = Vec::with_capacity(txs.len());
+    for root in 0..txs.len() {
+        // Depth-first, emitting each transaction after its parents
+        let mut stack = vec![(root, false)];
+        while let Some((index, parents_emitted)) = stack.pop() {
+            if parents_emitted {
+                order.push(index);
+                continue;
+            }
+            if visited[index] {
+                continue;
+            }
+            visited[index] = true;
+            stack.push((index, true));
+            for input in txs[index].input.iter().rev() {
+                if let Some(&parent) = index_by_txid.get(&input.previous_output.txid)
+                    && !visited[parent]
+                {
+                    stack.push((parent, false));
+                }
+            }
+        }
+    }
+    let mut txs: Vec<_> = txs.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|index| txs[index].take())
+        .collect()
+}
+
+impl Wallet {
+    /// Resubmit each unconfirmed wallet transaction via Bitcoin Core's
+    /// `sendrawtransaction`, parents first
+    pub async fn rebroadcast_unconfirmed(
+        &self,
+    ) -> Result<Vec<RebroadcastResult>, error::LockWallet> {
+        let unconfirmed_txs: Vec<_> = {
+            let wallet_read = self.inner.try_read_wallet().await?;
+            wallet_read
+                .transactions()
+                .filter(|tx| matches!(tx.chain_position, ChainPosition::Unconfirmed { .. }))
+                .map(|tx| tx.tx_node.tx.as_ref().clone())
+                .collect()
+        };
+        let unconfirmed_txs = sort_parents_first(unconfirmed_txs);
+        let mut res = Vec::with_capacity(unconfirmed_txs.len());
+        for tx in unconfirmed_txs {
+            let txid = convert::bdk_txid_to_bitcoin_txid(tx.compute_txid());
+            let status = match crate::rpc_client::broadcast_transaction(
+                &self.inner.main_client,
+                &tx,
+            )
+            .await
+            {
+                Ok(Some(_)) => RebroadcastStatus::Broadcast,
+                Ok(None) => RebroadcastStatus::OpDrivechainNotSupported,
+                Err(ClientError::Call(err)) if err.code() == RPC_VERIFY_ALREADY_IN_UTXO_SET => {
+                    RebroadcastStatus::AlreadyConfirmed
+                }
+                Err(ClientError::Call(err))
+                    if ALREADY_IN_MEMPOOL_ERR_MSGS.contains(&err.message()) =>
+                {
+                    RebroadcastStatus::Broadcast
+                }
+                Err(err) => RebroadcastStatus::Failed {
+                    error: format!("{:#}", ErrorChain::new(&err)),
+                },
+            };
+            if status.is_success() {
+                tracing::debug!(%txid, "Rebroadcast unconfirmed transaction");
+            } else {
+                tracing::warn!(%txid, ?status, "Failed to rebroadcast unconfirmed transaction");
+            }
+            res.push(RebroadcastResult { txid, status });
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bdk_wallet::bitcoin::{
+        Amount, OutPoint, Transaction, TxIn, TxOut, Txid, absolute::LockTime, hashes::Hash as _,
+        transaction::Version,
+    };
+
+    use super::sort_parents_first;
+
+    fn tx(spends: &[Txid], value: u64) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: spends
+                .iter()
+                .map(|txid| TxIn {
+                    previous_output: OutPoint {
+                        txid: *txid,
+                        vout: 0,
+                    },
+                    ..TxIn::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_sort_parents_first() {
+        let external = Txid::all_zeros();
+        let parent = tx(&[external], 1);
+        let child = tx(&[parent.compute_txid()], 2);
+        let grandchild = tx(&[child.compute_txid(), parent.compute_txid()], 3);
+        let unrelated = tx(&[external], 4);
+        let sorted = sort_parents_first(vec![
+            grandchild.clone(),
+            unrelated.clone(),
+            child.clone(),
+            parent.clone(),
+        ]);
+        assert_eq!(sorted, vec![parent, child, grandchild, unrelated]);
+    }
+}
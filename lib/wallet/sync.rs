@@ -3,16 +3,22 @@
 use std::time::SystemTime;
 
 use async_lock::RwLockWriteGuard;
-use bdk_chain::bdk_core;
+use bdk_chain::bdk_core::{
+    self,
+    spk_client::{SyncRequest, SyncResponse},
+};
 use bdk_electrum::electrum_client::ElectrumApi;
-use bdk_esplora::EsploraAsyncExt as _;
+use bdk_wallet::KeychainKind;
+use bitcoin::Amount;
 use either::Either::{self, Left, Right};
+use serde::Serialize;
 use tokio::time::Instant;
 use tracing::instrument;
 
-use super::{ElectrumClient, EsploraClient};
+use super::{ChainSource, ElectrumClient, EsploraClient};
 use crate::{
     cli::WalletSyncSource,
+    errors::ErrorChain,
     types::WithdrawalBundleEventKind,
     wallet::{
         BdkWallet, Persistence, WalletInner, error,
@@ -25,6 +31,7 @@ use crate::{
 pub(in crate::wallet) struct SyncWriteGuard<'a> {
     database: tokio::sync::MutexGuard<'a, Persistence>,
     last_sync: RwLockWriteGuard<'a, Option<SystemTime>>,
+    last_sync_success: &'a tokio::sync::watch::Sender<Option<SystemTime>>,
     pub(in crate::wallet) wallet: RwLockWriteGuardSome<'a, BdkWallet>,
 }
 
@@ -40,13 +47,57 @@ impl SyncWriteGuard<'_> {
                 file_path: self.database.file_path.clone(),
                 source: err,
             })?;
-        *self.last_sync = Some(SystemTime::now());
+        let now = SystemTime::now();
+        *self.last_sync = Some(now);
+        self.last_sync_success.send_replace(Some(now));
         Ok(())
     }
 }
 
+/// Clears the full scan cancel sender when dropped, including if the full
+/// scan is dropped before it completes
+struct FullScanCancelGuard<'a>(&'a parking_lot::Mutex<Option<tokio::sync::watch::Sender<bool>>>);
+
+impl Drop for FullScanCancelGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.lock() = None;
+    }
+}
+
+/// Result of a wallet sync
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SyncSummary {
+    /// `false` if the sync was skipped, eg. because the wallet is locked or
+    /// syncing is disabled
+    pub synced: bool,
+    /// Number of transactions discovered by the sync
+    pub new_transactions: usize,
+    /// Number of wallet outputs discovered by the sync
+    pub new_outputs: usize,
+}
+
 const ESPLORA_PARALLEL_REQUESTS: usize = 25;
 
+/// Number of script pubkeys to sync and persist at a time during a full scan
+const FULL_SCAN_CHUNK_SIZE: usize = 1000;
+
+fn check_full_scan_cancelled(
+    cancel_rx: &tokio::sync::watch::Receiver<bool>,
+) -> Result<(), error::FullScan> {
+    if *cancel_rx.borrow() {
+        Err(error::FullScan::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Resolves once the full scan is cancelled
+async fn full_scan_cancelled(mut cancel_rx: tokio::sync::watch::Receiver<bool>) {
+    if cancel_rx.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending().await
+    }
+}
+
 impl WalletInner {
     pub(in crate::wallet) async fn get_tip(&self) -> Result<bdk_core::BlockId, error::NotUnlocked> {
         let wallet = self.read_wallet().await?;
@@ -76,7 +127,10 @@ impl WalletInner {
                     WithdrawalBundleEventKind::Submitted => None,
                 });
         let () = self
-            .delete_bundle_proposals(finalized_withdrawal_bundles)
+            .delete_bundle_proposals(finalized_withdrawal_bundles, block_height)
+            .await?;
+        let () = self
+            .record_withdrawal_bundle_events(block.block_hash(), block_height, block_info)
             .await?;
         let sidechain_proposal_ids = block_info
             .sidechain_proposals()
@@ -84,6 +138,18 @@ impl WalletInner {
         let () = self
             .delete_pending_sidechain_proposals(sidechain_proposal_ids)
             .await?;
+        // Fee rate records are best-effort, and should not prevent the
+        // block from being connected
+        if let Err(err) = self
+            .record_block_fee_rates(block.block_hash(), block_height)
+            .await
+        {
+            tracing::warn!(
+                block_height,
+                "failed to record block fee rates: {:#}",
+                ErrorChain::new(&err)
+            );
+        }
         let mut database = self.bdk_db.lock().await;
         tracing::info!(
             block_hash = %block.block_hash(),
@@ -102,7 +168,9 @@ impl WalletInner {
 
     pub(in crate::wallet) async fn set_last_synced_now(&self) {
         let mut last_sync_write = self.last_sync.write().await;
-        *last_sync_write = Some(SystemTime::now());
+        let now = SystemTime::now();
+        *last_sync_write = Some(now);
+        self.last_sync_success.send_replace(Some(now));
     }
     /// Sync the wallet, returning a write guard on last_sync, wallet, and database
     /// if wallet was not locked.
@@ -129,38 +197,43 @@ impl WalletInner {
         };
         tracing::trace!("acquired upgradable read lock on wallet");
         let last_sync_write = self.last_sync.write().await;
-        let request = wallet_read.start_sync_with_revealed_spks().build();
-
-        tracing::trace!(
-            spks = request.progress().spks_remaining,
-            txids = request.progress().txids_remaining,
-            outpoints = request.progress().outpoints_remaining,
-            "Requesting sync via chain source"
-        );
-        let (source, update) = match &self.chain_source {
-            Either::Left(electrum_client) => {
-                const BATCH_SIZE: usize = 5;
-                const FETCH_PREV_TXOUTS: bool = false;
-                (
-                    "electrum",
-                    electrum_client.sync(request, BATCH_SIZE, FETCH_PREV_TXOUTS)?,
-                )
-            }
-
-            Either::Right(Either::Left(esplora_client)) => (
-                "esplora",
-                esplora_client
-                    .sync(request, ESPLORA_PARALLEL_REQUESTS)
-                    .await?,
-            ),
-            // This should be checked above, so we never get into this branch. However, handle
-            // it gracefully.
-            Either::Right(Either::Right(_)) => {
-                tracing::info!("`no-sync` sync source aborting",);
-                return Ok(None);
+        if self.config.wallet_opts.sync_cross_check_tips {
+            let () = self.cross_check_tips().await?;
+        }
+        let mut fetched = None;
+        let mut last_err = None;
+        for (sync_source, chain_source) in &self.chain_sources {
+            let request = wallet_read.start_sync_with_revealed_spks().build();
+            tracing::trace!(
+                spks = request.progress().spks_remaining,
+                txids = request.progress().txids_remaining,
+                outpoints = request.progress().outpoints_remaining,
+                "Requesting sync via {sync_source:?} chain source"
+            );
+            match self.fetch_sync_update(chain_source, request).await {
+                Ok(Some(update)) => {
+                    fetched = Some((*sync_source, update));
+                    break;
+                }
+                Ok(None) => return Ok(None),
+                Err(err) => {
+                    tracing::warn!(
+                        "failed to sync via {sync_source:?} chain source: {:#}",
+                        ErrorChain::new(&err)
+                    );
+                    last_err = Some(error::WalletSync::ChainSource {
+                        sync_source: *sync_source,
+                        source: Box::new(err),
+                    });
+                }
             }
+        }
+        let (source, update) = match (fetched, last_err) {
+            (Some(fetched), _) => fetched,
+            (None, Some(err)) => return Err(err),
+            (None, None) => return Ok(None),
         };
-        tracing::trace!("Fetched update from {source}, applying update");
+        tracing::trace!("Fetched update from {source:?}, applying update");
         // Upgrade wallet lock
         let mut wallet_write = RwLockUpgradableReadGuardSome::upgrade(wallet_read).await;
         wallet_write.with_mut(|wallet| wallet.apply_update(update))?;
@@ -171,10 +244,146 @@ impl WalletInner {
         Ok(Some(SyncWriteGuard {
             database: self.bdk_db.lock().await,
             last_sync: last_sync_write,
+            last_sync_success: &self.last_sync_success,
             wallet: wallet_write,
         }))
     }
 
+    /// Fetch a sync update via the chain source.
+    /// Returns `None` if syncing is disabled.
+    async fn fetch_sync_update(
+        &self,
+        chain_source: &ChainSource,
+        request: SyncRequest<(KeychainKind, u32)>,
+    ) -> Result<Option<SyncResponse>, error::WalletSync> {
+        let update = match chain_source {
+            Either::Left(electrum_pool) => {
+                const BATCH_SIZE: usize = 5;
+                const FETCH_PREV_TXOUTS: bool = false;
+                let electrum_client = electrum_pool.lease().await;
+                electrum_client.sync(request, BATCH_SIZE, FETCH_PREV_TXOUTS)?
+            }
+            Either::Right(Either::Left(esplora_client)) => {
+                esplora_client
+                    .rate_limited_sync(request, ESPLORA_PARALLEL_REQUESTS)
+                    .await?
+            }
+            // This should be checked above, so we never get into this branch. However, handle
+            // it gracefully.
+            Either::Right(Either::Right(_)) => {
+                tracing::info!("`no-sync` sync source aborting",);
+                return Ok(None);
+            }
+        };
+        Ok(Some(update))
+    }
+
+    /// Tip height of the chain source.
+    /// Returns `None` if syncing is disabled.
+    pub(in crate::wallet) async fn chain_source_tip_height(
+        chain_source: &ChainSource,
+    ) -> Result<Option<u32>, error::WalletSync> {
+        let height = match chain_source {
+            Either::Left(electrum_pool) => {
+                let electrum_client = electrum_pool.lease().await;
+                let notification = electrum_client.inner.block_headers_subscribe()?;
+                notification.height as u32
+            }
+            Either::Right(Either::Left(esplora_client)) => esplora_client
+                .rate_limited(|| esplora_client.get_height())
+                .await
+                .map_err(Box::new)?,
+            Either::Right(Either::Right(_)) => return Ok(None),
+        };
+        Ok(Some(height))
+    }
+
+    /// Block hash at the specified height, according to the chain source.
+    /// Returns `None` if syncing is disabled.
+    async fn chain_source_block_hash(
+        chain_source: &ChainSource,
+        height: u32,
+    ) -> Result<Option<bitcoin::BlockHash>, error::WalletSync> {
+        let block_hash = match chain_source {
+            Either::Left(electrum_pool) => {
+                let electrum_client = electrum_pool.lease().await;
+                electrum_client
+                    .inner
+                    .block_header(height as usize)?
+                    .block_hash()
+            }
+            Either::Right(Either::Left(esplora_client)) => esplora_client
+                .rate_limited(|| esplora_client.get_block_hash(height))
+                .await
+                .map_err(Box::new)?,
+            Either::Right(Either::Right(_)) => return Ok(None),
+        };
+        Ok(Some(block_hash))
+    }
+
+    /// Check that all reachable chain sources agree on the block hash at the
+    /// lowest of their tip heights. Unreachable chain sources are skipped,
+    /// but the check fails unless at least two chain sources agree.
+    async fn cross_check_tips(&self) -> Result<(), error::WalletSync> {
+        let mut tip_heights = Vec::new();
+        for (sync_source, chain_source) in &self.chain_sources {
+            match Self::chain_source_tip_height(chain_source).await {
+                Ok(Some(tip_height)) => tip_heights.push((*sync_source, chain_source, tip_height)),
+                Ok(None) => (),
+                Err(err) => tracing::warn!(
+                    "failed to get tip height via {sync_source:?} chain source, skipping tip cross-check: {:#}",
+                    ErrorChain::new(&err)
+                ),
+            }
+        }
+        let height = tip_heights
+            .iter()
+            .map(|(_, _, height)| *height)
+            .min()
+            .unwrap_or(0);
+        let mut first_block_hash: Option<(WalletSyncSource, bitcoin::BlockHash)> = None;
+        let mut agreeing_sources: usize = 0;
+        for (sync_source, chain_source, _) in tip_heights {
+            let block_hash = match Self::chain_source_block_hash(chain_source, height).await {
+                Ok(Some(block_hash)) => block_hash,
+                Ok(None) => continue,
+                Err(err) => {
+                    tracing::warn!(
+                        "failed to get block hash via {sync_source:?} chain source, skipping tip cross-check: {:#}",
+                        ErrorChain::new(&err)
+                    );
+                    continue;
+                }
+            };
+            match first_block_hash {
+                None => first_block_hash = Some((sync_source, block_hash)),
+                Some((other_sync_source, other_hash)) if other_hash != block_hash => {
+                    return Err(error::WalletSync::TipMismatch {
+                        height,
+                        sync_source,
+                        hash: block_hash,
+                        other_sync_source,
+                        other_hash,
+                    });
+                }
+                Some(_) => (),
+            }
+            agreeing_sources += 1;
+        }
+        if agreeing_sources < 2 {
+            return Err(error::WalletSync::TipCrossCheckInsufficientSources {
+                height,
+                sources: agreeing_sources,
+            });
+        }
+        tracing::trace!(
+            height,
+            sources = agreeing_sources,
+            "chain source tips agree"
+        );
+        Ok(())
+    }
+
     async fn address_has_txs(
         &self,
         chain_source: Either<&ElectrumClient, &EsploraClient>,
@@ -188,7 +397,7 @@ impl WalletInner {
                 .map_err(Left),
 
             Either::Right(esplora_client) => esplora_client
-                .get_address_txs(address, None)
+                .rate_limited(|| esplora_client.get_address_txs(address, None))
                 .await
                 .map(|txs| !txs.is_empty())
                 .map_err(Right),
@@ -237,15 +446,66 @@ impl WalletInner {
         Ok(checkpoint)
     }
 
+    /// Full scan via each chain source in priority order, until one succeeds
+    pub(in crate::wallet) async fn full_scan(
+        &self,
+    ) -> miette::Result<bdk_wallet::bitcoin::BlockHash, error::FullScan> {
+        let (cancel_tx, cancel_rx) = tokio::sync::watch::channel(false);
+        let _cancel_guard = {
+            let mut full_scan_cancel = self.full_scan_cancel.lock();
+            // A single cancel sender is shared, so concurrent full scans
+            // could not be cancelled independently
+            if full_scan_cancel.is_some() {
+                return Err(error::FullScan::InProgress);
+            }
+            *full_scan_cancel = Some(cancel_tx);
+            FullScanCancelGuard(&self.full_scan_cancel)
+        };
+        self.full_scan_cancellable(&cancel_rx).await
+    }
+
+    async fn full_scan_cancellable(
+        &self,
+        cancel_rx: &tokio::sync::watch::Receiver<bool>,
+    ) -> miette::Result<bdk_wallet::bitcoin::BlockHash, error::FullScan> {
+        let mut last_err = None;
+        for (sync_source, chain_source) in &self.chain_sources {
+            match self.full_scan_via(chain_source, cancel_rx).await {
+                Ok(tip) => {
+                    self.synced.send_replace(true);
+                    return Ok(tip);
+                }
+                Err(error::FullScan::Cancelled) => {
+                    tracing::info!("wallet full scan cancelled");
+                    return Err(error::FullScan::Cancelled);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "wallet full scan via {sync_source:?} chain source failed: {:#}",
+                        ErrorChain::new(&err)
+                    );
+                    last_err = Some(error::FullScan::ChainSource {
+                        sync_source: *sync_source,
+                        source: Box::new(err),
+                    });
+                }
+            }
+        }
+        Err(last_err.unwrap_or(error::FullScan::NoChainSources))
+    }
+
     // TODO: is this actually correct? Need help from the Rust grownups!
     #[allow(clippy::significant_drop_tightening, reason = "false positive")]
-    pub(in crate::wallet) async fn full_scan(
+    async fn full_scan_via(
         &self,
+        chain_source: &ChainSource,
+        cancel_rx: &tokio::sync::watch::Receiver<bool>,
     ) -> miette::Result<bdk_wallet::bitcoin::BlockHash, error::FullScan> {
         tracing::info!("starting wallet full scan");
 
-        let chain_source = match &self.chain_source {
-            Either::Left(electrum) => Either::Left(electrum),
+        // Hold a single Electrum connection for the duration of the scan
+        let chain_source = match chain_source {
+            Either::Left(electrum_pool) => Either::Left(electrum_pool.lease().await),
             Either::Right(Either::Left(esplora)) => Either::Right(esplora),
             // This should be picked up earlier, by never invoking `full_scan` with
             // a disabled sync source
@@ -256,7 +516,11 @@ impl WalletInner {
             }
         };
 
-        let mut start = SystemTime::now();
+        let address_source = chain_source
+            .as_ref()
+            .map_either(|electrum_lease| &**electrum_lease, |esplora| *esplora);
+
+        let start = SystemTime::now();
 
         let wallet_read = self
             .read_wallet_upgradable()
@@ -270,8 +534,9 @@ impl WalletInner {
 
             // First find upper bound by incrementing by 1000 until we find unused
             loop {
+                let () = check_full_scan_cancelled(cancel_rx)?;
                 let address = wallet_read.peek_address(keychain, last_used_index);
-                let has_txs = self.address_has_txs(chain_source, &address).await?;
+                let has_txs = self.address_has_txs(address_source, &address).await?;
 
                 if !has_txs {
                     break;
@@ -284,9 +549,10 @@ impl WalletInner {
             let mut low = last_used_index.saturating_sub(step);
 
             while low < high {
+                let () = check_full_scan_cancelled(cancel_rx)?;
                 let mid = low + (high - low) / 2;
                 let address = wallet_read.peek_address(keychain, mid);
-                let has_txs = self.address_has_txs(chain_source, &address).await?;
+                let has_txs = self.address_has_txs(address_source, &address).await?;
 
                 if !has_txs {
                     high = mid;
@@ -318,70 +584,146 @@ impl WalletInner {
 
         let local_chain = wallet_write.local_chain();
         let checkpoint = self.get_chain_checkpoint(local_chain).await?;
-        let request = wallet_write
+        let mut request = wallet_write
             .start_sync_with_revealed_spks()
-            .chain_tip(checkpoint);
+            .chain_tip(checkpoint.clone())
+            .build();
+        // Sync in chunks, persisting each chunk's update, so that progress
+        // is kept if the scan is cancelled or fails part way through.
+        let spks: Vec<_> = std::iter::from_fn(|| request.next_spk_with_index()).collect();
+        let chunks: Vec<Vec<_>> = if spks.is_empty() {
+            vec![Vec::new()]
+        } else {
+            spks.chunks(FULL_SCAN_CHUNK_SIZE)
+                .map(<[_]>::to_vec)
+                .collect()
+        };
+        let n_chunks = chunks.len();
 
-        let update = match chain_source {
-            Either::Left(electrum_client) => {
-                const BATCH_SIZE: usize = 100;
-                const FETCH_PREV_TXOUTS: bool = true;
-                electrum_client
-                    .sync(request, BATCH_SIZE, FETCH_PREV_TXOUTS)
-                    .map_err(error::FullScan::ElectrumSync)?
+        let mut bdk_db = self.bdk_db.lock().await;
+        for (chunk_idx, chunk) in chunks.into_iter().enumerate() {
+            if check_full_scan_cancelled(cancel_rx).is_err() {
+                tracing::info!(
+                    "wallet full scan cancelled, persisted {chunk_idx} of {n_chunks} chunks"
+                );
+                return Err(error::FullScan::Cancelled);
             }
+            let request = SyncRequest::builder()
+                .spks_with_indexes(chunk)
+                .chain_tip(checkpoint.clone())
+                .build();
+            let update = match address_source {
+                Either::Left(electrum_client) => {
+                    const BATCH_SIZE: usize = 100;
+                    const FETCH_PREV_TXOUTS: bool = true;
+                    electrum_client
+                        .sync(request, BATCH_SIZE, FETCH_PREV_TXOUTS)
+                        .map_err(error::FullScan::ElectrumSync)?
+                }
 
-            Either::Right(esplora_client) => esplora_client
-                .sync(request, ESPLORA_PARALLEL_REQUESTS)
+                Either::Right(esplora_client) => {
+                    tokio::select! {
+                        res = esplora_client.rate_limited_sync(request, ESPLORA_PARALLEL_REQUESTS) => {
+                            res.map_err(|err| error::FullScan::EsploraSync(*err))?
+                        }
+                        () = full_scan_cancelled(cancel_rx.clone()) => {
+                            tracing::info!(
+                                "wallet full scan cancelled, persisted {chunk_idx} of {n_chunks} chunks"
+                            );
+                            return Err(error::FullScan::Cancelled)
+                        }
+                    }
+                }
+            };
+            wallet_write
+                .with_mut(|wallet| {
+                    wallet
+                        .apply_update(update)
+                        .map(|_| wallet.persist_async(&mut bdk_db))
+                })
+                .map_err(error::FullScan::CannotConnect)?
                 .await
-                .map_err(|err| error::FullScan::EsploraSync(*err))?,
-        };
+                .map_err(|err| error::FullScan::PersistWallet(error::SqliteError::from(err)))?;
+            tracing::debug!(
+                "wallet full scan persisted chunk {} of {n_chunks}",
+                chunk_idx + 1
+            );
+        }
+        drop(bdk_db);
 
         tracing::info!(
             "wallet full scan complete in {:?}",
             start.elapsed().unwrap_or_default(),
         );
 
-        start = SystemTime::now();
-
-        let mut bdk_db = self.bdk_db.lock().await;
-
-        wallet_write
-            .with_mut(|wallet| {
-                wallet
-                    .apply_update(update)
-                    .map(|_| wallet.persist_async(&mut bdk_db))
-            })
-            .map_err(error::FullScan::CannotConnect)?
-            .await
-            .map_err(|err| error::FullScan::PersistWallet(error::SqliteError::from(err)))?;
-
         let tip = wallet_write.local_chain().tip().hash();
 
         drop(wallet_write);
 
-        tracing::info!(
-            "wallet full scan result persisted in {:?}",
-            start.elapsed().unwrap_or_default(),
-        );
-
         Ok(tip)
     }
 
+    /// Log a warning if the balance has dropped below the low balance
+    /// threshold, and notify `low_balance` subscribers of any change
+    fn check_low_balance(&self, balance: Amount, threshold: Amount) {
+        let low_balance = balance < threshold;
+        let was_low_balance = self.low_balance.send_replace(low_balance);
+        match (was_low_balance, low_balance) {
+            (false, true) => tracing::warn!(
+                target: "bip300301_enforcer_lib::wallet::low_balance",
+                %balance,
+                %threshold,
+                "wallet balance is below the low balance threshold"
+            ),
+            (true, false) => tracing::info!(
+                target: "bip300301_enforcer_lib::wallet::low_balance",
+                %balance,
+                %threshold,
+                "wallet balance is no longer below the low balance threshold"
+            ),
+            (false, false) | (true, true) => (),
+        }
+    }
+
     /// Sync the wallet if the wallet is not locked, committing changes
     #[allow(clippy::significant_drop_in_scrutinee, reason = "false positive")]
-    pub(in crate::wallet) async fn sync(&self) -> Result<(), error::WalletSync> {
+    pub(in crate::wallet) async fn sync(&self) -> Result<SyncSummary, error::WalletSync> {
         match self.sync_lock().await? {
             Some(sync_write) => {
                 let start = Instant::now();
+                // Changes from the sync have not been persisted yet
+                let (new_transactions, new_outputs) = match sync_write.wallet.staged() {
+                    Some(changeset) => {
+                        let new_outputs = changeset
+                            .tx_graph
+                            .txs
+                            .iter()
+                            .flat_map(|tx| &tx.output)
+                            .filter(|txout| sync_write.wallet.is_mine(txout.script_pubkey.clone()))
+                            .count();
+                        (changeset.tx_graph.txs.len(), new_outputs)
+                    }
+                    None => (0, 0),
+                };
+                if let Some(threshold) = self.config.wallet_opts.low_balance_threshold_sats {
+                    self.check_low_balance(
+                        sync_write.wallet.balance().total(),
+                        Amount::from_sat(threshold),
+                    );
+                }
                 tracing::trace!("obtained sync lock, committing changes");
                 let () = sync_write.commit().await?;
+                self.synced.send_replace(true);
                 tracing::trace!("sync lock commit complete in {:?}", start.elapsed());
-                Ok(())
+                Ok(SyncSummary {
+                    synced: true,
+                    new_transactions,
+                    new_outputs,
+                })
             }
             None => {
                 tracing::trace!("no sync lock, skipping commit");
-                Ok(())
+                Ok(SyncSummary::default())
             }
         }
     }
@@ -0,0 +1,66 @@
+//! Background monitor for the age of the last successful wallet sync
+
+use std::{
+    future::Future,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::wallet::Wallet;
+
+/// Interval between checks while the last sync is stale, so that a fresh
+/// sync is detected promptly
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+impl Wallet {
+    /// Receiver for whether the last successful sync is older than the
+    /// configured staleness threshold. Always `false` if sync staleness is
+    /// not monitored.
+    pub fn subscribe_sync_stale(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.inner.sync_stale.subscribe()
+    }
+
+    /// Check the age of the last successful sync until the shutdown signal
+    /// resolves, reporting the sync as stale while it is older than
+    /// `threshold`. Until the first sync, the age is measured from when the
+    /// monitor started.
+    pub async fn sync_staleness_monitor_task<F: Future<Output = ()>>(
+        &self,
+        threshold: Duration,
+        shutdown_signal: F,
+    ) {
+        tracing::debug!(?threshold, "sync staleness monitor: starting");
+        futures::pin_mut!(shutdown_signal);
+        let started = Instant::now();
+        loop {
+            // `last_sync` is write-locked for the duration of a sync, so a
+            // hung sync would never be reported as stale if it were read
+            // here.
+            let age = match *self.inner.last_sync_success.borrow() {
+                Some(last_sync) => SystemTime::now()
+                    .duration_since(last_sync)
+                    .unwrap_or_default(),
+                None => started.elapsed(),
+            };
+            let delay = if age > threshold {
+                if !self.inner.sync_stale.send_replace(true) {
+                    tracing::warn!(?age, "last successful wallet sync is stale");
+                }
+                STALE_CHECK_INTERVAL
+            } else {
+                if self.inner.sync_stale.send_replace(false) {
+                    tracing::info!("wallet sync is fresh again");
+                }
+                // Check again once the last sync would become stale
+                threshold - age + Duration::from_millis(1)
+            };
+            tokio::select! {
+                biased;  // Prioritize shutdown
+                () = &mut shutdown_signal => {
+                    tracing::info!("shutting down sync staleness monitor");
+                    return;
+                }
+                () = tokio::time::sleep(delay) => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Operator-configured limits on the mempool transactions included in block
+//! templates produced by the GBT server
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bitcoin::Txid;
+
+use crate::wallet::{WalletInner, confirmation_estimate::MempoolEntry, error};
+
+/// Compare fee rates, lowest first
+fn cmp_fee_rate(lhs: &MempoolEntry, rhs: &MempoolEntry) -> Ordering {
+    let lhs_rate = lhs.fees.modified.to_sat() as u128 * rhs.weight.max(1) as u128;
+    let rhs_rate = rhs.fees.modified.to_sat() as u128 * lhs.weight.max(1) as u128;
+    lhs_rate.cmp(&rhs_rate)
+}
+
+/// Transaction that can be selected, ordered by fee rate, with ties broken
+/// by txid
+struct Candidate<'a> {
+    txid: Txid,
+    entry: &'a MempoolEntry,
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_fee_rate(self.entry, other.entry).then_with(|| other.txid.cmp(&self.txid))
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+/// Select mempool transactions by fee rate, until either limit is reached.
+/// A transaction is only selected if all of its unconfirmed parents are
+/// selected. Returns the transactions that were not selected.
+fn exclude_over_limits(
+    entries: HashMap<Txid, MempoolEntry>,
+    max_txs: Option<usize>,
+    max_weight: Option<u64>,
+) -> HashSet<Txid> {
+    // Number of parents of each transaction that have not been selected yet
+    let mut unselected_parents = HashMap::<Txid, usize>::new();
+    let mut children = HashMap::<Txid, Vec<Txid>>::new();
+    let mut ready = BinaryHeap::new();
+    for (txid, entry) in &entries {
+        // Parents that are not candidates, eg. because they are already
+        // excluded, can never be selected
+        if !entry
+            .depends
+            .iter()
+            .all(|parent| entries.contains_key(parent))
+        {
+            continue;
+        }
+        if entry.depends.is_empty() {
+            ready.push(Candidate { txid: *txid, entry });
+            continue;
+        }
+        unselected_parents.insert(*txid, entry.depends.len());
+        for parent in &entry.depends {
+            children.entry(*parent).or_default().push(*txid);
+        }
+    }
+    let mut selected = HashSet::<Txid>::new();
+    let mut total_weight = 0;
+    while let Some(Candidate { txid, entry }) = ready.pop() {
+        if max_txs.is_some_and(|max_txs| selected.len() >= max_txs) {
+            break;
+        }
+        // Smaller transactions may still fit
+        if max_weight.is_some_and(|max_weight| total_weight + entry.weight > max_weight) {
+            continue;
+        }
+        selected.insert(txid);
+        total_weight += entry.weight;
+        for child in children.remove(&txid).into_iter().flatten() {
+            let Some(unselected) = unselected_parents.get_mut(&child) else {
+                continue;
+            };
+            *unselected -= 1;
+            if *unselected == 0 {
+                unselected_parents.remove(&child);
+                if let Some(entry) = entries.get(&child) {
+                    ready.push(Candidate { txid: child, entry });
+                }
+            }
+        }
+    }
+    entries
+        .into_keys()
+        .filter(|txid| !selected.contains(txid))
+        .collect()
+}
+
+impl WalletInner {
+    /// Mempool transactions to exclude from a block template, in order to
+    /// satisfy the configured template limits. Transactions in `excluded`
+    /// are already excluded, and do not count towards the limits.
+    /// The enforcer's mempool is not available to the block template hooks,
+    /// so mempool transactions are fetched from Bitcoin Core. The enforcer's
+    /// mempool is synced from Bitcoin Core's, so transactions that are only
+    /// in Bitcoin Core's mempool are excluded without effect, and
+    /// transactions that are only in the enforcer's mempool, eg. if evicted
+    /// from Bitcoin Core's mempool since the last sync, are not limited.
+    pub(in crate::wallet) async fn template_limit_exclusions(
+        &self,
+        excluded: &HashSet<Txid>,
+    ) -> Result<HashSet<Txid>, error::BitcoinCoreRPC> {
+        let max_txs = self.config.mining_opts.block_template_max_txs;
+        let max_weight = self.config.mining_opts.block_template_max_weight;
+        if max_txs.is_none() && max_weight.is_none() {
+            return Ok(HashSet::new());
+        }
+        let mut entries = self.fetch_mempool_entries().await?;
+        entries.retain(|txid, _| !excluded.contains(txid));
+        let exclude = exclude_over_limits(entries, max_txs, max_weight);
+        tracing::debug!(
+            excluded = exclude.len(),
+            ?max_txs,
+            ?max_weight,
+            "Applied block template limits"
+        );
+        Ok(exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use bitcoin::{Amount, Txid, hashes::Hash as _};
+
+    use super::exclude_over_limits;
+    use crate::wallet::confirmation_estimate::{MempoolEntry, MempoolEntryFees};
+
+    fn txid(n: u8) -> Txid {
+        Txid::from_byte_array([n; 32])
+    }
+
+    fn entry(fee_sats: u64, weight: u64, depends: &[u8]) -> MempoolEntry {
+        MempoolEntry {
+            vsize: weight.div_ceil(4),
+            weight,
+            fees: MempoolEntryFees {
+                modified: Amount::from_sat(fee_sats),
+            },
+            depends: depends.iter().copied().map(txid).collect(),
+        }
+    }
+
+    fn txids(ns: &[u8]) -> HashSet<Txid> {
+        ns.iter().copied().map(txid).collect()
+    }
+
+    #[test]
+    fn test_max_txs_excludes_lowest_fee_rates() {
+        let entries = HashMap::from([
+            (txid(1), entry(1_000, 400, &[])),
+            (txid(2), entry(3_000, 400, &[])),
+            (txid(3), entry(2_000, 400, &[])),
+        ]);
+        assert_eq!(exclude_over_limits(entries, Some(2), None), txids(&[1]));
+    }
+
+    #[test]
+    fn test_max_weight_selects_smaller_transactions() {
+        let entries = HashMap::from([
+            (txid(1), entry(10_000, 1_000, &[])),
+            (txid(2), entry(9_000, 1_000, &[])),
+            (txid(3), entry(800, 100, &[])),
+        ]);
+        // Transaction 2 does not fit, but the lower fee rate transaction 3
+        // does
+        assert_eq!(exclude_over_limits(entries, None, Some(1_200)), txids(&[2]));
+    }
+
+    #[test]
+    fn test_children_require_parents() {
+        let entries = HashMap::from([
+            (txid(1), entry(100, 400, &[])),
+            (txid(2), entry(10_000, 400, &[1])),
+            (txid(3), entry(1_000, 400, &[])),
+        ]);
+        // The high fee rate child is only selectable once its parent is
+        // selected
+        assert_eq!(exclude_over_limits(entries, Some(1), None), txids(&[1, 2]));
+        let entries = HashMap::from([
+            (txid(1), entry(100, 400, &[])),
+            (txid(2), entry(10_000, 400, &[1])),
+            (txid(3), entry(1_000, 400, &[])),
+        ]);
+        assert_eq!(exclude_over_limits(entries, Some(3), None), txids(&[]));
+    }
+
+    #[test]
+    fn test_children_of_excluded_transactions_are_excluded() {
+        // Transaction 1 is already excluded, so is not a candidate
+        let entries = HashMap::from([
+            (txid(2), entry(10_000, 400, &[1])),
+            (txid(3), entry(1_000, 400, &[2])),
+            (txid(4), entry(1_000, 400, &[])),
+        ]);
+        assert_eq!(exclude_over_limits(entries, Some(10), None), txids(&[2, 3]));
+    }
+}
@@ -20,6 +20,17 @@ impl ThreadSafeConnection {
             conn,
         })
     }
+
+    /// Rebuilds the database file, reclaiming unused space.
+    /// `VACUUM` holds an exclusive lock on the database until it completes.
+    pub async fn vacuum(&self) -> tokio_rusqlite::Result<()> {
+        self.conn
+            .call(|conn| {
+                conn.execute_batch("VACUUM;")?;
+                Ok(())
+            })
+            .await
+    }
 }
 
 /// Cribbed from the implementation of WalletPersister from BDK
@@ -0,0 +1,246 @@
+//! History of withdrawal bundle events in connected blocks
+
+use bitcoin::{BlockHash, Txid, hashes::Hash as _};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::{
+    types::{BlockInfo, M6id, SidechainNumber, WithdrawalBundleEventKind, WithdrawalBundleStatus},
+    wallet::{Wallet, WalletInner, error},
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WithdrawalBundleEventRecord {
+    pub sidechain_number: SidechainNumber,
+    pub m6id: M6id,
+    pub status: WithdrawalBundleStatus,
+    /// Only set for succeeded bundles
+    pub sequence_number: Option<u64>,
+    /// M6 txid. Only set for succeeded bundles.
+    pub txid: Option<Txid>,
+    pub block_height: u32,
+    pub block_hash: BlockHash,
+}
+
+/// Latest known status of a withdrawal bundle proposed by this wallet
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposedWithdrawalBundleStatus {
+    /// No events have been recorded for the bundle
+    Pending,
+    Submitted,
+    Failed,
+    Succeeded,
+}
+
+impl From<WithdrawalBundleStatus> for ProposedWithdrawalBundleStatus {
+    fn from(status: WithdrawalBundleStatus) -> Self {
+        match status {
+            WithdrawalBundleStatus::Submitted => Self::Submitted,
+            WithdrawalBundleStatus::Failed => Self::Failed,
+            WithdrawalBundleStatus::Succeeded => Self::Succeeded,
+        }
+    }
+}
+
+/// Withdrawal bundle proposed by this wallet via `put_withdrawal_bundle`
+#[derive(Clone, Debug, Serialize)]
+pub struct ProposedWithdrawalBundle {
+    pub sidechain_number: SidechainNumber,
+    pub m6id: M6id,
+    /// Unix timestamp, in seconds. Not set for bundles proposed before
+    /// proposals were recorded.
+    pub proposed_at: Option<u64>,
+    pub status: ProposedWithdrawalBundleStatus,
+    /// Height of the block with the latest event for the bundle, if any
+    pub status_block_height: Option<u32>,
+}
+
+fn status_to_sql(status: WithdrawalBundleStatus) -> &'static str {
+    match status {
+        WithdrawalBundleStatus::Submitted => "submitted",
+        WithdrawalBundleStatus::Failed => "failed",
+        WithdrawalBundleStatus::Succeeded => "succeeded",
+    }
+}
+
+fn status_from_sql(status: &str) -> Option<WithdrawalBundleStatus> {
+    match status {
+        "submitted" => Some(WithdrawalBundleStatus::Submitted),
+        "failed" => Some(WithdrawalBundleStatus::Failed),
+        "succeeded" => Some(WithdrawalBundleStatus::Succeeded),
+        _ => None,
+    }
+}
+
+impl WalletInner {
+    /// Record the withdrawal bundle events in a connected block, replacing
+    /// any records for reorged-out blocks at or above the same height
+    pub(in crate::wallet) async fn record_withdrawal_bundle_events(
+        &self,
+        block_hash: BlockHash,
+        block_height: u32,
+        block_info: &BlockInfo,
+    ) -> Result<(), rusqlite::Error> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection = |connection: &Connection| -> Result<_, rusqlite::Error> {
+            let tx = connection.unchecked_transaction()?;
+            let _ = tx.execute(
+                "DELETE FROM withdrawal_bundle_events WHERE block_height >= ?1;",
+                [block_height],
+            )?;
+            for (event_index, event) in block_info.withdrawal_bundle_events().enumerate() {
+                let (sequence_number, txid) = match &event.kind {
+                    WithdrawalBundleEventKind::Submitted | WithdrawalBundleEventKind::Failed => {
+                        (None, None)
+                    }
+                    WithdrawalBundleEventKind::Succeeded {
+                        sequence_number,
+                        transaction,
+                    } => (
+                        Some(*sequence_number),
+                        Some(transaction.compute_txid().to_byte_array()),
+                    ),
+                };
+                let _ = tx.execute(
+                    "INSERT INTO withdrawal_bundle_events
+                     (block_height, block_hash, event_index, sidechain_number, bundle_hash,
+                      status, sequence_number, txid)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
+                    (
+                        block_height,
+                        block_hash.as_byte_array(),
+                        event_index,
+                        event.sidechain_id.0,
+                        event.m6id.0.as_byte_array(),
+                        status_to_sql(WithdrawalBundleStatus::from(&event.kind)),
+                        sequence_number,
+                        txid,
+                    ),
+                )?;
+            }
+            tx.commit()
+        };
+        let connection = self.self_db.lock().await;
+        with_connection(&connection)
+    }
+
+    /// Delete the withdrawal bundle event records for a disconnected block,
+    /// and any blocks above it
+    pub(in crate::wallet) async fn delete_withdrawal_bundle_events_from(
+        &self,
+        block_height: u32,
+    ) -> Result<(), rusqlite::Error> {
+        self.self_db.lock().await.execute(
+            "DELETE FROM withdrawal_bundle_events WHERE block_height >= ?1;",
+            [block_height],
+        )?;
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Withdrawal bundle events for a sidechain in blocks at or above
+    /// `from_height`, in the order that they were processed
+    pub async fn list_withdrawal_bundle_events(
+        &self,
+        sidechain_number: SidechainNumber,
+        from_height: u32,
+    ) -> Result<Vec<WithdrawalBundleEventRecord>, error::ListWithdrawalBundleEvents> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection =
+            |connection: &Connection| -> Result<_, error::ListWithdrawalBundleEvents> {
+                let mut statement = connection.prepare(
+                    "SELECT bundle_hash, status, sequence_number, txid, block_height, block_hash
+                     FROM withdrawal_bundle_events
+                     WHERE sidechain_number = ?1 AND block_height >= ?2
+                     ORDER BY block_height ASC, event_index ASC;",
+                )?;
+                let rows = statement.query_map((sidechain_number.0, from_height), |row| {
+                    let bundle_hash: [u8; 32] = row.get(0)?;
+                    let status: String = row.get(1)?;
+                    let txid: Option<[u8; 32]> = row.get(3)?;
+                    let block_hash: [u8; 32] = row.get(5)?;
+                    Ok((
+                        M6id(Txid::from_byte_array(bundle_hash)),
+                        status,
+                        row.get::<_, Option<u64>>(2)?,
+                        txid.map(Txid::from_byte_array),
+                        row.get::<_, u32>(4)?,
+                        BlockHash::from_byte_array(block_hash),
+                    ))
+                })?;
+                rows.map(|row| {
+                    let (m6id, status, sequence_number, txid, block_height, block_hash) = row?;
+                    let status = status_from_sql(&status)
+                        .ok_or(error::ListWithdrawalBundleEvents::InvalidStatus { status })?;
+                    Ok(WithdrawalBundleEventRecord {
+                        sidechain_number,
+                        m6id,
+                        status,
+                        sequence_number,
+                        txid,
+                        block_height,
+                        block_hash,
+                    })
+                })
+                .collect()
+            };
+        let connection = self.inner.self_db_readers.lock().await;
+        with_connection(&connection)
+    }
+
+    /// Withdrawal bundles proposed by this wallet, with their latest known
+    /// status, in the order that they were proposed
+    pub async fn list_proposed_withdrawal_bundles(
+        &self,
+    ) -> Result<Vec<ProposedWithdrawalBundle>, error::ListWithdrawalBundleEvents> {
+        // Satisfy clippy with a single function call per lock
+        let with_connection =
+            |connection: &Connection| -> Result<_, error::ListWithdrawalBundleEvents> {
+                let mut statement = connection.prepare(
+                    "SELECT proposed.sidechain_number, proposed.bundle_hash, proposed.proposed_at,
+                            events.status, events.block_height
+                     FROM proposed_withdrawal_bundles AS proposed
+                     LEFT JOIN withdrawal_bundle_events AS events
+                     ON events.rowid = (
+                         SELECT rowid FROM withdrawal_bundle_events
+                         WHERE sidechain_number = proposed.sidechain_number
+                         AND bundle_hash = proposed.bundle_hash
+                         ORDER BY block_height DESC, event_index DESC
+                         LIMIT 1
+                     )
+                     ORDER BY proposed.rowid ASC;",
+                )?;
+                let rows = statement.query_map([], |row| {
+                    let bundle_hash: [u8; 32] = row.get(1)?;
+                    Ok((
+                        SidechainNumber(row.get(0)?),
+                        M6id(Txid::from_byte_array(bundle_hash)),
+                        row.get::<_, Option<u64>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<u32>>(4)?,
+                    ))
+                })?;
+                rows.map(|row| {
+                    let (sidechain_number, m6id, proposed_at, status, status_block_height) = row?;
+                    let status = match status {
+                        Some(status) => status_from_sql(&status)
+                            .ok_or(error::ListWithdrawalBundleEvents::InvalidStatus { status })?
+                            .into(),
+                        None => ProposedWithdrawalBundleStatus::Pending,
+                    };
+                    Ok(ProposedWithdrawalBundle {
+                        sidechain_number,
+                        m6id,
+                        proposed_at,
+                        status,
+                        status_block_height,
+                    })
+                })
+                .collect()
+            };
+        let connection = self.inner.self_db_readers.lock().await;
+        with_connection(&connection)
+    }
+}